@@ -32,6 +32,7 @@ pub trait UniqueIndexTrait<K, V> {
     /// Set unique value for the given key.
     /// - if overwrite: true - overwrites existing value if any.
     /// - else: returns error if value already exists.
+    ///
     /// Returns tuple for syncing.
     fn set(&mut self, key: K, value: V, overwrite: bool) -> Result<Vec<u8>, Error>;
 
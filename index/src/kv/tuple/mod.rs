@@ -328,7 +328,7 @@ mod tests {
             let kv_tuple_from_kv = KVTuple::new_delete(key);
             let bytes = kv_tuple_from_kv.to_bytes();
             let kv_tuple_from_bytes = KVTuple::from_bytes(&bytes);
-            assert_eq!(kv_tuple_from_bytes.is_ok(), true);
+            assert!(kv_tuple_from_bytes.is_ok());
             let kv_tuple_from_bytes = kv_tuple_from_bytes.unwrap();
             assert_eq!(kv_tuple_from_bytes.key(), Some(key.to_vec()));
             assert_eq!(kv_tuple_from_bytes.value(), None);
@@ -337,7 +337,7 @@ mod tests {
             let kv_tuple_from_kv = KVTuple::new_insert(key, value);
             let bytes = kv_tuple_from_kv.to_bytes();
             let kv_tuple_from_bytes = KVTuple::from_bytes(&bytes);
-            assert_eq!(kv_tuple_from_bytes.is_ok(), true);
+            assert!(kv_tuple_from_bytes.is_ok());
             let kv_tuple_from_bytes = kv_tuple_from_bytes.unwrap();
             assert_eq!(kv_tuple_from_bytes.key(), Some(key.to_vec()));
             assert_eq!(kv_tuple_from_bytes.value(), Some(value.to_vec()));
@@ -346,7 +346,7 @@ mod tests {
             let kv_tuple_from_kv = KVTuple::new_remove(key, value);
             let bytes = kv_tuple_from_kv.to_bytes();
             let kv_tuple_from_bytes = KVTuple::from_bytes(&bytes);
-            assert_eq!(kv_tuple_from_bytes.is_ok(), true);
+            assert!(kv_tuple_from_bytes.is_ok());
             let kv_tuple_from_bytes = kv_tuple_from_bytes.unwrap();
             assert_eq!(kv_tuple_from_bytes.key(), Some(key.to_vec()));
             assert_eq!(kv_tuple_from_bytes.value(), Some(value.to_vec()));
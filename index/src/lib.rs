@@ -37,7 +37,7 @@ impl IndexTrait<kv::tuple::KeyData, kv::tuple::ValueData> for BTreeIndex {
     ) -> Result<Vec<u8>, Error> {
         self.index
             .entry(key.clone())
-            .or_insert(BTreeSet::new())
+            .or_default()
             .insert(value.clone());
         let tuple = kv::tuple::KVTuple::new_insert(&key, &value);
         Ok(tuple.to_bytes())
@@ -333,7 +333,7 @@ impl IndexTrait<kv::tuple::KeyData, kv::tuple::ValueData> for HashMapIndex {
     ) -> Result<Vec<u8>, Error> {
         self.index
             .entry(key.clone())
-            .or_insert_with(BTreeSet::new)
+            .or_default()
             .insert(value.clone());
         let tuple = kv::tuple::KVTuple::new_insert(&key, &value);
         Ok(tuple.to_bytes())
@@ -599,7 +599,7 @@ mod tests {
         let v0 = vec![];
         let sync_bytes = btree_index.insert(k0.clone(), v0.clone()).unwrap();
         assert_eq!(btree_index.index.len(), 1);
-        assert_eq!(btree_index.exists(k0.clone(), v0.clone()), true);
+        assert!(btree_index.exists(k0.clone(), v0.clone()));
         assert_eq!(btree_index.get(k0.clone()), vec![v0.clone()]);
         assert_eq!(
             sync_bytes,
@@ -611,7 +611,7 @@ mod tests {
         let (k1, v1) = (vec![1], vec![1]);
         let sync_bytes = btree_index.insert(k1.clone(), v1.clone()).unwrap();
         assert_eq!(btree_index.index.len(), 2);
-        assert_eq!(btree_index.exists(k1.clone(), v1.clone()), true);
+        assert!(btree_index.exists(k1.clone(), v1.clone()));
         assert_eq!(btree_index.get(k1.clone()), vec![v1.clone()]);
         assert_eq!(
             sync_bytes,
@@ -621,7 +621,7 @@ mod tests {
         let v1_2 = vec![1, 1];
         let sync_bytes = btree_index.insert(k1.clone(), v1_2.clone()).unwrap();
         assert_eq!(btree_index.index.len(), 2);
-        assert_eq!(btree_index.exists(k1.clone(), v1_2.clone()), true);
+        assert!(btree_index.exists(k1.clone(), v1_2.clone()));
         assert_eq!(btree_index.get(k1.clone()), vec![v1.clone(), v1_2.clone()]);
         assert_eq!(
             sync_bytes,
@@ -631,7 +631,7 @@ mod tests {
         let (k2, v2) = (vec![2, 2], vec![2]);
         let sync_bytes = btree_index.insert(k2.clone(), v2.clone()).unwrap();
         assert_eq!(btree_index.index.len(), 3);
-        assert_eq!(btree_index.exists(k2.clone(), v2.clone()), true);
+        assert!(btree_index.exists(k2.clone(), v2.clone()));
         assert_eq!(btree_index.get(k2.clone()), vec![v2.clone()]);
         assert_eq!(
             sync_bytes,
@@ -641,7 +641,7 @@ mod tests {
         let v2_2 = vec![2, 2];
         let sync_bytes = btree_index.insert(k2.clone(), v2_2.clone()).unwrap();
         assert_eq!(btree_index.index.len(), 3);
-        assert_eq!(btree_index.exists(k2.clone(), v2_2.clone()), true);
+        assert!(btree_index.exists(k2.clone(), v2_2.clone()));
         assert_eq!(btree_index.get(k2.clone()), vec![v2.clone(), v2_2.clone()]);
         assert_eq!(
             sync_bytes,
@@ -651,7 +651,7 @@ mod tests {
         let v2_3 = vec![2, 2, 2];
         let sync_bytes = btree_index.insert(k2.clone(), v2_3.clone()).unwrap();
         assert_eq!(btree_index.index.len(), 3);
-        assert_eq!(btree_index.exists(k2.clone(), v2_3.clone()), true);
+        assert!(btree_index.exists(k2.clone(), v2_3.clone()));
         assert_eq!(
             btree_index.get(k2.clone()),
             vec![v2.clone(), v2_2.clone(), v2_3.clone()]
@@ -664,7 +664,7 @@ mod tests {
         let (k3, v3) = (vec![3, 3, 3], vec![3]);
         let sync_bytes = btree_index.insert(k3.clone(), v3.clone()).unwrap();
         assert_eq!(btree_index.index.len(), 4);
-        assert_eq!(btree_index.exists(k3.clone(), v3.clone()), true);
+        assert!(btree_index.exists(k3.clone(), v3.clone()));
         assert_eq!(btree_index.get(k3.clone()), vec![v3.clone()]);
         assert_eq!(
             sync_bytes,
@@ -674,7 +674,7 @@ mod tests {
         let v3_2 = vec![3, 3, 3];
         let sync_bytes = btree_index.insert(k3.clone(), v3_2.clone()).unwrap();
         assert_eq!(btree_index.index.len(), 4);
-        assert_eq!(btree_index.exists(k3.clone(), v3_2.clone()), true);
+        assert!(btree_index.exists(k3.clone(), v3_2.clone()));
         assert_eq!(btree_index.get(k3.clone()), vec![v3.clone(), v3_2.clone()]);
         assert_eq!(
             sync_bytes,
@@ -684,7 +684,7 @@ mod tests {
         let (k4, v4) = (vec![4, 4, 4, 4], vec![4]);
         let sync_bytes = btree_index.insert(k4.clone(), v4.clone()).unwrap();
         assert_eq!(btree_index.index.len(), 5);
-        assert_eq!(btree_index.exists(k4.clone(), v4.clone()), true);
+        assert!(btree_index.exists(k4.clone(), v4.clone()));
         assert_eq!(btree_index.get(k4.clone()), vec![v4.clone()]);
         assert_eq!(
             sync_bytes,
@@ -695,7 +695,7 @@ mod tests {
 
         let sync_bytes = btree_index.remove(k1.clone(), v1_2.clone()).unwrap();
         assert_eq!(btree_index.index.len(), 5);
-        assert_eq!(btree_index.exists(k1.clone(), v1_2.clone()), false);
+        assert!(!btree_index.exists(k1.clone(), v1_2.clone()));
         assert_eq!(btree_index.get(k1.clone()), vec![v1]);
         assert_eq!(
             sync_bytes,
@@ -704,7 +704,7 @@ mod tests {
 
         let sync_bytes = btree_index.remove(k2.clone(), v2_3.clone()).unwrap();
         assert_eq!(btree_index.index.len(), 5);
-        assert_eq!(btree_index.exists(k2.clone(), v2_3.clone()), false);
+        assert!(!btree_index.exists(k2.clone(), v2_3.clone()));
         assert_eq!(btree_index.get(k2.clone()), vec![v2, v2_2]);
         assert_eq!(
             sync_bytes,
@@ -713,7 +713,7 @@ mod tests {
 
         let sync_bytes = btree_index.remove(k0.clone(), v0.clone()).unwrap();
         assert_eq!(btree_index.index.len(), 4);
-        assert_eq!(btree_index.exists(k0.clone(), v0.clone()), false);
+        assert!(!btree_index.exists(k0.clone(), v0.clone()));
         assert_eq!(btree_index.get(k0.clone()), vec![] as Vec<Vec<u8>>);
         assert_eq!(sync_bytes, kv::tuple::KVTuple::new_delete(&k0).to_bytes());
 
@@ -740,7 +740,7 @@ mod tests {
         let v3_3 = vec![3, 3, 3];
         let sync_bytes = btree_index.insert(k3.clone(), v3_3.clone()).unwrap();
         assert_eq!(btree_index.index.len(), 4);
-        assert_eq!(btree_index.exists(k3.clone(), v3_3.clone()), true);
+        assert!(btree_index.exists(k3.clone(), v3_3.clone()));
         assert_eq!(btree_index.get(k3.clone()), vec![v3, v3_3.clone()]);
         assert_eq!(
             sync_bytes,
@@ -750,7 +750,7 @@ mod tests {
         let v4_2 = vec![4, 4];
         let sync_bytes = btree_index.insert(k4.clone(), v4_2.clone()).unwrap();
         assert_eq!(btree_index.index.len(), 4);
-        assert_eq!(btree_index.exists(k4.clone(), v4_2.clone()), true);
+        assert!(btree_index.exists(k4.clone(), v4_2.clone()));
         assert_eq!(btree_index.get(k4.clone()), vec![v4.clone(), v4_2.clone()]);
         assert_eq!(
             sync_bytes,
@@ -760,7 +760,7 @@ mod tests {
         let v4_3 = vec![4, 4, 4];
         let sync_bytes = btree_index.insert(k4.clone(), v4_3.clone()).unwrap();
         assert_eq!(btree_index.index.len(), 4);
-        assert_eq!(btree_index.exists(k4.clone(), v4_3.clone()), true);
+        assert!(btree_index.exists(k4.clone(), v4_3.clone()));
         assert_eq!(
             btree_index.get(k4.clone()),
             vec![v4.clone(), v4_2.clone(), v4_3.clone()]
@@ -773,7 +773,7 @@ mod tests {
         let v4_4 = vec![4, 4, 4, 4];
         let sync_bytes = btree_index.insert(k4.clone(), v4_4.clone()).unwrap();
         assert_eq!(btree_index.index.len(), 4);
-        assert_eq!(btree_index.exists(k4.clone(), v4_4.clone()), true);
+        assert!(btree_index.exists(k4.clone(), v4_4.clone()));
         assert_eq!(
             btree_index.get(k4.clone()),
             vec![v4.clone(), v4_2.clone(), v4_3.clone(), v4_4.clone()]
@@ -787,19 +787,19 @@ mod tests {
 
         let sync_bytes = btree_index.delete(k1.clone()).unwrap();
         assert_eq!(btree_index.index.len(), 3);
-        assert_eq!(btree_index.exists(k1.clone(), v1_2), false);
+        assert!(!btree_index.exists(k1.clone(), v1_2));
         assert_eq!(btree_index.get(k1.clone()), vec![] as Vec<Vec<u8>>);
         assert_eq!(sync_bytes, kv::tuple::KVTuple::new_delete(&k1).to_bytes());
 
         let sync_bytes = btree_index.delete(k2.clone()).unwrap();
         assert_eq!(btree_index.index.len(), 2);
-        assert_eq!(btree_index.exists(k2.clone(), v2_3), false);
+        assert!(!btree_index.exists(k2.clone(), v2_3));
         assert_eq!(btree_index.get(k2.clone()), vec![] as Vec<Vec<u8>>);
         assert_eq!(sync_bytes, kv::tuple::KVTuple::new_delete(&k2).to_bytes());
 
         let sync_bytes = btree_index.delete(k3.clone()).unwrap();
         assert_eq!(btree_index.index.len(), 1);
-        assert_eq!(btree_index.exists(k3.clone(), v3_3), false);
+        assert!(!btree_index.exists(k3.clone(), v3_3));
         assert_eq!(btree_index.get(k3.clone()), vec![] as Vec<Vec<u8>>);
         assert_eq!(sync_bytes, kv::tuple::KVTuple::new_delete(&k3).to_bytes());
 
@@ -822,7 +822,7 @@ mod tests {
 
         let sync_bytes = btree_index.remove(k4.clone(), v4.clone()).unwrap();
         assert_eq!(btree_index.index.len(), 1);
-        assert_eq!(btree_index.exists(k4.clone(), v4.clone()), false);
+        assert!(!btree_index.exists(k4.clone(), v4.clone()));
         assert_eq!(
             btree_index.get(k4.clone()),
             vec![v4_2.clone(), v4_3.clone(), v4_4.clone()]
@@ -834,7 +834,7 @@ mod tests {
 
         let sync_bytes = btree_index.remove(k4.clone(), v4_2.clone()).unwrap();
         assert_eq!(btree_index.index.len(), 1);
-        assert_eq!(btree_index.exists(k4.clone(), v4_2.clone()), false);
+        assert!(!btree_index.exists(k4.clone(), v4_2.clone()));
         assert_eq!(
             btree_index.get(k4.clone()),
             vec![v4_3.clone(), v4_4.clone()]
@@ -846,7 +846,7 @@ mod tests {
 
         let sync_bytes = btree_index.remove(k4.clone(), v4_3.clone()).unwrap();
         assert_eq!(btree_index.index.len(), 1);
-        assert_eq!(btree_index.exists(k4.clone(), v4_3.clone()), false);
+        assert!(!btree_index.exists(k4.clone(), v4_3.clone()));
         assert_eq!(btree_index.get(k4.clone()), vec![v4_4.clone()]);
         assert_eq!(
             sync_bytes,
@@ -855,7 +855,7 @@ mod tests {
 
         let sync_bytes = btree_index.remove(k4.clone(), v4_4.clone()).unwrap();
         assert_eq!(btree_index.index.len(), 0);
-        assert_eq!(btree_index.exists(k4.clone(), v4_4), false);
+        assert!(!btree_index.exists(k4.clone(), v4_4));
         assert_eq!(btree_index.get(k4.clone()), vec![] as Vec<Vec<u8>>);
         assert_eq!(sync_bytes, kv::tuple::KVTuple::new_delete(&k4).to_bytes());
     }
@@ -975,7 +975,7 @@ mod tests {
             assert_eq!(unique_btree_index.index.len(), expect_index_len);
 
             let exists = unique_btree_index.exists(key.to_vec(), value.to_vec());
-            assert_eq!(exists, true);
+            assert!(exists);
             let get_result = unique_btree_index.get(key.to_vec());
             assert_eq!(get_result, Some(value.to_vec()));
 
@@ -988,7 +988,7 @@ mod tests {
             assert_eq!(unique_btree_index.index.len(), expect_index_len);
 
             let exists = unique_btree_index.exists(key.to_vec(), value.to_vec());
-            assert_eq!(exists, true);
+            assert!(exists);
             let get_result = unique_btree_index.get(key.to_vec());
             assert_eq!(get_result, Some(value.to_vec()));
 
@@ -1002,7 +1002,7 @@ mod tests {
             assert_eq!(unique_btree_index.index.len(), expect_index_len);
 
             let exists = unique_btree_index.exists(key.to_vec(), value.to_vec());
-            assert_eq!(exists, true);
+            assert!(exists);
             let get_result = unique_btree_index.get(key.to_vec());
             assert_eq!(get_result, Some(value.to_vec()));
         }
@@ -1045,7 +1045,7 @@ mod tests {
             expect_index_len: usize,
         ) {
             let exists = unique_btree_index.exists(key.to_vec(), value.to_vec());
-            assert_eq!(exists, true);
+            assert!(exists);
             let get_result = unique_btree_index.get(key.to_vec());
             assert_eq!(get_result, Some(value.to_vec()));
 
@@ -1056,7 +1056,7 @@ mod tests {
             assert_eq!(unique_btree_index.index.len(), expect_index_len);
 
             let exists = unique_btree_index.exists(key.to_vec(), value.to_vec());
-            assert_eq!(exists, false);
+            assert!(!exists);
             let get_result = unique_btree_index.get(key.to_vec());
             assert_eq!(get_result, None);
 
@@ -1168,7 +1168,7 @@ mod tests {
         let v0 = vec![];
         let sync_bytes = hash_map_index.insert(k0.clone(), v0.clone()).unwrap();
         assert_eq!(hash_map_index.index.len(), 1);
-        assert_eq!(hash_map_index.exists(k0.clone(), v0.clone()), true);
+        assert!(hash_map_index.exists(k0.clone(), v0.clone()));
         assert_eq!(hash_map_index.get(k0.clone()), vec![v0.clone()]);
         assert_eq!(
             sync_bytes,
@@ -1180,7 +1180,7 @@ mod tests {
         let (k1, v1) = (vec![1], vec![1]);
         let sync_bytes = hash_map_index.insert(k1.clone(), v1.clone()).unwrap();
         assert_eq!(hash_map_index.index.len(), 2);
-        assert_eq!(hash_map_index.exists(k1.clone(), v1.clone()), true);
+        assert!(hash_map_index.exists(k1.clone(), v1.clone()));
         assert_eq!(hash_map_index.get(k1.clone()), vec![v1.clone()]);
         assert_eq!(
             sync_bytes,
@@ -1190,7 +1190,7 @@ mod tests {
         let v1_2 = vec![1, 1];
         let sync_bytes = hash_map_index.insert(k1.clone(), v1_2.clone()).unwrap();
         assert_eq!(hash_map_index.index.len(), 2);
-        assert_eq!(hash_map_index.exists(k1.clone(), v1_2.clone()), true);
+        assert!(hash_map_index.exists(k1.clone(), v1_2.clone()));
         assert_eq!(
             hash_map_index.get(k1.clone()),
             vec![v1.clone(), v1_2.clone()]
@@ -1203,7 +1203,7 @@ mod tests {
         let (k2, v2) = (vec![2, 2], vec![2]);
         let sync_bytes = hash_map_index.insert(k2.clone(), v2.clone()).unwrap();
         assert_eq!(hash_map_index.index.len(), 3);
-        assert_eq!(hash_map_index.exists(k2.clone(), v2.clone()), true);
+        assert!(hash_map_index.exists(k2.clone(), v2.clone()));
         assert_eq!(hash_map_index.get(k2.clone()), vec![v2.clone()]);
         assert_eq!(
             sync_bytes,
@@ -1213,7 +1213,7 @@ mod tests {
         let v2_2 = vec![2, 2];
         let sync_bytes = hash_map_index.insert(k2.clone(), v2_2.clone()).unwrap();
         assert_eq!(hash_map_index.index.len(), 3);
-        assert_eq!(hash_map_index.exists(k2.clone(), v2_2.clone()), true);
+        assert!(hash_map_index.exists(k2.clone(), v2_2.clone()));
         assert_eq!(
             hash_map_index.get(k2.clone()),
             vec![v2.clone(), v2_2.clone()]
@@ -1226,7 +1226,7 @@ mod tests {
         let v2_3 = vec![2, 2, 2];
         let sync_bytes = hash_map_index.insert(k2.clone(), v2_3.clone()).unwrap();
         assert_eq!(hash_map_index.index.len(), 3);
-        assert_eq!(hash_map_index.exists(k2.clone(), v2_3.clone()), true);
+        assert!(hash_map_index.exists(k2.clone(), v2_3.clone()));
         assert_eq!(
             hash_map_index.get(k2.clone()),
             vec![v2.clone(), v2_2.clone(), v2_3.clone()]
@@ -1239,7 +1239,7 @@ mod tests {
         let (k3, v3) = (vec![3, 3, 3], vec![3]);
         let sync_bytes = hash_map_index.insert(k3.clone(), v3.clone()).unwrap();
         assert_eq!(hash_map_index.index.len(), 4);
-        assert_eq!(hash_map_index.exists(k3.clone(), v3.clone()), true);
+        assert!(hash_map_index.exists(k3.clone(), v3.clone()));
         assert_eq!(hash_map_index.get(k3.clone()), vec![v3.clone()]);
         assert_eq!(
             sync_bytes,
@@ -1249,7 +1249,7 @@ mod tests {
         let v3_2 = vec![3, 3, 3];
         let sync_bytes = hash_map_index.insert(k3.clone(), v3_2.clone()).unwrap();
         assert_eq!(hash_map_index.index.len(), 4);
-        assert_eq!(hash_map_index.exists(k3.clone(), v3_2.clone()), true);
+        assert!(hash_map_index.exists(k3.clone(), v3_2.clone()));
         assert_eq!(
             hash_map_index.get(k3.clone()),
             vec![v3.clone(), v3_2.clone()]
@@ -1262,7 +1262,7 @@ mod tests {
         let (k4, v4) = (vec![4, 4, 4, 4], vec![4]);
         let sync_bytes = hash_map_index.insert(k4.clone(), v4.clone()).unwrap();
         assert_eq!(hash_map_index.index.len(), 5);
-        assert_eq!(hash_map_index.exists(k4.clone(), v4.clone()), true);
+        assert!(hash_map_index.exists(k4.clone(), v4.clone()));
         assert_eq!(hash_map_index.get(k4.clone()), vec![v4.clone()]);
         assert_eq!(
             sync_bytes,
@@ -1273,7 +1273,7 @@ mod tests {
 
         let sync_bytes = hash_map_index.remove(k1.clone(), v1_2.clone()).unwrap();
         assert_eq!(hash_map_index.index.len(), 5);
-        assert_eq!(hash_map_index.exists(k1.clone(), v1_2.clone()), false);
+        assert!(!hash_map_index.exists(k1.clone(), v1_2.clone()));
         assert_eq!(hash_map_index.get(k1.clone()), vec![v1]);
         assert_eq!(
             sync_bytes,
@@ -1282,7 +1282,7 @@ mod tests {
 
         let sync_bytes = hash_map_index.remove(k2.clone(), v2_3.clone()).unwrap();
         assert_eq!(hash_map_index.index.len(), 5);
-        assert_eq!(hash_map_index.exists(k2.clone(), v2_3.clone()), false);
+        assert!(!hash_map_index.exists(k2.clone(), v2_3.clone()));
         assert_eq!(hash_map_index.get(k2.clone()), vec![v2, v2_2]);
         assert_eq!(
             sync_bytes,
@@ -1291,7 +1291,7 @@ mod tests {
 
         let sync_bytes = hash_map_index.remove(k0.clone(), v0.clone()).unwrap();
         assert_eq!(hash_map_index.index.len(), 4);
-        assert_eq!(hash_map_index.exists(k0.clone(), v0.clone()), false);
+        assert!(!hash_map_index.exists(k0.clone(), v0.clone()));
         assert_eq!(hash_map_index.get(k0.clone()), vec![] as Vec<Vec<u8>>);
         assert_eq!(sync_bytes, kv::tuple::KVTuple::new_delete(&k0).to_bytes());
 
@@ -1318,7 +1318,7 @@ mod tests {
         let v3_3 = vec![3, 3, 3];
         let sync_bytes = hash_map_index.insert(k3.clone(), v3_3.clone()).unwrap();
         assert_eq!(hash_map_index.index.len(), 4);
-        assert_eq!(hash_map_index.exists(k3.clone(), v3_3.clone()), true);
+        assert!(hash_map_index.exists(k3.clone(), v3_3.clone()));
         assert_eq!(hash_map_index.get(k3.clone()), vec![v3, v3_3.clone()]);
         assert_eq!(
             sync_bytes,
@@ -1328,7 +1328,7 @@ mod tests {
         let v4_2 = vec![4, 4];
         let sync_bytes = hash_map_index.insert(k4.clone(), v4_2.clone()).unwrap();
         assert_eq!(hash_map_index.index.len(), 4);
-        assert_eq!(hash_map_index.exists(k4.clone(), v4_2.clone()), true);
+        assert!(hash_map_index.exists(k4.clone(), v4_2.clone()));
         assert_eq!(
             hash_map_index.get(k4.clone()),
             vec![v4.clone(), v4_2.clone()]
@@ -1341,7 +1341,7 @@ mod tests {
         let v4_3 = vec![4, 4, 4];
         let sync_bytes = hash_map_index.insert(k4.clone(), v4_3.clone()).unwrap();
         assert_eq!(hash_map_index.index.len(), 4);
-        assert_eq!(hash_map_index.exists(k4.clone(), v4_3.clone()), true);
+        assert!(hash_map_index.exists(k4.clone(), v4_3.clone()));
         assert_eq!(
             hash_map_index.get(k4.clone()),
             vec![v4.clone(), v4_2.clone(), v4_3.clone()]
@@ -1354,7 +1354,7 @@ mod tests {
         let v4_4 = vec![4, 4, 4, 4];
         let sync_bytes = hash_map_index.insert(k4.clone(), v4_4.clone()).unwrap();
         assert_eq!(hash_map_index.index.len(), 4);
-        assert_eq!(hash_map_index.exists(k4.clone(), v4_4.clone()), true);
+        assert!(hash_map_index.exists(k4.clone(), v4_4.clone()));
         assert_eq!(
             hash_map_index.get(k4.clone()),
             vec![v4.clone(), v4_2.clone(), v4_3.clone(), v4_4.clone()]
@@ -1368,19 +1368,19 @@ mod tests {
 
         let sync_bytes = hash_map_index.delete(k1.clone()).unwrap();
         assert_eq!(hash_map_index.index.len(), 3);
-        assert_eq!(hash_map_index.exists(k1.clone(), v1_2), false);
+        assert!(!hash_map_index.exists(k1.clone(), v1_2));
         assert_eq!(hash_map_index.get(k1.clone()), vec![] as Vec<Vec<u8>>);
         assert_eq!(sync_bytes, kv::tuple::KVTuple::new_delete(&k1).to_bytes());
 
         let sync_bytes = hash_map_index.delete(k2.clone()).unwrap();
         assert_eq!(hash_map_index.index.len(), 2);
-        assert_eq!(hash_map_index.exists(k2.clone(), v2_3), false);
+        assert!(!hash_map_index.exists(k2.clone(), v2_3));
         assert_eq!(hash_map_index.get(k2.clone()), vec![] as Vec<Vec<u8>>);
         assert_eq!(sync_bytes, kv::tuple::KVTuple::new_delete(&k2).to_bytes());
 
         let sync_bytes = hash_map_index.delete(k3.clone()).unwrap();
         assert_eq!(hash_map_index.index.len(), 1);
-        assert_eq!(hash_map_index.exists(k3.clone(), v3_3), false);
+        assert!(!hash_map_index.exists(k3.clone(), v3_3));
         assert_eq!(hash_map_index.get(k3.clone()), vec![] as Vec<Vec<u8>>);
         assert_eq!(sync_bytes, kv::tuple::KVTuple::new_delete(&k3).to_bytes());
 
@@ -1403,7 +1403,7 @@ mod tests {
 
         let sync_bytes = hash_map_index.remove(k4.clone(), v4.clone()).unwrap();
         assert_eq!(hash_map_index.index.len(), 1);
-        assert_eq!(hash_map_index.exists(k4.clone(), v4.clone()), false);
+        assert!(!hash_map_index.exists(k4.clone(), v4.clone()));
         assert_eq!(
             hash_map_index.get(k4.clone()),
             vec![v4_2.clone(), v4_3.clone(), v4_4.clone()]
@@ -1415,7 +1415,7 @@ mod tests {
 
         let sync_bytes = hash_map_index.remove(k4.clone(), v4_2.clone()).unwrap();
         assert_eq!(hash_map_index.index.len(), 1);
-        assert_eq!(hash_map_index.exists(k4.clone(), v4_2.clone()), false);
+        assert!(!hash_map_index.exists(k4.clone(), v4_2.clone()));
         assert_eq!(
             hash_map_index.get(k4.clone()),
             vec![v4_3.clone(), v4_4.clone()]
@@ -1427,7 +1427,7 @@ mod tests {
 
         let sync_bytes = hash_map_index.remove(k4.clone(), v4_3.clone()).unwrap();
         assert_eq!(hash_map_index.index.len(), 1);
-        assert_eq!(hash_map_index.exists(k4.clone(), v4_3.clone()), false);
+        assert!(!hash_map_index.exists(k4.clone(), v4_3.clone()));
         assert_eq!(hash_map_index.get(k4.clone()), vec![v4_4.clone()]);
         assert_eq!(
             sync_bytes,
@@ -1436,7 +1436,7 @@ mod tests {
 
         let sync_bytes = hash_map_index.remove(k4.clone(), v4_4.clone()).unwrap();
         assert_eq!(hash_map_index.index.len(), 0);
-        assert_eq!(hash_map_index.exists(k4.clone(), v4_4), false);
+        assert!(!hash_map_index.exists(k4.clone(), v4_4));
         assert_eq!(hash_map_index.get(k4.clone()), vec![] as Vec<Vec<u8>>);
         assert_eq!(sync_bytes, kv::tuple::KVTuple::new_delete(&k4).to_bytes());
     }
@@ -1556,7 +1556,7 @@ mod tests {
             assert_eq!(unique_hash_map_index.index.len(), expect_index_len);
 
             let exists = unique_hash_map_index.exists(key.to_vec(), value.to_vec());
-            assert_eq!(exists, true);
+            assert!(exists);
             let get_result = unique_hash_map_index.get(key.to_vec());
             assert_eq!(get_result, Some(value.to_vec()));
 
@@ -1569,7 +1569,7 @@ mod tests {
             assert_eq!(unique_hash_map_index.index.len(), expect_index_len);
 
             let exists = unique_hash_map_index.exists(key.to_vec(), value.to_vec());
-            assert_eq!(exists, true);
+            assert!(exists);
             let get_result = unique_hash_map_index.get(key.to_vec());
             assert_eq!(get_result, Some(value.to_vec()));
 
@@ -1583,7 +1583,7 @@ mod tests {
             assert_eq!(unique_hash_map_index.index.len(), expect_index_len);
 
             let exists = unique_hash_map_index.exists(key.to_vec(), value.to_vec());
-            assert_eq!(exists, true);
+            assert!(exists);
             let get_result = unique_hash_map_index.get(key.to_vec());
             assert_eq!(get_result, Some(value.to_vec()));
         }
@@ -1626,7 +1626,7 @@ mod tests {
             expect_index_len: usize,
         ) {
             let exists = unique_hash_map_index.exists(key.to_vec(), value.to_vec());
-            assert_eq!(exists, true);
+            assert!(exists);
             let get_result = unique_hash_map_index.get(key.to_vec());
             assert_eq!(get_result, Some(value.to_vec()));
 
@@ -1637,7 +1637,7 @@ mod tests {
             assert_eq!(unique_hash_map_index.index.len(), expect_index_len);
 
             let exists = unique_hash_map_index.exists(key.to_vec(), value.to_vec());
-            assert_eq!(exists, false);
+            assert!(!exists);
             let get_result = unique_hash_map_index.get(key.to_vec());
             assert_eq!(get_result, None);
 
@@ -50,10 +50,11 @@ fn btree_index_sync_new_log_file() {
         let mut file_writer: File = OpenOptions::new()
             .write(true)
             .create(true)
+            .truncate(false)
             .open(tmp_file_path)
             .unwrap();
         file_writer.seek(std::io::SeekFrom::End(0)).unwrap();
-        file_writer.write(&sync_bytes).unwrap();
+        file_writer.write_all(&sync_bytes).unwrap();
         // read from log file
         let bytes_in_log_file = read_full_file(tmp_file_path);
         let btree_index_from_file = BTreeIndex::from_bytes(&bytes_in_log_file);
@@ -81,10 +82,11 @@ fn btree_index_sync_new_log_file() {
         let mut file_writer: File = OpenOptions::new()
             .write(true)
             .create(true)
+            .truncate(false)
             .open(tmp_file_path)
             .unwrap();
         file_writer.seek(std::io::SeekFrom::End(0)).unwrap();
-        file_writer.write(&sync_bytes).unwrap();
+        file_writer.write_all(&sync_bytes).unwrap();
         // read from log file
         let bytes_in_log_file = read_full_file(tmp_file_path);
         let btree_index_from_file = BTreeIndex::from_bytes(&bytes_in_log_file);
@@ -107,10 +109,11 @@ fn btree_index_sync_new_log_file() {
         let mut file_writer: File = OpenOptions::new()
             .write(true)
             .create(true)
+            .truncate(false)
             .open(tmp_file_path)
             .unwrap();
         file_writer.seek(std::io::SeekFrom::End(0)).unwrap();
-        file_writer.write(&sync_bytes).unwrap();
+        file_writer.write_all(&sync_bytes).unwrap();
         // read from log file
         let bytes_in_log_file = read_full_file(tmp_file_path);
         let btree_index_from_file = BTreeIndex::from_bytes(&bytes_in_log_file);
@@ -124,7 +127,7 @@ fn btree_index_sync_new_log_file() {
     }
 
     // let tmp_file_path = "./tmp/btree_index_sync_new_log_file.hex";
-    let tmp_dir_path = tempfile::tempdir().unwrap().into_path();
+    let tmp_dir_path = tempfile::tempdir().unwrap().keep();
     let tmp_file_path: std::path::PathBuf = [
         tmp_dir_path.to_str().unwrap().to_string(),
         String::from("btree_index_sync_new_log_file.hex"),
@@ -183,6 +186,7 @@ fn btree_index_sync_new_log_file() {
     remove_dir_contents(tmp_dir_path);
 }
 
+#[test]
 fn btree_index_sync_existing_log_file() {}
 
 // ... ... ... ... ... ... ... ... ... ... ... ... ... ... ... ..
@@ -208,10 +212,11 @@ fn unqiue_btree_index_sync_new_log_file() {
         let mut file_writer: File = OpenOptions::new()
             .write(true)
             .create(true)
+            .truncate(false)
             .open(tmp_file_path)
             .unwrap();
         file_writer.seek(std::io::SeekFrom::End(0)).unwrap();
-        file_writer.write(&sync_bytes).unwrap();
+        file_writer.write_all(&sync_bytes).unwrap();
         // read from log file
         let bytes_in_log_file = read_full_file(tmp_file_path);
         let btree_index_from_file = UniqueBTreeIndex::from_bytes(&bytes_in_log_file);
@@ -236,10 +241,11 @@ fn unqiue_btree_index_sync_new_log_file() {
         let mut file_writer: File = OpenOptions::new()
             .write(true)
             .create(true)
+            .truncate(false)
             .open(tmp_file_path)
             .unwrap();
         file_writer.seek(std::io::SeekFrom::End(0)).unwrap();
-        file_writer.write(&sync_bytes).unwrap();
+        file_writer.write_all(&sync_bytes).unwrap();
         // read from log file
         let bytes_in_log_file = read_full_file(tmp_file_path);
         let btree_index_from_file = UniqueBTreeIndex::from_bytes(&bytes_in_log_file);
@@ -253,7 +259,7 @@ fn unqiue_btree_index_sync_new_log_file() {
     }
 
     // let tmp_file_path = "./tmp/unqiue_btree_index_sync_new_log_file.hex";
-    let tmp_dir_path = tempfile::tempdir().unwrap().into_path();
+    let tmp_dir_path = tempfile::tempdir().unwrap().keep();
     let tmp_file_path: std::path::PathBuf = [
         tmp_dir_path.to_str().unwrap().to_string(),
         String::from("unqiue_btree_index_sync_new_log_file.hex"),
@@ -314,10 +320,11 @@ fn hash_map_index_new_log_file() {
         let mut file_writer: File = OpenOptions::new()
             .write(true)
             .create(true)
+            .truncate(false)
             .open(tmp_file_path)
             .unwrap();
         file_writer.seek(std::io::SeekFrom::End(0)).unwrap();
-        file_writer.write(&sync_bytes).unwrap();
+        file_writer.write_all(&sync_bytes).unwrap();
         // read from log file
         let bytes_in_log_file = read_full_file(tmp_file_path);
         let hash_map_index_from_file = HashMapIndex::from_bytes(&bytes_in_log_file);
@@ -345,10 +352,11 @@ fn hash_map_index_new_log_file() {
         let mut file_writer: File = OpenOptions::new()
             .write(true)
             .create(true)
+            .truncate(false)
             .open(tmp_file_path)
             .unwrap();
         file_writer.seek(std::io::SeekFrom::End(0)).unwrap();
-        file_writer.write(&sync_bytes).unwrap();
+        file_writer.write_all(&sync_bytes).unwrap();
         // read from log file
         let bytes_in_log_file = read_full_file(tmp_file_path);
         let hash_map_index_from_file = HashMapIndex::from_bytes(&bytes_in_log_file);
@@ -375,10 +383,11 @@ fn hash_map_index_new_log_file() {
         let mut file_writer: File = OpenOptions::new()
             .write(true)
             .create(true)
+            .truncate(false)
             .open(tmp_file_path)
             .unwrap();
         file_writer.seek(std::io::SeekFrom::End(0)).unwrap();
-        file_writer.write(&sync_bytes).unwrap();
+        file_writer.write_all(&sync_bytes).unwrap();
         // read from log file
         let bytes_in_log_file = read_full_file(tmp_file_path);
         let hash_map_index_from_file = HashMapIndex::from_bytes(&bytes_in_log_file);
@@ -392,7 +401,7 @@ fn hash_map_index_new_log_file() {
     }
 
     // let tmp_file_path = "./tmp/hash_map_index_test.hex";
-    let tmp_dir_path = tempfile::tempdir().unwrap().into_path();
+    let tmp_dir_path = tempfile::tempdir().unwrap().keep();
     let tmp_file_path: std::path::PathBuf = [
         tmp_dir_path.to_str().unwrap().to_string(),
         String::from("hash_map_index_test.hex"),
@@ -474,10 +483,11 @@ fn unique_hash_map_index_new_log_file() {
         let mut file_writer: File = OpenOptions::new()
             .write(true)
             .create(true)
+            .truncate(false)
             .open(tmp_file_path)
             .unwrap();
         file_writer.seek(std::io::SeekFrom::End(0)).unwrap();
-        file_writer.write(&sync_bytes).unwrap();
+        file_writer.write_all(&sync_bytes).unwrap();
         // read from log file
         let bytes_in_log_file = read_full_file(tmp_file_path);
         let hash_map_index_from_file = UniqueHashMapIndex::from_bytes(&bytes_in_log_file);
@@ -502,10 +512,11 @@ fn unique_hash_map_index_new_log_file() {
         let mut file_writer: File = OpenOptions::new()
             .write(true)
             .create(true)
+            .truncate(false)
             .open(tmp_file_path)
             .unwrap();
         file_writer.seek(std::io::SeekFrom::End(0)).unwrap();
-        file_writer.write(&sync_bytes).unwrap();
+        file_writer.write_all(&sync_bytes).unwrap();
         // read from log file
         let bytes_in_log_file = read_full_file(tmp_file_path);
         let hash_map_index_from_file = UniqueHashMapIndex::from_bytes(&bytes_in_log_file);
@@ -519,7 +530,7 @@ fn unique_hash_map_index_new_log_file() {
     }
 
     // let tmp_file_path = "./tmp/unqiue_hash_map_index_sync_new_log_file.hex";
-    let tmp_dir_path = tempfile::tempdir().unwrap().into_path();
+    let tmp_dir_path = tempfile::tempdir().unwrap().keep();
     let tmp_file_path: std::path::PathBuf = [
         tmp_dir_path.to_str().unwrap().to_string(),
         String::from("unqiue_hash_map_index_sync_new_log_file.hex"),
@@ -0,0 +1,119 @@
+use index::index_traits::{IndexCloneTrait, IndexSerializationTrait, IndexTrait};
+use index::BTreeIndex;
+use storage::block_device::{Fault, FaultyBlockDevice, MemoryBlockDevice};
+use storage::{Storage, StorageOptions};
+
+/// Storage with block checksums on a memory device wrapped in a fault injecting device
+/// - returns (storage, device) - device is a clone, to script faults with
+fn new_faulty_storage(block_len: u32) -> (Storage, FaultyBlockDevice) {
+    let device = FaultyBlockDevice::new(Box::new(MemoryBlockDevice::new()));
+    let options = StorageOptions {
+        block_checksum: true,
+        ..Default::default()
+    };
+    let storage = Storage::new_with_device(Box::new(device.clone()), block_len, options).unwrap();
+    (storage, device)
+}
+
+/// Index as synced to log in storage
+fn btree_index_from_log(
+    storage: &mut Storage,
+    log_first_block_index: storage::BlockIndex,
+) -> Result<BTreeIndex, util::error::Error> {
+    let (_, _, bytes_in_log_file) = logchain::read_log(storage, log_first_block_index)?;
+    BTreeIndex::from_bytes(&bytes_in_log_file)
+}
+
+#[test]
+fn btree_index_sync_storage_faults() {
+    let (mut storage, device) = new_faulty_storage(16);
+    let (log_first_block_index, _) = logchain::create_log(&mut storage, &[]).unwrap();
+    let mut btree_index = BTreeIndex::from_bytes(&[]).unwrap();
+    let sync_bytes = btree_index
+        .insert(b"1x1".to_vec(), b"One".to_vec())
+        .unwrap();
+    logchain::append_log(&mut storage, log_first_block_index, &sync_bytes).unwrap();
+    let synced_btree_index = btree_index.clone();
+
+    // - append of next tuple fails, log holds last synced index
+    let sync_bytes = btree_index
+        .insert(b"2x2".to_vec(), b"Four".to_vec())
+        .unwrap();
+    device.fail_write(0, Fault::NoSpace);
+    let result = logchain::append_log(&mut storage, log_first_block_index, &sync_bytes);
+    assert_eq!(
+        result.err().unwrap().code(),
+        "write_blocks_failed_to_write_blocks"
+    );
+    let btree_index_from_file = btree_index_from_log(&mut storage, log_first_block_index).unwrap();
+    assert_eq!(
+        btree_index_from_file.index_clone(),
+        synced_btree_index.index_clone()
+    );
+
+    // - retry syncs the tuple
+    logchain::append_log(&mut storage, log_first_block_index, &sync_bytes).unwrap();
+    let btree_index_from_file = btree_index_from_log(&mut storage, log_first_block_index).unwrap();
+    assert_eq!(
+        btree_index_from_file.index_clone(),
+        btree_index.index_clone()
+    );
+}
+
+#[test]
+fn btree_index_sync_storage_crash() {
+    // crash at every byte written by sync, till sync completes without crash
+    let mut crash_after_bytes = 0;
+    loop {
+        let (mut storage, device) = new_faulty_storage(16);
+        let (log_first_block_index, _) = logchain::create_log(&mut storage, &[]).unwrap();
+        let mut btree_index = BTreeIndex::from_bytes(&[]).unwrap();
+        for (key, value) in [("1x1", "1"), ("2x2", "4")] {
+            let sync_bytes = btree_index
+                .insert(key.as_bytes().to_vec(), value.as_bytes().to_vec())
+                .unwrap();
+            logchain::append_log(&mut storage, log_first_block_index, &sync_bytes).unwrap();
+        }
+        let synced_btree_index = btree_index.clone();
+        let sync_bytes = btree_index
+            .insert(b"3x3".to_vec(), b"Nine".to_vec())
+            .unwrap();
+        device.crash_after_bytes(crash_after_bytes);
+        let result = logchain::append_log(&mut storage, log_first_block_index, &sync_bytes);
+        let crashed = result.is_err();
+        drop(storage);
+        // - reopen, log holds last synced or new index, or fails cleanly
+        device.revive();
+        let mut storage =
+            Storage::open_device(Box::new(device.clone()), StorageOptions::default()).unwrap();
+        match btree_index_from_log(&mut storage, log_first_block_index) {
+            Ok(btree_index_from_file) => {
+                let index_from_file = btree_index_from_file.index_clone();
+                assert!(
+                    index_from_file == synced_btree_index.index_clone()
+                        || index_from_file == btree_index.index_clone(),
+                    "crash after {} bytes",
+                    crash_after_bytes
+                );
+            }
+            Err(error) => assert!(
+                crashed
+                    && [
+                        "read_block_checksum_mismatch",
+                        "read_block_failed_to_read_block_data",
+                        "read_block_failed_to_read_block_checksum",
+                        "block_index_from_buffer_insufficient_buffer_size",
+                    ]
+                    .contains(&error.code()),
+                "crash after {} bytes: {}",
+                crash_after_bytes,
+                error.code()
+            ),
+        }
+        if !crashed {
+            break;
+        }
+        crash_after_bytes += 1;
+    }
+    assert!(crash_after_bytes > 0);
+}
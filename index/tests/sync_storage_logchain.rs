@@ -33,7 +33,7 @@ fn remove_dir_contents(path: std::path::PathBuf) {
 
 #[test]
 fn btree_index_sync_storage_logchain() {
-    let tmp_dir_path = tempfile::tempdir().unwrap().into_path();
+    let tmp_dir_path = tempfile::tempdir().unwrap().keep();
     let tmp_file_path: std::path::PathBuf = [
         tmp_dir_path.to_str().unwrap().to_string(),
         String::from("btree_index_sync_storage_logchain_0.hex"),
@@ -55,12 +55,16 @@ fn btree_index_sync_storage_logchain() {
 
     // read storage file
     let data = read_full_file(tmp_file_path);
-    // [16, 0, 0, 0, 4, 0, 0, 0, 255, 255, 255, 255]
+    // [b"XDBS", 1, 0, 0, 0, 0, 0, 0, 0, 16, 0, 0, 0, [0; 16], 4, 0, 0, 0, 255, 255, 255, 255]
     assert_eq!(
         data,
         vec![
-            // block_len
-            16, 0, 0, 0, // First block
+            // magic
+            b'X', b'D', b'B', b'S', // format version
+            1, 0, 0, 0, // feature flags
+            0, 0, 0, 0, // block_len
+            16, 0, 0, 0, // reserved
+            0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, // First block
             // - block_data_size
             4, 0, 0, 0, // - block_data = next_block_index in logchain
             255, 255, 255, 255
@@ -253,7 +257,7 @@ fn btree_index_sync_storage_logchain() {
 
 #[test]
 fn unqiue_btree_index_sync_storage_logchain() {
-    let tmp_dir_path = tempfile::tempdir().unwrap().into_path();
+    let tmp_dir_path = tempfile::tempdir().unwrap().keep();
     let tmp_file_path: std::path::PathBuf = [
         tmp_dir_path.to_str().unwrap().to_string(),
         String::from("unqiue_btree_index_sync_storage_logchain_0.hex"),
@@ -275,12 +279,16 @@ fn unqiue_btree_index_sync_storage_logchain() {
 
     // read storage file
     let data = read_full_file(tmp_file_path);
-    // [16, 0, 0, 0, 4, 0, 0, 0, 255, 255, 255, 255]
+    // [b"XDBS", 1, 0, 0, 0, 0, 0, 0, 0, 16, 0, 0, 0, [0; 16], 4, 0, 0, 0, 255, 255, 255, 255]
     assert_eq!(
         data,
         vec![
-            // block_len
-            16, 0, 0, 0, // First block
+            // magic
+            b'X', b'D', b'B', b'S', // format version
+            1, 0, 0, 0, // feature flags
+            0, 0, 0, 0, // block_len
+            16, 0, 0, 0, // reserved
+            0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, // First block
             // - block_data_size
             4, 0, 0, 0, // - block_data = next_block_index in logchain
             255, 255, 255, 255
@@ -392,7 +400,7 @@ fn unqiue_btree_index_sync_storage_logchain() {
 
 #[test]
 fn hash_map_index_sync_storage_logchain() {
-    let tmp_dir_path = tempfile::tempdir().unwrap().into_path();
+    let tmp_dir_path = tempfile::tempdir().unwrap().keep();
     let tmp_file_path: std::path::PathBuf = [
         tmp_dir_path.to_str().unwrap().to_string(),
         String::from("hash_map_index_sync_storage_logchain_0.hex"),
@@ -414,12 +422,16 @@ fn hash_map_index_sync_storage_logchain() {
 
     // read storage file
     let data = read_full_file(tmp_file_path);
-    // [16, 0, 0, 0, 4, 0, 0, 0, 255, 255, 255, 255]
+    // [b"XDBS", 1, 0, 0, 0, 0, 0, 0, 0, 16, 0, 0, 0, [0; 16], 4, 0, 0, 0, 255, 255, 255, 255]
     assert_eq!(
         data,
         vec![
-            // block_len
-            16, 0, 0, 0, // First block
+            // magic
+            b'X', b'D', b'B', b'S', // format version
+            1, 0, 0, 0, // feature flags
+            0, 0, 0, 0, // block_len
+            16, 0, 0, 0, // reserved
+            0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, // First block
             // - block_data_size
             4, 0, 0, 0, // - block_data = next_block_index in logchain
             255, 255, 255, 255
@@ -616,7 +628,7 @@ fn hash_map_index_sync_storage_logchain() {
 
 #[test]
 fn unique_hash_map_index_sync_storage_logchain() {
-    let tmp_dir_path = tempfile::tempdir().unwrap().into_path();
+    let tmp_dir_path = tempfile::tempdir().unwrap().keep();
     let tmp_file_path: std::path::PathBuf = [
         tmp_dir_path.to_str().unwrap().to_string(),
         String::from("unique_hash_map_index_sync_storage_logchain_0.hex"),
@@ -638,12 +650,16 @@ fn unique_hash_map_index_sync_storage_logchain() {
 
     // read storage file
     let data = read_full_file(tmp_file_path);
-    // [16, 0, 0, 0, 4, 0, 0, 0, 255, 255, 255, 255]
+    // [b"XDBS", 1, 0, 0, 0, 0, 0, 0, 0, 16, 0, 0, 0, [0; 16], 4, 0, 0, 0, 255, 255, 255, 255]
     assert_eq!(
         data,
         vec![
-            // block_len
-            16, 0, 0, 0, // First block
+            // magic
+            b'X', b'D', b'B', b'S', // format version
+            1, 0, 0, 0, // feature flags
+            0, 0, 0, 0, // block_len
+            16, 0, 0, 0, // reserved
+            0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, // First block
             // - block_data_size
             4, 0, 0, 0, // - block_data = next_block_index in logchain
             255, 255, 255, 255
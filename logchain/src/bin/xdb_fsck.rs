@@ -0,0 +1,83 @@
+//! Check a logchain storage file for problems, and repair those safe to repair
+//!
+//! Usage: xdb_fsck <storage file> [--repair] [--head <block index>]...
+//! - heads of logs in roots of storage are always checked, `--head` adds heads stored elsewhere
+//! - exits with 0 if no problem is left, 1 if a problem is left unrepaired, 2 if check fails
+
+use logchain::check_logs;
+use storage::fsck::CheckReport;
+use storage::{BlockIndex, Storage};
+
+const USAGE: &str = "Usage: xdb_fsck <storage file> [--repair] [--head <block index>]...";
+
+fn print_report<P: std::fmt::Debug>(report: &CheckReport<P>) {
+    for finding in report.findings.iter() {
+        let status = if finding.repaired {
+            "repaired"
+        } else {
+            "found"
+        };
+        println!("{}: {:?}", status, finding.problem);
+    }
+}
+
+fn main() {
+    let mut file_path = None;
+    let mut repair = false;
+    let mut heads: Vec<BlockIndex> = vec![];
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--repair" => repair = true,
+            "--head" => match args.next().and_then(|head| head.parse().ok()) {
+                Some(head) => heads.push(head),
+                None => {
+                    eprintln!("{}", USAGE);
+                    std::process::exit(2);
+                }
+            },
+            _ if file_path.is_none() && !arg.starts_with("--") => file_path = Some(arg),
+            _ => {
+                eprintln!("{}", USAGE);
+                std::process::exit(2);
+            }
+        }
+    }
+    let file_path = match file_path {
+        Some(file_path) => file_path,
+        None => {
+            eprintln!("{}", USAGE);
+            std::process::exit(2);
+        }
+    };
+
+    // - blocks of storage file
+    let storage_report = match Storage::check_file(file_path.clone(), repair) {
+        Ok(report) => report,
+        Err(error) => {
+            eprintln!("{:?}", error);
+            std::process::exit(2);
+        }
+    };
+    print_report(&storage_report);
+
+    // - logs, walked from their heads
+    let storage = if repair {
+        Storage::open(file_path)
+    } else {
+        Storage::open_read_only(file_path)
+    };
+    let log_report = storage.and_then(|mut storage| check_logs(&mut storage, &heads, repair));
+    let log_report = match log_report {
+        Ok(report) => report,
+        Err(error) => {
+            eprintln!("{:?}", error);
+            std::process::exit(2);
+        }
+    };
+    print_report(&log_report);
+
+    if storage_report.unrepaired().next().is_some() || log_report.unrepaired().next().is_some() {
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,183 @@
+use crate::segment_block_index::{
+    block_index_from_buffer, block_index_to_buffer, LAST_NEXT_BLOCK_INDEX,
+};
+use std::collections::{BTreeMap, BTreeSet};
+use storage::fsck::CheckReport;
+use storage::{BlockIndex, Storage};
+use util::error::Error;
+
+/// Problem found in logs of a storage by `check_logs`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LogProblem {
+    /// Head of a log is beyond end of storage, or a free block, not repaired
+    InvalidHead { head: BlockIndex },
+    /// Segment can not be read, e.g. its checksum does not match, or it is shorter than a link
+    /// - log is cut before it by repair
+    UnreadableSegment {
+        head: BlockIndex,
+        block_index: BlockIndex,
+        code: String,
+    },
+    /// Segment links to a block beyond end of storage
+    /// - log is cut after the segment by repair
+    NextPastEnd {
+        head: BlockIndex,
+        block_index: BlockIndex,
+        next_block_index: BlockIndex,
+    },
+    /// Segment links to a free block
+    /// - log is cut after the segment by repair
+    NextToFreeBlock {
+        head: BlockIndex,
+        block_index: BlockIndex,
+        next_block_index: BlockIndex,
+    },
+    /// Segment links back to a segment of its own log
+    /// - log is cut after the segment by repair
+    Cycle {
+        head: BlockIndex,
+        block_index: BlockIndex,
+        next_block_index: BlockIndex,
+    },
+    /// Segment is reachable from two heads, not repaired
+    SharedBlock {
+        block_index: BlockIndex,
+        heads: (BlockIndex, BlockIndex),
+    },
+    /// Used block not reachable from any head, not repaired
+    /// - heads may be stored outside storage, so it is left for the caller to delete
+    OrphanBlock { block_index: BlockIndex },
+}
+
+/// Check logs of a storage, walking each from its head, and repair those safe to repair if asked
+/// - heads: heads of logs stored outside storage, heads in roots of storage are always checked
+/// - every used block of storage must be a segment of a log, except blocks of root tables
+/// - repair cuts a log at its last intact segment, so its head still reads the intact part
+pub fn check_logs(
+    storage: &mut Storage,
+    heads: &[BlockIndex],
+    repair: bool,
+) -> Result<CheckReport<LogProblem>, Error> {
+    let block_index_width = storage.block_index_width();
+    let mut report = CheckReport::default();
+    let mut heads = heads.iter().cloned().collect::<BTreeSet<BlockIndex>>();
+    heads.extend(
+        storage
+            .list_roots()
+            .into_iter()
+            .map(|(_, block_index)| block_index),
+    );
+
+    // - walk each log from its head, recording the head each segment is reached from
+    let mut owners = BTreeMap::<BlockIndex, BlockIndex>::new();
+    let mut cut_segments = vec![];
+    for head in heads {
+        if head >= storage.end_block_count() || storage.is_free_block(head) {
+            report.push(LogProblem::InvalidHead { head }, false);
+            continue;
+        }
+        if let Some(owner) = owners.get(&head) {
+            report.push(
+                LogProblem::SharedBlock {
+                    block_index: head,
+                    heads: (*owner, head),
+                },
+                false,
+            );
+            continue;
+        }
+        let mut previous_block_index = None;
+        let mut block_index = head;
+        loop {
+            owners.insert(block_index, head);
+            // -- read segment & its link
+            let next_block_index = match storage.read_block(block_index) {
+                Ok((_, segment_payload)) => {
+                    block_index_from_buffer(block_index_width, &segment_payload)
+                }
+                Err(error) => Err(error),
+            };
+            let next_block_index = match next_block_index {
+                Ok(next_block_index) => next_block_index,
+                Err(error) => {
+                    let problem = LogProblem::UnreadableSegment {
+                        head,
+                        block_index,
+                        code: error.code().to_string(),
+                    };
+                    // log without an intact head can not be cut
+                    report.push(problem, repair && previous_block_index.is_some());
+                    if let Some(previous_block_index) = previous_block_index {
+                        cut_segments.push(previous_block_index);
+                    }
+                    break;
+                }
+            };
+            if next_block_index == LAST_NEXT_BLOCK_INDEX {
+                break;
+            }
+            // -- link must point to a used block, not yet reached
+            let problem = if next_block_index >= storage.end_block_count() {
+                Some(LogProblem::NextPastEnd {
+                    head,
+                    block_index,
+                    next_block_index,
+                })
+            } else if storage.is_free_block(next_block_index) {
+                Some(LogProblem::NextToFreeBlock {
+                    head,
+                    block_index,
+                    next_block_index,
+                })
+            } else {
+                match owners.get(&next_block_index) {
+                    Some(owner) if *owner == head => Some(LogProblem::Cycle {
+                        head,
+                        block_index,
+                        next_block_index,
+                    }),
+                    Some(owner) => {
+                        report.push(
+                            LogProblem::SharedBlock {
+                                block_index: next_block_index,
+                                heads: (*owner, head),
+                            },
+                            false,
+                        );
+                        break;
+                    }
+                    None => None,
+                }
+            };
+            if let Some(problem) = problem {
+                report.push(problem, repair);
+                cut_segments.push(block_index);
+                break;
+            }
+            previous_block_index = Some(block_index);
+            block_index = next_block_index;
+        }
+    }
+
+    // - used blocks not reached from any head
+    for block_index in storage.used_application_blocks() {
+        if !owners.contains_key(&block_index) {
+            report.push(LogProblem::OrphanBlock { block_index }, false);
+        }
+    }
+
+    // - cut logs at their last intact segment
+    if repair {
+        for block_index in cut_segments {
+            let (_, segment_payload) = storage.read_block(block_index)?;
+            let segment_payload = [
+                &block_index_to_buffer(block_index_width, LAST_NEXT_BLOCK_INDEX),
+                &segment_payload[block_index_width.size()..],
+            ]
+            .concat();
+            storage.write_block(block_index, &segment_payload)?;
+        }
+        storage.sync()?;
+    }
+    Ok(report)
+}
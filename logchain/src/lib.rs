@@ -1,23 +1,65 @@
-use storage::{BlockIndex, Storage};
+use storage::{BlockIndex, BlockIndexWidth, BlockRemap, Storage, StorageOptions, Transaction};
 use util::error::Error;
 use util::make_chunks;
 
 mod logchain_errors;
 
+mod fsck;
+pub use fsck::{check_logs, LogProblem};
+
 mod segment_block_index;
-use segment_block_index::{
-    block_index_from_buffer, block_index_to_buffer, BLOCK_INDEX_SIZE, LAST_NEXT_BLOCK_INDEX,
-};
+use segment_block_index::{block_index_from_buffer, block_index_to_buffer, LAST_NEXT_BLOCK_INDEX};
+
+/// Blocks segments of a log are written to
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SegmentAllocation {
+    /// Blocks picked by block allocator of storage
+    #[default]
+    Storage,
+    /// A run of contiguous free blocks if one exists, so the log is read without seeking,
+    /// else blocks picked by block allocator of storage
+    Contiguous,
+}
+
+/// Blocks to write given number of segments to, as per segment allocation
+/// - returns `storage_full` of storage, if storage would grow past its quota
+fn search_segment_block_indexes(
+    storage: &Storage,
+    count: BlockIndex,
+    allocation: SegmentAllocation,
+) -> Result<Vec<BlockIndex>, Error> {
+    match allocation {
+        SegmentAllocation::Storage => storage.search_block_allocation_indexes(count),
+        SegmentAllocation::Contiguous => {
+            match storage.search_contiguous_block_allocation_indexes(count) {
+                Some(block_indexes) => Ok(block_indexes),
+                None => storage.search_block_allocation_indexes(count),
+            }
+        }
+    }
+}
 
 type MakeSegmentPayloadListResult =
     Result<(Vec<(BlockIndex, Vec<u8>)>, BlockIndex, BlockIndex), Error>;
 /// Returns (Vector<(next_block_index, data_chunk)>, first_block_index, last_block_index)
+/// - returns `storage_full` if storage would grow past its quota, see `StorageOptions::quota`
 pub fn make_segment_payload_list(storage: &Storage, data: &[u8]) -> MakeSegmentPayloadListResult {
+    make_segment_payload_list_with_allocation(storage, data, SegmentAllocation::Storage)
+}
+
+/// Make segment payload list, with blocks picked as per given segment allocation
+/// - Returns (Vector<(next_block_index, data_chunk)>, first_block_index, last_block_index)
+pub fn make_segment_payload_list_with_allocation(
+    storage: &Storage,
+    data: &[u8],
+    allocation: SegmentAllocation,
+) -> MakeSegmentPayloadListResult {
     let block_len = storage.block_len() as usize;
-    let chunk_len = block_len - BLOCK_INDEX_SIZE;
+    let block_index_width = storage.block_index_width();
+    let chunk_len = block_len - block_index_width.size();
     let (blocks_required, chunks) = make_chunks(data, chunk_len);
     if blocks_required == 0 {
-        let block_indexes = storage.search_block_allocation_indexes(1);
+        let block_indexes = search_segment_block_indexes(storage, 1, allocation)?;
         if block_indexes.is_empty() {
             return Err(
                 logchain_errors::make_segment_payload_list_insufficient_blocks(blocks_required),
@@ -27,13 +69,18 @@ pub fn make_segment_payload_list(storage: &Storage, data: &[u8]) -> MakeSegmentP
         return Ok((
             vec![(
                 block_indexes[0],
-                [data, &block_index_to_buffer(LAST_NEXT_BLOCK_INDEX)].concat(),
+                [
+                    data,
+                    &block_index_to_buffer(block_index_width, LAST_NEXT_BLOCK_INDEX),
+                ]
+                .concat(),
             )],
             block_index,
             block_index,
         ));
     }
-    let block_indexes = storage.search_block_allocation_indexes(blocks_required as BlockIndex);
+    let block_indexes =
+        search_segment_block_indexes(storage, blocks_required as BlockIndex, allocation)?;
     if block_indexes.len() < blocks_required {
         return Err(
             logchain_errors::make_segment_payload_list_insufficient_blocks(blocks_required),
@@ -45,11 +92,14 @@ pub fn make_segment_payload_list(storage: &Storage, data: &[u8]) -> MakeSegmentP
             (
                 block_indexes[block_index], // block_index to store segment
                 [
-                    &block_index_to_buffer(if block_index < block_indexes.len() - 1 {
-                        block_indexes[block_index + 1]
-                    } else {
-                        LAST_NEXT_BLOCK_INDEX
-                    }), // next block index
+                    &block_index_to_buffer(
+                        block_index_width,
+                        if block_index < block_indexes.len() - 1 {
+                            block_indexes[block_index + 1]
+                        } else {
+                            LAST_NEXT_BLOCK_INDEX
+                        },
+                    )[..], // next block index
                     data_chunk, // segment payload
                 ]
                 .concat(),
@@ -62,33 +112,105 @@ pub fn make_segment_payload_list(storage: &Storage, data: &[u8]) -> MakeSegmentP
     Ok((segment_payloads, first_block_index, last_block_index))
 }
 
+/// Write segment payloads to their blocks, as a group
+fn write_segment_payload_list(
+    storage: &mut Storage,
+    payload_list: &[(BlockIndex, Vec<u8>)],
+) -> Result<usize, Error> {
+    let blocks = payload_list
+        .iter()
+        .map(|(block_index, segment_payload)| (*block_index, &segment_payload[..]))
+        .collect::<Vec<(BlockIndex, &[u8])>>();
+    storage.write_blocks(&blocks)
+}
+
 /// Add new log to storage with new block index
+/// - Segments are written as a group, with `Storage::write_blocks`
+/// - Log is reachable only through returned first_block_index, call `Storage::sync_barrier`
+///   before storing it in a block, to make the log durable ahead of the link to it
 /// - Returns (first_block_index, last_block_index)
 pub fn create_log(storage: &mut Storage, data: &[u8]) -> Result<(BlockIndex, BlockIndex), Error> {
+    create_log_with_allocation(storage, data, SegmentAllocation::Storage)
+}
+
+/// Add new log to storage, with blocks of segments picked as per given segment allocation
+/// - `SegmentAllocation::Contiguous` stores the log in a run of free blocks if one exists
+/// - Returns (first_block_index, last_block_index)
+pub fn create_log_with_allocation(
+    storage: &mut Storage,
+    data: &[u8],
+    allocation: SegmentAllocation,
+) -> Result<(BlockIndex, BlockIndex), Error> {
     let (payload_list, first_block_index, last_block_index) =
-        make_segment_payload_list(storage, data)?;
-    for (block_index, segment_payload) in payload_list.iter() {
-        storage.write_block(*block_index, segment_payload)?;
-    }
+        make_segment_payload_list_with_allocation(storage, data, allocation)?;
+    write_segment_payload_list(storage, &payload_list)?;
     Ok((first_block_index, last_block_index))
 }
 
 /// Append existing log to storage with new block index
 /// - appends last block with 1st chunk of data
 /// - store remaining chunks of data in new blocks
+/// - new blocks are written before the last block links to them, with a sync barrier in between.
+///   So as per sync policy of storage, a durable link never points to a lost block.
 /// - Returns last_block_index
 pub fn append_log(
     storage: &mut Storage,
     block_index: BlockIndex,
     data: &[u8],
 ) -> Result<BlockIndex, Error> {
+    let (payload_list, last_block_index, last_segment_payload, new_last_block_index) =
+        make_append_payload_list(storage, block_index, data)?;
+
+    // - write new blocks as a group
+    write_segment_payload_list(storage, &payload_list)?;
+
+    // - make new blocks durable, before existing last block links to them
+    if !payload_list.is_empty() {
+        storage.sync_barrier()?;
+    }
+
+    // - write updated last block
+    storage.write_block(last_block_index, &last_segment_payload)?;
+    Ok(new_last_block_index)
+}
+
+/// Append existing log to storage, committing new blocks & updated last block as one transaction
+/// - after a crash, log holds either all of the data or none of it, see `Storage::commit`
+/// - storage must have a journal
+/// - Returns last_block_index
+pub fn append_log_atomic(
+    storage: &mut Storage,
+    block_index: BlockIndex,
+    data: &[u8],
+) -> Result<BlockIndex, Error> {
+    let (payload_list, last_block_index, last_segment_payload, new_last_block_index) =
+        make_append_payload_list(storage, block_index, data)?;
+    let mut transaction = Transaction::new();
+    for (segment_block_index, segment_payload) in payload_list.iter() {
+        transaction.write_block(*segment_block_index, segment_payload);
+    }
+    transaction.write_block(last_block_index, &last_segment_payload);
+    storage.commit(&transaction)?;
+    Ok(new_last_block_index)
+}
+
+type MakeAppendPayloadListResult =
+    Result<(Vec<(BlockIndex, Vec<u8>)>, BlockIndex, Vec<u8>, BlockIndex), Error>;
+/// Segment payloads appending data to log, starting from any of its blocks
+/// - Returns (payload list of new blocks, last_block_index, its updated segment payload, new last_block_index)
+fn make_append_payload_list(
+    storage: &Storage,
+    block_index: BlockIndex,
+    data: &[u8],
+) -> MakeAppendPayloadListResult {
+    let block_index_width = storage.block_index_width();
     // traverse to last block of log
     let mut last_block_index = block_index; // no necessarily 1st or last block of log, prefer last block to elemenate search time
     loop {
         let (_, segment_payload) = storage.read_block(last_block_index)?;
 
         // parse next block index
-        let next_block_index = block_index_from_buffer(&segment_payload)?;
+        let next_block_index = block_index_from_buffer(block_index_width, &segment_payload)?;
 
         // check if last block
         if next_block_index != LAST_NEXT_BLOCK_INDEX {
@@ -108,8 +230,8 @@ pub fn append_log(
 
             // - update next_block_index of existing last segment
             let existing_last_segment_new_block_data = [
-                &block_index_to_buffer(new_next_block_index),
-                &segment_payload[4..],
+                &block_index_to_buffer(block_index_width, new_next_block_index),
+                &segment_payload[block_index_width.size()..],
                 &data[0..(if existing_last_block_void_size == 0 {
                     0
                 } else if existing_last_block_void_size > data.len() {
@@ -120,14 +242,12 @@ pub fn append_log(
             ]
             .concat();
 
-            // - write updated last block
-            storage.write_block(last_block_index, &existing_last_segment_new_block_data)?;
-
-            // - write new blocks
-            for (block_index, segment_payload) in payload_list.iter() {
-                storage.write_block(*block_index, segment_payload)?;
-            }
-            return Ok(new_last_block_index);
+            return Ok((
+                payload_list,
+                last_block_index,
+                existing_last_segment_new_block_data,
+                new_last_block_index,
+            ));
         }
     }
 }
@@ -139,13 +259,14 @@ pub fn delete_log(
     start_segment_block_index: BlockIndex,
     hard_delete: bool,
 ) -> Result<(BlockIndex, BlockIndex), Error> {
+    let block_index_width = storage.block_index_width();
     let mut block_index_cache = start_segment_block_index;
     loop {
         // read block
         let (_, segment_payload) = storage.read_block(block_index_cache)?;
 
         // parse next block index
-        let next_block_index = block_index_from_buffer(&segment_payload)?;
+        let next_block_index = block_index_from_buffer(block_index_width, &segment_payload)?;
 
         // delete block
         storage.delete_block(block_index_cache, hard_delete)?;
@@ -164,9 +285,10 @@ pub fn delete_log(
 /// - Returns (first_block_index, last_block_index, log_data)
 /// - log_data is concatenation of all segments
 pub fn read_log(
-    storage: &mut Storage,
+    storage: &Storage,
     start_segment_block_index: BlockIndex,
 ) -> Result<(BlockIndex, BlockIndex, Vec<u8>), Error> {
+    let block_index_width = storage.block_index_width();
     let mut block_index_cache = start_segment_block_index;
     let mut log_data = vec![];
     loop {
@@ -174,10 +296,10 @@ pub fn read_log(
         let (_, segment_payload) = storage.read_block(block_index_cache)?;
 
         // parse next block index
-        let next_block_index = block_index_from_buffer(&segment_payload)?;
+        let next_block_index = block_index_from_buffer(block_index_width, &segment_payload)?;
 
         // append segment payload to log data
-        log_data.extend_from_slice(&segment_payload[4..]);
+        log_data.extend_from_slice(&segment_payload[block_index_width.size()..]);
         if next_block_index != LAST_NEXT_BLOCK_INDEX {
             block_index_cache = next_block_index;
         } else {
@@ -185,3 +307,44 @@ pub fn read_log(
         }
     }
 }
+
+/// Rewrite next block index of a segment, if it links to a moved block
+/// - returns None if segment links to a block staying in place, or to no block
+fn remap_next_block_index(
+    block_index_width: BlockIndexWidth,
+    segment_payload: &[u8],
+    remap: &BlockRemap,
+) -> Option<Vec<u8>> {
+    let next_block_index = block_index_from_buffer(block_index_width, segment_payload).ok()?;
+    let new_next_block_index = remap.get(&next_block_index)?;
+    Some(
+        [
+            &block_index_to_buffer(block_index_width, *new_next_block_index),
+            &segment_payload[block_index_width.size()..],
+        ]
+        .concat(),
+    )
+}
+
+/// Compact storage of logs in place, with `Storage::compact`
+/// - every used block of storage must be a segment of a log, as next block indexes are rewritten
+/// - Returns remap of moved blocks, a first_block_index in remap has moved
+pub fn compact_logs(storage: &mut Storage) -> Result<BlockRemap, Error> {
+    let block_index_width = storage.block_index_width();
+    storage.compact(|segment_payload, remap| {
+        remap_next_block_index(block_index_width, segment_payload, remap)
+    })
+}
+
+/// Compact storage file of logs into a new file, with `Storage::compact_file`
+/// - every used block of storage must be a segment of a log, as next block indexes are rewritten
+/// - Returns remap of moved blocks, a first_block_index in remap has moved
+pub fn compact_logs_file(file_path: String, options: StorageOptions) -> Result<BlockRemap, Error> {
+    // - width of next block indexes is recorded in storage header
+    let block_index_width =
+        Storage::open_read_only_with_options(file_path.clone(), options.clone())?
+            .block_index_width();
+    Storage::compact_file(file_path, options, |segment_payload, remap| {
+        remap_next_block_index(block_index_width, segment_payload, remap)
+    })
+}
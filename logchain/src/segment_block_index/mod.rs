@@ -1,75 +1,94 @@
-use storage::BlockIndex;
+use storage::{BlockIndex, BlockIndexWidth};
 use util::error::Error;
 
 mod segment_block_index_errors;
 
-pub const BLOCK_INDEX_SIZE: usize = std::mem::size_of::<BlockIndex>();
-
 /// Value of next block index in last block of the chain.
+/// - stored as largest value of block index width of storage
 pub const LAST_NEXT_BLOCK_INDEX: BlockIndex = BlockIndex::MAX;
 
-pub fn block_index_from_buffer(buffer: &[u8]) -> Result<BlockIndex, Error> {
-    if buffer.len() < BLOCK_INDEX_SIZE {
-        return Err(
+/// Parse next block index from start of a segment, as wide as block indexes of storage
+pub fn block_index_from_buffer(
+    block_index_width: BlockIndexWidth,
+    buffer: &[u8],
+) -> Result<BlockIndex, Error> {
+    match block_index_width.from_bytes(buffer) {
+        Some(block_index) => Ok(block_index),
+        None => Err(
             segment_block_index_errors::block_index_from_buffer_insufficient_buffer_size(
                 buffer.len(),
             ),
-        );
+        ),
     }
-    Ok(BlockIndex::from_le_bytes([
-        buffer[0], buffer[1], buffer[2], buffer[3],
-    ]))
 }
 
-pub fn block_index_to_buffer(next_block_index: BlockIndex) -> [u8; BLOCK_INDEX_SIZE] {
-    BlockIndex::to_le_bytes(next_block_index)
+/// Serialize next block index of a segment, as wide as block indexes of storage
+pub fn block_index_to_buffer(
+    block_index_width: BlockIndexWidth,
+    next_block_index: BlockIndex,
+) -> Vec<u8> {
+    block_index_width.to_bytes(next_block_index)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    const U32: BlockIndexWidth = BlockIndexWidth::U32;
+    const U64: BlockIndexWidth = BlockIndexWidth::U64;
+
     #[test]
     fn test_block_index_to_buffer() {
-        assert_eq!(block_index_to_buffer(0x12345678), [0x78, 0x56, 0x34, 0x12]);
+        assert_eq!(
+            block_index_to_buffer(U32, 0x12345678),
+            [0x78, 0x56, 0x34, 0x12]
+        );
+        assert_eq!(
+            block_index_to_buffer(U64, 0x1_12345678),
+            [0x78, 0x56, 0x34, 0x12, 1, 0, 0, 0]
+        );
     }
 
     #[test]
     fn test_block_index_from_buffer() {
         // Normal case
-        let result = block_index_from_buffer(&[0x78, 0x56, 0x34, 0x12]);
+        let result = block_index_from_buffer(U32, &[0x78, 0x56, 0x34, 0x12]);
         assert!(result.is_ok());
         assert_eq!(result.unwrap(), 0x12345678);
         // Insufficient buffer size case
-        let result = block_index_from_buffer(&[0x78, 0x56, 0x34]);
-        assert_eq!(result.is_err(), true);
+        let result = block_index_from_buffer(U32, &[0x78, 0x56, 0x34]);
+        assert!(result.is_err());
+        let result = block_index_from_buffer(U64, &[0x78, 0x56, 0x34, 0x12]);
+        assert!(result.is_err());
     }
 
     #[test]
     fn test_block_index_to_buffer_and_back() {
-        // max u32
-        let n = BlockIndex::MAX;
-        let bytes = block_index_to_buffer(n);
-        let result = block_index_from_buffer(&bytes);
-        assert!(result.is_ok());
-        assert_eq!(result.unwrap(), n);
-        // min u32
-        let n = BlockIndex::MIN;
-        let bytes = block_index_to_buffer(n);
-        let result = block_index_from_buffer(&bytes);
-        assert!(result.is_ok());
-        assert_eq!(result.unwrap(), n);
-        // even value
-        let n = (BlockIndex::MAX / 4) * 2;
-        let bytes = block_index_to_buffer(n);
-        let result = block_index_from_buffer(&bytes);
-        assert!(result.is_ok());
-        assert_eq!(result.unwrap(), n);
-        // odd value
-        let n = (BlockIndex::MAX / 4) * 2 + 1;
-        let bytes = block_index_to_buffer(n);
-        let result = block_index_from_buffer(&bytes);
-        assert!(result.is_ok());
-        assert_eq!(result.unwrap(), n);
+        for width in [U32, U64] {
+            // last next block index
+            let n = LAST_NEXT_BLOCK_INDEX;
+            let bytes = block_index_to_buffer(width, n);
+            let result = block_index_from_buffer(width, &bytes);
+            assert!(result.is_ok());
+            assert_eq!(result.unwrap(), n);
+            // min
+            let n = BlockIndex::MIN;
+            let bytes = block_index_to_buffer(width, n);
+            let result = block_index_from_buffer(width, &bytes);
+            assert!(result.is_ok());
+            assert_eq!(result.unwrap(), n);
+            // even value
+            let n = (width.max_block_count() / 4) * 2;
+            let bytes = block_index_to_buffer(width, n);
+            let result = block_index_from_buffer(width, &bytes);
+            assert!(result.is_ok());
+            assert_eq!(result.unwrap(), n);
+            // odd value
+            let n = (width.max_block_count() / 4) * 2 + 1;
+            let bytes = block_index_to_buffer(width, n);
+            let result = block_index_from_buffer(width, &bytes);
+            assert!(result.is_ok());
+            assert_eq!(result.unwrap(), n);
+        }
     }
 }
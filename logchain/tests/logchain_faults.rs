@@ -0,0 +1,260 @@
+use logchain::{append_log, append_log_atomic, compact_logs, create_log, delete_log, read_log};
+use storage::block_device::{Fault, FaultyBlockDevice, MemoryBlockDevice};
+use storage::{Storage, StorageOptions, SyncPolicy};
+
+/// Storage with block checksums on a memory device wrapped in a fault injecting device
+/// - returns (storage, device) - device is a clone, to script faults with
+fn new_faulty_storage(block_len: u32) -> (Storage, FaultyBlockDevice) {
+    let device = FaultyBlockDevice::new(Box::new(MemoryBlockDevice::new()));
+    let options = StorageOptions {
+        block_checksum: true,
+        ..Default::default()
+    };
+    let storage = Storage::new_with_device(Box::new(device.clone()), block_len, options).unwrap();
+    (storage, device)
+}
+
+#[test]
+fn logchain_faults_create_and_read_log() {
+    let (mut storage, device) = new_faulty_storage(8);
+    let log_0_data = (1..=10).collect::<Vec<u8>>();
+    // - group write of segments fails or is torn, error is passed through
+    for fault in [Fault::NoSpace, Fault::Short(20)] {
+        device.fail_write(0, fault);
+        let result = create_log(&mut storage, &log_0_data);
+        assert_eq!(
+            result.err().unwrap().code(),
+            "write_blocks_failed_to_write_blocks"
+        );
+    }
+    // - retry succeeds
+    let (log_0_first_block_index, log_0_last_block_index) =
+        create_log(&mut storage, &log_0_data).unwrap();
+    // - read of a segment fails, error is passed through
+    device.fail_read(3, Fault::Io);
+    let result = read_log(&storage, log_0_first_block_index);
+    assert_eq!(
+        result.err().unwrap().code(),
+        "read_block_failed_to_read_block_header"
+    );
+    let (_, last_block_index, log_data) = read_log(&storage, log_0_first_block_index).unwrap();
+    assert_eq!(last_block_index, log_0_last_block_index);
+    assert_eq!(log_data, log_0_data);
+    // - delete fails at 1st segment, log is still readable
+    device.fail_write(0, Fault::Io);
+    let result = delete_log(&mut storage, log_0_first_block_index, false);
+    assert_eq!(
+        result.err().unwrap().code(),
+        "delete_block_failed_to_write_block_header"
+    );
+    let (_, _, log_data) = read_log(&storage, log_0_first_block_index).unwrap();
+    assert_eq!(log_data, log_0_data);
+}
+
+#[test]
+fn logchain_faults_append_log_crash() {
+    let log_data = (1..=10).collect::<Vec<u8>>();
+    let append_data = (11..=25).collect::<Vec<u8>>();
+    let new_log_data = [&log_data[..], &append_data[..]].concat();
+    // crash at every byte written by append, till append completes without crash
+    let mut crash_after_bytes = 0;
+    loop {
+        let (mut storage, device) = new_faulty_storage(8);
+        let (first_block_index, _) = create_log(&mut storage, &log_data).unwrap();
+        device.crash_after_bytes(crash_after_bytes);
+        let result = append_log(&mut storage, first_block_index, &append_data);
+        let crashed = result.is_err();
+        drop(storage);
+        // - reopen, log reads old or new data, or fails cleanly
+        device.revive();
+        let storage =
+            Storage::open_device(Box::new(device.clone()), StorageOptions::default()).unwrap();
+        match read_log(&storage, first_block_index) {
+            Ok((_, _, data)) => assert!(
+                data == log_data || data == new_log_data,
+                "crash after {} bytes: {:?}",
+                crash_after_bytes,
+                data
+            ),
+            Err(error) => assert!(
+                crashed
+                    && [
+                        "read_block_checksum_mismatch",
+                        "read_block_failed_to_read_block_data",
+                        "read_block_failed_to_read_block_checksum",
+                        "block_index_from_buffer_insufficient_buffer_size",
+                    ]
+                    .contains(&error.code()),
+                "crash after {} bytes: {}",
+                crash_after_bytes,
+                error.code()
+            ),
+        }
+        if !crashed {
+            break;
+        }
+        crash_after_bytes += 1;
+    }
+    assert!(crash_after_bytes > 0);
+}
+
+#[test]
+fn logchain_faults_append_log_power_failure() {
+    let log_data = (1..=10).collect::<Vec<u8>>();
+    let append_data = (11..=25).collect::<Vec<u8>>();
+    let new_log_data = [&log_data[..], &append_data[..]].concat();
+    // storage syncs only at barriers & on request
+    let new_storage = || {
+        let device = FaultyBlockDevice::new(Box::new(MemoryBlockDevice::new()));
+        let options = StorageOptions {
+            block_checksum: true,
+            sync_policy: SyncPolicy::EveryWrites(usize::MAX),
+            ..Default::default()
+        };
+        let mut storage = Storage::new_with_device(Box::new(device.clone()), 8, options).unwrap();
+        let (first_block_index, _) = create_log(&mut storage, &log_data).unwrap();
+        storage.sync().unwrap();
+        append_log(&mut storage, first_block_index, &append_data).unwrap();
+        (device, first_block_index)
+    };
+    // - writes after barrier rewrite existing last block only
+    let (device, _) = new_storage();
+    let unsynced_write_count = device.unsynced_write_count();
+    assert!(unsynced_write_count > 0);
+    // - power failure loses any single unsynced write, all of them, or none
+    let mut keep_patterns: Vec<Box<dyn Fn(usize) -> bool>> =
+        vec![Box::new(|_| false), Box::new(|_| true)];
+    for lost_position in 0..unsynced_write_count {
+        keep_patterns.push(Box::new(move |position| position != lost_position));
+        keep_patterns.push(Box::new(move |position| position == lost_position));
+    }
+    for keep in keep_patterns {
+        let (device, first_block_index) = new_storage();
+        device.lose_unsynced_writes(keep).unwrap();
+        let storage =
+            Storage::open_device(Box::new(device.clone()), StorageOptions::default()).unwrap();
+        // -- link never points to a lost block, log reads old or new data or fails checksum
+        match read_log(&storage, first_block_index) {
+            Ok((_, _, data)) => assert!(data == log_data || data == new_log_data),
+            Err(error) => assert_eq!(error.code(), "read_block_checksum_mismatch"),
+        }
+    }
+}
+
+#[test]
+fn logchain_faults_append_log_atomic_crash() {
+    let log_data = (1..=10).collect::<Vec<u8>>();
+    let append_data = (11..=25).collect::<Vec<u8>>();
+    let new_log_data = [&log_data[..], &append_data[..]].concat();
+    // crash at every byte written to storage or journal by append, till append completes without crash
+    let mut crash_after_bytes = 0;
+    loop {
+        let device = FaultyBlockDevice::new(Box::new(MemoryBlockDevice::new()));
+        let journal_device = device.new_sibling(Box::new(MemoryBlockDevice::new()));
+        let options = StorageOptions {
+            block_checksum: true,
+            ..Default::default()
+        };
+        let mut storage = Storage::new_with_devices(
+            Box::new(device.clone()),
+            Box::new(journal_device.clone()),
+            8,
+            options,
+        )
+        .unwrap();
+        let (first_block_index, _) = create_log(&mut storage, &log_data).unwrap();
+        storage.sync().unwrap();
+        device.crash_after_bytes(crash_after_bytes);
+        let result = append_log_atomic(&mut storage, first_block_index, &append_data);
+        let crashed = result.is_err();
+        drop(storage);
+        // - reopen, journal is redone, so log reads old or new data, never a torn segment
+        device.revive();
+        let storage = Storage::open_devices(
+            Box::new(device.clone()),
+            Box::new(journal_device.clone()),
+            StorageOptions::default(),
+        )
+        .unwrap();
+        let (_, _, data) = read_log(&storage, first_block_index).unwrap();
+        assert!(
+            data == log_data || data == new_log_data,
+            "crash after {} bytes: {:?}",
+            crash_after_bytes,
+            data
+        );
+        if !crashed {
+            break;
+        }
+        crash_after_bytes += 1;
+    }
+    assert!(crash_after_bytes > 0);
+}
+
+#[test]
+fn logchain_faults_compact_logs_crash() {
+    let log_0_data = (1..=20).collect::<Vec<u8>>();
+    let log_1_data = (21..=40).collect::<Vec<u8>>();
+    let append_data = (41..=50).collect::<Vec<u8>>();
+    let new_log_1_data = [&log_1_data[..], &append_data[..]].concat();
+    // crash at every byte written by compaction, till compaction completes without crash
+    let mut crash_after_bytes = 0;
+    loop {
+        let (mut storage, device) = new_faulty_storage(8);
+        let (log_0_first_block_index, _) = create_log(&mut storage, &log_0_data).unwrap();
+        let (log_1_first_block_index, _) = create_log(&mut storage, &log_1_data).unwrap();
+        append_log(&mut storage, log_1_first_block_index, &append_data).unwrap();
+        storage.set_root("log_1", log_1_first_block_index).unwrap();
+        delete_log(&mut storage, log_0_first_block_index, false).unwrap();
+        device.crash_after_bytes(crash_after_bytes);
+        let result = compact_logs(&mut storage);
+        let crashed = result.is_err();
+        drop(storage);
+        // - reopen, root leads to log with its data, or an in-place link rewrite is torn
+        device.revive();
+        let mut storage =
+            Storage::open_device(Box::new(device.clone()), StorageOptions::default()).unwrap();
+        let first_block_index = storage.get_root("log_1").unwrap();
+        match read_log(&storage, first_block_index) {
+            Ok((_, _, data)) => assert_eq!(
+                data, new_log_1_data,
+                "crash after {} bytes",
+                crash_after_bytes
+            ),
+            Err(error) => assert!(
+                crashed && error.code() == "read_block_checksum_mismatch",
+                "crash after {} bytes: {}",
+                crash_after_bytes,
+                error.code()
+            ),
+        }
+        // - compaction resumes after crash, unless a torn copy is left behind
+        if crashed {
+            match compact_logs(&mut storage) {
+                Ok(_) => {
+                    if let Some(first_block_index) = storage.get_root("log_1") {
+                        if let Ok((_, _, data)) = read_log(&storage, first_block_index) {
+                            assert_eq!(data, new_log_1_data);
+                        }
+                    }
+                }
+                Err(error) => assert!(
+                    [
+                        "read_block_checksum_mismatch",
+                        "read_block_failed_to_read_block_data",
+                        "read_block_failed_to_read_block_checksum",
+                    ]
+                    .contains(&error.code()),
+                    "crash after {} bytes: {}",
+                    crash_after_bytes,
+                    error.code()
+                ),
+            }
+        }
+        if !crashed {
+            break;
+        }
+        crash_after_bytes += 1;
+    }
+    assert!(crash_after_bytes > 0);
+}
@@ -1,5 +1,11 @@
-use logchain::{append_log, create_log, delete_log, make_segment_payload_list, read_log};
-use storage::{BlockIndex, Storage};
+use logchain::{
+    append_log, check_logs, compact_logs, compact_logs_file, create_log,
+    create_log_with_allocation, delete_log, make_segment_payload_list,
+    make_segment_payload_list_with_allocation, read_log, LogProblem, SegmentAllocation,
+};
+use storage::{
+    BlockCompression, BlockIndex, BlockIndexWidth, Storage, StorageOptions, StorageQuota,
+};
 
 fn read_full_file(file_name: &str) -> Vec<u8> {
     use std::fs::read;
@@ -29,7 +35,8 @@ fn remove_dir_contents(path: std::path::PathBuf) {
     remove_dir(path).unwrap();
 }
 
-const SIZE_OF_BLOCK_INDEX: usize = std::mem::size_of::<BlockIndex>();
+/// Size of next block index of a segment, in storages with 32-bit block indexes
+const SIZE_OF_BLOCK_INDEX: usize = 4;
 
 #[test]
 fn make_segment_payload_list_new_storage() {
@@ -44,7 +51,7 @@ fn make_segment_payload_list_new_storage() {
         read_full_file(path.to_str().unwrap())
     }
     // let tmp_file_path = "./tmp/make_segment_payload_list_new_storage.hex";
-    let tmp_dir_path = tempfile::tempdir().unwrap().into_path();
+    let tmp_dir_path = tempfile::tempdir().unwrap().keep();
     let tmp_file_path: std::path::PathBuf = [
         tmp_dir_path.to_str().unwrap().to_string(),
         String::from("make_segment_payload_list_new_storage.hex"),
@@ -56,8 +63,8 @@ fn make_segment_payload_list_new_storage() {
     // create new storage
     let block_len = 12;
     let storage_result = Storage::new(String::from(tmp_file_path), block_len);
-    assert_eq!(storage_result.is_ok(), true);
-    let mut storage = storage_result.unwrap();
+    assert!(storage_result.is_ok());
+    let storage = storage_result.unwrap();
     let expected = fetch_state("on_create.hex");
     let actual = read_full_file(tmp_file_path);
     assert_eq!(actual, expected);
@@ -67,7 +74,7 @@ fn make_segment_payload_list_new_storage() {
         1_u8, 2_u8, 3_u8, 4_u8, 5_u8, 6_u8, 7_u8, 8_u8, 9_u8, 10_u8, 11_u8, 12_u8, 13_u8, 14_u8,
         15_u8, 16_u8,
     ];
-    let result = make_segment_payload_list(&mut storage, &log_0_data);
+    let result = make_segment_payload_list(&storage, &log_0_data);
     assert!(result.is_ok());
     let (segment_list, first_block_index, last_block_index) = result.unwrap();
     // ([(0, [1, 0, 0, 0, 1, 2, 3, 4, 5, 6, 7, 8]), (1, [255, 255, 255, 255, 9, 10, 11, 12, 13, 14, 15, 16])], 0, 1)
@@ -85,7 +92,7 @@ fn make_segment_payload_list_new_storage() {
         30_u8, 31_u8, 32_u8, 33_u8, 34_u8, 35_u8, 36_u8, 37_u8, 38_u8, 39_u8, 40_u8, 41_u8, 42_u8,
         43_u8, 44_u8, 45_u8, 46_u8, 47_u8, 48_u8, 49_u8, 50_u8, 51_u8, 52_u8,
     ];
-    let result = make_segment_payload_list(&mut storage, &log_1_data);
+    let result = make_segment_payload_list(&storage, &log_1_data);
     assert!(result.is_ok());
     let (segment_list, first_block_index, last_block_index) = result.unwrap();
     // ([(0, [(0, [1, 0, 0, 0, 17, 18, 19, 20, 21, 22, 23, 24]), (1, [2, 0, 0, 0, 25, 26, 27, 28, 29, 30, 31, 32]), (2, [3, 0, 0, 0, 33, 34, 35, 36, 37, 38, 39, 40]), (3, [4, 0, 0, 0, 41, 42, 43, 44, 45, 46, 47, 48]), (4, [255, 255, 255, 255, 49, 50, 51, 52])], 0, 4)
@@ -103,13 +110,12 @@ fn make_segment_payload_list_new_storage() {
     assert_eq!(segment_list[4].0, 4);
     assert_eq!(
         segment_list[4].1.len(),
-        SIZE_OF_BLOCK_INDEX
-            + (log_1_data.len() % (block_len as usize - SIZE_OF_BLOCK_INDEX) as usize) as usize
+        SIZE_OF_BLOCK_INDEX + (log_1_data.len() % (block_len as usize - SIZE_OF_BLOCK_INDEX))
     );
 
     // check empty log
     let empty_log_data = vec![];
-    let result = make_segment_payload_list(&mut storage, &empty_log_data);
+    let result = make_segment_payload_list(&storage, &empty_log_data);
     assert!(result.is_ok());
     let (segment_list, first_block_index, last_block_index) = result.unwrap();
     // [(0, [255, 255, 255, 255])], 0, 0
@@ -126,7 +132,7 @@ fn make_segment_payload_list_new_storage() {
 #[test]
 fn make_segment_payload_list_existing_storage() {
     // let tmp_file_path = "./tmp/make_segment_payload_list_existing_storage.hex";
-    let tmp_dir_path = tempfile::tempdir().unwrap().into_path();
+    let tmp_dir_path = tempfile::tempdir().unwrap().keep();
     let tmp_file_path: std::path::PathBuf = [
         tmp_dir_path.to_str().unwrap().to_string(),
         String::from("make_segment_payload_list_existing_storage.hex"),
@@ -139,8 +145,8 @@ fn make_segment_payload_list_existing_storage() {
     let mut src_path =
         std::path::PathBuf::from("tests/samples/make_segment_payload_list_existing_storage");
     src_path.push("w-0_w-1_w-2_sd-0_hd-0_sd-1_hd-2_w-3_w-4_w-5_sd-3.hex");
-    std::fs::copy(src_path, tmp_file_path.clone()).unwrap();
-    let mut storage = Storage::open(String::from(tmp_file_path)).unwrap();
+    std::fs::copy(src_path, tmp_file_path).unwrap();
+    let storage = Storage::open(String::from(tmp_file_path)).unwrap();
     let block_len = storage.block_len();
 
     // check log 0
@@ -148,7 +154,7 @@ fn make_segment_payload_list_existing_storage() {
         1_u8, 2_u8, 3_u8, 4_u8, 5_u8, 6_u8, 7_u8, 8_u8, 9_u8, 10_u8, 11_u8, 12_u8, 13_u8, 14_u8,
         15_u8, 16_u8,
     ];
-    let result = make_segment_payload_list(&mut storage, &log_0_data);
+    let result = make_segment_payload_list(&storage, &log_0_data);
     assert!(result.is_ok());
     let (segment_list, first_block_index, last_block_index) = result.unwrap();
     // ([(0, [1, 0, 0, 0, 1, 2, 3, 4]), (1, [3, 0, 0, 0, 5, 6, 7, 8]), (3, [6, 0, 0, 0, 9, 10, 11, 12]), (6, [255, 255, 255, 255, 13, 14, 15, 16])], 0 , 6)
@@ -170,7 +176,7 @@ fn make_segment_payload_list_existing_storage() {
         30_u8, 31_u8, 32_u8, 33_u8, 34_u8, 35_u8, 36_u8, 37_u8, 38_u8, 39_u8, 40_u8, 41_u8, 42_u8,
         43_u8, 44_u8, 45_u8, 46_u8, 47_u8, 48_u8, 49_u8, 50_u8, 51_u8, 52_u8,
     ];
-    let result = make_segment_payload_list(&mut storage, &log_1_data);
+    let result = make_segment_payload_list(&storage, &log_1_data);
     assert!(result.is_ok());
     let (segment_list, first_block_index, last_block_index) = result.unwrap();
     // ([(0, [1, 0, 0, 0, 17, 18, 19, 20]), (1, [3, 0, 0, 0, 21, 22, 23, 24]), (3, [6, 0, 0, 0, 25, 26, 27, 28]), (6, [7, 0, 0, 0, 29, 30, 31, 32]), (7, [8, 0, 0, 0, 33, 34, 35, 36]), (8, [9, 0, 0, 0, 37, 38, 39, 40]), (9, [10, 0, 0, 0, 41, 42, 43, 44]), (10, [11, 0, 0, 0, 45, 46, 47, 48]), (11, [255, 255, 255, 255, 49, 50, 51, 52])], 0, 11)
@@ -198,7 +204,7 @@ fn make_segment_payload_list_existing_storage() {
 
     // empty log
     let log_2_data = vec![];
-    let result = make_segment_payload_list(&mut storage, &log_2_data);
+    let result = make_segment_payload_list(&storage, &log_2_data);
     assert!(result.is_ok());
     let (segment_list, first_block_index, last_block_index) = result.unwrap();
     // [(0, [255, 255, 255, 255])], 0, 0
@@ -222,7 +228,7 @@ fn create_log_new_storage() {
         read_full_file(path.to_str().unwrap())
     }
     // let tmp_file_path = "./tmp/create_log_new_storage.hex";
-    let tmp_dir_path = tempfile::tempdir().unwrap().into_path();
+    let tmp_dir_path = tempfile::tempdir().unwrap().keep();
     let tmp_file_path: std::path::PathBuf = [
         tmp_dir_path.to_str().unwrap().to_string(),
         String::from("create_log_new_storage.hex"),
@@ -234,7 +240,7 @@ fn create_log_new_storage() {
     // create new storage
     let block_len = 8;
     let storage_result = Storage::new(String::from(tmp_file_path), block_len);
-    assert_eq!(storage_result.is_ok(), true);
+    assert!(storage_result.is_ok());
     let mut storage = storage_result.unwrap();
     let expected = fetch_state("on_create.hex");
     let actual = read_full_file(tmp_file_path);
@@ -294,7 +300,7 @@ fn create_log_existing_storage() {
         read_full_file(path.to_str().unwrap())
     }
     // let tmp_file_path = "./tmp/create_log_existing_storage.hex";
-    let tmp_dir_path = tempfile::tempdir().unwrap().into_path();
+    let tmp_dir_path = tempfile::tempdir().unwrap().keep();
     let tmp_file_path: std::path::PathBuf = [
         tmp_dir_path.to_str().unwrap().to_string(),
         String::from("create_log_existing_storage.hex"),
@@ -306,7 +312,7 @@ fn create_log_existing_storage() {
     // copy "tests/samples/make_segment_payload_list_existing_storage/w-0_w-1_w-2_sd-0_hd-0_sd-1_hd-2_w-3_w-4_w-5_sd-3.hex" to tmp_file_path
     let mut src_path = std::path::PathBuf::from("tests/samples/create_log_existing_storage");
     src_path.push("w-0_w-1_w-2_sd-0_hd-0_sd-1_hd-2_w-2_w-3_w-4_w-5_sd-3.hex");
-    std::fs::copy(src_path, tmp_file_path.clone()).unwrap();
+    std::fs::copy(src_path, tmp_file_path).unwrap();
     let mut storage = Storage::open(String::from(tmp_file_path)).unwrap();
     let block_len = storage.block_len();
     assert_eq!(block_len, 8);
@@ -365,7 +371,7 @@ fn append_log_new_storage() {
         read_full_file(path.to_str().unwrap())
     }
     // let tmp_file_path = "./tmp/append_log_new_storage.hex";
-    let tmp_dir_path = tempfile::tempdir().unwrap().into_path();
+    let tmp_dir_path = tempfile::tempdir().unwrap().keep();
     let tmp_file_path: std::path::PathBuf = [
         tmp_dir_path.to_str().unwrap().to_string(),
         String::from("append_log_new_storage.hex"),
@@ -377,7 +383,7 @@ fn append_log_new_storage() {
     // create new storage
     let block_len = 12;
     let storage_result = Storage::new(String::from(tmp_file_path), block_len);
-    assert_eq!(storage_result.is_ok(), true);
+    assert!(storage_result.is_ok());
     let mut storage = storage_result.unwrap();
     let actual = read_full_file(tmp_file_path);
     let expected = fetch_state("create.hex");
@@ -589,6 +595,7 @@ fn append_log_new_storage() {
     remove_dir_contents(tmp_dir_path);
 }
 
+#[test]
 fn append_log_existing_storage() {}
 
 #[test]
@@ -600,7 +607,7 @@ fn delete_log_new_storage() {
             .collect();
         read_full_file(path.to_str().unwrap())
     }
-    let tmp_dir_path = tempfile::tempdir().unwrap().into_path();
+    let tmp_dir_path = tempfile::tempdir().unwrap().keep();
     let tmp_file_path: std::path::PathBuf = [
         tmp_dir_path.to_str().unwrap().to_string(),
         String::from("delete_log_new_storage.hex"),
@@ -610,7 +617,7 @@ fn delete_log_new_storage() {
     let tmp_file_path = tmp_file_path.to_str().unwrap();
     let block_len = 8;
     let storage_result = Storage::new(String::from(tmp_file_path), block_len);
-    assert_eq!(storage_result.is_ok(), true);
+    assert!(storage_result.is_ok());
     let mut storage = storage_result.unwrap();
 
     // write log 0
@@ -688,12 +695,13 @@ fn delete_log_new_storage() {
     remove_dir_contents(tmp_dir_path);
 }
 
+#[test]
 fn delete_log_existing_storage() {}
 
 #[test]
 fn read_log_new_storage() {
     // let tmp_file_path = "./tmp/read_log_new_storage.hex";
-    let tmp_dir_path = tempfile::tempdir().unwrap().into_path();
+    let tmp_dir_path = tempfile::tempdir().unwrap().keep();
     let tmp_file_path: std::path::PathBuf = [
         tmp_dir_path.to_str().unwrap().to_string(),
         String::from("read_log_new_storage.hex"),
@@ -705,7 +713,7 @@ fn read_log_new_storage() {
     // create new storage
     let block_len = 8;
     let storage_result = Storage::new(String::from(tmp_file_path), block_len);
-    assert_eq!(storage_result.is_ok(), true);
+    assert!(storage_result.is_ok());
     let mut storage = storage_result.unwrap();
 
     // add logs to storage
@@ -721,7 +729,7 @@ fn read_log_new_storage() {
     let log_0_last_block_index = last_block_index;
     {
         // test read_block for block_0
-        let result = read_log(&mut storage, log_0_first_block_index);
+        let result = read_log(&storage, log_0_first_block_index);
         assert!(result.is_ok());
         let (first_block_index, last_block_index, log_data) = result.unwrap();
         assert_eq!(first_block_index, log_0_first_block_index);
@@ -741,7 +749,7 @@ fn read_log_new_storage() {
     let log_1_last_block_index = last_block_index;
     {
         // test read_block for block_1
-        let result = read_log(&mut storage, log_1_first_block_index);
+        let result = read_log(&storage, log_1_first_block_index);
         assert!(result.is_ok());
         let (first_block_index, last_block_index, log_data) = result.unwrap();
         assert_eq!(first_block_index, log_1_first_block_index);
@@ -762,7 +770,7 @@ fn read_log_new_storage() {
     let log_2_last_block_index = last_block_index;
     {
         // test read_block for block_2
-        let result = read_log(&mut storage, log_2_first_block_index);
+        let result = read_log(&storage, log_2_first_block_index);
         assert!(result.is_ok());
         let (first_block_index, last_block_index, log_data) = result.unwrap();
         assert_eq!(first_block_index, log_2_first_block_index);
@@ -771,7 +779,7 @@ fn read_log_new_storage() {
     }
     {
         // test read_block for block_0
-        let result = read_log(&mut storage, log_0_first_block_index);
+        let result = read_log(&storage, log_0_first_block_index);
         assert!(result.is_ok());
         let (first_block_index, last_block_index, log_data) = result.unwrap();
         assert_eq!(first_block_index, log_0_first_block_index);
@@ -780,7 +788,7 @@ fn read_log_new_storage() {
     }
     {
         // test read_block for block_1
-        let result = read_log(&mut storage, log_1_first_block_index);
+        let result = read_log(&storage, log_1_first_block_index);
         assert!(result.is_ok());
         let (first_block_index, last_block_index, log_data) = result.unwrap();
         assert_eq!(first_block_index, log_1_first_block_index);
@@ -797,7 +805,7 @@ fn read_log_new_storage() {
     log_0_data.extend_from_slice(&log_0_append_data);
     {
         // test read_block for block_0
-        let result = read_log(&mut storage, log_0_first_block_index);
+        let result = read_log(&storage, log_0_first_block_index);
         assert!(result.is_ok());
         let (first_block_index, last_block_index, log_data) = result.unwrap();
         assert_eq!(first_block_index, log_0_first_block_index);
@@ -816,7 +824,7 @@ fn read_log_new_storage() {
     log_1_data.extend_from_slice(&log_1_append_data);
     {
         // test read_block for block_1
-        let result = read_log(&mut storage, log_1_first_block_index);
+        let result = read_log(&storage, log_1_first_block_index);
         assert!(result.is_ok());
         let (first_block_index, last_block_index, log_data) = result.unwrap();
         assert_eq!(first_block_index, log_1_first_block_index);
@@ -836,7 +844,7 @@ fn read_log_new_storage() {
     log_2_data.extend_from_slice(&log_2_append_data);
     {
         // test read_block for block_2
-        let result = read_log(&mut storage, log_2_first_block_index);
+        let result = read_log(&storage, log_2_first_block_index);
         assert!(result.is_ok());
         let (first_block_index, last_block_index, log_data) = result.unwrap();
         assert_eq!(first_block_index, log_2_first_block_index);
@@ -845,7 +853,7 @@ fn read_log_new_storage() {
     }
     {
         // test read_block for block_0
-        let result = read_log(&mut storage, log_0_first_block_index);
+        let result = read_log(&storage, log_0_first_block_index);
         assert!(result.is_ok());
         let (first_block_index, last_block_index, log_data) = result.unwrap();
         assert_eq!(first_block_index, log_0_first_block_index);
@@ -854,7 +862,7 @@ fn read_log_new_storage() {
     }
     {
         // test read_block for block_1
-        let result = read_log(&mut storage, log_1_first_block_index);
+        let result = read_log(&storage, log_1_first_block_index);
         assert!(result.is_ok());
         let (first_block_index, last_block_index, log_data) = result.unwrap();
         assert_eq!(first_block_index, log_1_first_block_index);
@@ -864,3 +872,432 @@ fn read_log_new_storage() {
 
     remove_dir_contents(tmp_dir_path);
 }
+
+#[test]
+fn logchain_in_memory_storage() {
+    let mut storage = Storage::new_in_memory(8, Default::default()).unwrap();
+    // - log 0, spans 3 blocks
+    let log_0_data = (1..=10).collect::<Vec<u8>>();
+    let (log_0_first_block_index, _) = create_log(&mut storage, &log_0_data).unwrap();
+    // - log 1, spans 2 blocks
+    let log_1_data = (11..=18).collect::<Vec<u8>>();
+    let (log_1_first_block_index, _) = create_log(&mut storage, &log_1_data).unwrap();
+    assert_eq!((log_0_first_block_index, log_1_first_block_index), (0, 3));
+    // - append to log 0
+    append_log(&mut storage, log_0_first_block_index, &[21, 22, 23]).unwrap();
+    let (_, _, log_data) = read_log(&storage, log_0_first_block_index).unwrap();
+    assert_eq!(log_data, [&log_0_data[..], &[21, 22, 23]].concat());
+    // - delete log 1, its blocks are reused by next log
+    delete_log(&mut storage, log_1_first_block_index, false).unwrap();
+    let (log_2_first_block_index, _) = create_log(&mut storage, &[31, 32]).unwrap();
+    assert_eq!(log_2_first_block_index, log_1_first_block_index);
+    let (_, _, log_data) = read_log(&storage, log_2_first_block_index).unwrap();
+    assert_eq!(log_data, vec![31, 32]);
+}
+
+#[test]
+fn logchain_roots() {
+    let tmp_dir_path = tempfile::tempdir().unwrap().keep();
+    let tmp_file_path: std::path::PathBuf = [
+        tmp_dir_path.to_str().unwrap().to_string(),
+        String::from("logchain_roots.hex"),
+    ]
+    .iter()
+    .collect();
+    let tmp_file_path = tmp_file_path.to_str().unwrap();
+    let mut storage = Storage::new(String::from(tmp_file_path), 8).unwrap();
+    let log_data = (1..=20).collect::<Vec<u8>>();
+    let (first_block_index, _) = create_log(&mut storage, &log_data).unwrap();
+    storage.sync_barrier().unwrap();
+    storage.set_root("events", first_block_index).unwrap();
+    drop(storage);
+    // - log is found by name after reopen
+    let storage = Storage::open(String::from(tmp_file_path)).unwrap();
+    let first_block_index = storage.get_root("events").unwrap();
+    let (_, _, data) = read_log(&storage, first_block_index).unwrap();
+    assert_eq!(data, log_data);
+    drop(storage);
+    // clear clutter
+    remove_dir_contents(tmp_dir_path);
+}
+
+#[test]
+fn logchain_compact_logs() {
+    let tmp_dir_path = tempfile::tempdir().unwrap().keep();
+    let tmp_file_path: std::path::PathBuf = [
+        tmp_dir_path.to_str().unwrap().to_string(),
+        String::from("logchain_compact_logs.hex"),
+    ]
+    .iter()
+    .collect();
+    let tmp_file_path = tmp_file_path.to_str().unwrap();
+    let logs_data = (0..4u8)
+        .map(|log| (0..20).map(|byte| log * 20 + byte).collect::<Vec<u8>>())
+        .collect::<Vec<_>>();
+    let mut storage = Storage::new(String::from(tmp_file_path), 8).unwrap();
+    let mut first_block_indexes = vec![];
+    for log_data in &logs_data {
+        let (first_block_index, _) = create_log(&mut storage, log_data).unwrap();
+        first_block_indexes.push(first_block_index);
+    }
+    // - logs 1 & 3 grow at end, log 0 is deleted last, leaving a hole at start
+    append_log(&mut storage, first_block_indexes[1], &[100; 10]).unwrap();
+    append_log(&mut storage, first_block_indexes[3], &[101; 10]).unwrap();
+    storage.sync_barrier().unwrap();
+    for (log, first_block_index) in first_block_indexes.iter().enumerate().skip(1) {
+        storage
+            .set_root(&format!("log_{}", log), *first_block_index)
+            .unwrap();
+    }
+    delete_log(&mut storage, first_block_indexes[0], false).unwrap();
+    let expected_logs_data = vec![
+        [&logs_data[1][..], &[100; 10]].concat(),
+        logs_data[2].clone(),
+        [&logs_data[3][..], &[101; 10]].concat(),
+    ];
+    let file_len = read_full_file(tmp_file_path).len();
+    // - links of logs follow moved segments, roots follow moved heads
+    let remap = compact_logs(&mut storage).unwrap();
+    assert!(!remap.is_empty());
+    assert!(read_full_file(tmp_file_path).len() < file_len);
+    for (log, expected_log_data) in (1..4).zip(&expected_logs_data) {
+        let first_block_index = storage.get_root(&format!("log_{}", log)).unwrap();
+        let (_, _, data) = read_log(&storage, first_block_index).unwrap();
+        assert_eq!(&data, expected_log_data);
+    }
+    // - logs stay appendable after compaction
+    let first_block_index = storage.get_root("log_2").unwrap();
+    append_log(&mut storage, first_block_index, &[102; 10]).unwrap();
+    let first_block_index = storage.get_root("log_1").unwrap();
+    delete_log(&mut storage, first_block_index, false).unwrap();
+    storage.remove_root("log_1").unwrap();
+    drop(storage);
+    let expected_logs_data = vec![
+        [&logs_data[2][..], &[102; 10]].concat(),
+        expected_logs_data[2].clone(),
+    ];
+    // - offline compaction renumbers all segments, links & roots
+    let remap = compact_logs_file(String::from(tmp_file_path), StorageOptions::default()).unwrap();
+    assert!(!remap.is_empty());
+    let storage = Storage::open(String::from(tmp_file_path)).unwrap();
+    for (log, expected_log_data) in (2..4).zip(&expected_logs_data) {
+        let first_block_index = storage.get_root(&format!("log_{}", log)).unwrap();
+        let (_, _, data) = read_log(&storage, first_block_index).unwrap();
+        assert_eq!(&data, expected_log_data);
+    }
+    assert_eq!(storage.get_root("log_1"), None);
+    drop(storage);
+    // clear clutter
+    remove_dir_contents(tmp_dir_path);
+}
+
+#[test]
+fn create_log_contiguous() {
+    let mut storage = Storage::new_in_memory(8, StorageOptions::default()).unwrap();
+    let mut first_block_indexes = vec![];
+    for log in 0..4u8 {
+        let (first_block_index, _) = create_log(&mut storage, &[log; 4]).unwrap();
+        first_block_indexes.push(first_block_index);
+    }
+    let (first_block_index, _) = create_log(&mut storage, &[4; 12]).unwrap();
+    first_block_indexes.push(first_block_index);
+    create_log(&mut storage, &[5; 4]).unwrap();
+    // - free runs [1], [4, 5, 6]
+    delete_log(&mut storage, first_block_indexes[1], false).unwrap();
+    delete_log(&mut storage, first_block_indexes[4], false).unwrap();
+    let log_data = (1..=12).collect::<Vec<u8>>();
+    // - default allocation scatters segments over lowest free blocks
+    let (payload_list, _, _) = make_segment_payload_list(&storage, &log_data).unwrap();
+    let block_indexes = payload_list
+        .iter()
+        .map(|(block_index, _)| *block_index)
+        .collect::<Vec<BlockIndex>>();
+    assert_eq!(block_indexes, vec![1, 4, 5]);
+    // - contiguous allocation picks a run of free blocks
+    let (payload_list, first_block_index, last_block_index) =
+        make_segment_payload_list_with_allocation(
+            &storage,
+            &log_data,
+            SegmentAllocation::Contiguous,
+        )
+        .unwrap();
+    assert_eq!(payload_list.len(), 3);
+    assert_eq!((first_block_index, last_block_index), (4, 6));
+    let (first_block_index, last_block_index) =
+        create_log_with_allocation(&mut storage, &log_data, SegmentAllocation::Contiguous).unwrap();
+    assert_eq!((first_block_index, last_block_index), (4, 6));
+    let (_, _, data) = read_log(&storage, first_block_index).unwrap();
+    assert_eq!(data, log_data);
+    // - no run fits, falls back to blocks picked by storage
+    let (first_block_index, last_block_index) =
+        create_log_with_allocation(&mut storage, &log_data, SegmentAllocation::Contiguous).unwrap();
+    assert_eq!((first_block_index, last_block_index), (1, 9));
+    let (_, _, data) = read_log(&storage, first_block_index).unwrap();
+    assert_eq!(data, log_data);
+}
+
+#[test]
+fn check_logs_flow() {
+    // - logs of 3 segments each, log 0 in a root, log 1 stored elsewhere
+    let new_storage = || {
+        let mut storage = Storage::new_in_memory(8, StorageOptions::default()).unwrap();
+        let (log_0_head, _) = create_log(&mut storage, &[1; 12]).unwrap();
+        let (log_1_head, _) = create_log(&mut storage, &[2; 12]).unwrap();
+        storage.set_root("log_0", log_0_head).unwrap();
+        (storage, log_0_head, log_1_head)
+    };
+    // link segment to given block, keeping its payload
+    let link = |storage: &mut Storage, block_index: BlockIndex, next_block_index: BlockIndex| {
+        let (_, segment_payload) = storage.read_block(block_index).unwrap();
+        let segment_payload = [
+            &(next_block_index as u32).to_le_bytes()[..],
+            &segment_payload[SIZE_OF_BLOCK_INDEX..],
+        ]
+        .concat();
+        storage.write_block(block_index, &segment_payload).unwrap();
+    };
+    let problems = |storage: &mut Storage, heads: &[BlockIndex], repair: bool| {
+        check_logs(storage, heads, repair)
+            .unwrap()
+            .findings
+            .into_iter()
+            .map(|finding| (finding.problem, finding.repaired))
+            .collect::<Vec<_>>()
+    };
+    // - intact logs, heads of roots are checked without being given
+    let (mut storage, log_0_head, log_1_head) = new_storage();
+    assert_eq!(problems(&mut storage, &[log_1_head], false), vec![]);
+    // - log not reached from any head
+    assert_eq!(
+        problems(&mut storage, &[], true),
+        vec![
+            (LogProblem::OrphanBlock { block_index: 3 }, false),
+            (LogProblem::OrphanBlock { block_index: 4 }, false),
+            (LogProblem::OrphanBlock { block_index: 5 }, false),
+        ]
+    );
+    assert_eq!(
+        problems(&mut storage, &[100], false),
+        [
+            vec![(LogProblem::InvalidHead { head: 100 }, false)],
+            (3..6)
+                .map(|block_index| (LogProblem::OrphanBlock { block_index }, false))
+                .collect::<Vec<_>>(),
+        ]
+        .concat()
+    );
+    // - links past end, to a free block, and back into its own log, are cut by repair
+    for (next_block_index, problem) in [
+        (
+            100,
+            LogProblem::NextPastEnd {
+                head: log_0_head,
+                block_index: 1,
+                next_block_index: 100,
+            },
+        ),
+        (
+            log_0_head,
+            LogProblem::Cycle {
+                head: log_0_head,
+                block_index: 1,
+                next_block_index: log_0_head,
+            },
+        ),
+    ] {
+        let (mut storage, log_0_head, log_1_head) = new_storage();
+        link(&mut storage, 1, next_block_index);
+        assert_eq!(
+            problems(&mut storage, &[log_1_head], false),
+            vec![
+                (problem.clone(), false),
+                (LogProblem::OrphanBlock { block_index: 2 }, false)
+            ]
+        );
+        assert_eq!(
+            problems(&mut storage, &[log_1_head], true),
+            vec![
+                (problem, true),
+                (LogProblem::OrphanBlock { block_index: 2 }, false)
+            ]
+        );
+        let (_, last_block_index, data) = read_log(&storage, log_0_head).unwrap();
+        assert_eq!((last_block_index, data), (1, vec![1; 8]));
+        assert_eq!(
+            problems(&mut storage, &[log_1_head], false),
+            vec![(LogProblem::OrphanBlock { block_index: 2 }, false)]
+        );
+    }
+    let (mut storage, log_0_head, log_1_head) = new_storage();
+    storage.delete_block(2, false).unwrap();
+    assert_eq!(
+        problems(&mut storage, &[log_1_head], true),
+        vec![(
+            LogProblem::NextToFreeBlock {
+                head: log_0_head,
+                block_index: 1,
+                next_block_index: 2,
+            },
+            true
+        )]
+    );
+    // - log linking into another log is not repaired
+    let (mut storage, log_0_head, log_1_head) = new_storage();
+    link(&mut storage, 1, 4);
+    assert_eq!(
+        problems(&mut storage, &[log_1_head], true),
+        vec![
+            (
+                LogProblem::SharedBlock {
+                    block_index: 4,
+                    heads: (log_0_head, log_1_head),
+                },
+                false
+            ),
+            (LogProblem::OrphanBlock { block_index: 2 }, false),
+        ]
+    );
+    // - segment too short for a link cuts log before it
+    let (mut storage, log_0_head, log_1_head) = new_storage();
+    storage.write_block(2, &[1, 2]).unwrap();
+    assert_eq!(
+        problems(&mut storage, &[log_1_head], true),
+        vec![(
+            LogProblem::UnreadableSegment {
+                head: log_0_head,
+                block_index: 2,
+                code: String::from("block_index_from_buffer_insufficient_buffer_size"),
+            },
+            true
+        )]
+    );
+    let (_, last_block_index, _) = read_log(&storage, log_0_head).unwrap();
+    assert_eq!(last_block_index, 1);
+}
+
+#[test]
+fn logchain_wide_block_index() {
+    let tmp_dir_path = tempfile::tempdir().unwrap().keep();
+    let tmp_file_path: std::path::PathBuf = [
+        tmp_dir_path.to_str().unwrap().to_string(),
+        String::from("logchain_wide_block_index.hex"),
+    ]
+    .iter()
+    .collect();
+    let tmp_file_path = tmp_file_path.to_str().unwrap();
+    let options = StorageOptions {
+        block_index_width: BlockIndexWidth::U64,
+        ..Default::default()
+    };
+    let mut storage = Storage::new_with_options(String::from(tmp_file_path), 16, options).unwrap();
+    // - segments of 8 bytes next block index & 8 bytes data
+    let (log_0_head, last_block_index) = create_log(&mut storage, &[1; 20]).unwrap();
+    assert_eq!((log_0_head, last_block_index), (0, 2));
+    let (_, segment_payload) = storage.read_block(0).unwrap();
+    assert_eq!(segment_payload[0..8], [1, 0, 0, 0, 0, 0, 0, 0]);
+    assert_eq!(segment_payload.len(), 16);
+    let (_, segment_payload) = storage.read_block(2).unwrap();
+    assert_eq!(segment_payload[0..8], [0xff; 8]);
+    assert_eq!(segment_payload.len(), 12);
+    let (log_1_head, _) = create_log(&mut storage, &[2; 4]).unwrap();
+    // - append fills last segment, then new segments
+    let last_block_index = append_log(&mut storage, log_0_head, &[3; 10]).unwrap();
+    assert_eq!(last_block_index, 4);
+    let expected_data = [vec![1; 20], vec![3; 10]].concat();
+    let (_, _, data) = read_log(&storage, log_0_head).unwrap();
+    assert_eq!(data, expected_data);
+    let report = check_logs(&mut storage, &[log_0_head, log_1_head], false).unwrap();
+    assert!(report.is_clean(), "{:?}", report);
+    // - compaction rewrites 8 bytes next block indexes
+    delete_log(&mut storage, log_1_head, false).unwrap();
+    drop(storage);
+    let remap = compact_logs_file(String::from(tmp_file_path), StorageOptions::default()).unwrap();
+    assert_eq!(remap.into_iter().collect::<Vec<_>>(), vec![(4, 3)]);
+    let storage = Storage::open(String::from(tmp_file_path)).unwrap();
+    assert_eq!(storage.block_index_width(), BlockIndexWidth::U64);
+    let (_, last_block_index, data) = read_log(&storage, log_0_head).unwrap();
+    assert_eq!((last_block_index, data), (3, expected_data));
+    drop(storage);
+    // clear clutter
+    remove_dir_contents(tmp_dir_path);
+}
+
+#[test]
+fn logchain_storage_full() {
+    let options = StorageOptions {
+        quota: Some(StorageQuota::Blocks(3)),
+        ..Default::default()
+    };
+    let mut storage = Storage::new_in_memory(8, options).unwrap();
+    // - log spans all 3 blocks of quota
+    let (log_head, _) = create_log(&mut storage, &[1; 10]).unwrap();
+    // - storage full is passed through, nothing is written
+    let result = make_segment_payload_list(&storage, &[2; 4]);
+    assert_eq!(result.err().unwrap().code(), "storage_full");
+    let result = create_log(&mut storage, &[2; 4]);
+    assert_eq!(result.err().unwrap().code(), "storage_full");
+    let result = append_log(&mut storage, log_head, &[3; 8]);
+    assert_eq!(result.err().unwrap().code(), "storage_full");
+    assert_eq!(storage.end_block_count(), 3);
+    let (_, _, data) = read_log(&storage, log_head).unwrap();
+    assert_eq!(data, vec![1; 10]);
+    // - deleted log frees blocks for a new one
+    delete_log(&mut storage, log_head, false).unwrap();
+    let (log_head, _) = create_log(&mut storage, &[2; 4]).unwrap();
+    assert_eq!(log_head, 0);
+}
+
+#[test]
+fn logchain_shadow_paging_snapshot() {
+    let tmp_dir_path = tempfile::tempdir().unwrap().keep();
+    let mut tmp_file_path_buf = tmp_dir_path.clone();
+    tmp_file_path_buf.push("logchain_shadow_paging_snapshot.bin");
+    let tmp_file_path = tmp_file_path_buf.to_str().unwrap().to_string();
+    let options = StorageOptions {
+        shadow_paging: true,
+        ..Default::default()
+    };
+    let mut storage = Storage::new_with_options(tmp_file_path.clone(), 8, options).unwrap();
+    let (log_head, _) = create_log(&mut storage, &[1; 10]).unwrap();
+    storage.set_root("log", log_head).unwrap();
+    storage.create_snapshot("v1").unwrap();
+    // - appending relinks last segment of the log, in a fresh block
+    append_log(&mut storage, log_head, &[2; 6]).unwrap();
+    storage.checkpoint().unwrap();
+    drop(storage);
+    // - snapshot reads the log as it was
+    let snapshot = Storage::open_snapshot(tmp_file_path.clone(), "v1").unwrap();
+    let (_, _, data) = read_log(&snapshot, snapshot.get_root("log").unwrap()).unwrap();
+    assert_eq!(data, vec![1; 10]);
+    drop(snapshot);
+    let storage = Storage::open(tmp_file_path.clone()).unwrap();
+    let (_, _, data) = read_log(&storage, log_head).unwrap();
+    assert_eq!(data, [vec![1; 10], vec![2; 6]].concat());
+    drop(storage);
+    // clear clutter
+    remove_dir_contents(tmp_dir_path);
+}
+
+#[test]
+fn logchain_compression() {
+    let data = b"{\"key\":\"user\",\"value\":1} ".repeat(40);
+    let mut uncompressed_storage = Storage::new_in_memory(64, StorageOptions::default()).unwrap();
+    let (_, uncompressed_last_block_index) = create_log(&mut uncompressed_storage, &data).unwrap();
+    for compression in [BlockCompression::Lz4, BlockCompression::Zstd] {
+        let options = StorageOptions {
+            compression,
+            ..Default::default()
+        };
+        let mut storage = Storage::new_in_memory(64, options).unwrap();
+        // - segments hold as much data as without compression
+        let (log_head, last_block_index) = create_log(&mut storage, &data).unwrap();
+        assert_eq!(last_block_index, uncompressed_last_block_index);
+        append_log(&mut storage, log_head, &data).unwrap();
+        let (_, _, log_data) = read_log(&storage, log_head).unwrap();
+        assert_eq!(log_data, [data.clone(), data.clone()].concat());
+        // - repetitive segments are stored compressed
+        let stats = storage.stats().unwrap();
+        assert!(stats.compressed_blocks > 0);
+        assert!(stats.stored_data_bytes < stats.data_bytes);
+    }
+}
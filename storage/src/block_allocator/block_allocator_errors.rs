@@ -0,0 +1,15 @@
+use crate::BlockIndex;
+use util::error::{Error, ErrorType};
+
+// .... .... BlockAllocator::allocate .... ....
+
+pub fn allocate_block_index_overflow(first_block_index: BlockIndex, count: BlockIndex) -> Error {
+    Error::new(
+        ErrorType::Happens,
+        "allocate_block_index_overflow",
+        Some(format!(
+            "Failed to allocate {} blocks from block index {}, block indexes overflow.",
+            count, first_block_index
+        )),
+    )
+}
@@ -0,0 +1,211 @@
+use crate::BlockIndex;
+use std::collections::BTreeSet;
+use util::error::Error;
+
+mod block_allocator_errors;
+
+/// Picks blocks of storage, new data is written to
+/// - Blocks are picked from free blocks in file, or from end_block_count on, which extends the file
+pub trait BlockAllocator: Send + Sync {
+    /// Block indexes to write given number of blocks to, in ascending order
+    /// - free_blocks: free blocks in storage file
+    /// - end_block_count: number of blocks in storage file
+    /// - returns `allocate_block_index_overflow` if block indexes would overflow BlockIndex
+    fn allocate(
+        &self,
+        free_blocks: &BTreeSet<BlockIndex>,
+        end_block_count: BlockIndex,
+        count: BlockIndex,
+    ) -> Result<Vec<BlockIndex>, Error>;
+}
+
+/// Lowest free blocks, then blocks beyond end of file
+/// - fills holes first, a chain may be scattered across the file
+pub struct FirstFitLowest;
+
+impl BlockAllocator for FirstFitLowest {
+    fn allocate(
+        &self,
+        free_blocks: &BTreeSet<BlockIndex>,
+        end_block_count: BlockIndex,
+        count: BlockIndex,
+    ) -> Result<Vec<BlockIndex>, Error> {
+        let mut block_indexes = free_blocks
+            .iter()
+            .take(count as usize)
+            .cloned()
+            .collect::<Vec<BlockIndex>>();
+        // push indexes beyond end_block_count if required
+        let extend_count = count - block_indexes.len() as BlockIndex;
+        block_indexes.extend(end_block_count..extend_end(end_block_count, extend_count)?);
+        Ok(block_indexes)
+    }
+}
+
+/// Smallest run of free blocks holding all blocks, else blocks beyond end of file
+/// - blocks are always contiguous, so a chain is read without seeking
+pub struct BestFitContiguous;
+
+impl BlockAllocator for BestFitContiguous {
+    fn allocate(
+        &self,
+        free_blocks: &BTreeSet<BlockIndex>,
+        end_block_count: BlockIndex,
+        count: BlockIndex,
+    ) -> Result<Vec<BlockIndex>, Error> {
+        let first_block_index =
+            contiguous_free_run(free_blocks, end_block_count, count).unwrap_or(end_block_count);
+        Ok((first_block_index..extend_end(first_block_index, count)?).collect())
+    }
+}
+
+/// Blocks beyond end of file only, free blocks are never reused
+/// - file grows with every write, for write once storages and benchmarks
+pub struct AppendOnly;
+
+impl BlockAllocator for AppendOnly {
+    fn allocate(
+        &self,
+        _free_blocks: &BTreeSet<BlockIndex>,
+        end_block_count: BlockIndex,
+        count: BlockIndex,
+    ) -> Result<Vec<BlockIndex>, Error> {
+        Ok((end_block_count..extend_end(end_block_count, count)?).collect())
+    }
+}
+
+/// End of a run of given number of blocks from first block index on
+/// - returns `allocate_block_index_overflow` if end does not fit in BlockIndex
+pub fn extend_end(first_block_index: BlockIndex, count: BlockIndex) -> Result<BlockIndex, Error> {
+    match first_block_index.checked_add(count) {
+        Some(end_block_index) => Ok(end_block_index),
+        None => Err(block_allocator_errors::allocate_block_index_overflow(
+            first_block_index,
+            count,
+        )),
+    }
+}
+
+/// First block of smallest run of free blocks, holding given number of blocks
+/// - a run of free blocks at end of file extends beyond it, so it holds any number of blocks,
+///   and is picked only if no run inside the file fits
+/// - None if no run of free blocks fits, or count is 0
+pub fn contiguous_free_run(
+    free_blocks: &BTreeSet<BlockIndex>,
+    end_block_count: BlockIndex,
+    count: BlockIndex,
+) -> Option<BlockIndex> {
+    if count == 0 {
+        return None;
+    }
+    // (run length, first block index) of best fitting run
+    let mut best_run: Option<(BlockIndex, BlockIndex)> = None;
+    let mut free_block_indexes = free_blocks.iter().cloned().peekable();
+    while let Some(run_start) = free_block_indexes.next() {
+        let mut run_end = run_start + 1;
+        while free_block_indexes.peek() == Some(&run_end) {
+            free_block_indexes.next();
+            run_end += 1;
+        }
+        let run_len = if run_end >= end_block_count {
+            BlockIndex::MAX
+        } else {
+            run_end - run_start
+        };
+        if run_len >= count && best_run.is_none_or(|(best_len, _)| run_len < best_len) {
+            best_run = Some((run_len, run_start));
+        }
+    }
+    best_run.map(|(_, run_start)| run_start)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_block_allocators() {
+        // - free runs [1], [3, 4, 5], [7, 8], and [10, 11] at end of file
+        let free_blocks = [1, 3, 4, 5, 7, 8, 10, 11]
+            .iter()
+            .cloned()
+            .collect::<BTreeSet<BlockIndex>>();
+        let end_block_count = 12;
+        assert_eq!(
+            FirstFitLowest
+                .allocate(&free_blocks, end_block_count, 3)
+                .unwrap(),
+            vec![1, 3, 4]
+        );
+        assert_eq!(
+            FirstFitLowest
+                .allocate(&free_blocks, end_block_count, 10)
+                .unwrap(),
+            vec![1, 3, 4, 5, 7, 8, 10, 11, 12, 13]
+        );
+        // - smallest fitting run, then run at end of file, extended beyond it
+        assert_eq!(
+            BestFitContiguous
+                .allocate(&free_blocks, end_block_count, 1)
+                .unwrap(),
+            vec![1]
+        );
+        assert_eq!(
+            BestFitContiguous
+                .allocate(&free_blocks, end_block_count, 2)
+                .unwrap(),
+            vec![7, 8]
+        );
+        assert_eq!(
+            BestFitContiguous
+                .allocate(&free_blocks, end_block_count, 3)
+                .unwrap(),
+            vec![3, 4, 5]
+        );
+        assert_eq!(
+            BestFitContiguous
+                .allocate(&free_blocks, end_block_count, 4)
+                .unwrap(),
+            vec![10, 11, 12, 13]
+        );
+        assert_eq!(
+            AppendOnly
+                .allocate(&free_blocks, end_block_count, 2)
+                .unwrap(),
+            vec![12, 13]
+        );
+        // - no free blocks
+        let free_blocks = BTreeSet::new();
+        for allocator in [
+            &FirstFitLowest as &dyn BlockAllocator,
+            &BestFitContiguous,
+            &AppendOnly,
+        ] {
+            assert_eq!(allocator.allocate(&free_blocks, 2, 2).unwrap(), vec![2, 3]);
+            assert_eq!(allocator.allocate(&free_blocks, 2, 0).unwrap(), vec![]);
+            // - block indexes past BlockIndex::MAX
+            assert_eq!(
+                allocator
+                    .allocate(&free_blocks, BlockIndex::MAX - 1, 2)
+                    .unwrap_err()
+                    .code(),
+                "allocate_block_index_overflow"
+            );
+        }
+    }
+
+    #[test]
+    fn test_contiguous_free_run() {
+        let free_blocks = [0, 1, 4, 5, 6]
+            .iter()
+            .cloned()
+            .collect::<BTreeSet<BlockIndex>>();
+        assert_eq!(contiguous_free_run(&free_blocks, 8, 2), Some(0));
+        assert_eq!(contiguous_free_run(&free_blocks, 8, 3), Some(4));
+        assert_eq!(contiguous_free_run(&free_blocks, 8, 4), None);
+        assert_eq!(contiguous_free_run(&free_blocks, 8, 0), None);
+        // - run at end of file fits any count
+        assert_eq!(contiguous_free_run(&free_blocks, 7, 4), Some(4));
+        assert_eq!(contiguous_free_run(&BTreeSet::new(), 7, 1), None);
+    }
+}
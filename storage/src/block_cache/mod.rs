@@ -0,0 +1,144 @@
+use crate::BlockIndex;
+use std::collections::{BTreeMap, HashMap};
+
+/// Counters of block cache, to tune its capacity
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BlockCacheStats {
+    /// Maximum number of blocks held in cache
+    pub capacity: usize,
+    /// Number of blocks held in cache
+    pub cached_blocks: usize,
+    /// Number of reads served from cache
+    pub hits: u64,
+    /// Number of reads served from storage file
+    pub misses: u64,
+}
+
+/// Bounded LRU cache of block data, held in memory
+/// - Capacity is number of blocks, 0 disables the cache
+/// - Least recently used block is evicted, once cache is full
+pub struct BlockCache {
+    capacity: usize,
+    /// block_index -> (last use tick, block data)
+    blocks: HashMap<BlockIndex, (u64, Vec<u8>)>,
+    /// last use tick -> block_index, oldest first
+    recency: BTreeMap<u64, BlockIndex>,
+    tick: u64,
+    hits: u64,
+    misses: u64,
+}
+
+impl BlockCache {
+    pub fn new(capacity: usize) -> BlockCache {
+        BlockCache {
+            capacity,
+            blocks: HashMap::new(),
+            recency: BTreeMap::new(),
+            tick: 0,
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.capacity > 0
+    }
+
+    /// Get copy of cached block data, and mark block as recently used
+    /// - counts a hit or a miss, if cache is enabled
+    pub fn get(&mut self, block_index: BlockIndex) -> Option<Vec<u8>> {
+        if !self.enabled() {
+            return None;
+        }
+        self.tick += 1;
+        match self.blocks.get_mut(&block_index) {
+            Some((last_use_tick, block_data)) => {
+                self.recency.remove(last_use_tick);
+                self.recency.insert(self.tick, block_index);
+                *last_use_tick = self.tick;
+                self.hits += 1;
+                Some(block_data.clone())
+            }
+            None => {
+                self.misses += 1;
+                None
+            }
+        }
+    }
+
+    /// Insert or update cached block data, evict least recently used blocks if full
+    pub fn put(&mut self, block_index: BlockIndex, block_data: &[u8]) {
+        if !self.enabled() {
+            return;
+        }
+        self.tick += 1;
+        if let Some((last_use_tick, _)) = self
+            .blocks
+            .insert(block_index, (self.tick, block_data.to_vec()))
+        {
+            self.recency.remove(&last_use_tick);
+        }
+        self.recency.insert(self.tick, block_index);
+        while self.blocks.len() > self.capacity {
+            let (_, evicted_block_index) = self.recency.pop_first().unwrap();
+            self.blocks.remove(&evicted_block_index);
+        }
+    }
+
+    /// Remove block from cache
+    pub fn remove(&mut self, block_index: BlockIndex) {
+        if let Some((last_use_tick, _)) = self.blocks.remove(&block_index) {
+            self.recency.remove(&last_use_tick);
+        }
+    }
+
+    pub fn stats(&self) -> BlockCacheStats {
+        BlockCacheStats {
+            capacity: self.capacity,
+            cached_blocks: self.blocks.len(),
+            hits: self.hits,
+            misses: self.misses,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_block_cache_lru_eviction() {
+        let mut block_cache = BlockCache::new(2);
+        block_cache.put(0, &[0]);
+        block_cache.put(1, &[1]);
+        // block 0 becomes most recently used
+        assert_eq!(block_cache.get(0), Some(vec![0]));
+        // block 1 is evicted
+        block_cache.put(2, &[2]);
+        assert_eq!(block_cache.get(1), None);
+        assert_eq!(block_cache.get(0), Some(vec![0]));
+        assert_eq!(block_cache.get(2), Some(vec![2]));
+        // update keeps a single entry
+        block_cache.put(2, &[2, 2]);
+        assert_eq!(block_cache.get(2), Some(vec![2, 2]));
+        block_cache.remove(0);
+        assert_eq!(block_cache.get(0), None);
+        assert_eq!(
+            block_cache.stats(),
+            BlockCacheStats {
+                capacity: 2,
+                cached_blocks: 1,
+                hits: 4,
+                misses: 2,
+            }
+        );
+    }
+
+    #[test]
+    fn test_block_cache_disabled() {
+        let mut block_cache = BlockCache::new(0);
+        block_cache.put(0, &[0]);
+        assert_eq!(block_cache.get(0), None);
+        assert_eq!(block_cache.stats(), BlockCacheStats::default());
+    }
+}
@@ -0,0 +1,104 @@
+/// Codec block data of a storage is compressed with, recorded in storage header
+/// - a block is stored compressed only if that makes it smaller, else it is stored raw,
+///   so a block holds up to block length bytes of data whatever the codec
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BlockCompression {
+    /// Blocks are stored raw
+    #[default]
+    None,
+    /// LZ4 block format, fast to compress & decompress
+    Lz4,
+    /// Zstandard, at default level, smaller than LZ4 but slower to compress
+    Zstd,
+}
+
+/// Compression level of Zstandard, its default
+const ZSTD_LEVEL: i32 = 3;
+
+/// Size of data size stored ahead of compressed data of a block, as 4 bytes little endian
+pub(crate) const COMPRESSED_DATA_SIZE_LEN: usize = 4;
+
+impl BlockCompression {
+    /// Compress block data, as data size followed by compressed data
+    /// - returns None if codec is None, or compressed block is not smaller than data
+    pub(crate) fn compress(self, data: &[u8]) -> Option<Vec<u8>> {
+        let compressed_data = match self {
+            BlockCompression::None => return None,
+            BlockCompression::Lz4 => lz4_flex::block::compress(data),
+            BlockCompression::Zstd => zstd::bulk::compress(data, ZSTD_LEVEL).ok()?,
+        };
+        if COMPRESSED_DATA_SIZE_LEN + compressed_data.len() >= data.len() {
+            return None;
+        }
+        Some([&(data.len() as u32).to_le_bytes()[..], &compressed_data].concat())
+    }
+
+    /// Decompress block compressed by `compress`
+    /// - returns None if block is corrupt, or its data would not fit in given block length
+    pub(crate) fn decompress(self, bytes: &[u8], block_len: u32) -> Option<Vec<u8>> {
+        let data_size = compressed_data_size(bytes)?;
+        if data_size > block_len {
+            return None;
+        }
+        let compressed_data = &bytes[COMPRESSED_DATA_SIZE_LEN..];
+        let data = match self {
+            BlockCompression::None => return None,
+            BlockCompression::Lz4 => {
+                lz4_flex::block::decompress(compressed_data, data_size as usize).ok()?
+            }
+            BlockCompression::Zstd => {
+                zstd::bulk::decompress(compressed_data, data_size as usize).ok()?
+            }
+        };
+        if data.len() != data_size as usize {
+            return None;
+        }
+        Some(data)
+    }
+}
+
+/// Size of data of a compressed block, from start of its bytes
+/// - returns None if bytes are shorter than the data size
+pub(crate) fn compressed_data_size(bytes: &[u8]) -> Option<u32> {
+    let bytes = bytes.get(0..COMPRESSED_DATA_SIZE_LEN)?;
+    Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_block_compression_compress_and_back() {
+        let data = [b"segment of a log, ".repeat(8), vec![7; 64]].concat();
+        for compression in [BlockCompression::Lz4, BlockCompression::Zstd] {
+            let bytes = compression.compress(&data).unwrap();
+            assert!(bytes.len() < data.len());
+            assert_eq!(compressed_data_size(&bytes), Some(data.len() as u32));
+            assert_eq!(
+                compression.decompress(&bytes, data.len() as u32),
+                Some(data.clone())
+            );
+            // - data larger than block length, or torn compressed data is corrupt
+            assert_eq!(compression.decompress(&bytes, data.len() as u32 - 1), None);
+            assert_eq!(
+                compression.decompress(&bytes[..bytes.len() - 1], data.len() as u32),
+                None
+            );
+        }
+        assert_eq!(BlockCompression::None.compress(&data), None);
+    }
+
+    #[test]
+    fn test_block_compression_incompressible() {
+        // - short & random data does not shrink, so it is stored raw
+        let data = (0..64u32)
+            .map(|value| (value.wrapping_mul(2654435761) >> 24) as u8)
+            .collect::<Vec<u8>>();
+        for compression in [BlockCompression::Lz4, BlockCompression::Zstd] {
+            assert_eq!(compression.compress(&data), None);
+            assert_eq!(compression.compress(&[1, 2, 3]), None);
+            assert_eq!(compression.compress(&[]), None);
+        }
+    }
+}
@@ -0,0 +1,293 @@
+use super::BlockDevice;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// Fault injected into a read, write or sync of a `FaultyBlockDevice`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Fault {
+    /// Only given number of bytes are read or written, call succeeds
+    /// - a sync with this fault fails like `Fault::Io`
+    Short(usize),
+    /// Call fails with ENOSPC, nothing is written
+    NoSpace,
+    /// Call fails with EIO, nothing is read or written
+    Io,
+}
+
+/// Block device to test I/O error paths, wraps another device and fails calls as scripted
+/// - Faults are scheduled for the n-th next read, write or sync, and fire once
+/// - A crash after N bytes writes up to N more bytes, then fails every call,
+///   till the device is revived like after a restart. Bytes written before the crash persist.
+/// - Writes since last sync are tracked, so a power failure losing any of them can be simulated
+/// - Clones share the wrapped device and the script, so a test can keep a clone
+///   to script faults and reopen the device, after handing one to `Storage`
+/// - Siblings wrap other devices on the same power supply, so a single crash hits all of them,
+///   e.g. a storage and its journal
+#[derive(Clone)]
+pub struct FaultyBlockDevice {
+    state: Arc<Mutex<FaultyBlockDeviceState>>,
+    power: Arc<Mutex<Power>>,
+}
+
+/// Power supply of sibling devices
+struct Power {
+    /// Number of bytes left to write to any sibling before crash, if a crash is scheduled
+    crash_after_bytes: Option<u64>,
+    crashed: bool,
+}
+
+struct FaultyBlockDeviceState {
+    device: Box<dyn BlockDevice>,
+    /// Number of reads, writes & syncs called so far
+    read_count: usize,
+    write_count: usize,
+    sync_count: usize,
+    /// Faults scheduled by read, write & sync count they fire at
+    read_faults: Vec<(usize, Fault)>,
+    write_faults: Vec<(usize, Fault)>,
+    sync_faults: Vec<(usize, Fault)>,
+    /// Bytes of wrapped device at last sync, None till first write
+    synced_bytes: Option<Vec<u8>>,
+    /// Writes since last sync, as (offset, data)
+    unsynced_writes: Vec<(u64, Vec<u8>)>,
+}
+
+/// Read all bytes of device
+fn read_all(device: &dyn BlockDevice) -> std::io::Result<Vec<u8>> {
+    let mut bytes = vec![0u8; device.size()? as usize];
+    let read_size = device.read_at(&mut bytes, 0)?;
+    bytes.truncate(read_size);
+    Ok(bytes)
+}
+
+/// Take fault scheduled at given call count, if any
+fn take_fault(faults: &mut Vec<(usize, Fault)>, count: usize) -> Option<Fault> {
+    let position = faults.iter().position(|(at_count, _)| *at_count == count)?;
+    Some(faults.remove(position).1)
+}
+
+fn fault_error(fault: Fault) -> std::io::Error {
+    match fault {
+        Fault::NoSpace => std::io::Error::new(
+            std::io::ErrorKind::StorageFull,
+            "injected fault: no space left on device",
+        ),
+        _ => std::io::Error::other("injected fault: input/output error"),
+    }
+}
+
+fn crashed_error() -> std::io::Error {
+    std::io::Error::other("injected fault: device crashed")
+}
+
+impl FaultyBlockDevice {
+    pub fn new(device: Box<dyn BlockDevice>) -> FaultyBlockDevice {
+        let power = Arc::new(Mutex::new(Power {
+            crash_after_bytes: None,
+            crashed: false,
+        }));
+        FaultyBlockDevice::new_on_power(device, power)
+    }
+
+    /// Wrap another device on the same power supply as this one
+    /// - a crash after N bytes counts bytes written to either device, and crashes both
+    /// - faults & unsynced writes are tracked per device
+    pub fn new_sibling(&self, device: Box<dyn BlockDevice>) -> FaultyBlockDevice {
+        FaultyBlockDevice::new_on_power(device, self.power.clone())
+    }
+
+    fn new_on_power(device: Box<dyn BlockDevice>, power: Arc<Mutex<Power>>) -> FaultyBlockDevice {
+        FaultyBlockDevice {
+            state: Arc::new(Mutex::new(FaultyBlockDeviceState {
+                device,
+                read_count: 0,
+                write_count: 0,
+                sync_count: 0,
+                read_faults: Vec::new(),
+                write_faults: Vec::new(),
+                sync_faults: Vec::new(),
+                synced_bytes: None,
+                unsynced_writes: Vec::new(),
+            })),
+            power,
+        }
+    }
+
+    fn state(&self) -> MutexGuard<'_, FaultyBlockDeviceState> {
+        // a test panicking while holding the lock leaves the state usable
+        self.state.lock().unwrap_or_else(|error| error.into_inner())
+    }
+
+    /// Lock power supply, never held while locking state
+    fn power(&self) -> MutexGuard<'_, Power> {
+        self.power.lock().unwrap_or_else(|error| error.into_inner())
+    }
+
+    /// Inject fault into n-th next read, 0 for the very next read
+    pub fn fail_read(&self, nth: usize, fault: Fault) {
+        let mut state = self.state();
+        let at_count = state.read_count + nth;
+        state.read_faults.push((at_count, fault));
+    }
+
+    /// Inject fault into n-th next write, 0 for the very next write
+    pub fn fail_write(&self, nth: usize, fault: Fault) {
+        let mut state = self.state();
+        let at_count = state.write_count + nth;
+        state.write_faults.push((at_count, fault));
+    }
+
+    /// Inject fault into n-th next sync, 0 for the very next sync
+    pub fn fail_sync(&self, nth: usize, fault: Fault) {
+        let mut state = self.state();
+        let at_count = state.sync_count + nth;
+        state.sync_faults.push((at_count, fault));
+    }
+
+    /// Crash once given number of bytes more are written
+    /// - write crossing the limit is torn, only bytes up to the limit reach the device
+    pub fn crash_after_bytes(&self, bytes: u64) {
+        self.power().crash_after_bytes = Some(bytes);
+    }
+
+    /// Check if device crashed
+    pub fn crashed(&self) -> bool {
+        self.power().crashed
+    }
+
+    /// Bring crashed device back, like after a restart
+    /// - drops every scheduled fault of this device, and crash of every sibling
+    pub fn revive(&self) {
+        {
+            let mut power = self.power();
+            power.crashed = false;
+            power.crash_after_bytes = None;
+        }
+        let mut state = self.state();
+        state.read_faults.clear();
+        state.write_faults.clear();
+        state.sync_faults.clear();
+    }
+
+    /// Number of writes since last sync
+    pub fn unsynced_write_count(&self) -> usize {
+        self.state().unsynced_writes.len()
+    }
+
+    /// Simulate a power failure, writes since last sync are lost unless kept
+    /// - keep: called with position of each unsynced write, 0 for the oldest
+    /// - kept writes are applied in order on bytes of last sync, and count as synced after
+    pub fn lose_unsynced_writes(&self, keep: impl Fn(usize) -> bool) -> std::io::Result<()> {
+        let mut state = self.state();
+        let synced_bytes = match state.synced_bytes.take() {
+            Some(synced_bytes) => synced_bytes,
+            None => return Ok(()),
+        };
+        let unsynced_writes = std::mem::take(&mut state.unsynced_writes);
+        state.device.set_size(0)?;
+        state.device.write_at(&synced_bytes, 0)?;
+        for (position, (offset, data)) in unsynced_writes.iter().enumerate() {
+            if keep(position) {
+                state.device.write_at(data, *offset)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Number of (reads, writes, syncs) called so far, failed calls included
+    pub fn call_counts(&self) -> (usize, usize, usize) {
+        let state = self.state();
+        (state.read_count, state.write_count, state.sync_count)
+    }
+}
+
+impl BlockDevice for FaultyBlockDevice {
+    fn read_at(&self, buffer: &mut [u8], offset: u64) -> std::io::Result<usize> {
+        let mut state = self.state();
+        let read_count = state.read_count;
+        state.read_count += 1;
+        if self.crashed() {
+            return Err(crashed_error());
+        }
+        match take_fault(&mut state.read_faults, read_count) {
+            Some(Fault::Short(size)) => {
+                let size = size.min(buffer.len());
+                state.device.read_at(&mut buffer[..size], offset)
+            }
+            Some(fault) => Err(fault_error(fault)),
+            None => state.device.read_at(buffer, offset),
+        }
+    }
+
+    fn write_at(&mut self, data: &[u8], offset: u64) -> std::io::Result<usize> {
+        let mut state = self.state();
+        let write_count = state.write_count;
+        state.write_count += 1;
+        if self.crashed() {
+            return Err(crashed_error());
+        }
+        let data = match take_fault(&mut state.write_faults, write_count) {
+            Some(Fault::Short(size)) => &data[..size.min(data.len())],
+            Some(fault) => return Err(fault_error(fault)),
+            None => data,
+        };
+        if state.synced_bytes.is_none() {
+            state.synced_bytes = Some(read_all(state.device.as_ref())?);
+        }
+        let crash_after_bytes = self.power().crash_after_bytes;
+        if let Some(bytes_left) = crash_after_bytes {
+            if data.len() as u64 > bytes_left {
+                // - torn write, then crash
+                let data = &data[..bytes_left as usize];
+                state.device.write_at(data, offset)?;
+                state.unsynced_writes.push((offset, data.to_vec()));
+                let mut power = self.power();
+                power.crash_after_bytes = None;
+                power.crashed = true;
+                return Err(crashed_error());
+            }
+            self.power().crash_after_bytes = Some(bytes_left - data.len() as u64);
+        }
+        let write_size = state.device.write_at(data, offset)?;
+        state
+            .unsynced_writes
+            .push((offset, data[..write_size].to_vec()));
+        Ok(write_size)
+    }
+
+    /// Vectored write is a single write call, faults apply to all buffers together
+    fn write_vectored_at(&mut self, buffers: &[&[u8]], offset: u64) -> std::io::Result<usize> {
+        self.write_at(&buffers.concat(), offset)
+    }
+
+    fn size(&self) -> std::io::Result<u64> {
+        let state = self.state();
+        if self.crashed() {
+            return Err(crashed_error());
+        }
+        state.device.size()
+    }
+
+    fn set_size(&mut self, size: u64) -> std::io::Result<()> {
+        let mut state = self.state();
+        if self.crashed() {
+            return Err(crashed_error());
+        }
+        state.device.set_size(size)
+    }
+
+    fn sync(&mut self) -> std::io::Result<()> {
+        let mut state = self.state();
+        let sync_count = state.sync_count;
+        state.sync_count += 1;
+        if self.crashed() {
+            return Err(crashed_error());
+        }
+        if let Some(fault) = take_fault(&mut state.sync_faults, sync_count) {
+            return Err(fault_error(fault));
+        }
+        state.device.sync()?;
+        state.synced_bytes = Some(read_all(state.device.as_ref())?);
+        state.unsynced_writes.clear();
+        Ok(())
+    }
+}
@@ -0,0 +1,211 @@
+use super::BlockDevice;
+use std::fs::{File, OpenOptions};
+
+/// Block device on a file, with positional read & write syscalls
+pub struct FileBlockDevice {
+    file: File,
+}
+
+impl FileBlockDevice {
+    /// Open file in given path for reading & writing
+    /// - creates a new file if it does not exist
+    /// - truncate: if true, truncates the file to 0 bytes
+    pub fn open(file_path: &str, truncate: bool) -> std::io::Result<FileBlockDevice> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .truncate(truncate)
+            .create(true)
+            .open(file_path)?;
+        Ok(FileBlockDevice { file })
+    }
+
+    /// Open existing file in given path for reading only
+    /// - writes fail, as the file is not opened for writing
+    pub fn open_read_only(file_path: &str) -> std::io::Result<FileBlockDevice> {
+        let file = File::open(file_path)?;
+        Ok(FileBlockDevice { file })
+    }
+}
+
+impl BlockDevice for FileBlockDevice {
+    fn read_at(&self, buffer: &mut [u8], offset: u64) -> std::io::Result<usize> {
+        // read till buffer is full or end of file, a single syscall may return less
+        let mut read_size = 0;
+        while read_size < buffer.len() {
+            match read_at(
+                &self.file,
+                &mut buffer[read_size..],
+                offset + read_size as u64,
+            ) {
+                Ok(0) => break,
+                Ok(size) => read_size += size,
+                Err(error) if error.kind() == std::io::ErrorKind::Interrupted => {}
+                Err(error) => return Err(error),
+            }
+        }
+        Ok(read_size)
+    }
+
+    fn write_at(&mut self, data: &[u8], offset: u64) -> std::io::Result<usize> {
+        // write till all data is written, a single syscall may write less
+        let mut write_size = 0;
+        while write_size < data.len() {
+            match write_at(&self.file, &data[write_size..], offset + write_size as u64) {
+                Ok(0) => break,
+                Ok(size) => write_size += size,
+                Err(error) if error.kind() == std::io::ErrorKind::Interrupted => {}
+                Err(error) => return Err(error),
+            }
+        }
+        Ok(write_size)
+    }
+
+    #[cfg(unix)]
+    fn write_vectored_at(&mut self, buffers: &[&[u8]], offset: u64) -> std::io::Result<usize> {
+        // write till all buffers are written, a single syscall may write less
+        let total_size = buffers.iter().map(|buffer| buffer.len()).sum::<usize>();
+        let mut write_size = 0;
+        while write_size < total_size {
+            let remaining_buffers = skip_bytes(buffers, write_size);
+            match write_vectored_at(&self.file, &remaining_buffers, offset + write_size as u64) {
+                Ok(0) => break,
+                Ok(size) => write_size += size,
+                Err(error) if error.kind() == std::io::ErrorKind::Interrupted => {}
+                Err(error) => return Err(error),
+            }
+        }
+        Ok(write_size)
+    }
+
+    fn size(&self) -> std::io::Result<u64> {
+        Ok(self.file.metadata()?.len())
+    }
+
+    fn set_size(&mut self, size: u64) -> std::io::Result<()> {
+        self.file.set_len(size)
+    }
+
+    fn sync(&mut self) -> std::io::Result<()> {
+        self.file.sync_data()
+    }
+
+    fn punch_hole(&mut self, offset: u64, len: u64) -> std::io::Result<()> {
+        if !punch_file_hole(&self.file, offset, len)? {
+            super::write_zeros(self, offset, len)?;
+        }
+        Ok(())
+    }
+
+    fn allocated_size(&self) -> std::io::Result<u64> {
+        file_allocated_size(&self.file)
+    }
+}
+
+/// Deallocate given range of file with fallocate, keeping file size
+/// - returns false if file system or OS can not punch holes, and range is left as is
+#[cfg(target_os = "linux")]
+pub(super) fn punch_file_hole(file: &File, offset: u64, len: u64) -> std::io::Result<bool> {
+    use std::os::unix::io::AsRawFd;
+    if len == 0 {
+        return Ok(true);
+    }
+    // SAFETY: fallocate only reads its integer arguments, fd is owned by file
+    let result = unsafe {
+        libc::fallocate(
+            file.as_raw_fd(),
+            libc::FALLOC_FL_PUNCH_HOLE | libc::FALLOC_FL_KEEP_SIZE,
+            offset as libc::off_t,
+            len as libc::off_t,
+        )
+    };
+    if result < 0 {
+        let error = std::io::Error::last_os_error();
+        return match error.raw_os_error() {
+            Some(libc::EOPNOTSUPP) | Some(libc::ENOSYS) => Ok(false),
+            _ => Err(error),
+        };
+    }
+    Ok(true)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(super) fn punch_file_hole(_file: &File, _offset: u64, _len: u64) -> std::io::Result<bool> {
+    Ok(false)
+}
+
+/// Bytes of disk allocated to file, from 512 byte units reported by stat
+#[cfg(unix)]
+pub(super) fn file_allocated_size(file: &File) -> std::io::Result<u64> {
+    use std::os::unix::fs::MetadataExt;
+    Ok(file.metadata()?.blocks() * 512)
+}
+
+#[cfg(not(unix))]
+pub(super) fn file_allocated_size(file: &File) -> std::io::Result<u64> {
+    Ok(file.metadata()?.len())
+}
+
+#[cfg(unix)]
+fn read_at(file: &File, buffer: &mut [u8], offset: u64) -> std::io::Result<usize> {
+    std::os::unix::fs::FileExt::read_at(file, buffer, offset)
+}
+
+#[cfg(unix)]
+fn write_at(file: &File, data: &[u8], offset: u64) -> std::io::Result<usize> {
+    std::os::unix::fs::FileExt::write_at(file, data, offset)
+}
+
+#[cfg(windows)]
+fn read_at(file: &File, buffer: &mut [u8], offset: u64) -> std::io::Result<usize> {
+    std::os::windows::fs::FileExt::seek_read(file, buffer, offset)
+}
+
+#[cfg(windows)]
+fn write_at(file: &File, data: &[u8], offset: u64) -> std::io::Result<usize> {
+    std::os::windows::fs::FileExt::seek_write(file, data, offset)
+}
+
+/// Buffers left after skipping given number of bytes from the start
+#[cfg(unix)]
+fn skip_bytes<'a>(buffers: &[&'a [u8]], mut skip_size: usize) -> Vec<&'a [u8]> {
+    let mut remaining_buffers = Vec::with_capacity(buffers.len());
+    for buffer in buffers {
+        if skip_size >= buffer.len() {
+            skip_size -= buffer.len();
+            continue;
+        }
+        remaining_buffers.push(&buffer[skip_size..]);
+        skip_size = 0;
+    }
+    remaining_buffers
+}
+
+/// Single pwritev syscall, with at most IOV_MAX buffers
+#[cfg(unix)]
+fn write_vectored_at(file: &File, buffers: &[&[u8]], offset: u64) -> std::io::Result<usize> {
+    use std::os::unix::io::AsRawFd;
+    // IOV_MAX is 1024 on Linux & macOS
+    const IOV_MAX: usize = 1024;
+    let iovecs = buffers
+        .iter()
+        .take(IOV_MAX)
+        .map(|buffer| libc::iovec {
+            iov_base: buffer.as_ptr() as *mut libc::c_void,
+            iov_len: buffer.len(),
+        })
+        .collect::<Vec<libc::iovec>>();
+    // SAFETY: iovecs point into buffers, which outlive the call, and are only read from
+    let write_size = unsafe {
+        libc::pwritev(
+            file.as_raw_fd(),
+            iovecs.as_ptr(),
+            iovecs.len() as libc::c_int,
+            offset as libc::off_t,
+        )
+    };
+    if write_size < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(write_size as usize)
+}
@@ -0,0 +1,57 @@
+use super::BlockDevice;
+
+/// Block device held in memory, lost on drop
+/// - Useful for tests and temporary storages, without temp files
+#[derive(Default)]
+pub struct MemoryBlockDevice {
+    bytes: Vec<u8>,
+}
+
+impl MemoryBlockDevice {
+    pub fn new() -> MemoryBlockDevice {
+        MemoryBlockDevice { bytes: Vec::new() }
+    }
+
+    /// Device with given bytes, say a storage file read into memory
+    pub fn from_bytes(bytes: Vec<u8>) -> MemoryBlockDevice {
+        MemoryBlockDevice { bytes }
+    }
+
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+}
+
+impl BlockDevice for MemoryBlockDevice {
+    fn read_at(&self, buffer: &mut [u8], offset: u64) -> std::io::Result<usize> {
+        let offset = offset as usize;
+        if offset >= self.bytes.len() {
+            return Ok(0);
+        }
+        let read_size = buffer.len().min(self.bytes.len() - offset);
+        buffer[..read_size].copy_from_slice(&self.bytes[offset..offset + read_size]);
+        Ok(read_size)
+    }
+
+    fn write_at(&mut self, data: &[u8], offset: u64) -> std::io::Result<usize> {
+        let offset = offset as usize;
+        if offset + data.len() > self.bytes.len() {
+            self.bytes.resize(offset + data.len(), 0);
+        }
+        self.bytes[offset..offset + data.len()].copy_from_slice(data);
+        Ok(data.len())
+    }
+
+    fn size(&self) -> std::io::Result<u64> {
+        Ok(self.bytes.len() as u64)
+    }
+
+    fn set_size(&mut self, size: u64) -> std::io::Result<()> {
+        self.bytes.resize(size as usize, 0);
+        Ok(())
+    }
+
+    fn sync(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,141 @@
+use super::file_block_device::{file_allocated_size, punch_file_hole};
+use super::BlockDevice;
+use memmap2::{MmapMut, MmapOptions};
+use std::fs::{File, OpenOptions};
+
+/// Block device on a file, memory mapped into address space
+/// - Reads & writes are memory copies, page cache of the OS does the I/O
+/// - Writes beyond the end extend the file, and map it again
+pub struct MmapBlockDevice {
+    file: File,
+    /// None while file is empty, as an empty file can not be mapped
+    mmap: Option<MmapMut>,
+    /// File is opened for reading only, and mapped private, so writes are refused
+    read_only: bool,
+}
+
+impl MmapBlockDevice {
+    /// Open file in given path for reading & writing, and map it
+    /// - creates a new file if it does not exist
+    /// - truncate: if true, truncates the file to 0 bytes
+    pub fn open(file_path: &str, truncate: bool) -> std::io::Result<MmapBlockDevice> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .truncate(truncate)
+            .create(true)
+            .open(file_path)?;
+        let mut device = MmapBlockDevice {
+            file,
+            mmap: None,
+            read_only: false,
+        };
+        device.remap()?;
+        Ok(device)
+    }
+
+    /// Open existing file in given path for reading only, and map it
+    /// - writes fail with permission denied
+    pub fn open_read_only(file_path: &str) -> std::io::Result<MmapBlockDevice> {
+        let file = File::open(file_path)?;
+        let mut device = MmapBlockDevice {
+            file,
+            mmap: None,
+            read_only: true,
+        };
+        device.remap()?;
+        Ok(device)
+    }
+
+    fn check_writable(&self) -> std::io::Result<()> {
+        if self.read_only {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::PermissionDenied,
+                "device is opened read-only",
+            ));
+        }
+        Ok(())
+    }
+
+    /// Map whole file again, after its length changed
+    fn remap(&mut self) -> std::io::Result<()> {
+        self.mmap = None;
+        if self.file.metadata()?.len() > 0 {
+            // SAFETY: file is owned by this device. Like any storage file,
+            // it must not be modified by other processes while mapped.
+            // A file opened read-only can only be mapped private, copy on write.
+            self.mmap = Some(unsafe {
+                if self.read_only {
+                    MmapOptions::new().map_copy(&self.file)?
+                } else {
+                    MmapMut::map_mut(&self.file)?
+                }
+            });
+        }
+        Ok(())
+    }
+
+    fn mapped_bytes(&self) -> &[u8] {
+        match &self.mmap {
+            Some(mmap) => &mmap[..],
+            None => &[],
+        }
+    }
+}
+
+impl BlockDevice for MmapBlockDevice {
+    fn read_at(&self, buffer: &mut [u8], offset: u64) -> std::io::Result<usize> {
+        let bytes = self.mapped_bytes();
+        let offset = offset as usize;
+        if offset >= bytes.len() {
+            return Ok(0);
+        }
+        let read_size = buffer.len().min(bytes.len() - offset);
+        buffer[..read_size].copy_from_slice(&bytes[offset..offset + read_size]);
+        Ok(read_size)
+    }
+
+    fn write_at(&mut self, data: &[u8], offset: u64) -> std::io::Result<usize> {
+        self.check_writable()?;
+        let end = offset as usize + data.len();
+        if end > self.mapped_bytes().len() {
+            self.file.set_len(end as u64)?;
+            self.remap()?;
+        }
+        if let Some(mmap) = &mut self.mmap {
+            mmap[offset as usize..end].copy_from_slice(data);
+        }
+        Ok(data.len())
+    }
+
+    fn size(&self) -> std::io::Result<u64> {
+        Ok(self.mapped_bytes().len() as u64)
+    }
+
+    fn set_size(&mut self, size: u64) -> std::io::Result<()> {
+        self.check_writable()?;
+        self.mmap = None;
+        self.file.set_len(size)?;
+        self.remap()
+    }
+
+    fn sync(&mut self) -> std::io::Result<()> {
+        if let Some(mmap) = &self.mmap {
+            mmap.flush()?;
+        }
+        self.file.sync_data()
+    }
+
+    /// Hole punched in file shows in shared mapping as zeros
+    fn punch_hole(&mut self, offset: u64, len: u64) -> std::io::Result<()> {
+        self.check_writable()?;
+        if !punch_file_hole(&self.file, offset, len)? {
+            super::write_zeros(self, offset, len)?;
+        }
+        Ok(())
+    }
+
+    fn allocated_size(&self) -> std::io::Result<u64> {
+        file_allocated_size(&self.file)
+    }
+}
@@ -0,0 +1,282 @@
+mod faulty_block_device;
+mod file_block_device;
+mod memory_block_device;
+mod mmap_block_device;
+mod segmented_block_device;
+
+pub use faulty_block_device::{Fault, FaultyBlockDevice};
+pub use file_block_device::FileBlockDevice;
+pub use memory_block_device::MemoryBlockDevice;
+pub use mmap_block_device::MmapBlockDevice;
+pub use segmented_block_device::SegmentedBlockDevice;
+
+/// Random access bytes, a storage is laid out on
+/// - Offsets are absolute, so reads and writes do not move any shared cursor
+/// - Writes beyond the end extend the device, bytes skipped in between are 0
+pub trait BlockDevice: Send + Sync {
+    /// Read bytes at given offset into buffer
+    /// - returns number of bytes read, less than buffer length only at end of device
+    fn read_at(&self, buffer: &mut [u8], offset: u64) -> std::io::Result<usize>;
+
+    /// Write bytes at given offset
+    /// - returns number of bytes written
+    fn write_at(&mut self, data: &[u8], offset: u64) -> std::io::Result<usize>;
+
+    /// Write buffers one after another, at given offset
+    /// - returns number of bytes written, less than total length of buffers only on a short write
+    fn write_vectored_at(&mut self, buffers: &[&[u8]], offset: u64) -> std::io::Result<usize> {
+        let mut write_size = 0;
+        for buffer in buffers {
+            let buffer_write_size = self.write_at(buffer, offset + write_size as u64)?;
+            write_size += buffer_write_size;
+            if buffer_write_size != buffer.len() {
+                break;
+            }
+        }
+        Ok(write_size)
+    }
+
+    /// Length of device in bytes
+    fn size(&self) -> std::io::Result<u64>;
+
+    /// Truncate or extend device to given length in bytes
+    fn set_size(&mut self, size: u64) -> std::io::Result<()>;
+
+    /// Flush written bytes to durable storage, if any
+    fn sync(&mut self) -> std::io::Result<()>;
+
+    /// Release bytes in given range, which read as 0 afterwards, size is unchanged
+    /// - default writes zeros, for devices which can not deallocate a range
+    fn punch_hole(&mut self, offset: u64, len: u64) -> std::io::Result<()> {
+        write_zeros(self, offset, len)
+    }
+
+    /// Bytes of durable storage allocated to device, less than size if it has holes
+    /// - default is size, for devices without holes
+    fn allocated_size(&self) -> std::io::Result<u64> {
+        self.size()
+    }
+}
+
+/// Write zeros to given range, in chunks of at most 64 KiB
+/// - fallback for devices & file systems which can not punch holes
+fn write_zeros<D: BlockDevice + ?Sized>(
+    device: &mut D,
+    offset: u64,
+    len: u64,
+) -> std::io::Result<()> {
+    const ZEROS_CHUNK_SIZE: u64 = 64 * 1024;
+    let zeros = vec![0u8; len.min(ZEROS_CHUNK_SIZE) as usize];
+    let mut write_size = 0;
+    while write_size < len {
+        let chunk_size = (len - write_size).min(ZEROS_CHUNK_SIZE) as usize;
+        let chunk_write_size = device.write_at(&zeros[..chunk_size], offset + write_size)?;
+        if chunk_write_size != chunk_size {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::WriteZero,
+                "failed to write zeros",
+            ));
+        }
+        write_size += chunk_size as u64;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_block_device_flow(device: &mut dyn BlockDevice) {
+        assert_eq!(device.size().unwrap(), 0);
+        let mut buffer = [0u8; 4];
+        assert_eq!(device.read_at(&mut buffer, 0).unwrap(), 0);
+        // write extends device
+        assert_eq!(device.write_at(&[1, 2, 3, 4], 0).unwrap(), 4);
+        assert_eq!(device.size().unwrap(), 4);
+        // write beyond end fills skipped bytes with 0
+        assert_eq!(device.write_at(&[9, 9], 6).unwrap(), 2);
+        assert_eq!(device.size().unwrap(), 8);
+        let mut buffer = [0u8; 8];
+        assert_eq!(device.read_at(&mut buffer, 0).unwrap(), 8);
+        assert_eq!(buffer, [1, 2, 3, 4, 0, 0, 9, 9]);
+        // overwrite in place
+        assert_eq!(device.write_at(&[5, 5], 2).unwrap(), 2);
+        // read at end of device is short
+        let mut buffer = [0u8; 4];
+        assert_eq!(device.read_at(&mut buffer, 6).unwrap(), 2);
+        assert_eq!(buffer[..2], [9, 9]);
+        assert_eq!(device.read_at(&mut buffer, 2).unwrap(), 4);
+        assert_eq!(buffer, [5, 5, 0, 0]);
+        // vectored write
+        let write_size = device
+            .write_vectored_at(&[&[6], &[], &[7, 7], &[8]], 3)
+            .unwrap();
+        assert_eq!(write_size, 4);
+        assert_eq!(device.read_at(&mut buffer, 2).unwrap(), 4);
+        assert_eq!(buffer, [5, 6, 7, 7]);
+        assert_eq!(device.size().unwrap(), 8);
+        // truncate
+        device.set_size(3).unwrap();
+        assert_eq!(device.size().unwrap(), 3);
+        assert_eq!(device.read_at(&mut buffer, 0).unwrap(), 3);
+        assert_eq!(buffer[..3], [1, 2, 5]);
+        device.sync().unwrap();
+        // punched hole reads as 0, size is unchanged
+        device.punch_hole(1, 1).unwrap();
+        assert_eq!(device.size().unwrap(), 3);
+        assert_eq!(device.read_at(&mut buffer, 0).unwrap(), 3);
+        assert_eq!(buffer[..3], [1, 0, 5]);
+        assert!(device.allocated_size().is_ok());
+    }
+
+    #[test]
+    fn test_memory_block_device() {
+        let mut device = MemoryBlockDevice::new();
+        test_block_device_flow(&mut device);
+        assert_eq!(device.bytes(), &[1, 0, 5]);
+    }
+
+    #[test]
+    fn test_faulty_block_device() {
+        let mut device = FaultyBlockDevice::new(Box::new(MemoryBlockDevice::new()));
+        test_block_device_flow(&mut device);
+        // scheduled faults fire once, at n-th next call
+        device.fail_write(1, Fault::Short(1));
+        device.fail_write(2, Fault::NoSpace);
+        device.fail_read(0, Fault::Io);
+        device.fail_sync(0, Fault::Io);
+        assert_eq!(device.write_at(&[7, 7], 0).unwrap(), 2);
+        assert_eq!(device.write_at(&[8, 8], 0).unwrap(), 1);
+        let error = device.write_at(&[9, 9], 0).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::StorageFull);
+        let mut buffer = [0u8; 3];
+        assert!(device.read_at(&mut buffer, 0).is_err());
+        assert_eq!(device.read_at(&mut buffer, 0).unwrap(), 3);
+        assert_eq!(buffer, [8, 7, 5]);
+        assert!(device.sync().is_err());
+        device.sync().unwrap();
+        // crash tears the write crossing the limit, then every call fails
+        device.crash_after_bytes(3);
+        assert_eq!(device.write_at(&[1, 1], 0).unwrap(), 2);
+        assert!(device.write_at(&[2, 2], 2).is_err());
+        assert!(device.crashed());
+        assert!(device.read_at(&mut buffer, 0).is_err());
+        assert!(device.write_at(&[3], 0).is_err());
+        // bytes written before crash persist after revive
+        device.revive();
+        assert_eq!(device.size().unwrap(), 3);
+        assert_eq!(device.read_at(&mut buffer, 0).unwrap(), 3);
+        assert_eq!(buffer, [1, 1, 2]);
+        assert_eq!(device.call_counts(), (11, 11, 3));
+    }
+
+    #[test]
+    fn test_faulty_block_device_lose_unsynced_writes() {
+        let mut device = FaultyBlockDevice::new(Box::new(MemoryBlockDevice::new()));
+        device.write_at(&[1, 1, 1, 1], 0).unwrap();
+        device.sync().unwrap();
+        device.write_at(&[2, 2], 0).unwrap();
+        device.write_at(&[3, 3], 2).unwrap();
+        device.write_at(&[4, 4], 4).unwrap();
+        assert_eq!(device.unsynced_write_count(), 3);
+        // - 2nd write is kept, others are lost
+        device
+            .lose_unsynced_writes(|position| position == 1)
+            .unwrap();
+        assert_eq!(device.unsynced_write_count(), 0);
+        let mut buffer = [0u8; 6];
+        assert_eq!(device.read_at(&mut buffer, 0).unwrap(), 4);
+        assert_eq!(buffer[..4], [1, 1, 3, 3]);
+        // - every write is lost
+        device.write_at(&[5, 5], 4).unwrap();
+        device.lose_unsynced_writes(|_| false).unwrap();
+        assert_eq!(device.size().unwrap(), 4);
+    }
+
+    #[test]
+    fn test_file_block_device() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let file_path = tmp_dir.path().join("device.hex");
+        let file_path = file_path.to_str().unwrap();
+        let mut device = FileBlockDevice::open(file_path, true).unwrap();
+        test_block_device_flow(&mut device);
+        drop(device);
+        assert_eq!(std::fs::read(file_path).unwrap(), vec![1, 0, 5]);
+    }
+
+    #[test]
+    fn test_mmap_block_device() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let file_path = tmp_dir.path().join("device.hex");
+        let file_path = file_path.to_str().unwrap();
+        let mut device = MmapBlockDevice::open(file_path, true).unwrap();
+        test_block_device_flow(&mut device);
+        drop(device);
+        assert_eq!(std::fs::read(file_path).unwrap(), vec![1, 0, 5]);
+        // reopen existing file
+        let device = MmapBlockDevice::open(file_path, false).unwrap();
+        assert_eq!(device.size().unwrap(), 3);
+    }
+
+    #[test]
+    fn test_segmented_block_device() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let file_path = tmp_dir.path().join("device.hex");
+        let file_path = file_path.to_str().unwrap();
+        let dir_path = tmp_dir.path().join("device.segments");
+        let dir_path = dir_path.to_str().unwrap();
+        // - 2 bytes in header file, 3 bytes in each segment file
+        let mut device = SegmentedBlockDevice::create(file_path, dir_path, 2, 3).unwrap();
+        test_block_device_flow(&mut device);
+        drop(device);
+        assert_eq!(std::fs::read(file_path).unwrap(), vec![1, 0]);
+        let segment_path = SegmentedBlockDevice::segment_file_path(dir_path, 0);
+        assert_eq!(std::fs::read(&segment_path).unwrap(), vec![5]);
+        // - segments are created on demand, skipped ones read as 0
+        let mut device = SegmentedBlockDevice::open(file_path, dir_path).unwrap();
+        assert_eq!(device.segment_len(), 3);
+        assert_eq!(device.write_at(&[7, 7, 7, 7], 9).unwrap(), 4);
+        assert_eq!(device.size().unwrap(), 13);
+        let mut buffer = [9u8; 13];
+        assert_eq!(device.read_at(&mut buffer, 0).unwrap(), 13);
+        assert_eq!(buffer, [1, 0, 5, 0, 0, 0, 0, 0, 0, 7, 7, 7, 7]);
+        device.sync().unwrap();
+        assert_eq!(std::fs::read_dir(dir_path).unwrap().count(), 5);
+        // - missing segment can not be read, others still can
+        drop(device);
+        std::fs::remove_file(SegmentedBlockDevice::segment_file_path(dir_path, 1)).unwrap();
+        let device = SegmentedBlockDevice::open_read_only(file_path, dir_path).unwrap();
+        assert_eq!(device.size().unwrap(), 13);
+        assert!(device.read_at(&mut buffer, 4).is_err());
+        assert_eq!(device.read_at(&mut buffer[..4], 9).unwrap(), 4);
+        assert_eq!(buffer[..4], [7, 7, 7, 7]);
+        drop(device);
+        // - truncate removes segments beyond new size
+        let mut device = SegmentedBlockDevice::open(file_path, dir_path).unwrap();
+        device.set_size(4).unwrap();
+        assert_eq!(device.size().unwrap(), 4);
+        assert_eq!(std::fs::read_dir(dir_path).unwrap().count(), 2);
+    }
+
+    #[test]
+    fn test_read_only_block_devices() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let file_path = tmp_dir.path().join("device.hex");
+        let file_path = file_path.to_str().unwrap();
+        assert!(FileBlockDevice::open_read_only(file_path).is_err());
+        std::fs::write(file_path, [1, 2, 5]).unwrap();
+        let devices: Vec<Box<dyn BlockDevice>> = vec![
+            Box::new(FileBlockDevice::open_read_only(file_path).unwrap()),
+            Box::new(MmapBlockDevice::open_read_only(file_path).unwrap()),
+        ];
+        for mut device in devices {
+            let mut buffer = [0; 3];
+            assert_eq!(device.read_at(&mut buffer, 0).unwrap(), 3);
+            assert_eq!(buffer, [1, 2, 5]);
+            assert!(device.write_at(&[9], 0).is_err());
+            assert!(device.set_size(0).is_err());
+            assert!(device.punch_hole(0, 1).is_err());
+        }
+        assert_eq!(std::fs::read(file_path).unwrap(), vec![1, 2, 5]);
+    }
+}
@@ -0,0 +1,421 @@
+use super::{BlockDevice, FileBlockDevice};
+use std::collections::BTreeSet;
+
+/// Magic bytes at the start of manifest of a segment directory
+const SEGMENT_MANIFEST_MAGIC: [u8; 4] = *b"XDBG";
+
+/// Manifest holds magic bytes, then header length & segment length as 8 bytes little endian
+const SEGMENT_MANIFEST_SIZE: usize = 20;
+
+const SEGMENT_MANIFEST_FILE_NAME: &str = "manifest";
+
+const SEGMENT_FILE_EXTENSION: &str = "segment";
+
+/// Block device over a header file and a directory of fixed-size segment files
+/// - First `header_len` bytes are in header file, every further `segment_len` bytes in a segment file of their own
+/// - Segment files are created on demand, when bytes beyond last segment are written
+/// - Every segment but the last is `segment_len` long, so a segment file can be archived
+///   once nothing in it changes; reads & writes of a missing segment fail with not found
+/// - Header & segment lengths are recorded in a manifest in the directory
+pub struct SegmentedBlockDevice {
+    header: FileBlockDevice,
+    dir_path: String,
+    header_len: u64,
+    segment_len: u64,
+    /// Segment files in order, None if a segment file is missing, e.g. archived
+    segments: Vec<Option<FileBlockDevice>>,
+    /// Segments written since last sync
+    unsynced_segments: BTreeSet<usize>,
+    /// Segment files created or removed since last sync, so directory is synced too
+    unsynced_dir: bool,
+    /// Files are opened for reading only, so writes are refused
+    read_only: bool,
+}
+
+impl SegmentedBlockDevice {
+    /// Create new device, header in file in given path, segments in given directory
+    /// - truncates header file, and removes any segments in directory
+    /// - header_len, segment_len: bytes held in header file, and in each segment file
+    pub fn create(
+        file_path: &str,
+        dir_path: &str,
+        header_len: u64,
+        segment_len: u64,
+    ) -> std::io::Result<SegmentedBlockDevice> {
+        if segment_len == 0 {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "segment length is 0",
+            ));
+        }
+        if std::path::Path::new(dir_path).exists() {
+            std::fs::remove_dir_all(dir_path)?;
+        }
+        std::fs::create_dir_all(dir_path)?;
+        let mut manifest = Vec::with_capacity(SEGMENT_MANIFEST_SIZE);
+        manifest.extend_from_slice(&SEGMENT_MANIFEST_MAGIC);
+        manifest.extend_from_slice(&header_len.to_le_bytes());
+        manifest.extend_from_slice(&segment_len.to_le_bytes());
+        let mut manifest_device =
+            FileBlockDevice::open(&SegmentedBlockDevice::manifest_path(dir_path), true)?;
+        manifest_device.write_at(&manifest, 0)?;
+        manifest_device.sync()?;
+        Ok(SegmentedBlockDevice {
+            header: FileBlockDevice::open(file_path, true)?,
+            dir_path: dir_path.to_string(),
+            header_len,
+            segment_len,
+            segments: vec![],
+            unsynced_segments: BTreeSet::new(),
+            unsynced_dir: true,
+            read_only: false,
+        })
+    }
+
+    /// Open existing device, header in file in given path, segments in given directory
+    /// - segment files missing before the last one are left out, their bytes can not be read
+    pub fn open(file_path: &str, dir_path: &str) -> std::io::Result<SegmentedBlockDevice> {
+        SegmentedBlockDevice::open_files(file_path, dir_path, false)
+    }
+
+    /// Open existing device for reading only
+    /// - writes fail with permission denied
+    pub fn open_read_only(
+        file_path: &str,
+        dir_path: &str,
+    ) -> std::io::Result<SegmentedBlockDevice> {
+        SegmentedBlockDevice::open_files(file_path, dir_path, true)
+    }
+
+    /// Path of segment file with given index in given directory
+    pub fn segment_file_path(dir_path: &str, segment_index: usize) -> String {
+        format!(
+            "{}/{:010}.{}",
+            dir_path, segment_index, SEGMENT_FILE_EXTENSION
+        )
+    }
+
+    /// Bytes held in each segment file
+    pub fn segment_len(&self) -> u64 {
+        self.segment_len
+    }
+
+    fn manifest_path(dir_path: &str) -> String {
+        format!("{}/{}", dir_path, SEGMENT_MANIFEST_FILE_NAME)
+    }
+
+    fn open_files(
+        file_path: &str,
+        dir_path: &str,
+        read_only: bool,
+    ) -> std::io::Result<SegmentedBlockDevice> {
+        let open_file = |path: &str| {
+            if read_only {
+                FileBlockDevice::open_read_only(path)
+            } else {
+                FileBlockDevice::open(path, false)
+            }
+        };
+
+        // - header & segment lengths from manifest
+        let manifest = std::fs::read(SegmentedBlockDevice::manifest_path(dir_path))?;
+        if manifest.len() != SEGMENT_MANIFEST_SIZE || manifest[0..4] != SEGMENT_MANIFEST_MAGIC {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "segment manifest is invalid",
+            ));
+        }
+        let mut value_bytes = [0u8; 8];
+        value_bytes.copy_from_slice(&manifest[4..12]);
+        let header_len = u64::from_le_bytes(value_bytes);
+        value_bytes.copy_from_slice(&manifest[12..20]);
+        let segment_len = u64::from_le_bytes(value_bytes);
+        if segment_len == 0 {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "segment length is 0",
+            ));
+        }
+
+        // - segment files present in directory, by index
+        let mut segment_indexes = BTreeSet::new();
+        for entry in std::fs::read_dir(dir_path)? {
+            let path = entry?.path();
+            if path.extension().and_then(|extension| extension.to_str())
+                != Some(SEGMENT_FILE_EXTENSION)
+            {
+                continue;
+            }
+            let segment_index = path
+                .file_stem()
+                .and_then(|file_stem| file_stem.to_str())
+                .and_then(|file_stem| file_stem.parse::<usize>().ok());
+            if let Some(segment_index) = segment_index {
+                segment_indexes.insert(segment_index);
+            }
+        }
+        let segment_count = segment_indexes
+            .iter()
+            .next_back()
+            .map_or(0, |segment_index| segment_index + 1);
+        let mut segments = Vec::with_capacity(segment_count);
+        for segment_index in 0..segment_count {
+            if segment_indexes.contains(&segment_index) {
+                let segment_path = SegmentedBlockDevice::segment_file_path(dir_path, segment_index);
+                segments.push(Some(open_file(&segment_path)?));
+            } else {
+                segments.push(None);
+            }
+        }
+
+        Ok(SegmentedBlockDevice {
+            header: open_file(file_path)?,
+            dir_path: dir_path.to_string(),
+            header_len,
+            segment_len,
+            segments,
+            unsynced_segments: BTreeSet::new(),
+            unsynced_dir: false,
+            read_only,
+        })
+    }
+
+    fn check_writable(&self) -> std::io::Result<()> {
+        if self.read_only {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::PermissionDenied,
+                "device is opened read-only",
+            ));
+        }
+        Ok(())
+    }
+
+    /// Segment index & offset in segment of given offset, None if it is in header file
+    fn locate(&self, offset: u64) -> Option<(usize, u64)> {
+        if offset < self.header_len {
+            return None;
+        }
+        let segment_offset = offset - self.header_len;
+        Some((
+            (segment_offset / self.segment_len) as usize,
+            segment_offset % self.segment_len,
+        ))
+    }
+
+    fn segment(&self, segment_index: usize) -> std::io::Result<&FileBlockDevice> {
+        match self.segments.get(segment_index) {
+            Some(Some(segment)) => Ok(segment),
+            _ => Err(self.missing_segment_error(segment_index)),
+        }
+    }
+
+    fn missing_segment_error(&self, segment_index: usize) -> std::io::Error {
+        std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            format!(
+                "segment file {} is missing",
+                SegmentedBlockDevice::segment_file_path(&self.dir_path, segment_index)
+            ),
+        )
+    }
+
+    /// Make sure segment with given index exists, creating it & segments before it if needed
+    /// - header file & previous last segment are filled up to their full length, reading as 0
+    fn ensure_segment(&mut self, segment_index: usize) -> std::io::Result<&mut FileBlockDevice> {
+        if segment_index >= self.segments.len() {
+            if self.header.size()? < self.header_len {
+                self.header.set_size(self.header_len)?;
+            }
+            if let Some(Some(last_segment)) = self.segments.last_mut() {
+                last_segment.set_size(self.segment_len)?;
+                self.unsynced_segments.insert(self.segments.len() - 1);
+            }
+            while self.segments.len() <= segment_index {
+                let new_segment_index = self.segments.len();
+                let segment_path =
+                    SegmentedBlockDevice::segment_file_path(&self.dir_path, new_segment_index);
+                let mut segment = FileBlockDevice::open(&segment_path, true)?;
+                if new_segment_index < segment_index {
+                    segment.set_size(self.segment_len)?;
+                }
+                self.segments.push(Some(segment));
+                self.unsynced_segments.insert(new_segment_index);
+                self.unsynced_dir = true;
+            }
+        }
+        let missing_segment_error = self.missing_segment_error(segment_index);
+        self.unsynced_segments.insert(segment_index);
+        self.segments[segment_index]
+            .as_mut()
+            .ok_or(missing_segment_error)
+    }
+
+    #[cfg(unix)]
+    fn sync_dir(&self) -> std::io::Result<()> {
+        std::fs::File::open(&self.dir_path)?.sync_all()
+    }
+
+    #[cfg(not(unix))]
+    fn sync_dir(&self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl BlockDevice for SegmentedBlockDevice {
+    fn read_at(&self, buffer: &mut [u8], offset: u64) -> std::io::Result<usize> {
+        // read piece by piece, each within header file or a single segment
+        let mut read_size = 0;
+        while read_size < buffer.len() {
+            let piece_offset = offset + read_size as u64;
+            let remaining_size = (buffer.len() - read_size) as u64;
+            let (piece_size, piece_read_size) = match self.locate(piece_offset) {
+                None => {
+                    let piece_size = remaining_size.min(self.header_len - piece_offset) as usize;
+                    let piece = &mut buffer[read_size..read_size + piece_size];
+                    (piece_size, self.header.read_at(piece, piece_offset)?)
+                }
+                Some((segment_index, _)) if segment_index >= self.segments.len() => break,
+                Some((segment_index, segment_offset)) => {
+                    let piece_size = remaining_size.min(self.segment_len - segment_offset) as usize;
+                    let piece = &mut buffer[read_size..read_size + piece_size];
+                    (
+                        piece_size,
+                        self.segment(segment_index)?
+                            .read_at(piece, segment_offset)?,
+                    )
+                }
+            };
+            read_size += piece_read_size;
+            if piece_read_size != piece_size {
+                break;
+            }
+        }
+        Ok(read_size)
+    }
+
+    fn write_at(&mut self, data: &[u8], offset: u64) -> std::io::Result<usize> {
+        self.check_writable()?;
+        // write piece by piece, each within header file or a single segment
+        let mut write_size = 0;
+        while write_size < data.len() {
+            let piece_offset = offset + write_size as u64;
+            let remaining_size = (data.len() - write_size) as u64;
+            let (piece_size, piece_write_size) = match self.locate(piece_offset) {
+                None => {
+                    let piece_size = remaining_size.min(self.header_len - piece_offset) as usize;
+                    let piece = &data[write_size..write_size + piece_size];
+                    (piece_size, self.header.write_at(piece, piece_offset)?)
+                }
+                Some((segment_index, segment_offset)) => {
+                    let piece_size = remaining_size.min(self.segment_len - segment_offset) as usize;
+                    let piece = &data[write_size..write_size + piece_size];
+                    let segment = self.ensure_segment(segment_index)?;
+                    (piece_size, segment.write_at(piece, segment_offset)?)
+                }
+            };
+            write_size += piece_write_size;
+            if piece_write_size != piece_size {
+                break;
+            }
+        }
+        Ok(write_size)
+    }
+
+    fn size(&self) -> std::io::Result<u64> {
+        let segment_count = self.segments.len() as u64;
+        match self.segments.last() {
+            None => self.header.size(),
+            Some(last_segment) => {
+                let last_segment_size = match last_segment {
+                    Some(last_segment) => last_segment.size()?,
+                    None => self.segment_len,
+                };
+                Ok(self.header_len + (segment_count - 1) * self.segment_len + last_segment_size)
+            }
+        }
+    }
+
+    fn set_size(&mut self, size: u64) -> std::io::Result<()> {
+        self.check_writable()?;
+        // - segments beyond new size are removed
+        let segment_count = match self.locate(size) {
+            None => 0,
+            Some((segment_index, 0)) => segment_index,
+            Some((segment_index, _)) => segment_index + 1,
+        };
+        while self.segments.len() > segment_count {
+            let segment_index = self.segments.len() - 1;
+            self.segments.pop();
+            self.unsynced_segments.remove(&segment_index);
+            let segment_path =
+                SegmentedBlockDevice::segment_file_path(&self.dir_path, segment_index);
+            if let Err(error) = std::fs::remove_file(segment_path) {
+                if error.kind() != std::io::ErrorKind::NotFound {
+                    return Err(error);
+                }
+            }
+            self.unsynced_dir = true;
+        }
+        // - last segment, or header file, is truncated or extended to new size
+        if segment_count == 0 {
+            return self.header.set_size(size);
+        }
+        let last_segment_size =
+            size - self.header_len - (segment_count as u64 - 1) * self.segment_len;
+        self.ensure_segment(segment_count - 1)?
+            .set_size(last_segment_size)
+    }
+
+    fn sync(&mut self) -> std::io::Result<()> {
+        self.header.sync()?;
+        while let Some(segment_index) = self.unsynced_segments.pop_first() {
+            if let Some(Some(segment)) = self.segments.get_mut(segment_index) {
+                if let Err(error) = segment.sync() {
+                    self.unsynced_segments.insert(segment_index);
+                    return Err(error);
+                }
+            }
+        }
+        if self.unsynced_dir {
+            self.sync_dir()?;
+            self.unsynced_dir = false;
+        }
+        Ok(())
+    }
+
+    fn punch_hole(&mut self, offset: u64, len: u64) -> std::io::Result<()> {
+        self.check_writable()?;
+        // punch piece by piece, each within header file or a single segment
+        let end = offset + len;
+        let mut piece_offset = offset;
+        while piece_offset < end {
+            let piece_size = match self.locate(piece_offset) {
+                None => {
+                    let piece_size = (end - piece_offset).min(self.header_len - piece_offset);
+                    self.header.punch_hole(piece_offset, piece_size)?;
+                    piece_size
+                }
+                Some((segment_index, segment_offset)) => {
+                    let piece_size = (end - piece_offset).min(self.segment_len - segment_offset);
+                    // - nothing to release beyond last segment
+                    if segment_index >= self.segments.len() {
+                        break;
+                    }
+                    self.ensure_segment(segment_index)?
+                        .punch_hole(segment_offset, piece_size)?;
+                    piece_size
+                }
+            };
+            piece_offset += piece_size;
+        }
+        Ok(())
+    }
+
+    fn allocated_size(&self) -> std::io::Result<u64> {
+        let mut allocated_size = self.header.allocated_size()?;
+        for segment in self.segments.iter().flatten() {
+            allocated_size += segment.allocated_size()?;
+        }
+        Ok(allocated_size)
+    }
+}
@@ -0,0 +1,106 @@
+use crate::BlockIndex;
+
+/// Width of block indexes & block data sizes stored in storage file, recorded in storage header
+/// - 32-bit files hold up to about 4 billion blocks, and are readable by every format version 1 reader
+/// - largest value of the width is reserved, as next block index of last block of a chain
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BlockIndexWidth {
+    /// 4 bytes block indexes & block data sizes
+    #[default]
+    U32,
+    /// 8 bytes block indexes & block data sizes
+    U64,
+}
+
+impl BlockIndexWidth {
+    /// Size of a stored block index in bytes
+    pub fn size(self) -> usize {
+        match self {
+            BlockIndexWidth::U32 => 4,
+            BlockIndexWidth::U64 => 8,
+        }
+    }
+
+    /// Number of block indexes storable, largest value of the width excluded
+    pub fn max_block_count(self) -> BlockIndex {
+        match self {
+            BlockIndexWidth::U32 => u32::MAX as BlockIndex,
+            BlockIndexWidth::U64 => BlockIndex::MAX,
+        }
+    }
+
+    /// Serialize block index as little endian
+    /// - `BlockIndex::MAX` is stored as largest value of the width
+    /// - block index must be below `max_block_count`, else it is truncated
+    pub fn to_bytes(self, block_index: BlockIndex) -> Vec<u8> {
+        match self {
+            BlockIndexWidth::U32 if block_index == BlockIndex::MAX => {
+                u32::MAX.to_le_bytes().to_vec()
+            }
+            BlockIndexWidth::U32 => (block_index as u32).to_le_bytes().to_vec(),
+            BlockIndexWidth::U64 => block_index.to_le_bytes().to_vec(),
+        }
+    }
+
+    /// Parse block index from start of given bytes, None if bytes are shorter than the width
+    /// - largest value of the width is read as `BlockIndex::MAX`
+    pub fn from_bytes(self, bytes: &[u8]) -> Option<BlockIndex> {
+        match self {
+            BlockIndexWidth::U32 => {
+                let bytes = bytes.get(0..4)?;
+                let value = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+                if value == u32::MAX {
+                    Some(BlockIndex::MAX)
+                } else {
+                    Some(value as BlockIndex)
+                }
+            }
+            BlockIndexWidth::U64 => {
+                let bytes = bytes.get(0..8)?;
+                let mut value_bytes = [0u8; 8];
+                value_bytes.copy_from_slice(bytes);
+                Some(BlockIndex::from_le_bytes(value_bytes))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_block_index_width_to_bytes_and_back() {
+        let width = BlockIndexWidth::U32;
+        assert_eq!(width.to_bytes(0x12345678), vec![0x78, 0x56, 0x34, 0x12]);
+        assert_eq!(
+            width.from_bytes(&[0x78, 0x56, 0x34, 0x12, 9]),
+            Some(0x12345678)
+        );
+        assert_eq!(width.to_bytes(BlockIndex::MAX), vec![0xff; 4]);
+        assert_eq!(width.from_bytes(&[0xff; 4]), Some(BlockIndex::MAX));
+        assert_eq!(width.from_bytes(&[0xff; 3]), None);
+        let width = BlockIndexWidth::U64;
+        let block_index = 0x1_0000_0002;
+        assert_eq!(width.to_bytes(block_index), vec![2, 0, 0, 0, 1, 0, 0, 0]);
+        assert_eq!(
+            width.from_bytes(&width.to_bytes(block_index)),
+            Some(block_index)
+        );
+        assert_eq!(width.from_bytes(&[0xff; 8]), Some(BlockIndex::MAX));
+        assert_eq!(width.from_bytes(&[0xff; 4]), None);
+    }
+
+    #[test]
+    fn test_block_index_width_max_block_count() {
+        assert_eq!(
+            BlockIndexWidth::U32.max_block_count(),
+            u32::MAX as BlockIndex
+        );
+        assert_eq!(BlockIndexWidth::U64.max_block_count(), BlockIndex::MAX);
+        assert_eq!(
+            (BlockIndexWidth::U32.size(), BlockIndexWidth::U64.size()),
+            (4, 8)
+        );
+    }
+}
@@ -0,0 +1,126 @@
+use crate::{BlockIndex, Storage};
+use std::collections::BTreeSet;
+use util::error::Error;
+
+/// Iterator over blocks of a storage, returned by `Storage::iter_blocks` & `Storage::iter_used_blocks`
+/// - yields Ok((block_index, block_data)), or Err if a block can not be read, then moves on to next block
+/// - block indexes are those of application, as read by `Storage::read_block`, blocks of root tables are skipped
+pub struct BlockIter<'a> {
+    storage: &'a Storage,
+    next_block_index: BlockIndex,
+    /// Skip free blocks, instead of yielding them with empty data
+    used_only: bool,
+    /// Blocks of root tables, which hold no data of application
+    root_table_blocks: BTreeSet<BlockIndex>,
+}
+
+impl<'a> BlockIter<'a> {
+    pub(crate) fn new(storage: &'a Storage, used_only: bool) -> BlockIter<'a> {
+        BlockIter {
+            storage,
+            next_block_index: 0,
+            used_only,
+            root_table_blocks: storage.application_root_table_blocks(),
+        }
+    }
+}
+
+impl Iterator for BlockIter<'_> {
+    type Item = Result<(BlockIndex, Vec<u8>), Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let block_index = self.next_block_index;
+            if block_index >= self.storage.end_block_count() {
+                return None;
+            }
+            self.next_block_index += 1;
+            if self.root_table_blocks.contains(&block_index) {
+                continue;
+            }
+            let physical_block_index = match self.storage.physical_block_index(block_index) {
+                Some(physical_block_index) => physical_block_index,
+                None if self.used_only => continue,
+                None => return Some(Ok((block_index, Vec::new()))),
+            };
+            self.storage.io_counters.count_read();
+            return Some(
+                self.storage
+                    .read_block_from_device(physical_block_index)
+                    .map(|(_, block_data)| (block_index, block_data)),
+            );
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self
+            .storage
+            .end_block_count()
+            .saturating_sub(self.next_block_index) as usize;
+        if self.used_only {
+            (0, Some(remaining))
+        } else {
+            (
+                remaining.saturating_sub(self.root_table_blocks.len()),
+                Some(remaining),
+            )
+        }
+    }
+}
+
+/// Iterator over block headers of a storage, returned by `Storage::iter_block_headers`
+/// - yields Ok((block_index, block_data_size)), or Err if a header can not be read, then moves on to next block
+/// - block indexes are those of application, blocks of root tables are skipped
+/// - data size of a compressed block is its size before compression
+pub struct BlockHeaderIter<'a> {
+    storage: &'a Storage,
+    next_block_index: BlockIndex,
+    /// Blocks of root tables, which hold no data of application
+    root_table_blocks: BTreeSet<BlockIndex>,
+}
+
+impl<'a> BlockHeaderIter<'a> {
+    pub(crate) fn new(storage: &'a Storage) -> BlockHeaderIter<'a> {
+        BlockHeaderIter {
+            storage,
+            next_block_index: 0,
+            root_table_blocks: storage.application_root_table_blocks(),
+        }
+    }
+}
+
+impl Iterator for BlockHeaderIter<'_> {
+    type Item = Result<(BlockIndex, u64), Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let block_index = self.next_block_index;
+            if block_index >= self.storage.end_block_count() {
+                return None;
+            }
+            self.next_block_index += 1;
+            if self.root_table_blocks.contains(&block_index) {
+                continue;
+            }
+            return match self.storage.physical_block_index(block_index) {
+                Some(physical_block_index) => Some(
+                    self.storage
+                        .read_block_data_size(physical_block_index)
+                        .map(|block_data_size| (block_index, block_data_size)),
+                ),
+                None => Some(Ok((block_index, 0))),
+            };
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self
+            .storage
+            .end_block_count()
+            .saturating_sub(self.next_block_index) as usize;
+        (
+            remaining.saturating_sub(self.root_table_blocks.len()),
+            Some(remaining),
+        )
+    }
+}
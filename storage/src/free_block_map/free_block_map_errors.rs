@@ -0,0 +1,53 @@
+use util::error::{Error, ErrorType};
+
+// .... .... FreeBlockMap::create .... ....
+
+pub fn create_open_file(io_error: std::io::Error) -> Error {
+    Error::new(
+        ErrorType::Happens,
+        "free_block_map_create_failed_to_open_file",
+        Some(format!(
+            "Failed to open free block map file for writing, check permissions and path.\n {}",
+            io_error
+        )),
+    )
+}
+
+// .... .... FreeBlockMap::write_all .... ....
+
+pub fn write_all_write_map(io_error: std::io::Error) -> Error {
+    Error::new(
+        ErrorType::Happens,
+        "free_block_map_write_all_failed_to_write_map",
+        Some(format!(
+            "Failed to write free block map file, check permissions and disk state.\n {}",
+            io_error
+        )),
+    )
+}
+
+// .... .... FreeBlockMap::mark_used, FreeBlockMap::mark_free .... ....
+
+pub fn update_write_map(io_error: std::io::Error) -> Error {
+    Error::new(
+        ErrorType::Happens,
+        "free_block_map_update_failed_to_write_map",
+        Some(format!(
+            "Failed to update free block map file, check permissions and disk state.\n {}",
+            io_error
+        )),
+    )
+}
+
+// .... .... FreeBlockMap::sync .... ....
+
+pub fn sync_sync_map(io_error: std::io::Error) -> Error {
+    Error::new(
+        ErrorType::Happens,
+        "free_block_map_sync_failed_to_sync_map",
+        Some(format!(
+            "Failed to sync free block map file to durable storage.\n {}",
+            io_error
+        )),
+    )
+}
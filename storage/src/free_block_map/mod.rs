@@ -0,0 +1,444 @@
+use crate::storage_stats::{DataTotals, DATA_TOTALS_SIZE};
+use crate::{BlockIndex, BlockIndexWidth};
+use std::collections::BTreeSet;
+use std::fs::{File, OpenOptions};
+use util::error::Error;
+
+mod free_block_map_errors;
+
+/// Magic bytes at the start of every free block map file
+const FREE_BLOCK_MAP_MAGIC: [u8; 4] = *b"XDBF";
+
+/// Size of magic bytes, followed by end_block_count as wide as block indexes of storage
+const FREE_BLOCK_MAP_MAGIC_SIZE: usize = 4;
+
+/// 4 bytes magic, 4 or 8 bytes end_block_count, 24 bytes data totals
+fn header_size(block_index_width: BlockIndexWidth) -> usize {
+    FREE_BLOCK_MAP_MAGIC_SIZE + block_index_width.size() + DATA_TOTALS_SIZE
+}
+
+/// Bitmap of free blocks, persisted next to storage file
+/// - Saves a scan of every block header on `Storage::open`
+/// - Bit of a block is set, if the block is free
+/// - Stores end_block_count, so a map not matching the storage file length is stale
+/// - Stores totals of data in used blocks as of last clean sync, so `Storage::stats` does not
+///   read every block header either
+///
+/// Map is updated before a block is written and after a block is deleted, but writes to map & storage file
+/// are not ordered. So the map is trusted on open only if storage header is marked clean, see `Storage::sync`.
+pub struct FreeBlockMap {
+    file: File,
+    end_block_count: BlockIndex,
+    block_index_width: BlockIndexWidth,
+    data_totals: DataTotals,
+    bitmap: Vec<u8>,
+}
+
+fn bitmap_len(end_block_count: BlockIndex) -> usize {
+    (end_block_count as usize).div_ceil(8)
+}
+
+/// Read data totals & bitmap from map file, None if map file is corrupt or stale
+fn read_bitmap(
+    file: &mut File,
+    end_block_count: BlockIndex,
+    block_index_width: BlockIndexWidth,
+) -> Option<(DataTotals, Vec<u8>)> {
+    use std::io::prelude::*;
+    let mut bytes = vec![];
+    file.read_to_end(&mut bytes).ok()?;
+    let header_size = header_size(block_index_width);
+    if bytes.len() != header_size + bitmap_len(end_block_count)
+        || bytes[0..4] != FREE_BLOCK_MAP_MAGIC
+        || block_index_width.from_bytes(&bytes[FREE_BLOCK_MAP_MAGIC_SIZE..])
+            != Some(end_block_count)
+    {
+        return None;
+    }
+    let data_totals_offset = header_size - DATA_TOTALS_SIZE;
+    let data_totals = DataTotals::from_bytes(&bytes[data_totals_offset..header_size]);
+    Some((data_totals, bytes.split_off(header_size)))
+}
+
+fn free_blocks_from_bitmap(bitmap: &[u8], end_block_count: BlockIndex) -> BTreeSet<BlockIndex> {
+    (0..end_block_count)
+        .filter(|block_index| bitmap[*block_index as usize / 8] & (1 << (block_index % 8)) != 0)
+        .collect::<BTreeSet<BlockIndex>>()
+}
+
+impl FreeBlockMap {
+    /// Path of free block map file, for storage file in given path
+    pub fn file_path(storage_file_path: &str) -> String {
+        format!("{}.free", storage_file_path)
+    }
+
+    /// Create/Overwrite free block map file, with given free blocks & data totals
+    pub fn create(
+        storage_file_path: &str,
+        free_blocks: &BTreeSet<BlockIndex>,
+        end_block_count: BlockIndex,
+        data_totals: DataTotals,
+        block_index_width: BlockIndexWidth,
+    ) -> Result<FreeBlockMap, Error> {
+        let file_result = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .truncate(true)
+            .create(true)
+            .open(FreeBlockMap::file_path(storage_file_path));
+        if let Err(result_error) = file_result {
+            return Err(free_block_map_errors::create_open_file(result_error));
+        }
+        let mut bitmap = vec![0u8; bitmap_len(end_block_count)];
+        for block_index in free_blocks.range(..end_block_count) {
+            bitmap[*block_index as usize / 8] |= 1 << (block_index % 8);
+        }
+        let mut free_block_map = FreeBlockMap {
+            file: file_result.unwrap(),
+            end_block_count,
+            block_index_width,
+            data_totals,
+            bitmap,
+        };
+        free_block_map.write_all()?;
+        Ok(free_block_map)
+    }
+
+    /// Load free block map file, for storage file in given path
+    /// - returns None if map file is missing, corrupt or stale
+    /// - map is stale if its end_block_count does not match given end_block_count
+    pub fn load(
+        storage_file_path: &str,
+        end_block_count: BlockIndex,
+        block_index_width: BlockIndexWidth,
+    ) -> Option<(FreeBlockMap, BTreeSet<BlockIndex>, DataTotals)> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(FreeBlockMap::file_path(storage_file_path))
+            .ok()?;
+        let (data_totals, bitmap) = read_bitmap(&mut file, end_block_count, block_index_width)?;
+        let free_blocks = free_blocks_from_bitmap(&bitmap, end_block_count);
+        Some((
+            FreeBlockMap {
+                file,
+                end_block_count,
+                block_index_width,
+                data_totals,
+                bitmap,
+            },
+            free_blocks,
+            data_totals,
+        ))
+    }
+
+    /// Read free blocks from free block map file, for storage file in given path
+    /// - opens map file for reading only, for storages opened read-only
+    /// - returns None if map file is missing, corrupt or stale
+    pub fn read(
+        storage_file_path: &str,
+        end_block_count: BlockIndex,
+        block_index_width: BlockIndexWidth,
+    ) -> Option<(BTreeSet<BlockIndex>, DataTotals)> {
+        let mut file = File::open(FreeBlockMap::file_path(storage_file_path)).ok()?;
+        let (data_totals, bitmap) = read_bitmap(&mut file, end_block_count, block_index_width)?;
+        Some((
+            free_blocks_from_bitmap(&bitmap, end_block_count),
+            data_totals,
+        ))
+    }
+
+    /// Mark block as used, before it is written
+    /// - extends map if block is beyond end_block_count, blocks skipped in between are free
+    pub fn mark_used(&mut self, block_index: BlockIndex) -> Result<(), Error> {
+        if block_index >= self.end_block_count {
+            let first_changed_byte = self.end_block_count as usize / 8;
+            for skipped_block_index in self.end_block_count..block_index {
+                self.set_bit(skipped_block_index, true);
+            }
+            self.set_bit(block_index, false);
+            self.end_block_count = block_index + 1;
+            return self.write_from(first_changed_byte);
+        }
+        self.set_bit(block_index, false);
+        self.write_from_to(block_index as usize / 8, block_index as usize / 8 + 1)
+    }
+
+    /// Mark block as free, after it is deleted
+    pub fn mark_free(&mut self, block_index: BlockIndex) -> Result<(), Error> {
+        if block_index >= self.end_block_count {
+            return Ok(());
+        }
+        self.set_bit(block_index, true);
+        self.write_from_to(block_index as usize / 8, block_index as usize / 8 + 1)
+    }
+
+    /// Drop blocks from given block index to the end, after storage file is truncated
+    pub fn truncate(&mut self, end_block_count: BlockIndex) -> Result<(), Error> {
+        if end_block_count >= self.end_block_count {
+            return Ok(());
+        }
+        self.end_block_count = end_block_count;
+        self.bitmap.truncate(bitmap_len(end_block_count));
+        // - clear bits beyond end in last byte, so the map matches a freshly created one
+        if !end_block_count.is_multiple_of(8) {
+            let last_byte_index = self.bitmap.len() - 1;
+            self.bitmap[last_byte_index] &= (1 << (end_block_count % 8)) - 1;
+        }
+        self.write_all()
+    }
+
+    /// Record totals of data in used blocks, before storage is marked clean
+    /// - totals are kept in memory by storage, and written only when they are to be trusted on open
+    pub fn set_data_totals(&mut self, data_totals: DataTotals) -> Result<(), Error> {
+        use std::io::prelude::*;
+        if data_totals == self.data_totals {
+            return Ok(());
+        }
+        let write_result = self
+            .file
+            .seek(std::io::SeekFrom::Start(
+                (header_size(self.block_index_width) - DATA_TOTALS_SIZE) as u64,
+            ))
+            .and_then(|_| self.file.write_all(&data_totals.to_bytes()));
+        if let Err(result_error) = write_result {
+            return Err(free_block_map_errors::update_write_map(result_error));
+        }
+        self.data_totals = data_totals;
+        Ok(())
+    }
+
+    /// Flush map file to durable storage
+    pub fn sync(&mut self) -> Result<(), Error> {
+        if let Err(result_error) = self.file.sync_data() {
+            return Err(free_block_map_errors::sync_sync_map(result_error));
+        }
+        Ok(())
+    }
+
+    fn set_bit(&mut self, block_index: BlockIndex, free: bool) {
+        let byte_index = block_index as usize / 8;
+        if byte_index >= self.bitmap.len() {
+            self.bitmap.resize(byte_index + 1, 0);
+        }
+        if free {
+            self.bitmap[byte_index] |= 1 << (block_index % 8);
+        } else {
+            self.bitmap[byte_index] &= !(1 << (block_index % 8));
+        }
+    }
+
+    /// Write full map file
+    fn write_all(&mut self) -> Result<(), Error> {
+        use std::io::prelude::*;
+        let bytes = [
+            &FREE_BLOCK_MAP_MAGIC[..],
+            &self.block_index_width.to_bytes(self.end_block_count),
+            &self.data_totals.to_bytes(),
+            &self.bitmap,
+        ]
+        .concat();
+        let write_result = self
+            .file
+            .seek(std::io::SeekFrom::Start(0))
+            .and_then(|_| self.file.write_all(&bytes))
+            .and_then(|_| self.file.set_len(bytes.len() as u64));
+        if let Err(result_error) = write_result {
+            return Err(free_block_map_errors::write_all_write_map(result_error));
+        }
+        Ok(())
+    }
+
+    /// Write bitmap bytes from given byte to the end, and end_block_count
+    fn write_from(&mut self, first_byte: usize) -> Result<(), Error> {
+        use std::io::prelude::*;
+        let write_result = self
+            .file
+            .seek(std::io::SeekFrom::Start(
+                (header_size(self.block_index_width) + first_byte) as u64,
+            ))
+            .and_then(|_| self.file.write_all(&self.bitmap[first_byte..]))
+            .and_then(|_| {
+                self.file
+                    .seek(std::io::SeekFrom::Start(FREE_BLOCK_MAP_MAGIC_SIZE as u64))
+            })
+            .and_then(|_| {
+                self.file
+                    .write_all(&self.block_index_width.to_bytes(self.end_block_count))
+            });
+        if let Err(result_error) = write_result {
+            return Err(free_block_map_errors::update_write_map(result_error));
+        }
+        Ok(())
+    }
+
+    /// Write bitmap bytes in given range
+    fn write_from_to(&mut self, first_byte: usize, end_byte: usize) -> Result<(), Error> {
+        use std::io::prelude::*;
+        let write_result = self
+            .file
+            .seek(std::io::SeekFrom::Start(
+                (header_size(self.block_index_width) + first_byte) as u64,
+            ))
+            .and_then(|_| self.file.write_all(&self.bitmap[first_byte..end_byte]));
+        if let Err(result_error) = write_result {
+            return Err(free_block_map_errors::update_write_map(result_error));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tmp_storage_file_path(tmp_dir: &tempfile::TempDir) -> String {
+        tmp_dir
+            .path()
+            .join("storage.hex")
+            .to_str()
+            .unwrap()
+            .to_string()
+    }
+
+    #[test]
+    fn test_free_block_map_create_and_load() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let storage_file_path = tmp_storage_file_path(&tmp_dir);
+        let free_blocks = [0, 3, 8, 9]
+            .iter()
+            .cloned()
+            .collect::<BTreeSet<BlockIndex>>();
+        let data_totals = DataTotals::of_block(7, 7, false);
+        FreeBlockMap::create(
+            &storage_file_path,
+            &free_blocks,
+            10,
+            data_totals,
+            BlockIndexWidth::U32,
+        )
+        .unwrap();
+        let bytes = std::fs::read(FreeBlockMap::file_path(&storage_file_path)).unwrap();
+        assert_eq!(bytes[0..8], [b'X', b'D', b'B', b'F', 10, 0, 0, 0]);
+        assert_eq!(bytes[8..32], data_totals.to_bytes());
+        assert_eq!(bytes[32..], [0b0000_1001, 0b0000_0011]);
+        let (_, loaded_free_blocks, loaded_data_totals) =
+            FreeBlockMap::load(&storage_file_path, 10, BlockIndexWidth::U32).unwrap();
+        assert_eq!(loaded_free_blocks, free_blocks);
+        assert_eq!(loaded_data_totals, data_totals);
+        assert_eq!(
+            FreeBlockMap::read(&storage_file_path, 10, BlockIndexWidth::U32).unwrap(),
+            (free_blocks, data_totals)
+        );
+        // stale map
+        assert!(FreeBlockMap::load(&storage_file_path, 11, BlockIndexWidth::U32).is_none());
+        assert!(FreeBlockMap::load(&storage_file_path, 9, BlockIndexWidth::U32).is_none());
+        assert!(FreeBlockMap::read(&storage_file_path, 9, BlockIndexWidth::U32).is_none());
+        // missing map
+        std::fs::remove_file(FreeBlockMap::file_path(&storage_file_path)).unwrap();
+        assert!(FreeBlockMap::load(&storage_file_path, 10, BlockIndexWidth::U32).is_none());
+    }
+
+    #[test]
+    fn test_free_block_map_mark_used_and_free() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let storage_file_path = tmp_storage_file_path(&tmp_dir);
+        let mut free_block_map = FreeBlockMap::create(
+            &storage_file_path,
+            &BTreeSet::new(),
+            0,
+            DataTotals::default(),
+            BlockIndexWidth::U32,
+        )
+        .unwrap();
+        free_block_map.mark_used(0).unwrap();
+        free_block_map.mark_used(1).unwrap();
+        // skipped blocks 2..10 are free
+        free_block_map.mark_used(10).unwrap();
+        free_block_map.mark_free(0).unwrap();
+        free_block_map.mark_used(4).unwrap();
+        // free beyond end is a no-op
+        free_block_map.mark_free(20).unwrap();
+        let (_, free_blocks, _) =
+            FreeBlockMap::load(&storage_file_path, 11, BlockIndexWidth::U32).unwrap();
+        assert_eq!(
+            free_blocks.into_iter().collect::<Vec<_>>(),
+            vec![0, 2, 3, 5, 6, 7, 8, 9]
+        );
+        // truncate drops blocks beyond end
+        free_block_map.truncate(6).unwrap();
+        let (_, free_blocks, _) =
+            FreeBlockMap::load(&storage_file_path, 6, BlockIndexWidth::U32).unwrap();
+        assert_eq!(
+            free_blocks.into_iter().collect::<Vec<_>>(),
+            vec![0, 2, 3, 5]
+        );
+        let free_blocks = [0, 2, 3, 5]
+            .iter()
+            .cloned()
+            .collect::<BTreeSet<BlockIndex>>();
+        let bytes = std::fs::read(FreeBlockMap::file_path(&storage_file_path)).unwrap();
+        FreeBlockMap::create(
+            &storage_file_path,
+            &free_blocks,
+            6,
+            DataTotals::default(),
+            BlockIndexWidth::U32,
+        )
+        .unwrap();
+        assert_eq!(
+            std::fs::read(FreeBlockMap::file_path(&storage_file_path)).unwrap(),
+            bytes
+        );
+    }
+
+    #[test]
+    fn test_free_block_map_wide_block_index() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let storage_file_path = tmp_storage_file_path(&tmp_dir);
+        let free_blocks = [1].iter().cloned().collect::<BTreeSet<BlockIndex>>();
+        let mut free_block_map = FreeBlockMap::create(
+            &storage_file_path,
+            &free_blocks,
+            2,
+            DataTotals::default(),
+            BlockIndexWidth::U64,
+        )
+        .unwrap();
+        free_block_map.mark_used(3).unwrap();
+        let bytes = std::fs::read(FreeBlockMap::file_path(&storage_file_path)).unwrap();
+        assert_eq!(bytes[4..12], [4, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(bytes[36..], [0b0000_0110]);
+        let (_, loaded_free_blocks, _) =
+            FreeBlockMap::load(&storage_file_path, 4, BlockIndexWidth::U64).unwrap();
+        assert_eq!(
+            loaded_free_blocks.into_iter().collect::<Vec<_>>(),
+            vec![1, 2]
+        );
+        // - map of other width is stale
+        assert!(FreeBlockMap::read(&storage_file_path, 4, BlockIndexWidth::U32).is_none());
+    }
+
+    #[test]
+    fn test_free_block_map_set_data_totals() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let storage_file_path = tmp_storage_file_path(&tmp_dir);
+        let mut free_block_map = FreeBlockMap::create(
+            &storage_file_path,
+            &BTreeSet::new(),
+            0,
+            DataTotals::default(),
+            BlockIndexWidth::U32,
+        )
+        .unwrap();
+        free_block_map.mark_used(0).unwrap();
+        let data_totals = DataTotals::of_block(256, 40, true);
+        free_block_map.set_data_totals(data_totals).unwrap();
+        // - totals are kept by a later resize of the map
+        free_block_map.mark_used(9).unwrap();
+        let (free_blocks, loaded_data_totals) =
+            FreeBlockMap::read(&storage_file_path, 10, BlockIndexWidth::U32).unwrap();
+        assert_eq!(free_blocks.len(), 8);
+        assert_eq!(loaded_data_totals, data_totals);
+    }
+}
@@ -0,0 +1,92 @@
+use crate::BlockIndex;
+
+/// Problem found in a storage file by `Storage::check_file`
+/// - last block of a file may end right after its data, so file length need not be
+///   storage header plus a multiple of block stride
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum StorageProblem {
+    /// Fewer bytes than a block header at end of file, left by a crash while appending a block
+    /// - repair truncates the torn bytes
+    TornBlockHeader {
+        block_index: BlockIndex,
+        size: usize,
+    },
+    /// Last block of file ends before the data its header claims
+    /// - repair truncates the block, its data is incomplete anyway
+    TruncatedBlock {
+        block_index: BlockIndex,
+        missing_bytes: usize,
+    },
+    /// Block header claims more data than a block holds
+    /// - repair marks the block free
+    InvalidBlockDataSize {
+        block_index: BlockIndex,
+        block_data_size: u64,
+    },
+    /// Block data does not match its checksum, not repaired
+    ChecksumMismatch { block_index: BlockIndex },
+    /// Compressed block data does not decompress, not repaired
+    CorruptCompressedData { block_index: BlockIndex },
+    /// Free block map next to storage file does not match block headers
+    /// - repair removes the map, it is rebuilt on next open
+    StaleFreeBlockMap,
+    /// Both root table slots are set, and neither holds an intact table, not repaired
+    CorruptRootTables,
+}
+
+/// Problem found, and whether it is repaired
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Finding<P> {
+    pub problem: P,
+    pub repaired: bool,
+}
+
+/// Problems found by a check, in order found
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CheckReport<P> {
+    pub findings: Vec<Finding<P>>,
+}
+
+impl<P> Default for CheckReport<P> {
+    fn default() -> Self {
+        CheckReport { findings: vec![] }
+    }
+}
+
+impl<P> CheckReport<P> {
+    /// Record a problem found, repaired or not
+    pub fn push(&mut self, problem: P, repaired: bool) {
+        self.findings.push(Finding { problem, repaired });
+    }
+
+    /// Check if no problem is found
+    pub fn is_clean(&self) -> bool {
+        self.findings.is_empty()
+    }
+
+    /// Problems left, not repaired
+    pub fn unrepaired(&self) -> impl Iterator<Item = &P> {
+        self.findings
+            .iter()
+            .filter(|finding| !finding.repaired)
+            .map(|finding| &finding.problem)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_report() {
+        let mut report = CheckReport::default();
+        assert!(report.is_clean());
+        report.push(StorageProblem::StaleFreeBlockMap, true);
+        report.push(StorageProblem::ChecksumMismatch { block_index: 2 }, false);
+        assert!(!report.is_clean());
+        assert_eq!(
+            report.unrepaired().collect::<Vec<_>>(),
+            vec![&StorageProblem::ChecksumMismatch { block_index: 2 }]
+        );
+    }
+}
@@ -0,0 +1,75 @@
+use util::error::{Error, ErrorType};
+
+// .... .... Journal::open .... ....
+
+pub fn open_open_journal(io_error: std::io::Error) -> Error {
+    Error::new(
+        ErrorType::Happens,
+        "journal_open_failed_to_open_journal",
+        Some(format!(
+            "Failed to open journal next to storage file, check permissions and disk state.\n {}",
+            io_error
+        )),
+    )
+}
+
+// .... .... Journal::write .... ....
+
+pub fn write_write_journal(io_error: std::io::Error) -> Error {
+    Error::new(
+        ErrorType::Happens,
+        "journal_write_failed_to_write_journal",
+        Some(format!(
+            "Failed to write transaction to journal, check permissions and disk state.\n {}",
+            io_error
+        )),
+    )
+}
+
+pub fn write_write_journal_success(write_size: usize) -> Error {
+    Error::new(
+        ErrorType::Happens,
+        "journal_write_failed_to_write_journal_success",
+        Some(format!(
+            "Transaction is written to journal partially, check disk state.\n\tWrite Size: {} bytes",
+            write_size
+        )),
+    )
+}
+
+pub fn write_sync_journal(io_error: std::io::Error) -> Error {
+    Error::new(
+        ErrorType::Happens,
+        "journal_write_failed_to_sync_journal",
+        Some(format!(
+            "Failed to sync journal, transaction is not committed, check disk state.\n {}",
+            io_error
+        )),
+    )
+}
+
+// .... .... Journal::read .... ....
+
+pub fn read_read_journal(io_error: std::io::Error) -> Error {
+    Error::new(
+        ErrorType::Happens,
+        "journal_read_failed_to_read_journal",
+        Some(format!(
+            "Failed to read journal, check permissions and disk state.\n {}",
+            io_error
+        )),
+    )
+}
+
+// .... .... Journal::clear .... ....
+
+pub fn clear_clear_journal(io_error: std::io::Error) -> Error {
+    Error::new(
+        ErrorType::Happens,
+        "journal_clear_failed_to_clear_journal",
+        Some(format!(
+            "Failed to clear journal, its transaction is redone on next open, check disk state.\n {}",
+            io_error
+        )),
+    )
+}
@@ -0,0 +1,297 @@
+use crate::block_device::{BlockDevice, FileBlockDevice};
+use crate::BlockIndex;
+use util::checksum::crc32c;
+use util::error::Error;
+
+mod journal_errors;
+
+/// Magic bytes at the start of a journal holding a committed transaction
+const JOURNAL_MAGIC: [u8; 4] = *b"XDBJ";
+
+/// 4 bytes magic, 4 bytes record count, 8 bytes payload length, 4 bytes CRC32C checksum of payload
+const JOURNAL_HEADER_SIZE: usize = 20;
+
+/// Kind of a record, stored as 1 byte ahead of its block index
+const RECORD_KIND_WRITE: u8 = 1;
+const RECORD_KIND_DELETE: u8 = 2;
+const RECORD_KIND_HARD_DELETE: u8 = 3;
+
+/// Change to a block, redone from journal if a commit is cut short
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum JournalRecord {
+    Write {
+        block_index: BlockIndex,
+        data: Vec<u8>,
+    },
+    Delete {
+        block_index: BlockIndex,
+        hard_delete: bool,
+    },
+}
+
+/// Group of block writes & deletes, committed atomically by `Storage::commit`
+/// - changes are applied in order they are added, a later change to a block wins
+/// - nothing reaches storage till commit
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Transaction {
+    pub(crate) records: Vec<JournalRecord>,
+}
+
+impl Transaction {
+    pub fn new() -> Transaction {
+        Transaction::default()
+    }
+
+    /// Write given data to block on commit
+    pub fn write_block(&mut self, block_index: BlockIndex, data: &[u8]) {
+        self.records.push(JournalRecord::Write {
+            block_index,
+            data: data.to_vec(),
+        });
+    }
+
+    /// Delete block on commit, as `Storage::delete_block`
+    pub fn delete_block(&mut self, block_index: BlockIndex, hard_delete: bool) {
+        self.records.push(JournalRecord::Delete {
+            block_index,
+            hard_delete,
+        });
+    }
+
+    /// Check if transaction has no change
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+}
+
+/// Redo journal of a storage, holding at most one committed transaction
+/// - transaction is written & synced to journal before any block of storage changes,
+///   then applied to storage, synced, and journal is cleared
+/// - a crash before journal is synced loses the transaction, a crash after it is redone on open
+/// - a torn journal fails its checksum, and is ignored as never committed
+pub(crate) struct Journal {
+    device: Box<dyn BlockDevice>,
+}
+
+impl Journal {
+    pub fn new(device: Box<dyn BlockDevice>) -> Journal {
+        Journal { device }
+    }
+
+    /// Path of journal, next to storage file in given path
+    pub fn file_path(file_path: &str) -> String {
+        format!("{}.journal", file_path)
+    }
+
+    /// Open journal of storage file in given path, created if missing
+    /// - truncate: if true, drops a transaction left in journal, for a new storage file
+    pub fn open(file_path: &str, truncate: bool) -> Result<Journal, Error> {
+        match FileBlockDevice::open(&Journal::file_path(file_path), truncate) {
+            Ok(device) => Ok(Journal::new(Box::new(device))),
+            Err(result_error) => Err(journal_errors::open_open_journal(result_error)),
+        }
+    }
+
+    /// Read records of committed transaction in journal of storage file in given path, without opening it for writing
+    /// - returns None if journal is missing, empty or torn
+    pub fn read_file(file_path: &str) -> Result<Option<Vec<JournalRecord>>, Error> {
+        let journal_file_path = Journal::file_path(file_path);
+        if !std::path::Path::new(&journal_file_path).exists() {
+            return Ok(None);
+        }
+        match FileBlockDevice::open_read_only(&journal_file_path) {
+            Ok(device) => Journal::new(Box::new(device)).read(),
+            Err(result_error) => Err(journal_errors::open_open_journal(result_error)),
+        }
+    }
+
+    /// Write records of a transaction to journal, and sync it
+    /// - transaction is committed once this returns
+    pub fn write(&mut self, records: &[JournalRecord]) -> Result<(), Error> {
+        let payload = records_to_bytes(records);
+        let bytes = [
+            &JOURNAL_MAGIC[..],
+            &(records.len() as u32).to_le_bytes(),
+            &(payload.len() as u64).to_le_bytes(),
+            &crc32c(&payload).to_le_bytes(),
+            &payload,
+        ]
+        .concat();
+        let write_result = self
+            .device
+            .set_size(0)
+            .and_then(|_| self.device.write_at(&bytes, 0));
+        match write_result {
+            Ok(write_size) if write_size == bytes.len() => {}
+            Ok(write_size) => return Err(journal_errors::write_write_journal_success(write_size)),
+            Err(result_error) => return Err(journal_errors::write_write_journal(result_error)),
+        }
+        if let Err(result_error) = self.device.sync() {
+            return Err(journal_errors::write_sync_journal(result_error));
+        }
+        Ok(())
+    }
+
+    /// Read records of committed transaction in journal
+    /// - returns None if journal is empty, torn, or not a journal
+    pub fn read(&self) -> Result<Option<Vec<JournalRecord>>, Error> {
+        let size = match self.device.size() {
+            Ok(size) => size as usize,
+            Err(result_error) => return Err(journal_errors::read_read_journal(result_error)),
+        };
+        if size < JOURNAL_HEADER_SIZE {
+            return Ok(None);
+        }
+        let mut bytes = vec![0u8; size];
+        match self.device.read_at(&mut bytes, 0) {
+            Ok(read_size) => bytes.truncate(read_size),
+            Err(result_error) => return Err(journal_errors::read_read_journal(result_error)),
+        }
+        if bytes.len() < JOURNAL_HEADER_SIZE || bytes[0..4] != JOURNAL_MAGIC {
+            return Ok(None);
+        }
+        let record_count = u32::from_le_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]) as usize;
+        let mut payload_len_bytes = [0u8; 8];
+        payload_len_bytes.copy_from_slice(&bytes[8..16]);
+        let payload_len = u64::from_le_bytes(payload_len_bytes) as usize;
+        let checksum = u32::from_le_bytes([bytes[16], bytes[17], bytes[18], bytes[19]]);
+        let payload = match bytes.get(JOURNAL_HEADER_SIZE..JOURNAL_HEADER_SIZE + payload_len) {
+            Some(payload) if crc32c(payload) == checksum => payload,
+            _ => return Ok(None),
+        };
+        Ok(records_from_bytes(payload, record_count))
+    }
+
+    /// Clear journal, after its transaction is applied to storage & synced
+    pub fn clear(&mut self) -> Result<(), Error> {
+        if let Err(result_error) = self.device.set_size(0) {
+            return Err(journal_errors::clear_clear_journal(result_error));
+        }
+        if let Err(result_error) = self.device.sync() {
+            return Err(journal_errors::clear_clear_journal(result_error));
+        }
+        Ok(())
+    }
+}
+
+/// Serialize records, each as kind, 8 bytes block index, and data length & data of a write
+fn records_to_bytes(records: &[JournalRecord]) -> Vec<u8> {
+    let mut bytes = vec![];
+    for record in records {
+        match record {
+            JournalRecord::Write { block_index, data } => {
+                bytes.push(RECORD_KIND_WRITE);
+                bytes.extend_from_slice(&block_index.to_le_bytes());
+                bytes.extend_from_slice(&(data.len() as u32).to_le_bytes());
+                bytes.extend_from_slice(data);
+            }
+            JournalRecord::Delete {
+                block_index,
+                hard_delete,
+            } => {
+                bytes.push(if *hard_delete {
+                    RECORD_KIND_HARD_DELETE
+                } else {
+                    RECORD_KIND_DELETE
+                });
+                bytes.extend_from_slice(&block_index.to_le_bytes());
+            }
+        }
+    }
+    bytes
+}
+
+/// Parse given number of records, None if bytes do not hold exactly that many
+fn records_from_bytes(bytes: &[u8], record_count: usize) -> Option<Vec<JournalRecord>> {
+    let mut records = Vec::with_capacity(record_count);
+    let mut position = 0;
+    for _ in 0..record_count {
+        let kind = *bytes.get(position)?;
+        let mut block_index_bytes = [0u8; 8];
+        block_index_bytes.copy_from_slice(bytes.get(position + 1..position + 9)?);
+        let block_index = BlockIndex::from_le_bytes(block_index_bytes);
+        position += 9;
+        let record = match kind {
+            RECORD_KIND_WRITE => {
+                let data_len_bytes = bytes.get(position..position + 4)?;
+                let data_len = u32::from_le_bytes([
+                    data_len_bytes[0],
+                    data_len_bytes[1],
+                    data_len_bytes[2],
+                    data_len_bytes[3],
+                ]) as usize;
+                position += 4;
+                let data = bytes.get(position..position + data_len)?.to_vec();
+                position += data_len;
+                JournalRecord::Write { block_index, data }
+            }
+            RECORD_KIND_DELETE | RECORD_KIND_HARD_DELETE => JournalRecord::Delete {
+                block_index,
+                hard_delete: kind == RECORD_KIND_HARD_DELETE,
+            },
+            _ => return None,
+        };
+        records.push(record);
+    }
+    if position != bytes.len() {
+        return None;
+    }
+    Some(records)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block_device::MemoryBlockDevice;
+
+    #[test]
+    fn test_journal_write_read_clear() {
+        let mut journal = Journal::new(Box::new(MemoryBlockDevice::new()));
+        assert_eq!(journal.read().unwrap(), None);
+        let mut transaction = Transaction::new();
+        assert!(transaction.is_empty());
+        transaction.write_block(3, &[1, 2, 3]);
+        transaction.delete_block(1, false);
+        transaction.delete_block(1 << 40, true);
+        transaction.write_block(0, &[]);
+        journal.write(&transaction.records).unwrap();
+        assert_eq!(journal.read().unwrap(), Some(transaction.records.clone()));
+        // - record of a write is kind, block index, data length & data
+        let mut bytes = vec![0u8; JOURNAL_HEADER_SIZE + 16];
+        journal.device.read_at(&mut bytes, 0).unwrap();
+        assert_eq!(bytes[0..8], [b'X', b'D', b'B', b'J', 4, 0, 0, 0]);
+        assert_eq!(
+            bytes[JOURNAL_HEADER_SIZE..JOURNAL_HEADER_SIZE + 16],
+            [1, 3, 0, 0, 0, 0, 0, 0, 0, 3, 0, 0, 0, 1, 2, 3]
+        );
+        journal.clear().unwrap();
+        assert_eq!(journal.read().unwrap(), None);
+    }
+
+    #[test]
+    fn test_journal_torn() {
+        let mut transaction = Transaction::new();
+        transaction.write_block(3, &[1, 2, 3]);
+        transaction.delete_block(1, false);
+        let mut journal = Journal::new(Box::new(MemoryBlockDevice::new()));
+        journal.write(&transaction.records).unwrap();
+        let size = journal.device.size().unwrap();
+        // - journal cut at any byte is ignored
+        for torn_size in 0..size {
+            let mut bytes = vec![0u8; size as usize];
+            journal.device.read_at(&mut bytes, 0).unwrap();
+            let mut torn_journal = Journal::new(Box::new(MemoryBlockDevice::new()));
+            torn_journal
+                .device
+                .write_at(&bytes[..torn_size as usize], 0)
+                .unwrap();
+            assert_eq!(torn_journal.read().unwrap(), None);
+        }
+        // - flipped byte of payload fails checksum
+        journal
+            .device
+            .write_at(&[9], JOURNAL_HEADER_SIZE as u64 + 1)
+            .unwrap();
+        assert_eq!(journal.read().unwrap(), None);
+    }
+}
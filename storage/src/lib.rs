@@ -1,62 +1,443 @@
+use util::checksum::crc32c;
 use util::error::Error;
 mod storage_errors;
 
-/// 4 bytes for index for a block
-pub type BlockIndex = u32;
+mod free_block_map;
+use free_block_map::FreeBlockMap;
+
+pub mod block_device;
+use block_device::{
+    BlockDevice, FileBlockDevice, MemoryBlockDevice, MmapBlockDevice, SegmentedBlockDevice,
+};
+
+pub mod block_allocator;
+pub mod fsck;
+use block_allocator::{AppendOnly, BestFitContiguous, BlockAllocator, FirstFitLowest};
+use fsck::{CheckReport, StorageProblem};
+
+mod block_cache;
+use block_cache::BlockCache;
+pub use block_cache::BlockCacheStats;
+
+mod storage_lock;
+pub use storage_lock::LockMode;
+use storage_lock::StorageLock;
+
+mod root_table;
+use root_table::RootTable;
+
+mod block_iter;
+pub use block_iter::{BlockHeaderIter, BlockIter};
+
+mod block_index_width;
+pub use block_index_width::BlockIndexWidth;
+
+mod block_compression;
+pub use block_compression::BlockCompression;
+use block_compression::{compressed_data_size, COMPRESSED_DATA_SIZE_LEN};
+
+mod journal;
+pub use journal::Transaction;
+use journal::{Journal, JournalRecord};
+
+mod shadow_pages;
+use shadow_pages::{ShadowPages, ShadowTable};
+
+mod storage_stats;
+pub use storage_stats::StorageStats;
+use storage_stats::{DataTotals, IoCounters};
+
+/// Index for a block, stored in file as 4 or 8 bytes as per block index width of storage
+pub type BlockIndex = u64;
 /// 4 bytes to store, blockLength, blockSize
 type BlockLength = u32; // stored in file
+/// Size of data stored in a block, stored in file as wide as block indexes
+type BlockDataSize = u64;
+
+/// Block indexes of blocks moved by compaction, old block index -> new block index
+pub type BlockRemap = BTreeMap<BlockIndex, BlockIndex>;
+
+/// Holes are punched in pages of file system, a page shared with a used block is kept
+const HOLE_PAGE_SIZE: u64 = 4096;
 
 //  ... ... ... ... ... ... ... ... Storage Header ... ... ... ... ... ... ... ... ... ..
 
 /// Main Header for storage file
+/// - Stores magic bytes to identify a storage file
+/// - Stores format version & feature flags as 4 bytes unsied integers as little endian
+/// - Highest bit of feature flags is not a feature, but set while storage is clean, see `STORAGE_STATE_CLEAN`
 /// - Stores constant capacity of each block as 4 bytes unsied integer as little endian
+/// - Stores 2 root table slots as 4 bytes unsied integers as little endian,
+///   or 8 bytes with 64-bit block indexes
+/// - Remaining bytes are reserved for future use, and are always 0
+///
+/// Files of format version 0 are headerless, except a 4 bytes block_len.
+/// Highest bit of their block_len flags if blocks carry a checksum.
 struct StorageHeader {
+    version: StorageFormatVersion,
+    features: StorageFeatures,
+    /// Storage file & free block map were synced after last change, and none is made since
+    clean: bool,
     block_len: BlockLength,
+    /// First block index + 1 of root table in each slot, 0 if slot is empty
+    root_slots: [BlockIndex; 2],
 }
 
-const STORAGE_HEADER_SIZE: usize = std::mem::size_of::<StorageHeader>();
+/// Version of storage file format
+pub type StorageFormatVersion = u32;
+/// Feature flags of storage file
+type StorageFeatures = u32;
+
+/// Magic bytes at the start of every storage file, since format version 1
+const STORAGE_MAGIC: [u8; 4] = *b"XDBS";
+
+/// Format version of newly created storage files
+pub const STORAGE_FORMAT_VERSION: StorageFormatVersion = 1;
+
+const STORAGE_HEADER_SIZE: usize = 32;
+
+/// Size of storage header of format version 0, a block_len only
+const STORAGE_HEADER_V0_SIZE: usize = std::mem::size_of::<BlockLength>();
+
+/// Flag bit in block_len of format version 0, set if blocks carry a checksum
+const STORAGE_HEADER_V0_CHECKSUM_FLAG: BlockLength = 1 << (BlockLength::BITS - 1);
+
+/// Feature flag, set if each block header stores CRC32C checksum of block data
+const STORAGE_FEATURE_BLOCK_CHECKSUM: StorageFeatures = 1 << 0;
+
+/// Feature flag, set if block indexes & block data sizes are stored as 8 bytes
+const STORAGE_FEATURE_WIDE_BLOCK_INDEX: StorageFeatures = 1 << 1;
+
+/// Feature flag, set if blocks are stored in segment files of a directory next to storage file
+/// - storage file holds storage header only, so readers unaware of segments must not open it
+const STORAGE_FEATURE_SEGMENTED: StorageFeatures = 1 << 2;
+
+/// Feature flag, set if block indexes of application are mapped to blocks of storage file by page tables
+/// - page tables are stored in root tables, so readers unaware of them must not open it
+const STORAGE_FEATURE_SHADOW_PAGING: StorageFeatures = 1 << 3;
+
+/// Feature flag, set if block data may be compressed with LZ4
+/// - highest bit of data size in block header is set for a compressed block
+const STORAGE_FEATURE_LZ4_COMPRESSION: StorageFeatures = 1 << 4;
+
+/// Feature flag, set if block data may be compressed with Zstandard, as LZ4 flag
+const STORAGE_FEATURE_ZSTD_COMPRESSION: StorageFeatures = 1 << 5;
+
+/// State flag in feature flags, set when storage is synced or closed, and cleared before it is next changed
+/// - free block map is trusted on open only while it is set, a crash in between leaves it clear
+const STORAGE_STATE_CLEAN: StorageFeatures = 1 << 31;
+
+/// Feature flags this version of storage knows how to handle
+const STORAGE_SUPPORTED_FEATURES: StorageFeatures = STORAGE_FEATURE_BLOCK_CHECKSUM
+    | STORAGE_FEATURE_WIDE_BLOCK_INDEX
+    | STORAGE_FEATURE_SEGMENTED
+    | STORAGE_FEATURE_SHADOW_PAGING
+    | STORAGE_FEATURE_LZ4_COMPRESSION
+    | STORAGE_FEATURE_ZSTD_COMPRESSION;
 
 impl StorageHeader {
-    fn new(block_len: BlockLength) -> Self {
-        StorageHeader { block_len }
+    fn new(
+        block_len: BlockLength,
+        block_checksum: bool,
+        block_index_width: BlockIndexWidth,
+    ) -> Self {
+        let mut features = 0;
+        if block_checksum {
+            features |= STORAGE_FEATURE_BLOCK_CHECKSUM;
+        }
+        if block_index_width == BlockIndexWidth::U64 {
+            features |= STORAGE_FEATURE_WIDE_BLOCK_INDEX;
+        }
+        StorageHeader {
+            version: STORAGE_FORMAT_VERSION,
+            features,
+            clean: false,
+            block_len,
+            root_slots: [0, 0],
+        }
+    }
+
+    /// Parse storage header of format version 1 or later
+    /// - returns error if magic bytes do not match, or format version or features are unsupported
+    fn from_bytes(bytes: [u8; STORAGE_HEADER_SIZE]) -> Result<StorageHeader, Error> {
+        if bytes[0..4] != STORAGE_MAGIC {
+            return Err(storage_errors::storage_header_invalid_magic(
+                bytes[0..4].to_vec(),
+            ));
+        }
+        let version = StorageFormatVersion::from_le_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]);
+        if version > STORAGE_FORMAT_VERSION {
+            return Err(storage_errors::storage_header_unsupported_version(version));
+        }
+        let features = StorageFeatures::from_le_bytes([bytes[8], bytes[9], bytes[10], bytes[11]]);
+        let clean = features & STORAGE_STATE_CLEAN != 0;
+        let features = features & !STORAGE_STATE_CLEAN;
+        // - a storage is compressed with a single codec
+        let compression_features =
+            STORAGE_FEATURE_LZ4_COMPRESSION | STORAGE_FEATURE_ZSTD_COMPRESSION;
+        if features & !STORAGE_SUPPORTED_FEATURES != 0
+            || features & compression_features == compression_features
+        {
+            return Err(storage_errors::storage_header_unsupported_features(
+                features,
+            ));
+        }
+        let block_len = BlockLength::from_le_bytes([bytes[12], bytes[13], bytes[14], bytes[15]]);
+        let mut storage_header = StorageHeader {
+            version,
+            features,
+            clean,
+            block_len,
+            root_slots: [0, 0],
+        };
+        // - root slots are as wide as block indexes, 0 is an empty slot, so never reserved
+        let width = storage_header.block_index_width();
+        for (slot, root_slot) in storage_header.root_slots.iter_mut().enumerate() {
+            let offset = 16 + slot * width.size();
+            let slot_bytes = &bytes[offset..offset + width.size()];
+            let mut value_bytes = [0u8; 8];
+            value_bytes[..width.size()].copy_from_slice(slot_bytes);
+            *root_slot = BlockIndex::from_le_bytes(value_bytes);
+        }
+        Ok(storage_header)
     }
 
-    fn from_bytes(bytes: [u8; STORAGE_HEADER_SIZE]) -> StorageHeader {
-        let block_len = BlockLength::from_le_bytes(bytes);
-        StorageHeader { block_len }
+    /// Parse storage header of format version 0
+    fn from_v0_bytes(bytes: [u8; STORAGE_HEADER_V0_SIZE]) -> StorageHeader {
+        let stored_value = BlockLength::from_le_bytes(bytes);
+        let features = if stored_value & STORAGE_HEADER_V0_CHECKSUM_FLAG != 0 {
+            STORAGE_FEATURE_BLOCK_CHECKSUM
+        } else {
+            0
+        };
+        StorageHeader {
+            version: 0,
+            features,
+            clean: false,
+            block_len: stored_value & !STORAGE_HEADER_V0_CHECKSUM_FLAG,
+            root_slots: [0, 0],
+        }
     }
 
+    /// Serialize storage header in current format version
     fn to_bytes(&self) -> [u8; STORAGE_HEADER_SIZE] {
-        BlockLength::to_le_bytes(self.block_len)
+        let mut bytes = [0u8; STORAGE_HEADER_SIZE];
+        bytes[0..4].copy_from_slice(&STORAGE_MAGIC);
+        bytes[4..8].copy_from_slice(&STORAGE_FORMAT_VERSION.to_le_bytes());
+        let features = if self.clean {
+            self.features | STORAGE_STATE_CLEAN
+        } else {
+            self.features
+        };
+        bytes[8..12].copy_from_slice(&features.to_le_bytes());
+        bytes[12..16].copy_from_slice(&self.block_len.to_le_bytes());
+        let width = self.block_index_width().size();
+        for (slot, root_slot) in self.root_slots.iter().enumerate() {
+            let offset = 16 + slot * width;
+            bytes[offset..offset + width].copy_from_slice(&root_slot.to_le_bytes()[..width]);
+        }
+        bytes
+    }
+
+    /// Size of storage header in file, as per its format version
+    fn size(&self) -> usize {
+        if self.version == 0 {
+            STORAGE_HEADER_V0_SIZE
+        } else {
+            STORAGE_HEADER_SIZE
+        }
+    }
+
+    /// Check if blocks carry a checksum
+    fn block_checksum(&self) -> bool {
+        self.features & STORAGE_FEATURE_BLOCK_CHECKSUM != 0
+    }
+
+    /// Width of block indexes & block data sizes stored in file
+    fn block_index_width(&self) -> BlockIndexWidth {
+        if self.features & STORAGE_FEATURE_WIDE_BLOCK_INDEX != 0 {
+            BlockIndexWidth::U64
+        } else {
+            BlockIndexWidth::U32
+        }
+    }
+
+    /// Check if blocks are stored in segment files
+    fn segmented(&self) -> bool {
+        self.features & STORAGE_FEATURE_SEGMENTED != 0
+    }
+
+    /// Check if storage is shadow paged
+    fn shadow_paging(&self) -> bool {
+        self.features & STORAGE_FEATURE_SHADOW_PAGING != 0
+    }
+
+    /// Codec block data is compressed with
+    fn compression(&self) -> BlockCompression {
+        if self.features & STORAGE_FEATURE_LZ4_COMPRESSION != 0 {
+            BlockCompression::Lz4
+        } else if self.features & STORAGE_FEATURE_ZSTD_COMPRESSION != 0 {
+            BlockCompression::Zstd
+        } else {
+            BlockCompression::None
+        }
+    }
+
+    /// Parse block header of 4 or 8 bytes
+    /// - highest bit of data size marks a compressed block, if storage is compressed
+    fn parse_block_header(&self, bytes: &[u8]) -> BlockHeader {
+        let mut block_header = BlockHeader::from_bytes(bytes);
+        if self.compression() != BlockCompression::None {
+            let compressed_flag = BlockHeader::compressed_flag(self.block_index_width());
+            block_header.compressed = block_header.block_data_size & compressed_flag != 0;
+            block_header.block_data_size &= !compressed_flag;
+        }
+        block_header
+    }
+
+    /// Size of block data size at start of each block header
+    fn block_data_size_len(&self) -> usize {
+        match self.block_index_width() {
+            BlockIndexWidth::U32 => BLOCK_HEADER_SIZE,
+            BlockIndexWidth::U64 => WIDE_BLOCK_HEADER_SIZE,
+        }
+    }
+
+    /// Size of header of each block, checksum included if enabled
+    fn block_header_size(&self) -> usize {
+        if self.block_checksum() {
+            self.block_data_size_len() + BLOCK_CHECKSUM_SIZE
+        } else {
+            self.block_data_size_len()
+        }
     }
 }
 
 #[cfg(test)]
 mod unit_tests_storage_header {
     use super::*;
+
+    const HEADER_BYTES_16777472: [u8; STORAGE_HEADER_SIZE] = [
+        b'X', b'D', b'B', b'S', // magic
+        1, 0, 0, 0, // version
+        0, 0, 0, 0, // features
+        0, 1, 0, 1, // block_len
+        0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, // reserved
+    ];
+
     #[test]
     fn test_storage_header_to_bytes() {
-        let storage_header = StorageHeader::new(16777472);
+        let storage_header = StorageHeader::new(16777472, false, BlockIndexWidth::U32);
         let bytes = storage_header.to_bytes();
-        assert_eq!(bytes, [0, 1, 0, 1]);
+        assert_eq!(bytes, HEADER_BYTES_16777472);
+        let storage_header = StorageHeader::new(16777472, true, BlockIndexWidth::U32);
+        let bytes = storage_header.to_bytes();
+        assert_eq!(bytes[0..8], HEADER_BYTES_16777472[0..8]);
+        assert_eq!(bytes[8..12], [1, 0, 0, 0]);
+        assert_eq!(bytes[12..], HEADER_BYTES_16777472[12..]);
     }
 
     #[test]
     fn test_storage_header_from_bytes() {
-        let storage_header = StorageHeader::from_bytes([0, 2, 0, 2]);
+        let storage_header = StorageHeader::from_bytes(HEADER_BYTES_16777472).unwrap();
+        assert_eq!(storage_header.version, 1);
+        assert_eq!(storage_header.block_len, 16777472);
+        assert!(!storage_header.block_checksum());
+        assert_eq!(storage_header.size(), STORAGE_HEADER_SIZE);
+        assert_eq!(storage_header.root_slots, [0, 0]);
+        // root table slots
+        let mut bytes = HEADER_BYTES_16777472;
+        bytes[16..24].copy_from_slice(&[3, 0, 0, 0, 0, 1, 0, 0]);
+        let storage_header = StorageHeader::from_bytes(bytes).unwrap();
+        assert_eq!(storage_header.root_slots, [3, 256]);
+        assert_eq!(storage_header.to_bytes(), bytes);
+        // foreign file
+        let mut bytes = HEADER_BYTES_16777472;
+        bytes[0] = b'Y';
+        let result = StorageHeader::from_bytes(bytes);
+        assert_eq!(result.err().unwrap().code(), "storage_header_invalid_magic");
+        // too new format version
+        let mut bytes = HEADER_BYTES_16777472;
+        bytes[4] = 2;
+        let result = StorageHeader::from_bytes(bytes);
+        assert_eq!(
+            result.err().unwrap().code(),
+            "storage_header_unsupported_version"
+        );
+        // unknown feature
+        let mut bytes = HEADER_BYTES_16777472;
+        bytes[11] = 64;
+        let result = StorageHeader::from_bytes(bytes);
+        assert_eq!(
+            result.err().unwrap().code(),
+            "storage_header_unsupported_features"
+        );
+        // clean state is not a feature
+        let mut bytes = HEADER_BYTES_16777472;
+        bytes[11] = 128;
+        let storage_header = StorageHeader::from_bytes(bytes).unwrap();
+        assert!(storage_header.clean);
+        assert_eq!(storage_header.features, 0);
+        assert_eq!(storage_header.to_bytes(), bytes);
+    }
+
+    #[test]
+    fn test_storage_header_from_v0_bytes() {
+        let storage_header = StorageHeader::from_v0_bytes([0, 2, 0, 2]);
+        assert_eq!(storage_header.version, 0);
+        assert_eq!(storage_header.block_len, 33554944);
+        assert!(!storage_header.block_checksum());
+        assert_eq!(storage_header.size(), STORAGE_HEADER_V0_SIZE);
+        let storage_header = StorageHeader::from_v0_bytes([0, 2, 0, 130]);
+        assert_eq!(storage_header.block_len, 33554944);
+        assert!(storage_header.block_checksum());
+        // upgrade to current format version
+        let bytes = storage_header.to_bytes();
+        let storage_header = StorageHeader::from_bytes(bytes).unwrap();
+        assert_eq!(storage_header.version, STORAGE_FORMAT_VERSION);
         assert_eq!(storage_header.block_len, 33554944);
+        assert!(storage_header.block_checksum());
     }
 
     #[test]
     fn test_storage_header_full_flow() {
         let block_length = 16777472;
-        let expected_bytes = [0, 1, 0, 1];
-        let storage_header = StorageHeader::new(block_length);
+        let storage_header = StorageHeader::new(block_length, false, BlockIndexWidth::U32);
         assert_eq!(storage_header.block_len, block_length);
         let bytes = storage_header.to_bytes();
-        assert_eq!(bytes, expected_bytes);
-        let storage_header = StorageHeader::from_bytes(bytes);
+        assert_eq!(bytes, HEADER_BYTES_16777472);
+        let storage_header = StorageHeader::from_bytes(bytes).unwrap();
         assert_eq!(storage_header.block_len, block_length);
+        assert!(!storage_header.block_checksum());
+    }
+
+    #[test]
+    fn test_storage_header_block_header_size() {
+        let storage_header = StorageHeader::new(8, false, BlockIndexWidth::U32);
+        assert_eq!(storage_header.block_header_size(), BLOCK_HEADER_SIZE);
+        let storage_header = StorageHeader::new(8, true, BlockIndexWidth::U32);
+        assert_eq!(
+            storage_header.block_header_size(),
+            BLOCK_HEADER_SIZE + BLOCK_CHECKSUM_SIZE
+        );
+        let storage_header = StorageHeader::new(8, true, BlockIndexWidth::U64);
+        assert_eq!(
+            storage_header.block_header_size(),
+            WIDE_BLOCK_HEADER_SIZE + BLOCK_CHECKSUM_SIZE
+        );
+    }
+
+    #[test]
+    fn test_storage_header_wide_block_index() {
+        let mut storage_header = StorageHeader::new(8, false, BlockIndexWidth::U64);
+        storage_header.root_slots = [0x1_0000_0003, 7];
+        let bytes = storage_header.to_bytes();
+        assert_eq!(bytes[8..12], [2, 0, 0, 0]);
+        assert_eq!(bytes[16..24], [3, 0, 0, 0, 1, 0, 0, 0]);
+        assert_eq!(bytes[24..32], [7, 0, 0, 0, 0, 0, 0, 0]);
+        let storage_header = StorageHeader::from_bytes(bytes).unwrap();
+        assert_eq!(storage_header.block_index_width(), BlockIndexWidth::U64);
+        assert_eq!(storage_header.root_slots, [0x1_0000_0003, 7]);
     }
 }
 
@@ -65,25 +446,65 @@ mod unit_tests_storage_header {
 //  ... ... ... ... ... ... ... ... Block Header ... ... ... ... ... ... ... ... ... ....
 
 /// Header of each block
-/// - Stores size of data stored in the block as 4 bytes unsied integer as little endian
+/// - Stores size of data stored in the block as 4 bytes unsied integer as little endian,
+///   or 8 bytes with 64-bit block indexes
+/// - Highest bit of data size is set if the data is compressed, in compressed storages only
 struct BlockHeader {
-    block_data_size: BlockLength,
+    block_data_size: BlockDataSize,
+    compressed: bool,
 }
 
-pub const BLOCK_HEADER_SIZE: usize = std::mem::size_of::<BlockHeader>();
+/// Size of block header of a file with 32-bit block indexes
+pub const BLOCK_HEADER_SIZE: usize = 4;
+
+/// Size of block header of a file with 64-bit block indexes
+pub const WIDE_BLOCK_HEADER_SIZE: usize = 8;
+
+/// 4 bytes CRC32C checksum of block data, stored after block header if enabled
+type BlockChecksum = u32;
+
+pub const BLOCK_CHECKSUM_SIZE: usize = std::mem::size_of::<BlockChecksum>();
 
 impl BlockHeader {
-    fn new(block_data_size: u32) -> BlockHeader {
-        BlockHeader { block_data_size }
+    fn new(block_data_size: BlockDataSize) -> BlockHeader {
+        BlockHeader {
+            block_data_size,
+            compressed: false,
+        }
+    }
+
+    /// Header of a block holding given size of compressed data
+    fn new_compressed(block_data_size: BlockDataSize) -> BlockHeader {
+        BlockHeader {
+            block_data_size,
+            compressed: true,
+        }
     }
 
-    fn from_bytes(bytes: [u8; BLOCK_HEADER_SIZE]) -> BlockHeader {
-        let block_data_size = BlockLength::from_le_bytes(bytes);
-        BlockHeader { block_data_size }
+    /// Bit of data size marking a compressed block, highest bit of given width
+    fn compressed_flag(block_index_width: BlockIndexWidth) -> BlockDataSize {
+        1 << (block_index_width.size() * 8 - 1)
     }
 
-    fn to_bytes(&self) -> [u8; BLOCK_HEADER_SIZE] {
-        BlockLength::to_le_bytes(self.block_data_size)
+    /// Parse block header of 4 or 8 bytes, as per length of given bytes
+    fn from_bytes(bytes: &[u8]) -> BlockHeader {
+        let mut block_data_size_bytes = [0u8; WIDE_BLOCK_HEADER_SIZE];
+        block_data_size_bytes[..bytes.len()].copy_from_slice(bytes);
+        let block_data_size = BlockDataSize::from_le_bytes(block_data_size_bytes);
+        BlockHeader::new(block_data_size)
+    }
+
+    /// Serialize block header, as wide as block indexes of given width
+    fn to_bytes(&self, block_index_width: BlockIndexWidth) -> Vec<u8> {
+        let size = match block_index_width {
+            BlockIndexWidth::U32 => BLOCK_HEADER_SIZE,
+            BlockIndexWidth::U64 => WIDE_BLOCK_HEADER_SIZE,
+        };
+        let mut block_data_size = self.block_data_size;
+        if self.compressed {
+            block_data_size |= BlockHeader::compressed_flag(block_index_width);
+        }
+        block_data_size.to_le_bytes()[..size].to_vec()
     }
 }
 
@@ -94,14 +515,18 @@ mod unit_test_block_header {
     #[test]
     fn test_block_header_to_bytes() {
         let block_header = BlockHeader::new(16777472);
-        let bytes = block_header.to_bytes();
+        let bytes = block_header.to_bytes(BlockIndexWidth::U32);
         assert_eq!(bytes, [0, 1, 0, 1]);
+        let bytes = block_header.to_bytes(BlockIndexWidth::U64);
+        assert_eq!(bytes, [0, 1, 0, 1, 0, 0, 0, 0]);
     }
 
     #[test]
     fn test_block_header_from_bytes() {
-        let block_header = BlockHeader::from_bytes([0, 2, 0, 2]);
+        let block_header = BlockHeader::from_bytes(&[0, 2, 0, 2]);
         assert_eq!(block_header.block_data_size, 33554944);
+        let block_header = BlockHeader::from_bytes(&[0, 2, 0, 2, 1, 0, 0, 0]);
+        assert_eq!(block_header.block_data_size, 0x1_0200_0200);
     }
 
     #[test]
@@ -110,19 +535,165 @@ mod unit_test_block_header {
         let expected_bytes = [0, 1, 0, 1];
         let block_header = BlockHeader::new(block_data_size);
         assert_eq!(block_header.block_data_size, block_data_size);
-        let bytes = block_header.to_bytes();
+        let bytes = block_header.to_bytes(BlockIndexWidth::U32);
         assert_eq!(bytes, expected_bytes);
-        let block_header = BlockHeader::from_bytes(bytes);
+        let block_header = BlockHeader::from_bytes(&bytes);
         assert_eq!(block_header.block_data_size, block_data_size);
     }
+
+    #[test]
+    fn test_block_header_compressed() {
+        let block_header = BlockHeader::new_compressed(258);
+        assert_eq!(block_header.to_bytes(BlockIndexWidth::U32), [2, 1, 0, 128]);
+        let bytes = block_header.to_bytes(BlockIndexWidth::U64);
+        assert_eq!(bytes, [2, 1, 0, 0, 0, 0, 0, 128]);
+        // - flag is parsed only from header of a compressed storage
+        let mut storage_header = StorageHeader::new(512, false, BlockIndexWidth::U64);
+        let block_header = storage_header.parse_block_header(&bytes);
+        assert!(!block_header.compressed);
+        storage_header.features |= STORAGE_FEATURE_ZSTD_COMPRESSION;
+        let block_header = storage_header.parse_block_header(&bytes);
+        assert!(block_header.compressed);
+        assert_eq!(block_header.block_data_size, 258);
+    }
 }
 
 // ... ... ... ... ... ... ... ... ... ... ... ... ... ... ... ... ... ... ... ... ... ..
 
 // ... ... ... ... ... ... ... ... ... Storage ... ... ... ... ... ... ... ... ... ....
 
-use std::collections::BTreeSet;
-use std::fs::{File, OpenOptions};
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+/// Backend, a storage file in given path is read & written with
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum StorageBackend {
+    /// Positional read & write syscalls on the file
+    #[default]
+    File,
+    /// File memory mapped into address space
+    Mmap,
+    /// Storage header in the file, blocks in a directory of segment files next to it,
+    /// each holding given number of blocks, see `Storage::segment_dir_path`
+    /// - segment files are created on demand, and read & written with positional syscalls
+    /// - existing segmented storages are opened as such, whichever backend is given
+    Segmented { segment_block_count: BlockIndex },
+}
+
+/// Policy picking blocks new data is written to, see `block_allocator` for custom allocators
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum AllocationPolicy {
+    /// Lowest free blocks, then blocks beyond end of file
+    #[default]
+    FirstFitLowest,
+    /// Smallest run of free blocks holding all blocks, else blocks beyond end of file
+    BestFitContiguous,
+    /// Blocks beyond end of file only, free blocks are never reused
+    AppendOnly,
+}
+
+impl AllocationPolicy {
+    fn block_allocator(self) -> Box<dyn BlockAllocator> {
+        match self {
+            AllocationPolicy::FirstFitLowest => Box::new(FirstFitLowest),
+            AllocationPolicy::BestFitContiguous => Box::new(BestFitContiguous),
+            AllocationPolicy::AppendOnly => Box::new(AppendOnly),
+        }
+    }
+}
+
+/// Limit on size of a storage, in blocks or in bytes of storage file
+/// - bytes are rounded down to whole blocks, after storage header
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StorageQuota {
+    /// Number of blocks, used or free
+    Blocks(BlockIndex),
+    /// Length of storage file
+    Bytes(u64),
+}
+
+/// Storage grew past its soft quota, passed to hook set by `Storage::set_quota_warning_hook`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct QuotaWarning {
+    /// Number of blocks in storage, after the write growing it
+    pub end_block_count: BlockIndex,
+    /// Soft quota in blocks
+    pub soft_quota_block_count: BlockIndex,
+    /// Quota in blocks, None if storage has no quota
+    pub quota_block_count: Option<BlockIndex>,
+}
+
+/// When writes to storage are flushed to durable storage, besides explicit `Storage::sync`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SyncPolicy {
+    /// Never sync implicitly, writes reach durable storage when the OS flushes them
+    #[default]
+    Never,
+    /// Sync after every write
+    Always,
+    /// Sync after every given number of writes
+    EveryWrites(usize),
+    /// Sync on a write, if given number of milliseconds passed since last sync
+    /// - checked on writes only, there is no background flush
+    EveryMillis(u64),
+}
+
+/// Options to create or open a storage file
+/// - Options recorded in storage header are loaded from file on open
+#[derive(Clone, Debug, Default)]
+pub struct StorageOptions {
+    /// Store CRC32C checksum of data in each block header, verified on every read
+    /// - recorded in storage header
+    pub block_checksum: bool,
+    /// Maximum number of blocks held in memory by block cache, 0 disables the cache
+    pub block_cache_capacity: usize,
+    /// Backend to read & write storage file with, ignored for storages on a given device
+    pub backend: StorageBackend,
+    /// When writes are synced to durable storage
+    pub sync_policy: SyncPolicy,
+    /// Re-read & verify every block on open, instead of trusting the free block map
+    /// - block data must fit in the block, and match its checksum if enabled
+    pub strict: bool,
+    /// Time to wait for a storage file locked by another storage, before failing with `storage_locked`
+    /// - None fails at once
+    pub lock_timeout: Option<Duration>,
+    /// Release space of deleted blocks to file system, with holes punched into free blocks,
+    /// and free blocks at end of file truncated
+    /// - falls back to writing zeros where file system can not punch holes
+    pub punch_holes: bool,
+    /// Policy picking blocks new data is written to
+    pub allocation_policy: AllocationPolicy,
+    /// Width of block indexes & block data sizes stored in file, 64-bit for files beyond about 4 billion blocks
+    /// - recorded in storage header
+    pub block_index_width: BlockIndexWidth,
+    /// Limit on size of storage, allocation & writes growing storage past it fail with `storage_full`
+    /// - blocks within storage can still be rewritten, and free blocks reused
+    /// - None leaves storage bound by `Storage::max_block_count` only
+    pub quota: Option<StorageQuota>,
+    /// Size of storage, hook set by `Storage::set_quota_warning_hook` is called when a write grows storage past it
+    pub soft_quota: Option<StorageQuota>,
+    /// Never overwrite a live block, see `Storage::checkpoint`
+    /// - block indexes of application are mapped to fresh blocks of storage file on every write,
+    ///   and changes survive a crash once checkpointed
+    /// - recorded in storage header
+    pub shadow_paging: bool,
+    /// Codec block data is compressed with, a block is stored raw where that is smaller
+    /// - blocks hold block length bytes of data as without compression, see `Storage::stats` for bytes taken off stored data
+    /// - recorded in storage header
+    pub compression: BlockCompression,
+}
+
+/// Size of storage file, as read & as allocated on disk
+/// - physical size is less than logical size, if file has holes
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StorageSpace {
+    /// Length of storage file in bytes
+    pub logical_size: u64,
+    /// Bytes of disk allocated to storage file
+    pub physical_size: u64,
+}
 
 pub struct Storage {
     header: StorageHeader,
@@ -130,235 +701,1089 @@ pub struct Storage {
     free_blocks: BTreeSet<BlockIndex>,
     /// Number of blocks in the storage file (used or free)
     end_block_count: BlockIndex,
-    /// File object for writing
-    file_writer: File,
+    /// Device storage file is read from & written to
+    device: Box<dyn BlockDevice>,
     /// Index of last written byte in the file
     write_pointer: usize,
-    /// File object for reading
-    file_reader: File,
     /// Index of last read byte in the file
-    read_pointer: usize,
+    /// - atomic, as reads take shared reference to storage
+    read_pointer: AtomicUsize,
+    /// Free blocks persisted next to storage file, kept in sync with free_blocks
+    /// - None for storages on a given device, which have no path to persist it to
+    free_block_map: Option<FreeBlockMap>,
+    /// Recently used blocks held in memory
+    /// - behind a lock, as reads take shared reference to storage and update the cache
+    block_cache: Mutex<BlockCache>,
+    /// When writes are synced to durable storage
+    sync_policy: SyncPolicy,
+    /// Number of writes since last sync
+    unsynced_write_count: usize,
+    /// Time of last sync
+    last_sync_instant: Instant,
+    /// Advisory lock on storage file, held until storage is dropped
+    /// - None for storages on a given device
+    lock: Option<StorageLock>,
+    /// Storage is opened read-only, mutating calls fail with `storage_read_only`
+    read_only: bool,
+    /// Root tables in slots of storage header
+    /// - None if slot is empty, or its table is torn by a crash
+    root_tables: [Option<RootTable>; 2],
+    /// Punch holes into deleted blocks, and truncate free blocks at end of file
+    punch_holes: bool,
+    /// Picks blocks new data is written to
+    block_allocator: Box<dyn BlockAllocator>,
+    /// Cumulative bytes & blocks read & written, since storage is opened
+    io_counters: IoCounters,
+    /// Running totals of data in used blocks, see `Storage::stats`
+    /// - None if unknown, after a write or delete failed midway
+    data_totals: Option<DataTotals>,
+    /// Limit on size of storage
+    quota: Option<StorageQuota>,
+    /// Size of storage, quota warning hook is called past it
+    soft_quota: Option<StorageQuota>,
+    /// Called when a write grows storage past its soft quota
+    quota_warning_hook: Option<QuotaWarningHook>,
+    /// Redo journal transactions are committed through, see `Storage::commit`
+    /// - None for storages on a given device without a journal device
+    journal: Option<Journal>,
+    /// Page tables mapping block indexes of application to blocks of storage file
+    /// - None unless storage is shadow paged
+    shadow: Option<ShadowPages>,
 }
 
+/// Hook called when a write grows storage past its soft quota
+pub type QuotaWarningHook = Box<dyn Fn(&QuotaWarning) + Send + Sync>;
+
 impl Storage {
     pub fn block_len(&self) -> BlockLength {
         self.header.block_len
     }
-    //  ... ... ... ... ... ... Static Functions ... ... ... ... ... ... .
 
-    /// Open storage file for writing
-    /// - creates a new file if it does not exist
-    /// - truncate: if true, truncates the file to 0 bytes
-    /// - truncate: if false, no modification to the file
-    /// - returns: (file_object_for_writing, write_pointer) - write_pointer is always 0
-    fn open_file_writer(file_path: &str, truncate: bool) -> Result<(File, usize), Error> {
-        let file_writer_result = OpenOptions::new()
-            .write(true)
-            .truncate(truncate)
-            .create(true)
-            .open(file_path);
-        if let Err(result_error) = file_writer_result {
-            return Err(storage_errors::open_file_writer_open_file(result_error));
-        }
-        let file_writer = file_writer_result.unwrap();
-        let write_pointer = 0;
-        Ok((file_writer, write_pointer))
-    }
-
-    /// Open storage file for reading
-    /// - returns: (file_object_for_reading, read_pointer) - read_pointer is always 0
-    fn open_file_reader(file_path: &str) -> Result<(File, usize), Error> {
-        let file_reader_result = OpenOptions::new().read(true).open(file_path);
-        if let Err(result_error) = file_reader_result {
-            return Err(storage_errors::open_file_reader_open_file(result_error));
+    /// Check if blocks in storage carry a checksum
+    pub fn block_checksum(&self) -> bool {
+        self.header.block_checksum()
+    }
+
+    /// Width of block indexes & block data sizes stored in storage file
+    /// - block indexes stored in blocks, e.g. links between blocks, are expected to be as wide
+    pub fn block_index_width(&self) -> BlockIndexWidth {
+        self.header.block_index_width()
+    }
+
+    /// Number of blocks storage file can hold
+    /// - block index must fit in block index width, with its largest value reserved as end of chain
+    /// - offset of end of last block must fit in a file offset
+    pub fn max_block_count(&self) -> BlockIndex {
+        let block_stride =
+            (self.header.block_header_size() + self.header.block_len as usize) as u64;
+        let offset_block_count = (usize::MAX as u64 - self.header.size() as u64) / block_stride;
+        self.header
+            .block_index_width()
+            .max_block_count()
+            .min(offset_block_count)
+    }
+
+    /// Number of blocks storage can grow to, as per its quota, None if storage has no quota
+    /// - bounded by `max_block_count`
+    pub fn quota_block_count(&self) -> Option<BlockIndex> {
+        self.quota
+            .map(|quota| self.block_count_of_quota(quota).min(self.max_block_count()))
+    }
+
+    /// Number of blocks storage can grow to, as per its soft quota, None if storage has no soft quota
+    pub fn soft_quota_block_count(&self) -> Option<BlockIndex> {
+        self.soft_quota
+            .map(|soft_quota| self.block_count_of_quota(soft_quota))
+    }
+
+    /// Number of whole blocks fitting in given quota
+    fn block_count_of_quota(&self, quota: StorageQuota) -> BlockIndex {
+        match quota {
+            StorageQuota::Blocks(block_count) => block_count,
+            StorageQuota::Bytes(size) => {
+                let block_stride =
+                    (self.header.block_header_size() + self.header.block_len as usize) as u64;
+                size.saturating_sub(self.header.size() as u64) / block_stride
+            }
         }
-        let file_reader = file_reader_result.unwrap();
-        let read_pointer = 0;
-        Ok((file_reader, read_pointer))
     }
 
-    // // ... ... ... ... ... Storage Constructors ... ... ... ... ... .
+    /// Check if given block is within storage, or storage may grow to hold it as per its quota
+    fn within_quota(&self, block_index: BlockIndex) -> bool {
+        match self.quota_block_count() {
+            Some(quota_block_count) => {
+                block_index < self.end_block_count || block_index < quota_block_count
+            }
+            None => true,
+        }
+    }
 
-    /// Create new storage file
-    /// - Create/Overwrite new storage file in given path
-    /// - Initializes storage header
-    pub fn new(file_path: String, block_len: u32) -> Result<Storage, Error> {
-        let (file_writer, write_pointer) = Storage::open_file_writer(&file_path, true)?;
-        let (file_reader, read_pointer) = Storage::open_file_reader(&file_path)?;
+    /// Set hook called when a write grows storage past its soft quota
+    /// - called once each time storage grows past it, from the thread writing
+    pub fn set_quota_warning_hook(&mut self, hook: QuotaWarningHook) {
+        self.quota_warning_hook = Some(hook);
+    }
 
-        // Initialize storage object
-        let mut storage = Storage {
-            header: StorageHeader::new(block_len),
-            free_blocks: BTreeSet::new(),
-            end_block_count: 0,
-            file_writer,
-            write_pointer,
-            file_reader,
-            read_pointer,
+    /// Call quota warning hook, if storage grew past its soft quota since it had given number of blocks
+    fn warn_soft_quota(&self, previous_end_block_count: BlockIndex) {
+        let soft_quota_block_count = match self.soft_quota_block_count() {
+            Some(soft_quota_block_count) => soft_quota_block_count,
+            None => return,
         };
+        if previous_end_block_count <= soft_quota_block_count
+            && self.end_block_count > soft_quota_block_count
+        {
+            if let Some(hook) = &self.quota_warning_hook {
+                hook(&QuotaWarning {
+                    end_block_count: self.end_block_count,
+                    soft_quota_block_count,
+                    quota_block_count: self.quota_block_count(),
+                });
+            }
+        }
+    }
 
-        // Write storage header to file
-        storage.set_storage_header()?;
+    /// Lock block cache
+    /// - a thread panicking while holding the lock leaves the cache usable
+    fn block_cache(&self) -> MutexGuard<'_, BlockCache> {
+        self.block_cache
+            .lock()
+            .unwrap_or_else(|error| error.into_inner())
+    }
 
-        Ok(storage)
+    /// Hit & miss counters of block cache
+    pub fn block_cache_stats(&self) -> BlockCacheStats {
+        self.block_cache().stats()
     }
 
-    /// Open existing storage file
-    /// - Loads storage header
-    /// - Loads free blocks Set
-    pub fn open(file_path: String) -> Result<Storage, Error> {
-        let (file_writer, write_pointer) = Storage::open_file_writer(&file_path, false)?;
-        let (file_reader, read_pointer) = Storage::open_file_reader(&file_path)?;
+    /// Format version of storage file
+    /// - 0 for headerless files, which can be upgraded with `Storage::migrate`
+    pub fn format_version(&self) -> StorageFormatVersion {
+        self.header.version
+    }
 
-        // Initialize storage object
-        let mut storage = Storage {
-            header: StorageHeader::new(0),
-            free_blocks: BTreeSet::new(),
-            end_block_count: 0,
-            file_writer,
-            write_pointer,
-            file_reader,
-            read_pointer,
-        };
+    /// Device storage file is read from & written to
+    pub fn device(&self) -> &dyn BlockDevice {
+        self.device.as_ref()
+    }
 
-        // - read and update storage header from file
-        storage.get_storage_header()?;
+    /// Check if storage is opened read-only, by `Storage::open_read_only`
+    pub fn read_only(&self) -> bool {
+        self.read_only
+    }
 
-        // - read file and count
-        // -- total blocks - update self.end_block_count
-        // -- free blocks - update self.free_blocks
-        storage.read_storage_block_headers()?;
+    /// Check if storage has a journal, so `Storage::commit` can commit transactions
+    pub fn journaled(&self) -> bool {
+        self.journal.is_some()
+    }
 
-        Ok(storage)
+    /// Mode storage file is locked in
+    /// - None for storages on a given device, which are not locked
+    pub fn lock_mode(&self) -> Option<LockMode> {
+        self.lock.as_ref().map(|lock| lock.mode())
     }
-    // // ... ... ... ... ... ... ... ... ... ... ... ... ... ... ... ....
 
-    // ... ... ... ... ... ... ... ... ... ... ... ... ... ... ... ... ...
+    /// Number of blocks in storage file, used or free
+    /// - number of block indexes of application of a shadow paged storage
+    pub fn end_block_count(&self) -> BlockIndex {
+        match &self.shadow {
+            Some(shadow) => shadow.end_block_count(),
+            None => self.end_block_count,
+        }
+    }
 
-    // ... ... ... ... ... . InMemory Logic Functions ... ... ... ... ....
+    /// Check if block is within storage file and free, without reading it from file
+    pub fn is_free_block(&self, block_index: BlockIndex) -> bool {
+        match &self.shadow {
+            Some(shadow) => shadow.free_blocks().contains(&block_index),
+            None => self.block_exists(block_index) && self.free_blocks.contains(&block_index),
+        }
+    }
 
-    /// check if block is within storage file, without reading it from file (in memory)
-    fn block_exists(&mut self, block_index: BlockIndex) -> bool {
-        block_index < self.end_block_count
+    /// Codec block data of storage is compressed with
+    pub fn compression(&self) -> BlockCompression {
+        self.header.compression()
     }
 
-    /// Check if block is empty, without reading it from file (in memory)
-    fn block_empty(&mut self, block_index: BlockIndex) -> bool {
-        if self.block_exists(block_index) {
-            self.free_blocks.contains(&block_index)
-        } else {
-            true
+    /// Check if storage is shadow paged, see `Storage::checkpoint`
+    pub fn shadow_paging(&self) -> bool {
+        self.header.shadow_paging()
+    }
+
+    /// Replace allocator picking blocks new data is written to, set by allocation policy on open
+    pub fn set_block_allocator(&mut self, block_allocator: Box<dyn BlockAllocator>) {
+        self.block_allocator = block_allocator;
+    }
+
+    /// Space usage & I/O counters of storage
+    /// - data in used blocks is summed up as blocks are written & deleted, and persisted in free block map.
+    ///   Header of every used block is read only if a write or delete failed midway.
+    pub fn stats(&self) -> Result<StorageStats, Error> {
+        let space = self.space()?;
+        let mut stats = StorageStats {
+            block_len: self.header.block_len,
+            total_blocks: self.end_block_count,
+            used_blocks: self.end_block_count - self.free_blocks.len() as BlockIndex,
+            free_blocks: self.free_blocks.len() as BlockIndex,
+            logical_size: space.logical_size,
+            physical_size: space.physical_size,
+            block_cache: self.block_cache_stats(),
+            ..Default::default()
+        };
+
+        // - runs of adjacent free blocks
+        let mut run_len = 0;
+        let mut previous_free_block_index = None;
+        for block_index in self.free_blocks.iter() {
+            if previous_free_block_index.is_some_and(|previous| previous + 1 == *block_index) {
+                run_len += 1;
+            } else {
+                stats.free_runs += 1;
+                run_len = 1;
+            }
+            stats.largest_free_run = stats.largest_free_run.max(run_len);
+            previous_free_block_index = Some(*block_index);
         }
+
+        // - data in used blocks, before & after compression
+        let data_totals = match self.data_totals {
+            Some(data_totals) => data_totals,
+            None => self.read_data_totals()?,
+        };
+        data_totals.fill(&mut stats);
+
+        self.io_counters.fill(&mut stats);
+        Ok(stats)
     }
 
-    // ... ... ... ... ... ... ... ... ... ... ... ... ... ... ... ... ...
+    /// Logical & physical size of storage file
+    pub fn space(&self) -> Result<StorageSpace, Error> {
+        let logical_size = match self.device.size() {
+            Ok(size) => size,
+            Err(result_error) => return Err(storage_errors::space_read_size(result_error)),
+        };
+        let physical_size = match self.device.allocated_size() {
+            Ok(size) => size,
+            Err(result_error) => return Err(storage_errors::space_read_size(result_error)),
+        };
+        Ok(StorageSpace {
+            logical_size,
+            physical_size,
+        })
+    }
+    //  ... ... ... ... ... ... Static Functions ... ... ... ... ... ... .
 
-    // ... ... ... ... ... ... File IO Functions ... ... ... ... ... ... .
+    /// Open storage file in given path for reading & writing, with given backend
+    /// - creates a new file if it does not exist
+    /// - truncate: if true, truncates the file to 0 bytes
+    /// - truncate: if false, no modification to the file
+    /// - existing storage file with a segment directory is opened with its segments
+    /// - segmented storages are created with `create_segmented_device` instead
+    fn open_file_device(
+        file_path: &str,
+        truncate: bool,
+        backend: StorageBackend,
+    ) -> Result<Box<dyn BlockDevice>, Error> {
+        let segment_dir_path = Storage::segment_dir_path(file_path);
+        let device_result = if !truncate && std::path::Path::new(&segment_dir_path).is_dir() {
+            SegmentedBlockDevice::open(file_path, &segment_dir_path)
+                .map(|device| Box::new(device) as Box<dyn BlockDevice>)
+        } else {
+            match backend {
+                StorageBackend::File | StorageBackend::Segmented { .. } => {
+                    FileBlockDevice::open(file_path, truncate)
+                        .map(|device| Box::new(device) as Box<dyn BlockDevice>)
+                }
+                StorageBackend::Mmap => MmapBlockDevice::open(file_path, truncate)
+                    .map(|device| Box::new(device) as Box<dyn BlockDevice>),
+            }
+        };
+        if let Err(result_error) = device_result {
+            return Err(storage_errors::open_file_device_open_file(result_error));
+        }
+        Ok(device_result.unwrap())
+    }
 
-    /// Set storage header in storage file
-    /// - Write storage header to file
-    /// - NOTE: This can only be used once when creating a new storage file
-    /// - returns: write pointer
-    fn set_storage_header(&mut self) -> Result<usize, Error> {
-        use std::io::prelude::*;
-        let file = &mut self.file_writer;
-        // Write storage header to file
-        let header_bytes = self.header.to_bytes();
-        // -- seek writer pointer to beginning of file
-        let ptr_seek_result = file.seek(std::io::SeekFrom::Start(0));
-        if let Err(result_error) = ptr_seek_result {
-            return Err(storage_errors::set_storage_header_seek_start(result_error));
-        }
-        // -- write storage header
-        self.write_pointer = ptr_seek_result.unwrap() as usize;
-        let write_result = file.write(&header_bytes);
-        // if write_result.is_err() {
-        //     return Err(storage_errors::set_storage_header_write_header(
-        //         write_result.unwrap_err(),
-        //     ));
-        // }
-        if let Err(result_error) = write_result {
-            return Err(storage_errors::set_storage_header_write_header(
+    /// Open existing storage file in given path for reading only, with given backend
+    /// - storage file with a segment directory is opened with its segments
+    fn open_read_only_file_device(
+        file_path: &str,
+        backend: StorageBackend,
+    ) -> Result<Box<dyn BlockDevice>, Error> {
+        let segment_dir_path = Storage::segment_dir_path(file_path);
+        let device_result = if std::path::Path::new(&segment_dir_path).is_dir() {
+            SegmentedBlockDevice::open_read_only(file_path, &segment_dir_path)
+                .map(|device| Box::new(device) as Box<dyn BlockDevice>)
+        } else {
+            match backend {
+                StorageBackend::File | StorageBackend::Segmented { .. } => {
+                    FileBlockDevice::open_read_only(file_path)
+                        .map(|device| Box::new(device) as Box<dyn BlockDevice>)
+                }
+                StorageBackend::Mmap => MmapBlockDevice::open_read_only(file_path)
+                    .map(|device| Box::new(device) as Box<dyn BlockDevice>),
+            }
+        };
+        if let Err(result_error) = device_result {
+            return Err(storage_errors::open_read_only_file_device_open_file(
                 result_error,
             ));
         }
-        // -- verify write operation was successful
-        let write_size = write_result.unwrap();
-        if write_size != STORAGE_HEADER_SIZE {
-            return Err(storage_errors::set_storage_header_write_header_success(
-                write_size,
+        Ok(device_result.unwrap())
+    }
+
+    /// Create segmented device for a new storage file in given path, truncating the file
+    /// - each segment file holds given number of whole blocks
+    fn create_segmented_device(
+        file_path: &str,
+        header: &StorageHeader,
+        segment_block_count: BlockIndex,
+    ) -> Result<Box<dyn BlockDevice>, Error> {
+        let block_stride = (header.block_header_size() + header.block_len as usize) as u64;
+        let segment_len = match segment_block_count.checked_mul(block_stride) {
+            Some(segment_len) if segment_block_count > 0 => segment_len,
+            _ => {
+                return Err(storage_errors::new_invalid_segment_block_count(
+                    segment_block_count,
+                ))
+            }
+        };
+        let device_result = SegmentedBlockDevice::create(
+            file_path,
+            &Storage::segment_dir_path(file_path),
+            header.size() as u64,
+            segment_len,
+        );
+        if let Err(result_error) = device_result {
+            return Err(storage_errors::create_segmented_device_create_segments(
+                result_error,
             ));
         }
-        self.write_pointer += write_size;
-        Ok(self.write_pointer)
+        Ok(Box::new(device_result.unwrap()))
     }
 
-    /// Get storage header from storage file
-    /// - Read storage header from file
-    /// - update storage header in object
-    /// - returns: read pointer
-    fn get_storage_header(&mut self) -> Result<usize, Error> {
-        use std::io::prelude::*;
-        let file = &mut self.file_reader;
+    /// Directory of segment files, next to storage file in given path
+    /// - segment file `n` holds blocks from `n * segment_block_count`, and can be archived
+    ///   or moved once none of its blocks changes, its blocks fail to read while it is missing
+    pub fn segment_dir_path(file_path: &str) -> String {
+        format!("{}.segments", file_path)
+    }
 
-        // - Read storage header from file
-        // -- seek reader pointer to beginning of file
-        let ptr_seek_result = file.seek(std::io::SeekFrom::Start(0));
-        if let Err(result_error) = ptr_seek_result {
-            return Err(storage_errors::get_storage_header_seek_start(result_error));
+    /// Check storage header & segment directory next to storage file agree on storage being segmented
+    /// - a storage file without its segments, or segments left by an overwritten storage, is not opened
+    fn check_segment_dir(&self, file_path: &str) -> Result<(), Error> {
+        let segment_dir_exists =
+            std::path::Path::new(&Storage::segment_dir_path(file_path)).is_dir();
+        if self.header.segmented() != segment_dir_exists {
+            return Err(storage_errors::check_segment_dir_mismatch(
+                file_path,
+                self.header.segmented(),
+            ));
         }
-        // -- read storage header
-        let mut header_bytes = [0u8; STORAGE_HEADER_SIZE];
-        self.read_pointer = ptr_seek_result.unwrap() as usize;
-        let read_result = file.read(&mut header_bytes);
-        if let Err(result_error) = read_result {
-            return Err(storage_errors::get_storage_header_read_header(result_error));
+        Ok(())
+    }
+
+    /// Storage header of a new storage, with features picked by given options
+    /// - returns error if block length is 0, or too large for its data size to carry compressed flag
+    fn new_storage_header(
+        block_len: u32,
+        options: &StorageOptions,
+    ) -> Result<StorageHeader, Error> {
+        if block_len == 0 {
+            return Err(storage_errors::new_invalid_block_len(block_len));
+        }
+        let mut header =
+            StorageHeader::new(block_len, options.block_checksum, options.block_index_width);
+        if options.shadow_paging {
+            header.features |= STORAGE_FEATURE_SHADOW_PAGING;
+        }
+        match options.compression {
+            BlockCompression::None => {}
+            BlockCompression::Lz4 => header.features |= STORAGE_FEATURE_LZ4_COMPRESSION,
+            BlockCompression::Zstd => header.features |= STORAGE_FEATURE_ZSTD_COMPRESSION,
+        }
+        if options.compression != BlockCompression::None
+            && block_len as BlockDataSize
+                >= BlockHeader::compressed_flag(header.block_index_width())
+        {
+            return Err(storage_errors::new_block_len_too_large_to_compress(
+                block_len,
+            ));
+        }
+        Ok(header)
+    }
+
+    /// Storage object on given device, with no blocks loaded yet
+    fn init(
+        device: Box<dyn BlockDevice>,
+        header: StorageHeader,
+        options: &StorageOptions,
+    ) -> Storage {
+        Storage {
+            header,
+            free_blocks: BTreeSet::new(),
+            end_block_count: 0,
+            device,
+            write_pointer: 0,
+            read_pointer: AtomicUsize::new(0),
+            free_block_map: None,
+            block_cache: Mutex::new(BlockCache::new(options.block_cache_capacity)),
+            sync_policy: options.sync_policy,
+            unsynced_write_count: 0,
+            last_sync_instant: Instant::now(),
+            lock: None,
+            read_only: false,
+            root_tables: [None, None],
+            punch_holes: options.punch_holes,
+            block_allocator: options.allocation_policy.block_allocator(),
+            io_counters: IoCounters::default(),
+            data_totals: Some(DataTotals::default()),
+            quota: options.quota,
+            soft_quota: options.soft_quota,
+            quota_warning_hook: None,
+            journal: None,
+            shadow: None,
+        }
+    }
+
+    // // ... ... ... ... ... Storage Constructors ... ... ... ... ... .
+
+    /// Create new storage file
+    /// - Create/Overwrite new storage file in given path
+    /// - Initializes storage header
+    /// - Locks storage file exclusively, returns `storage_locked` if another storage holds it
+    pub fn new(file_path: String, block_len: u32) -> Result<Storage, Error> {
+        Storage::new_with_options(file_path, block_len, StorageOptions::default())
+    }
+
+    /// Create new storage file with given options
+    /// - Create/Overwrite new storage file in given path
+    /// - Initializes storage header
+    /// - Segments of an overwritten segmented storage are removed
+    pub fn new_with_options(
+        file_path: String,
+        block_len: u32,
+        options: StorageOptions,
+    ) -> Result<Storage, Error> {
+        let mut header = Storage::new_storage_header(block_len, &options)?;
+
+        // - lock before truncating, so a storage in use is never overwritten
+        let lock = StorageLock::acquire(&file_path, LockMode::Exclusive, options.lock_timeout)?;
+        let device = match options.backend {
+            StorageBackend::Segmented {
+                segment_block_count,
+            } => {
+                header.features |= STORAGE_FEATURE_SEGMENTED;
+                Storage::create_segmented_device(&file_path, &header, segment_block_count)?
+            }
+            _ => {
+                let segment_dir_path = Storage::segment_dir_path(&file_path);
+                if std::path::Path::new(&segment_dir_path).is_dir() {
+                    if let Err(result_error) = std::fs::remove_dir_all(&segment_dir_path) {
+                        return Err(storage_errors::new_remove_segments(result_error));
+                    }
+                }
+                Storage::open_file_device(&file_path, true, options.backend)?
+            }
+        };
+        let mut storage = Storage::new_with_header(device, header, &options)?;
+        storage.lock = Some(lock);
+
+        // - empty journal, a transaction left by an overwritten storage is dropped
+        let mut journal = Journal::open(&file_path, true)?;
+        journal.clear()?;
+        storage.journal = Some(journal);
+
+        // Create empty free block map
+        storage.free_block_map = Some(FreeBlockMap::create(
+            &file_path,
+            &BTreeSet::new(),
+            0,
+            DataTotals::default(),
+            storage.block_index_width(),
+        )?);
+
+        Ok(storage)
+    }
+
+    /// Create new storage in memory, lost on drop
+    /// - Useful for tests and temporary storages
+    /// - journal is kept in memory too
+    pub fn new_in_memory(block_len: u32, options: StorageOptions) -> Result<Storage, Error> {
+        Storage::new_with_devices(
+            Box::new(MemoryBlockDevice::new()),
+            Box::new(MemoryBlockDevice::new()),
+            block_len,
+            options,
+        )
+    }
+
+    /// Create new storage on given device
+    /// - Overwrites storage header at start of device
+    /// - No free block map is persisted, free blocks are counted on open
+    pub fn new_with_device(
+        device: Box<dyn BlockDevice>,
+        block_len: u32,
+        options: StorageOptions,
+    ) -> Result<Storage, Error> {
+        let header = Storage::new_storage_header(block_len, &options)?;
+        Storage::new_with_header(device, header, &options)
+    }
+
+    /// Create new storage on given device, with journal on given journal device
+    /// - Overwrites storage header at start of device, and clears journal device
+    pub fn new_with_devices(
+        device: Box<dyn BlockDevice>,
+        journal_device: Box<dyn BlockDevice>,
+        block_len: u32,
+        options: StorageOptions,
+    ) -> Result<Storage, Error> {
+        let mut storage = Storage::new_with_device(device, block_len, options)?;
+        let mut journal = Journal::new(journal_device);
+        journal.clear()?;
+        storage.journal = Some(journal);
+        Ok(storage)
+    }
+
+    /// Create new storage on given device, with given storage header
+    fn new_with_header(
+        device: Box<dyn BlockDevice>,
+        header: StorageHeader,
+        options: &StorageOptions,
+    ) -> Result<Storage, Error> {
+        // Initialize storage object
+        let mut storage = Storage::init(device, header, options);
+        if storage.header.shadow_paging() {
+            storage.shadow = Some(ShadowPages::default());
+        }
+
+        // Write storage header to file
+        storage.set_storage_header()?;
+        storage.sync_after_write()?;
+
+        Ok(storage)
+    }
+
+    /// Open existing storage file
+    /// - Loads storage header
+    /// - Loads free blocks Set
+    /// - Locks storage file exclusively, returns `storage_locked` if another storage holds it
+    /// - returns error if file is not a storage file, or its format version is too new
+    pub fn open(file_path: String) -> Result<Storage, Error> {
+        Storage::open_with_options(file_path, StorageOptions::default())
+    }
+
+    /// Open existing storage file with given options
+    /// - Options recorded in storage header are loaded from file, given values are ignored
+    pub fn open_with_options(file_path: String, options: StorageOptions) -> Result<Storage, Error> {
+        let lock = StorageLock::acquire(&file_path, LockMode::Exclusive, options.lock_timeout)?;
+        let device = Storage::open_file_device(&file_path, false, options.backend)?;
+
+        // Initialize storage object
+        let mut storage = Storage::init(
+            device,
+            StorageHeader::new(0, false, BlockIndexWidth::U32),
+            &options,
+        );
+        storage.lock = Some(lock);
+
+        // - read and update storage header from file
+        storage.get_storage_header()?;
+        storage.check_segment_dir(&file_path)?;
+        storage.journal = Some(Journal::open(&file_path, false)?);
+
+        // - load free blocks from free block map, if storage is clean & map matches storage file length
+        // -- strict mode does not trust the map
+        let end_block_count = storage.end_block_count_from_file_len()?;
+        if !options.strict && storage.header.clean {
+            if let Some((free_block_map, free_blocks, data_totals)) =
+                FreeBlockMap::load(&file_path, end_block_count, storage.block_index_width())
+            {
+                storage.end_block_count = end_block_count;
+                storage.free_blocks = free_blocks;
+                storage.data_totals = Some(data_totals);
+                storage.free_block_map = Some(free_block_map);
+                storage.recover_journal()?;
+                storage.load_root_tables()?;
+                storage.load_shadow_pages()?;
+                return Ok(storage);
+            }
+        }
+
+        // - free block map is missing or stale, fallback to read file and count
+        // -- total blocks - update self.end_block_count
+        // -- free blocks - update self.free_blocks
+        // -- data totals - update self.data_totals
+        storage.read_storage_block_headers()?;
+
+        // - redo transaction committed to journal, before blocks it rewrites are verified
+        storage.recover_journal()?;
+
+        // - verify every used block in strict mode
+        if options.strict {
+            storage.verify_blocks()?;
+        }
+
+        // - rebuild free block map
+        storage.free_block_map = Some(FreeBlockMap::create(
+            &file_path,
+            &storage.free_blocks,
+            storage.end_block_count,
+            storage.data_totals.unwrap_or_default(),
+            storage.block_index_width(),
+        )?);
+
+        // - load roots & page tables
+        storage.load_root_tables()?;
+        storage.load_shadow_pages()?;
+
+        Ok(storage)
+    }
+
+    /// Open existing storage file for reading only
+    /// - Never opens the file for writing, nor creates or repairs it, so works on read-only mounts
+    /// - Locks storage file shared, returns `storage_locked` if a writer holds it
+    /// - Loads free blocks from free block map if it is up to date, else counts them from block headers
+    /// - `write_block`, `write_blocks` & `delete_block` return `storage_read_only`
+    pub fn open_read_only(file_path: String) -> Result<Storage, Error> {
+        Storage::open_read_only_with_options(file_path, StorageOptions::default())
+    }
+
+    /// Open existing storage file for reading only, with given options
+    /// - Options recorded in storage header are loaded from file, given values are ignored
+    pub fn open_read_only_with_options(
+        file_path: String,
+        options: StorageOptions,
+    ) -> Result<Storage, Error> {
+        let lock = StorageLock::acquire(&file_path, LockMode::Shared, options.lock_timeout)?;
+        let device = Storage::open_read_only_file_device(&file_path, options.backend)?;
+
+        // Initialize storage object
+        let mut storage = Storage::init(
+            device,
+            StorageHeader::new(0, false, BlockIndexWidth::U32),
+            &options,
+        );
+        storage.lock = Some(lock);
+        storage.read_only = true;
+
+        // - read and update storage header from file
+        storage.get_storage_header()?;
+        storage.check_segment_dir(&file_path)?;
+
+        // - a transaction committed to journal can not be redone without writing
+        if Journal::read_file(&file_path)?.is_some() {
+            return Err(storage_errors::open_read_only_pending_journal(&file_path));
+        }
+
+        // - load free blocks from free block map if storage is clean, without opening it for writing
+        let end_block_count = storage.end_block_count_from_file_len()?;
+        if !options.strict && storage.header.clean {
+            if let Some((free_blocks, data_totals)) =
+                FreeBlockMap::read(&file_path, end_block_count, storage.block_index_width())
+            {
+                storage.end_block_count = end_block_count;
+                storage.free_blocks = free_blocks;
+                storage.data_totals = Some(data_totals);
+                storage.load_root_tables()?;
+                storage.load_shadow_pages()?;
+                return Ok(storage);
+            }
+        }
+
+        // - free block map is missing or stale, fallback to read file and count
+        storage.read_storage_block_headers()?;
+
+        // - verify every used block in strict mode
+        if options.strict {
+            storage.verify_blocks()?;
+        }
+
+        // - load roots & page tables
+        storage.load_root_tables()?;
+        storage.load_shadow_pages()?;
+
+        Ok(storage)
+    }
+
+    /// Open existing storage on given device
+    /// - Loads storage header
+    /// - Counts free blocks from block headers
+    pub fn open_device(
+        device: Box<dyn BlockDevice>,
+        options: StorageOptions,
+    ) -> Result<Storage, Error> {
+        Storage::open_device_with_journal(device, None, options)
+    }
+
+    /// Open existing storage on given device, with journal on given journal device
+    /// - Redoes transaction committed to journal, if a crash cut its commit short
+    pub fn open_devices(
+        device: Box<dyn BlockDevice>,
+        journal_device: Box<dyn BlockDevice>,
+        options: StorageOptions,
+    ) -> Result<Storage, Error> {
+        Storage::open_device_with_journal(device, Some(Journal::new(journal_device)), options)
+    }
+
+    /// Open existing storage on given device, with given journal if any
+    fn open_device_with_journal(
+        device: Box<dyn BlockDevice>,
+        journal: Option<Journal>,
+        options: StorageOptions,
+    ) -> Result<Storage, Error> {
+        // Initialize storage object
+        let mut storage = Storage::init(
+            device,
+            StorageHeader::new(0, false, BlockIndexWidth::U32),
+            &options,
+        );
+        storage.journal = journal;
+
+        // - read and update storage header from file
+        storage.get_storage_header()?;
+
+        // - read file and count total & free blocks
+        storage.read_storage_block_headers()?;
+
+        // - redo transaction committed to journal
+        storage.recover_journal()?;
+
+        // - verify every used block in strict mode
+        if options.strict {
+            storage.verify_blocks()?;
+        }
+
+        // - load roots & page tables
+        storage.load_root_tables()?;
+        storage.load_shadow_pages()?;
+
+        Ok(storage)
+    }
+
+    /// Upgrade storage file in given path to current format version
+    /// - Headerless files of format version 0 get magic bytes, format version & feature flags
+    /// - Blocks are copied as is, after the new storage header
+    /// - Upgraded file is written next to the original file, then renamed over it.
+    ///   So a crash during migration leaves the original file untouched.
+    /// - Storage file stays locked till it is replaced, upgraded file is locked before it is renamed over it
+    /// - Directory is synced after the rename, so a migration that returns Ok survives a crash
+    /// - Upgraded file is removed if writing or renaming it fails
+    /// - returns: format version of the file before migration
+    pub fn migrate(file_path: String) -> Result<StorageFormatVersion, Error> {
+        let mut storage = Storage::open(file_path.clone())?;
+        let old_header_size = storage.header.size();
+        let old_version = storage.header.version;
+        if old_version == STORAGE_FORMAT_VERSION {
+            return Ok(old_version);
+        }
+        let new_header = StorageHeader::new(
+            storage.header.block_len,
+            storage.block_checksum(),
+            storage.block_index_width(),
+        );
+        // - keep storage file locked until it is replaced, nothing is written to it on drop
+        let _lock = storage.lock.take();
+        drop(storage);
+
+        // - write upgraded file next to original file, removed if that fails
+        // -- locked before it is written, so it is never unlocked once renamed over original file
+        let migrate_file_path = format!("{}.migrate", file_path);
+        let _migrate_lock = StorageLock::acquire(&migrate_file_path, LockMode::Exclusive, None)?;
+        let write_result = Storage::write_migrate_file(
+            &file_path,
+            &migrate_file_path,
+            &new_header,
+            old_header_size,
+        );
+        if let Err(error) = write_result {
+            let _ = std::fs::remove_file(&migrate_file_path);
+            return Err(error);
+        }
+
+        // - replace original file with upgraded file
+        if let Err(result_error) = std::fs::rename(&migrate_file_path, &file_path) {
+            let _ = std::fs::remove_file(&migrate_file_path);
+            return Err(storage_errors::migrate_replace_file(result_error));
+        }
+        // -- sync directory, else the rename may be lost on a crash and file reverts to old format
+        if let Err(result_error) = Storage::sync_parent_dir(&file_path) {
+            return Err(storage_errors::migrate_sync_dir(result_error));
+        }
+        Ok(old_version)
+    }
+
+    /// Write new storage header & blocks after old storage header to migrated file, and sync it
+    fn write_migrate_file(
+        file_path: &str,
+        migrate_file_path: &str,
+        new_header: &StorageHeader,
+        old_header_size: usize,
+    ) -> Result<(), Error> {
+        use std::io::prelude::*;
+        // - write new storage header to temporary file
+        let migrate_file_result = std::fs::File::create(migrate_file_path);
+        if let Err(result_error) = migrate_file_result {
+            return Err(storage_errors::migrate_write_header(result_error));
+        }
+        let mut migrate_file = migrate_file_result.unwrap();
+        if let Err(result_error) = migrate_file.write_all(&new_header.to_bytes()) {
+            return Err(storage_errors::migrate_write_header(result_error));
+        }
+
+        // - copy blocks after old storage header
+        let file_reader_result = std::fs::File::open(file_path);
+        if let Err(result_error) = file_reader_result {
+            return Err(storage_errors::migrate_copy_blocks(result_error));
+        }
+        let mut file_reader = file_reader_result.unwrap();
+        if let Err(result_error) =
+            file_reader.seek(std::io::SeekFrom::Start(old_header_size as u64))
+        {
+            return Err(storage_errors::migrate_copy_blocks(result_error));
+        }
+        if let Err(result_error) = std::io::copy(&mut file_reader, &mut migrate_file) {
+            return Err(storage_errors::migrate_copy_blocks(result_error));
+        }
+        if let Err(result_error) = migrate_file.sync_all() {
+            return Err(storage_errors::migrate_copy_blocks(result_error));
+        }
+        Ok(())
+    }
+
+    /// Sync directory holding file in given path, so a rename into it survives a crash
+    #[cfg(unix)]
+    fn sync_parent_dir(file_path: &str) -> std::io::Result<()> {
+        let dir_path = match std::path::Path::new(file_path).parent() {
+            Some(dir_path) if !dir_path.as_os_str().is_empty() => dir_path,
+            _ => std::path::Path::new("."),
+        };
+        std::fs::File::open(dir_path)?.sync_all()
+    }
+
+    #[cfg(not(unix))]
+    fn sync_parent_dir(_file_path: &str) -> std::io::Result<()> {
+        Ok(())
+    }
+    // // ... ... ... ... ... ... ... ... ... ... ... ... ... ... ... ....
+
+    // ... ... ... ... ... ... ... ... ... ... ... ... ... ... ... ... ...
+
+    // ... ... ... ... ... . InMemory Logic Functions ... ... ... ... ....
+
+    /// check if block is within storage file, without reading it from file (in memory)
+    fn block_exists(&self, block_index: BlockIndex) -> bool {
+        block_index < self.end_block_count
+    }
+
+    /// Offset of block in storage file
+    /// - block index must not be beyond `max_block_count`, so offset does not overflow
+    fn block_offset(&self, block_index: BlockIndex) -> usize {
+        self.header.size()
+            + block_index as usize
+                * (self.header.block_header_size() + self.header.block_len as usize)
+    }
+
+    /// Check if block is empty, without reading it from file (in memory)
+    fn block_empty(&self, block_index: BlockIndex) -> bool {
+        if self.block_exists(block_index) {
+            self.free_blocks.contains(&block_index)
+        } else {
+            true
+        }
+    }
+
+    // ... ... ... ... ... ... ... ... ... ... ... ... ... ... ... ... ...
+
+    // ... ... ... ... ... ... File IO Functions ... ... ... ... ... ... .
+
+    /// Set storage header in storage file
+    /// - Write storage header to file
+    /// - NOTE: This can only be used once when creating a new storage file
+    /// - returns: write pointer
+    fn set_storage_header(&mut self) -> Result<usize, Error> {
+        // Write storage header to start of file
+        let header_bytes = self.header.to_bytes();
+        let write_result = self.device.write_at(&header_bytes, 0);
+        if let Err(result_error) = write_result {
+            return Err(storage_errors::set_storage_header_write_header(
+                result_error,
+            ));
+        }
+        // -- verify write operation was successful
+        let write_size = write_result.unwrap();
+        if write_size != STORAGE_HEADER_SIZE {
+            return Err(storage_errors::set_storage_header_write_header_success(
+                write_size,
+            ));
+        }
+        self.write_pointer = write_size;
+        Ok(self.write_pointer)
+    }
+
+    /// Get storage header from storage file
+    /// - Read storage header from file
+    /// - update storage header in object
+    /// - returns: read pointer
+    fn get_storage_header(&mut self) -> Result<usize, Error> {
+        // - Read storage header from start of file
+        // -- read initial bytes, magic bytes or block_len of headerless format version 0
+        let mut header_bytes = [0u8; STORAGE_HEADER_SIZE];
+        let read_result = self
+            .device
+            .read_at(&mut header_bytes[..STORAGE_HEADER_V0_SIZE], 0);
+        if let Err(result_error) = read_result {
+            return Err(storage_errors::get_storage_header_read_header(result_error));
         }
         // -- verify read operation was successful
         let read_size = read_result.unwrap();
-        if read_size != STORAGE_HEADER_SIZE as usize {
+        if read_size != STORAGE_HEADER_V0_SIZE {
             return Err(storage_errors::get_storage_header_read_header_success(
                 read_size,
             ));
         }
         // -- update read pointer
-        self.read_pointer += read_size;
+        *self.read_pointer.get_mut() = read_size;
 
         // - parse storage header
-        let storage_header = StorageHeader::from_bytes(header_bytes);
+        let storage_header = if header_bytes[..STORAGE_HEADER_V0_SIZE] == STORAGE_MAGIC {
+            // -- read rest of storage header
+            let read_result = self.device.read_at(
+                &mut header_bytes[STORAGE_HEADER_V0_SIZE..],
+                STORAGE_HEADER_V0_SIZE as u64,
+            );
+            if let Err(result_error) = read_result {
+                return Err(storage_errors::get_storage_header_read_header(result_error));
+            }
+            // -- verify read operation was successful
+            let read_size = read_result.unwrap();
+            if read_size != STORAGE_HEADER_SIZE - STORAGE_HEADER_V0_SIZE {
+                return Err(storage_errors::get_storage_header_read_header_success(
+                    STORAGE_HEADER_V0_SIZE + read_size,
+                ));
+            }
+            // -- update read pointer
+            *self.read_pointer.get_mut() += read_size;
+            StorageHeader::from_bytes(header_bytes)?
+        } else {
+            let storage_header = StorageHeader::from_v0_bytes([
+                header_bytes[0],
+                header_bytes[1],
+                header_bytes[2],
+                header_bytes[3],
+            ]);
+            // -- headerless file with no capacity in blocks is not a storage file
+            if storage_header.block_len == 0 {
+                return Err(storage_errors::storage_header_invalid_magic(
+                    header_bytes[..STORAGE_HEADER_V0_SIZE].to_vec(),
+                ));
+            }
+            storage_header
+        };
 
         // - copy storage header to storage object
         self.header = storage_header;
 
+        // - any file without magic bytes parses as a headerless header, verify its blocks
+        if self.header.version == 0 {
+            self.verify_v0_storage_file()?;
+        }
+
         // - return read pointer
-        Ok(read_size)
+        Ok(*self.read_pointer.get_mut())
+    }
+
+    /// Verify a headerless file is a storage file of format version 0, before anything is written next to it
+    /// - every block header holds a data size within block length
+    /// - file ends at end of a block, or at end of data of its last block
+    /// - a block header torn at end of file is left to open, as left by a crash
+    /// - returns `not_storage_file` otherwise
+    fn verify_v0_storage_file(&self) -> Result<(), Error> {
+        let size_result = self.device.size();
+        if let Err(result_error) = size_result {
+            return Err(storage_errors::verify_v0_storage_file_read_file(
+                result_error,
+            ));
+        }
+        let file_len = size_result.unwrap();
+        let block_header_size = self.header.block_header_size() as u64;
+        let block_stride = block_header_size + self.header.block_len as u64;
+        let mut block_offset = self.header.size() as u64;
+        while block_offset < file_len {
+            // - block header torn at end of file
+            if file_len - block_offset < BLOCK_HEADER_SIZE as u64 {
+                break;
+            }
+            let mut block_header_bytes = [0u8; BLOCK_HEADER_SIZE];
+            let read_result = self.device.read_at(&mut block_header_bytes, block_offset);
+            if let Err(result_error) = read_result {
+                return Err(storage_errors::verify_v0_storage_file_read_file(
+                    result_error,
+                ));
+            }
+            let block_data_size = u32::from_le_bytes(block_header_bytes) as u64;
+            if block_data_size > self.header.block_len as u64 {
+                return Err(storage_errors::not_storage_file(format!(
+                    "data size {} at offset {} exceeds block length {}",
+                    block_data_size, block_offset, self.header.block_len
+                )));
+            }
+            // - last block is written up to end of its data
+            let block_end = block_offset + block_stride;
+            if block_end > file_len
+                && file_len != block_offset + block_header_size + block_data_size
+            {
+                return Err(storage_errors::not_storage_file(format!(
+                    "file length {} ends neither at a block nor at data of last block",
+                    file_len
+                )));
+            }
+            block_offset = block_end;
+        }
+        Ok(())
+    }
+
+    /// Count number of blocks in storage file from its length, without reading block headers
+    /// - last block may be shorter than block length, as it is written up to its data size
+    fn end_block_count_from_file_len(&self) -> Result<BlockIndex, Error> {
+        let size_result = self.device.size();
+        if let Err(result_error) = size_result {
+            return Err(storage_errors::end_block_count_from_file_len_read_metadata(
+                result_error,
+            ));
+        }
+        let file_len = size_result.unwrap() as usize;
+        let header_size = self.header.size();
+        if file_len <= header_size {
+            return Ok(0);
+        }
+        let block_stride = self.header.block_header_size() + self.header.block_len as usize;
+        Ok((file_len - header_size).div_ceil(block_stride) as BlockIndex)
     }
 
     /// Count number of blocks in storage file
     /// - update self.end_block_count
     /// - update self.free_blocks
+    /// - update self.data_totals
     /// - returns: read pointer
     fn read_storage_block_headers(&mut self) -> Result<usize, Error> {
-        use std::io::prelude::*;
-        let file = &mut self.file_reader;
-
         // - read file and count
         // -- total blocks - update self.end_block_count
         // -- free blocks - update self.free_blocks
+        // -- data in used blocks - update self.data_totals
         let mut free_blocks = BTreeSet::new();
-        // -- seek reader pointer to end of STORAGE_HEADER_SIZE - offset of first block
-        let ptr_seek_result = file.seek(std::io::SeekFrom::Start(STORAGE_HEADER_SIZE as u64));
-        if let Err(result_error) = ptr_seek_result {
-            return Err(
-                storage_errors::read_storage_block_headers_seek_1st_block_offset(result_error),
-            );
-        }
+        let mut data_totals = DataTotals::default();
         // -- traverse all blocks in file, untill end of file
         let mut block_index = 0;
         loop {
-            // - read block header
-            let mut block_header_bytes = [0u8; BLOCK_HEADER_SIZE];
-            let read_result = file.read(&mut block_header_bytes);
+            // - read block header, checksum if any is skipped with block data
+            let block_offset = self.block_offset(block_index);
+            let block_data_size_len = self.header.block_data_size_len();
+            let mut block_header_bytes = [0u8; WIDE_BLOCK_HEADER_SIZE];
+            let read_result = self.device.read_at(
+                &mut block_header_bytes[..block_data_size_len],
+                block_offset as u64,
+            );
             if let Err(result_error) = read_result {
                 return Err(
                     storage_errors::read_storage_block_headers_read_block_header(result_error),
@@ -371,39 +1796,64 @@ impl Storage {
                 // end of file reached
                 break;
             }
-            if read_size != BLOCK_HEADER_SIZE {
-                return Err(
-                    storage_errors::read_storage_block_headers_read_block_header_success(read_size),
-                );
+            if read_size != block_data_size_len {
+                // -- block header torn at end of file, by a crash while appending the block
+                // -- truncate torn bytes, so the block can be written again
+                // -- read-only storage leaves them, torn block is not counted
+                let size_result = self.device.size();
+                if let Err(result_error) = size_result {
+                    return Err(
+                        storage_errors::read_storage_block_headers_truncate_torn_block(
+                            result_error,
+                        ),
+                    );
+                }
+                if size_result.unwrap() != (block_offset + read_size) as u64 {
+                    return Err(
+                        storage_errors::read_storage_block_headers_read_block_header_success(
+                            read_size,
+                        ),
+                    );
+                }
+                if self.read_only {
+                    break;
+                }
+                self.mark_dirty()?;
+                if let Err(result_error) = self.device.set_size(block_offset as u64) {
+                    return Err(
+                        storage_errors::read_storage_block_headers_truncate_torn_block(
+                            result_error,
+                        ),
+                    );
+                }
+                break;
             }
             // -- update read pointer
-            self.read_pointer += read_size;
+            *self.read_pointer.get_mut() = block_offset + read_size;
             // -- parse block header
-            let block_header = BlockHeader::from_bytes(block_header_bytes);
+            let block_header = self
+                .header
+                .parse_block_header(&block_header_bytes[..block_data_size_len]);
+            // -- block can not hold more data than its capacity, unless file is corrupt or foreign
+            if block_header.block_data_size > self.header.block_len as BlockDataSize {
+                return Err(
+                    storage_errors::read_storage_block_headers_invalid_block_data_size(
+                        block_index,
+                        block_header.block_data_size,
+                    ),
+                );
+            }
 
             // - check if block is free
             if block_header.block_data_size == 0 {
                 // -- add block to free blocks
                 free_blocks.insert(block_index);
+            } else {
+                // -- add data of used block to totals
+                data_totals.add(&self.block_data_totals(block_index, &block_header)?);
             }
             // -- increment block index
             block_index += 1;
-
-            // - seek reader pointer to end of block
-            let ptr_seek_result =
-                file.seek(std::io::SeekFrom::Current(self.header.block_len as i64));
-            if let Err(result_error) = ptr_seek_result {
-                return Err(storage_errors::read_storage_block_headers_seek_next_block(
-                    result_error,
-                ));
-            }
-            let ptr_seek_result = ptr_seek_result.unwrap() as usize;
-            self.read_pointer = ptr_seek_result;
-            // -- verify seek operation was successful
-            if ptr_seek_result != self.read_pointer {
-                // end of file reached
-                break;
-            }
         }
 
         // - update end block count
@@ -412,62 +1862,120 @@ impl Storage {
         // - update free blocks
         self.free_blocks = free_blocks;
 
+        // - update data totals
+        self.data_totals = Some(data_totals);
+
         // - return
-        Ok(self.read_pointer)
+        Ok(*self.read_pointer.get_mut())
     }
 
     /// Read block data from storage file
+    /// - served from block cache if cached, read_pointer is unchanged then
     /// - return (read_pointer, block_data)
-    pub fn read_block(&mut self, block_index: BlockIndex) -> Result<(usize, Vec<u8>), Error> {
+    /// - takes shared reference, so many threads can read at once
+    pub fn read_block(&self, block_index: BlockIndex) -> Result<(usize, Vec<u8>), Error> {
+        if let Some(shadow) = &self.shadow {
+            return match shadow.physical(block_index) {
+                Some(physical_block_index) => self.read_physical_block(physical_block_index),
+                None => {
+                    self.io_counters.count_read();
+                    Ok((self.read_pointer.load(Ordering::Relaxed), Vec::new()))
+                }
+            };
+        }
+        self.read_physical_block(block_index)
+    }
+
+    /// Read block of storage file, bypassing page tables of a shadow paged storage
+    fn read_physical_block(&self, block_index: BlockIndex) -> Result<(usize, Vec<u8>), Error> {
+        self.io_counters.count_read();
         if self.block_empty(block_index) {
             // return current read_pointer and empty vector
-            return Ok((self.read_pointer as usize, Vec::new()));
+            return Ok((self.read_pointer.load(Ordering::Relaxed), Vec::new()));
         }
-        use std::io::prelude::*;
-        let block_length = self.header.block_len;
-        let block_offset: usize = STORAGE_HEADER_SIZE
-            + block_index as usize * (BLOCK_HEADER_SIZE + block_length as usize);
-
-        // - seek reader to block offset
-        let seek_result = self
-            .file_reader
-            .seek(std::io::SeekFrom::Start(block_offset as u64));
-        if let Err(result_error) = seek_result {
-            return Err(storage_errors::read_block_seek_block_offset(result_error));
-        }
-        // -- verify seek operation was successful
-        let seek_position = seek_result.unwrap() as usize;
-        if seek_position != block_offset {
-            return Err(storage_errors::read_block_seek_block_offset_success(
-                seek_position,
-            ));
+        if let Some(block_data) = self.block_cache().get(block_index) {
+            return Ok((self.read_pointer.load(Ordering::Relaxed), block_data));
         }
-        self.read_pointer = seek_position;
 
-        // - read block data length from inital 4 bytes
-        let block_data_size_bytes = &mut [0u8; 4];
-        let read_result = self.file_reader.read(block_data_size_bytes);
+        // - read block from file
+        let (read_pointer, block_data) = self.read_block_from_device(block_index)?;
+        self.read_pointer.store(read_pointer, Ordering::Relaxed);
+
+        // - cache block data
+        self.block_cache().put(block_index, &block_data);
+
+        // - return read_pointer and block_data
+        Ok((read_pointer, block_data))
+    }
+
+    /// Read block data from storage file, bypassing block cache
+    /// - verify block data size & checksum
+    /// - return (read_pointer, block_data)
+    fn read_block_from_device(&self, block_index: BlockIndex) -> Result<(usize, Vec<u8>), Error> {
+        let block_offset = self.block_offset(block_index);
+        let mut read_pointer = block_offset;
+
+        // - read block data length from inital 4 or 8 bytes
+        let block_data_size_len = self.header.block_data_size_len();
+        let block_data_size_bytes = &mut [0u8; WIDE_BLOCK_HEADER_SIZE];
+        let read_result = self.device.read_at(
+            &mut block_data_size_bytes[..block_data_size_len],
+            read_pointer as u64,
+        );
         if let Err(result_error) = read_result {
             return Err(storage_errors::read_block_read_block_header(result_error));
         }
         // -- verify read operation was successful
         let read_size = read_result.unwrap();
-        if read_size != BLOCK_HEADER_SIZE {
+        if read_size != block_data_size_len {
             return Err(storage_errors::read_block_read_block_header_success(
                 read_size,
             ));
         }
-        self.read_pointer += read_size;
-        let block_header = BlockHeader::from_bytes(*block_data_size_bytes);
-
+        read_pointer += read_size;
+        let block_header = self
+            .header
+            .parse_block_header(&block_data_size_bytes[..block_data_size_len]);
+        // -- block can not hold more data than its capacity, unless file is corrupt
+        if block_header.block_data_size > self.header.block_len as BlockDataSize {
+            return Err(storage_errors::read_block_invalid_block_data_size(
+                block_index,
+                block_header.block_data_size,
+            ));
+        }
+
+        // - read block checksum if enabled
+        let block_checksum = if self.header.block_checksum() {
+            let block_checksum_bytes = &mut [0u8; BLOCK_CHECKSUM_SIZE];
+            let read_result = self
+                .device
+                .read_at(block_checksum_bytes, read_pointer as u64);
+            if let Err(result_error) = read_result {
+                return Err(storage_errors::read_block_read_block_checksum(result_error));
+            }
+            // -- verify read operation was successful
+            let read_size = read_result.unwrap();
+            if read_size != BLOCK_CHECKSUM_SIZE {
+                return Err(storage_errors::read_block_read_block_checksum_success(
+                    read_size,
+                ));
+            }
+            read_pointer += read_size;
+            Some(BlockChecksum::from_le_bytes(*block_checksum_bytes))
+        } else {
+            None
+        };
+
         // - read block data to vec
         let mut block_data = vec![0u8; block_header.block_data_size as usize];
-        let read_result = self.file_reader.read(&mut block_data[..]);
+        let read_result = self
+            .device
+            .read_at(&mut block_data[..], read_pointer as u64);
         if let Err(result_error) = read_result {
             return Err(storage_errors::read_block_read_block_data(result_error));
         }
         let read_size = read_result.unwrap();
-        self.read_pointer += read_size;
+        read_pointer += read_size;
 
         // - verify read operation was successful
         if read_size != block_header.block_data_size as usize {
@@ -476,114 +1984,610 @@ impl Storage {
             ));
         }
 
-        // - return read_pointer and block_data
-        Ok((self.read_pointer, block_data))
+        // - verify block checksum
+        if let Some(expected_checksum) = block_checksum {
+            let actual_checksum = crc32c(&block_data);
+            if actual_checksum != expected_checksum {
+                return Err(storage_errors::read_block_checksum_mismatch(
+                    block_index,
+                    expected_checksum,
+                    actual_checksum,
+                ));
+            }
+        }
+        self.io_counters
+            .count_bytes_read(read_pointer - block_offset);
+
+        // - decompress block data
+        if block_header.compressed {
+            let compression = self.header.compression();
+            return match compression.decompress(&block_data, self.header.block_len) {
+                Some(block_data) => Ok((read_pointer, block_data)),
+                None => Err(storage_errors::read_block_decompress(
+                    block_index,
+                    compression,
+                )),
+            };
+        }
+
+        Ok((read_pointer, block_data))
+    }
+
+    /// Read data size from header of block in storage file, bypassing block cache
+    /// - size before compression for a compressed block
+    fn read_block_data_size(&self, block_index: BlockIndex) -> Result<BlockDataSize, Error> {
+        let block_header = self.read_block_header(block_index)?;
+        if block_header.compressed {
+            return self.read_compressed_data_size(block_index);
+        }
+        Ok(block_header.block_data_size)
+    }
+
+    /// Read header of block in storage file, bypassing block cache
+    fn read_block_header(&self, block_index: BlockIndex) -> Result<BlockHeader, Error> {
+        let block_data_size_len = self.header.block_data_size_len();
+        let block_header_bytes = &mut [0u8; WIDE_BLOCK_HEADER_SIZE];
+        let read_result = self.device.read_at(
+            &mut block_header_bytes[..block_data_size_len],
+            self.block_offset(block_index) as u64,
+        );
+        if let Err(result_error) = read_result {
+            return Err(storage_errors::read_block_data_size_read_block_header(
+                result_error,
+            ));
+        }
+        // -- verify read operation was successful
+        let read_size = read_result.unwrap();
+        if read_size != block_data_size_len {
+            return Err(storage_errors::read_block_data_size_read_block_header_success(read_size));
+        }
+        Ok(self
+            .header
+            .parse_block_header(&block_header_bytes[..block_data_size_len]))
+    }
+
+    /// Read size of data before compression, stored ahead of compressed data of a compressed block
+    fn read_compressed_data_size(&self, block_index: BlockIndex) -> Result<BlockDataSize, Error> {
+        let data_size_bytes = &mut [0u8; COMPRESSED_DATA_SIZE_LEN];
+        let read_result = self.device.read_at(
+            data_size_bytes,
+            (self.block_offset(block_index) + self.header.block_header_size()) as u64,
+        );
+        if let Err(result_error) = read_result {
+            return Err(storage_errors::read_compressed_data_size_read_data_size(
+                result_error,
+            ));
+        }
+        // -- verify read operation was successful
+        let read_size = read_result.unwrap();
+        if read_size != COMPRESSED_DATA_SIZE_LEN {
+            return Err(
+                storage_errors::read_compressed_data_size_read_data_size_success(read_size),
+            );
+        }
+        Ok(compressed_data_size(data_size_bytes).unwrap_or_default() as BlockDataSize)
+    }
+
+    /// Totals of data in block with given header, data size before compression read for a compressed block
+    fn block_data_totals(
+        &self,
+        block_index: BlockIndex,
+        block_header: &BlockHeader,
+    ) -> Result<DataTotals, Error> {
+        let data_size = if block_header.compressed {
+            self.read_compressed_data_size(block_index)?
+        } else {
+            block_header.block_data_size
+        };
+        Ok(DataTotals::of_block(
+            data_size,
+            block_header.block_data_size,
+            block_header.compressed,
+        ))
+    }
+
+    /// Totals of data in a block about to be overwritten or deleted, taken off running totals
+    /// - read from block header, unless block is free or running totals are unknown anyway
+    fn used_block_data_totals(&self, block_index: BlockIndex) -> Result<DataTotals, Error> {
+        if self.data_totals.is_none() || self.block_empty(block_index) {
+            return Ok(DataTotals::default());
+        }
+        let block_header = self.read_block_header(block_index)?;
+        self.block_data_totals(block_index, &block_header)
+    }
+
+    /// Sum up data in used blocks from header of each used block
+    fn read_data_totals(&self) -> Result<DataTotals, Error> {
+        let mut data_totals = DataTotals::default();
+        for block_index in 0..self.end_block_count {
+            if self.free_blocks.contains(&block_index) {
+                continue;
+            }
+            let block_header = self.read_block_header(block_index)?;
+            data_totals.add(&self.block_data_totals(block_index, &block_header)?);
+        }
+        Ok(data_totals)
+    }
+
+    /// Iterate over every block in storage, in ascending order of index
+    /// - yields (block_index, block_data), block data of a free block is empty
+    /// - blocks of root tables are skipped, as in `Storage::used_application_blocks`
+    /// - blocks are read bypassing block cache, so a scan does not evict cached blocks
+    pub fn iter_blocks(&self) -> BlockIter<'_> {
+        BlockIter::new(self, false)
+    }
+
+    /// Iterate over used blocks in storage, in ascending order of index
+    /// - yields (block_index, block_data), free blocks are skipped without reading them
+    /// - blocks of root tables are skipped, as in `Storage::used_application_blocks`
+    /// - blocks are read bypassing block cache, so a scan does not evict cached blocks
+    pub fn iter_used_blocks(&self) -> BlockIter<'_> {
+        BlockIter::new(self, true)
+    }
+
+    /// Iterate over headers of every block in storage, in ascending order of index
+    /// - yields (block_index, block_data_size) as read from block header, 0 for a free block
+    /// - data size of a compressed block is its size before compression
+    /// - blocks of root tables are skipped, as in `Storage::used_application_blocks`
+    pub fn iter_block_headers(&self) -> BlockHeaderIter<'_> {
+        BlockHeaderIter::new(self)
+    }
+
+    /// Block of storage file holding block with given index, None if block is free
+    /// - block index of application of a shadow paged storage is mapped through its page tables
+    fn physical_block_index(&self, block_index: BlockIndex) -> Option<BlockIndex> {
+        match &self.shadow {
+            Some(shadow) => shadow.physical(block_index),
+            None if self.block_empty(block_index) => None,
+            None => Some(block_index),
+        }
+    }
+
+    /// Blocks of root tables among block indexes of application
+    /// - none in a shadow paged storage, its root tables are outside its page tables
+    fn application_root_table_blocks(&self) -> BTreeSet<BlockIndex> {
+        match &self.shadow {
+            Some(_) => BTreeSet::new(),
+            None => self.root_table_blocks(),
+        }
+    }
+
+    /// Read every used block from storage file, to verify its data size & checksum
+    fn verify_blocks(&self) -> Result<(), Error> {
+        for block_index in 0..self.end_block_count {
+            if !self.free_blocks.contains(&block_index) {
+                self.read_block_from_device(block_index)?;
+            }
+        }
+        Ok(())
     }
 
     /// Write block data to storage file
+    /// - block of a shadow paged storage is written to a fresh block of storage file
     /// - return write_pointer
     pub fn write_block(&mut self, block_index: BlockIndex, data: &[u8]) -> Result<usize, Error> {
-        use std::io::prelude::*;
-        let block_length = self.header.block_len;
-        let block_offset = STORAGE_HEADER_SIZE
-            + block_index as usize * (BLOCK_HEADER_SIZE + block_length as usize);
-
-        // - seek writer to block offset
-        let seek_result = self
-            .file_writer
-            .seek(std::io::SeekFrom::Start(block_offset as u64));
-        if let Err(result_error) = seek_result {
-            return Err(storage_errors::write_block_seek_block_offset(result_error));
-        }
-        // -- verify seek operation was successful
-        let seek_position = seek_result.unwrap() as usize;
-        if seek_position != block_offset {
-            return Err(storage_errors::write_block_seek_block_offset_success(
-                seek_position,
+        if self.shadow.is_some() {
+            return self.write_shadow_blocks("write_block", &[(block_index, data)]);
+        }
+        self.write_physical_block(block_index, data)
+    }
+
+    /// Write block of storage file, bypassing page tables of a shadow paged storage
+    fn write_physical_block(
+        &mut self,
+        block_index: BlockIndex,
+        data: &[u8],
+    ) -> Result<usize, Error> {
+        if self.read_only {
+            return Err(storage_errors::storage_read_only("write_block"));
+        }
+        // - data must fit in the block, else it would overwrite next block
+        if data.len() > self.header.block_len as usize {
+            return Err(storage_errors::write_block_data_too_large(
+                block_index,
+                data.len(),
+                self.header.block_len,
+            ));
+        }
+        // - block index must fit in file
+        if block_index >= self.max_block_count() {
+            return Err(storage_errors::write_block_index_out_of_range(
+                block_index,
+                self.max_block_count(),
             ));
         }
-        self.write_pointer = seek_position;
+        // - storage must not grow past its quota
+        if !self.within_quota(block_index) {
+            return Err(storage_errors::storage_full(
+                "write_block",
+                block_index,
+                self.quota_block_count().unwrap_or_default(),
+            ));
+        }
+        let block_offset = self.block_offset(block_index);
+        let previous_end_block_count = self.end_block_count;
+
+        // - drop stale block data from block cache, in case write fails midway
+        self.block_cache().remove(block_index);
+
+        // - running totals are unknown till block is written, data it held is taken off then
+        let previous_block_totals = self.used_block_data_totals(block_index)?;
+        let data_totals = self.data_totals.take();
+
+        // - mark storage dirty & block used in free block map, before it is written
+        self.mark_dirty()?;
+        if self.block_empty(block_index) {
+            if let Some(free_block_map) = &mut self.free_block_map {
+                free_block_map.mark_used(block_index)?;
+            }
+        }
+        self.write_pointer = block_offset;
+
+        // - compress block data if storage is compressed, stored raw where that is smaller
+        let compressed_data = self.header.compression().compress(data);
+        let (block_header, stored_data) = match &compressed_data {
+            Some(compressed_data) => (
+                BlockHeader::new_compressed(compressed_data.len() as BlockDataSize),
+                &compressed_data[..],
+            ),
+            None => (BlockHeader::new(data.len() as BlockDataSize), data),
+        };
 
         // - Write Block Header
-        // -- write block header to inital BLOCK_HEADER_SIZE bytes
-        let block_header = BlockHeader::new(data.len() as BlockLength);
-        let write_result = self.file_writer.write(&block_header.to_bytes());
+        // -- write block header to inital 4 or 8 bytes
+        let write_result = self.device.write_at(
+            &block_header.to_bytes(self.header.block_index_width()),
+            self.write_pointer as u64,
+        );
         if let Err(result_error) = write_result {
             return Err(storage_errors::write_block_write_block_header(result_error));
         }
         let write_size = write_result.unwrap();
         self.write_pointer += write_size;
         // -- verify write operation was successful
-        if write_size != BLOCK_HEADER_SIZE {
+        if write_size != self.header.block_data_size_len() {
             return Err(storage_errors::write_block_write_block_header_success(
                 write_size,
             ));
         }
 
+        // - Write Block Checksum
+        // -- write checksum of block data after block header if enabled
+        if self.header.block_checksum() {
+            let block_checksum: BlockChecksum = crc32c(stored_data);
+            let write_result = self
+                .device
+                .write_at(&block_checksum.to_le_bytes(), self.write_pointer as u64);
+            if let Err(result_error) = write_result {
+                return Err(storage_errors::write_block_write_block_checksum(
+                    result_error,
+                ));
+            }
+            let write_size = write_result.unwrap();
+            self.write_pointer += write_size;
+            // -- verify write operation was successful
+            if write_size != BLOCK_CHECKSUM_SIZE {
+                return Err(storage_errors::write_block_write_block_checksum_success(
+                    write_size,
+                ));
+            }
+        }
+
         // - Write Block Data
         // -- write block data to file
-        let write_result = self.file_writer.write(data);
+        let write_result = self.device.write_at(stored_data, self.write_pointer as u64);
         if let Err(result_error) = write_result {
             return Err(storage_errors::write_block_write_block_data(result_error));
         }
         let write_size = write_result.unwrap();
         self.write_pointer += write_size;
         // -- verify write operation was successful
-        if write_size != data.len() {
+        if write_size != stored_data.len() {
             return Err(storage_errors::write_block_write_block_data_success(
                 write_size,
             ));
         }
 
-        // - update free_blocks map
+        // - update free_blocks map & data totals
         self.free_blocks.remove(&block_index);
+        self.data_totals = data_totals.map(|mut data_totals| {
+            data_totals.subtract(&previous_block_totals);
+            data_totals.add(&DataTotals::of_block(
+                data.len() as u64,
+                stored_data.len() as u64,
+                block_header.compressed,
+            ));
+            data_totals
+        });
+        self.io_counters
+            .count_writes(1, self.write_pointer - block_offset);
+
+        // - update block cache, write goes through to file
+        self.block_cache().put(block_index, data);
 
-        // - update max_block_index
+        // - update max_block_index, blocks skipped in between are free
         if block_index >= self.end_block_count {
+            self.free_blocks.extend(self.end_block_count..block_index);
             self.end_block_count = block_index + 1;
         }
+        self.warn_soft_quota(previous_end_block_count);
+
+        // - release pages in unused tail of compressed block
+        if block_header.compressed {
+            self.punch_compressed_tail(block_index, stored_data.len())?;
+        }
+
+        // - sync if due as per sync policy
+        self.sync_after_write()?;
+
+        // - return write pointer
+        Ok(self.write_pointer)
+    }
+
+    /// Punch hole into unused tail of a compressed block, so compression saves disk space
+    /// - whole pages after stored data of the block only, pages shared with its data or next block are kept.
+    ///   So blocks of about a page or less save no disk space.
+    /// - last block of storage file is skipped, the file ends at its data
+    fn punch_compressed_tail(
+        &mut self,
+        block_index: BlockIndex,
+        stored_data_size: usize,
+    ) -> Result<(), Error> {
+        if block_index + 1 >= self.end_block_count {
+            return Ok(());
+        }
+        let data_end = (self.block_offset(block_index)
+            + self.header.block_header_size()
+            + stored_data_size) as u64;
+        let block_end = self.block_offset(block_index + 1) as u64;
+        let hole_start = data_end.div_ceil(HOLE_PAGE_SIZE) * HOLE_PAGE_SIZE;
+        let hole_end = block_end - block_end % HOLE_PAGE_SIZE;
+        if hole_end <= hole_start {
+            return Ok(());
+        }
+        if let Err(result_error) = self.device.punch_hole(hole_start, hole_end - hole_start) {
+            return Err(storage_errors::punch_compressed_tail_punch_hole(
+                result_error,
+                hole_start,
+                hole_end - hole_start,
+            ));
+        }
+        Ok(())
+    }
+
+    /// Write data of many blocks to storage file, as a group
+    /// - blocks are sorted by index, runs of adjacent blocks are written with a single vectored write
+    /// - unused tail of a block followed by another block in the run is zero filled
+    /// - counts as a single write for sync policy, so group is synced at most once
+    /// - returns error if a block index repeats or data of a block does not fit in it,
+    ///   nothing is written then
+    /// - return write_pointer
+    pub fn write_blocks(&mut self, blocks: &[(BlockIndex, &[u8])]) -> Result<usize, Error> {
+        if self.shadow.is_some() {
+            return self.write_shadow_blocks("write_blocks", blocks);
+        }
+        self.write_physical_blocks(blocks)
+    }
+
+    /// Write blocks of storage file as a group, bypassing page tables of a shadow paged storage
+    fn write_physical_blocks(&mut self, blocks: &[(BlockIndex, &[u8])]) -> Result<usize, Error> {
+        if self.read_only {
+            return Err(storage_errors::storage_read_only("write_blocks"));
+        }
+        if blocks.is_empty() {
+            return Ok(self.write_pointer);
+        }
+        // - data must fit in the block, else it would overwrite next block
+        for (block_index, data) in blocks.iter() {
+            if data.len() > self.header.block_len as usize {
+                return Err(storage_errors::write_blocks_data_too_large(
+                    *block_index,
+                    data.len(),
+                    self.header.block_len,
+                ));
+            }
+            if *block_index >= self.max_block_count() {
+                return Err(storage_errors::write_blocks_index_out_of_range(
+                    *block_index,
+                    self.max_block_count(),
+                ));
+            }
+            if !self.within_quota(*block_index) {
+                return Err(storage_errors::storage_full(
+                    "write_blocks",
+                    *block_index,
+                    self.quota_block_count().unwrap_or_default(),
+                ));
+            }
+        }
+        let previous_end_block_count = self.end_block_count;
+        // - sort blocks by index, reject repeated index
+        let mut blocks = blocks.to_vec();
+        blocks.sort_by_key(|(block_index, _)| *block_index);
+        for pair in blocks.windows(2) {
+            if pair[0].0 == pair[1].0 {
+                return Err(storage_errors::write_blocks_repeated_block_index(pair[0].0));
+            }
+        }
+
+        // - running totals are unknown till blocks are written, data they held is taken off then
+        let mut previous_blocks_totals = DataTotals::default();
+        for (block_index, _) in blocks.iter() {
+            previous_blocks_totals.add(&self.used_block_data_totals(*block_index)?);
+        }
+        let data_totals = self.data_totals.take();
+
+        // - drop stale block data from block cache, in case write fails midway
+        // - mark storage dirty & blocks used in free block map, before they are written
+        self.mark_dirty()?;
+        for (block_index, _) in blocks.iter() {
+            self.block_cache().remove(*block_index);
+            if self.block_empty(*block_index) {
+                if let Some(free_block_map) = &mut self.free_block_map {
+                    free_block_map.mark_used(*block_index)?;
+                }
+            }
+        }
+
+        // - compress block data if storage is compressed, stored raw where that is smaller
+        let compressed_blocks = blocks
+            .iter()
+            .map(|(_, data)| self.header.compression().compress(data))
+            .collect::<Vec<Option<Vec<u8>>>>();
+        let stored_blocks = blocks
+            .iter()
+            .zip(compressed_blocks.iter())
+            .map(|((_, data), compressed_data)| match compressed_data {
+                Some(compressed_data) => &compressed_data[..],
+                None => *data,
+            })
+            .collect::<Vec<&[u8]>>();
+
+        // - block headers, checksums included if enabled
+        let block_headers = stored_blocks
+            .iter()
+            .zip(compressed_blocks.iter())
+            .map(|(data, compressed_data)| {
+                let block_header = if compressed_data.is_some() {
+                    BlockHeader::new_compressed(data.len() as BlockDataSize)
+                } else {
+                    BlockHeader::new(data.len() as BlockDataSize)
+                };
+                let mut block_header_bytes = block_header.to_bytes(self.header.block_index_width());
+                if self.header.block_checksum() {
+                    let block_checksum: BlockChecksum = crc32c(data);
+                    block_header_bytes.extend_from_slice(&block_checksum.to_le_bytes());
+                }
+                block_header_bytes
+            })
+            .collect::<Vec<Vec<u8>>>();
+        let zero_fill = vec![0u8; self.header.block_len as usize];
+
+        // - write each run of adjacent blocks
+        let mut run_start = 0;
+        while run_start < blocks.len() {
+            // -- find end of run
+            let mut run_end = run_start + 1;
+            while run_end < blocks.len() && blocks[run_end].0 == blocks[run_end - 1].0 + 1 {
+                run_end += 1;
+            }
+            // -- header & data of each block, zero fill between blocks
+            let mut buffers: Vec<&[u8]> = Vec::with_capacity((run_end - run_start) * 3);
+            for position in run_start..run_end {
+                let data = stored_blocks[position];
+                buffers.push(&block_headers[position]);
+                buffers.push(data);
+                if position + 1 < run_end {
+                    let fill_size = (self.header.block_len as usize).saturating_sub(data.len());
+                    buffers.push(&zero_fill[..fill_size]);
+                }
+            }
+            let run_size = buffers.iter().map(|buffer| buffer.len()).sum::<usize>();
+            let run_offset = self.block_offset(blocks[run_start].0);
+            let write_result = self.device.write_vectored_at(&buffers, run_offset as u64);
+            if let Err(result_error) = write_result {
+                return Err(storage_errors::write_blocks_write_blocks(result_error));
+            }
+            // -- verify write operation was successful
+            let write_size = write_result.unwrap();
+            self.write_pointer = run_offset + write_size;
+            if write_size != run_size {
+                return Err(storage_errors::write_blocks_write_blocks_success(
+                    write_size,
+                ));
+            }
+
+            self.io_counters
+                .count_writes(run_end - run_start, write_size);
+
+            // -- update free_blocks map & block cache
+            for (block_index, data) in blocks[run_start..run_end].iter() {
+                self.free_blocks.remove(block_index);
+                self.block_cache().put(*block_index, data);
+            }
+
+            // -- update max_block_index, blocks skipped in between are free
+            let last_block_index = blocks[run_end - 1].0;
+            if last_block_index >= self.end_block_count {
+                self.free_blocks
+                    .extend(self.end_block_count..blocks[run_start].0);
+                self.end_block_count = last_block_index + 1;
+            }
+            run_start = run_end;
+        }
+
+        // - update data totals
+        self.data_totals = data_totals.map(|mut data_totals| {
+            data_totals.subtract(&previous_blocks_totals);
+            for (position, (_, data)) in blocks.iter().enumerate() {
+                data_totals.add(&DataTotals::of_block(
+                    data.len() as u64,
+                    stored_blocks[position].len() as u64,
+                    compressed_blocks[position].is_some(),
+                ));
+            }
+            data_totals
+        });
+        self.warn_soft_quota(previous_end_block_count);
+
+        // - release pages in unused tails of compressed blocks, zero filled by runs
+        for (position, (block_index, _)) in blocks.iter().enumerate() {
+            if compressed_blocks[position].is_some() {
+                self.punch_compressed_tail(*block_index, stored_blocks[position].len())?;
+            }
+        }
+
+        // - sync if due as per sync policy
+        self.sync_after_write()?;
 
         // - return write pointer
         Ok(self.write_pointer)
     }
 
     /// Write block data to storage file
+    /// - block of a shadow paged storage is unmapped, its block of storage file is released
+    ///   once no checkpoint or snapshot holds it
     /// - return write_pointer
     pub fn delete_block(
         &mut self,
         block_index: BlockIndex,
         hard_delete: bool,
     ) -> Result<usize, Error> {
+        if self.shadow.is_some() {
+            return self.delete_shadow_block(block_index, hard_delete);
+        }
+        self.delete_physical_block(block_index, hard_delete)
+    }
+
+    /// Delete block of storage file, bypassing page tables of a shadow paged storage
+    fn delete_physical_block(
+        &mut self,
+        block_index: BlockIndex,
+        hard_delete: bool,
+    ) -> Result<usize, Error> {
+        if self.read_only {
+            return Err(storage_errors::storage_read_only("delete_block"));
+        }
         if !self.block_exists(block_index)
             || (!hard_delete && self.free_blocks.contains(&block_index))
         {
             return Ok(self.write_pointer);
         }
-        use std::io::prelude::*;
-        let block_length = self.header.block_len;
-        let block_offset = STORAGE_HEADER_SIZE
-            + block_index as usize * (BLOCK_HEADER_SIZE + block_length as usize);
-
-        // - seek writer to block offset
-        let seek_result = self
-            .file_writer
-            .seek(std::io::SeekFrom::Start(block_offset as u64));
-        if let Err(result_error) = seek_result {
-            return Err(storage_errors::delete_block_seek_block_offset(result_error));
-        }
-        // -- verify seek operation was successful
-        let seek_position = seek_result.unwrap() as usize;
-        if seek_position != block_offset {
-            return Err(storage_errors::delete_block_seek_block_offset_success(
-                seek_position,
-            ));
-        }
+        // - running totals are unknown till block is deleted, data it held is taken off then
+        let previous_block_totals = self.used_block_data_totals(block_index)?;
+        let data_totals = self.data_totals.take();
+
+        self.mark_dirty()?;
+        let block_offset = self.block_offset(block_index);
         self.write_pointer = block_offset;
 
         // - Write Block Header
-        // -- write block header to inital BLOCK_HEADER_SIZE bytes
+        // -- write block header to inital 4 or 8 bytes
         let block_header = BlockHeader::new(0);
-        let write_result = self.file_writer.write(&block_header.to_bytes());
+        let write_result = self.device.write_at(
+            &block_header.to_bytes(self.header.block_index_width()),
+            self.write_pointer as u64,
+        );
         if let Err(result_error) = write_result {
             return Err(storage_errors::delete_block_write_block_header(
                 result_error,
@@ -592,24 +2596,46 @@ impl Storage {
         let write_size = write_result.unwrap();
         self.write_pointer += write_size;
         // -- verify write operation was successful
-        if write_size != BLOCK_HEADER_SIZE {
+        if write_size != self.header.block_data_size_len() {
             return Err(storage_errors::delete_block_write_block_header_success(
                 write_size,
             ));
         }
 
-        // - hard delete block
-        if hard_delete {
-            // post successful block header write, writer pointer must be at data offset
-            // - overwrite full block with zeros
-            let block_data_of_zeros = vec![0u8; block_length as usize];
-            let write_result = self.file_writer.write(&block_data_of_zeros[..]);
+        // - release block data to file system if punching holes, else overwrite it with zeros by hard delete
+        // -- punching holes also releases free blocks next to it, in pages the block touches
+        if self.punch_holes {
+            let (hole_start, hole_end) = self.free_run_hole(block_index);
+            // -- a hole keeps file size, last block of file is extended to its full length
+            let extend_result = match self.device.size() {
+                Ok(file_len) if file_len < hole_end => self.device.set_size(hole_end),
+                Ok(_) => Ok(()),
+                Err(result_error) => Err(result_error),
+            };
+            if let Err(result_error) = extend_result {
+                return Err(storage_errors::delete_block_truncate(result_error));
+            }
+            if let Err(result_error) = self.device.punch_hole(hole_start, hole_end - hole_start) {
+                return Err(storage_errors::delete_block_punch_hole(
+                    result_error,
+                    hole_start,
+                    hole_end - hole_start,
+                ));
+            }
+            self.write_pointer = self.block_offset(block_index + 1);
+        } else if hard_delete {
+            // -- overwrite rest of block with zeros, checksum included
+            let block_data_of_zeros =
+                vec![0u8; self.block_offset(block_index + 1) - self.write_pointer];
+            let write_result = self
+                .device
+                .write_at(&block_data_of_zeros, self.write_pointer as u64);
             if let Err(result_error) = write_result {
                 return Err(storage_errors::delete_block_write_block_data(result_error));
             }
             let write_size = write_result.unwrap();
             // -- verify write operation was successful
-            if write_size != block_length as usize {
+            if write_size != block_data_of_zeros.len() {
                 return Err(storage_errors::delete_block_write_block_data_success(
                     write_size,
                 ));
@@ -618,38 +2644,1242 @@ impl Storage {
             self.write_pointer += write_size;
         }
 
-        // update free_blocks map
+        // update free_blocks map & data totals
         self.free_blocks.insert(block_index);
+        self.data_totals = data_totals.map(|mut data_totals| {
+            data_totals.subtract(&previous_block_totals);
+            data_totals
+        });
+        self.io_counters
+            .count_delete(self.header.block_data_size_len());
+
+        // - drop block from block cache
+        self.block_cache().remove(block_index);
+
+        // - mark block free in free block map, after it is deleted
+        if let Some(free_block_map) = &mut self.free_block_map {
+            free_block_map.mark_free(block_index)?;
+        }
+
+        // - truncate free blocks at end of file
+        if self.punch_holes && block_index + 1 == self.end_block_count {
+            self.truncate_free_blocks()?;
+        }
+
+        // - sync if due as per sync policy
+        self.sync_after_write()?;
 
         // return write pointer
         Ok(self.write_pointer)
     }
 
+    /// Byte range to punch a hole into, for a block being deleted
+    /// - block from end of its header, extended over free blocks around it, within pages the block touches.
+    ///   Free blocks are all zeros once punched, so pages shared by free blocks only are released too.
+    fn free_run_hole(&self, block_index: BlockIndex) -> (u64, u64) {
+        let block_start = self.block_offset(block_index) as u64;
+        let block_end = self.block_offset(block_index + 1) as u64;
+        let page_start = block_start - block_start % HOLE_PAGE_SIZE;
+        let page_end = block_end.div_ceil(HOLE_PAGE_SIZE) * HOLE_PAGE_SIZE;
+        let mut run_start_index = block_index;
+        while run_start_index > 0
+            && self.free_blocks.contains(&(run_start_index - 1))
+            && self.block_offset(run_start_index) as u64 > page_start
+        {
+            run_start_index -= 1;
+        }
+        let mut run_end_index = block_index + 1;
+        while run_end_index < self.end_block_count
+            && self.free_blocks.contains(&run_end_index)
+            && (self.block_offset(run_end_index) as u64) < page_end
+        {
+            run_end_index += 1;
+        }
+        let hole_start = if run_start_index == block_index {
+            block_start + self.header.block_data_size_len() as u64
+        } else {
+            (self.block_offset(run_start_index) as u64).max(page_start)
+        };
+        let hole_end = (self.block_offset(run_end_index) as u64).min(page_end);
+        (hole_start, hole_end)
+    }
+
+    /// Flush writes to durable storage, storage file & free block map
+    /// - marks storage clean, so free block map is trusted on next open
+    pub fn sync(&mut self) -> Result<(), Error> {
+        self.sync_writes()?;
+        self.mark_clean()
+    }
+
+    /// Flush writes to durable storage, storage file & free block map, leaving storage dirty
+    /// - used by sync policy & commits, which would pay for a header write on every sync else
+    fn sync_writes(&mut self) -> Result<(), Error> {
+        if let Err(result_error) = self.device.sync() {
+            return Err(storage_errors::sync_sync_device(result_error));
+        }
+        if let Some(free_block_map) = &mut self.free_block_map {
+            free_block_map.sync()?;
+        }
+        self.io_counters.count_sync();
+        self.unsynced_write_count = 0;
+        self.last_sync_instant = Instant::now();
+        Ok(())
+    }
+
+    /// Check if storage can be marked clean, once its writes are synced
+    /// - only a storage with a free block map is, header of format version 0 has no room for the flag
+    fn can_mark_clean(&self) -> bool {
+        !self.header.clean
+            && !self.read_only
+            && self.free_block_map.is_some()
+            && self.header.version != 0
+    }
+
+    /// Mark storage clean in storage header, after writes are synced
+    /// - running totals of data are persisted in free block map first, summed up again if unknown
+    fn mark_clean(&mut self) -> Result<(), Error> {
+        if !self.can_mark_clean() {
+            return Ok(());
+        }
+        let data_totals = match self.data_totals {
+            Some(data_totals) => data_totals,
+            None => self.read_data_totals()?,
+        };
+        self.data_totals = Some(data_totals);
+        if let Some(free_block_map) = &mut self.free_block_map {
+            free_block_map.set_data_totals(data_totals)?;
+            free_block_map.sync()?;
+        }
+        self.header.clean = true;
+        if let Err(error) = self.write_storage_state() {
+            self.header.clean = false;
+            return Err(error);
+        }
+        Ok(())
+    }
+
+    /// Mark storage dirty in storage header, before it is changed
+    /// - header is synced ahead of the change, so a crash never leaves a changed storage marked clean
+    fn mark_dirty(&mut self) -> Result<(), Error> {
+        if !self.header.clean {
+            return Ok(());
+        }
+        self.header.clean = false;
+        if let Err(error) = self.write_storage_state() {
+            self.header.clean = true;
+            return Err(error);
+        }
+        Ok(())
+    }
+
+    /// Write storage header with its clean state, and sync it
+    fn write_storage_state(&mut self) -> Result<(), Error> {
+        let write_pointer = self.write_pointer;
+        let write_result = self.set_storage_header();
+        self.write_pointer = write_pointer;
+        write_result?;
+        if let Err(result_error) = self.device.sync() {
+            return Err(storage_errors::sync_sync_device(result_error));
+        }
+        Ok(())
+    }
+
+    /// Make writes before the barrier durable ahead of writes after it
+    /// - say, a block must be durable before a block linking to it is written
+    /// - syncs if any write is unsynced, unless sync policy is `SyncPolicy::Never`
+    pub fn sync_barrier(&mut self) -> Result<(), Error> {
+        if self.sync_policy == SyncPolicy::Never || self.unsynced_write_count == 0 {
+            return Ok(());
+        }
+        self.sync_writes()
+    }
+
+    /// Commit writes & deletes of given transaction atomically
+    /// - after a crash, storage holds either all changes of the transaction or none of them
+    /// - transaction is written & synced to journal first, then applied to storage & synced,
+    ///   then journal is cleared. A crash in between is recovered on open, by redoing the journal.
+    /// - every write is checked before journal is written, so a failing check leaves storage untouched
+    /// - returns `commit_no_journal` for storages on a given device without a journal device
+    /// - shadow paged storage applies transaction & checkpoints instead, without journal,
+    ///   so writes since last checkpoint are committed along
+    pub fn commit(&mut self, transaction: &Transaction) -> Result<(), Error> {
+        if self.read_only {
+            return Err(storage_errors::storage_read_only("commit"));
+        }
+        if self.journal.is_none() && self.shadow.is_none() {
+            return Err(storage_errors::commit_no_journal());
+        }
+        if transaction.is_empty() {
+            return Ok(());
+        }
+
+        // - check writes as `write_block` does
+        for record in transaction.records.iter() {
+            if let JournalRecord::Write { block_index, data } = record {
+                let block_index = *block_index;
+                if data.len() > self.header.block_len as usize {
+                    return Err(storage_errors::write_block_data_too_large(
+                        block_index,
+                        data.len(),
+                        self.header.block_len,
+                    ));
+                }
+                if block_index >= self.max_block_count() {
+                    return Err(storage_errors::write_block_index_out_of_range(
+                        block_index,
+                        self.max_block_count(),
+                    ));
+                }
+                if self.shadow.is_none() && !self.within_quota(block_index) {
+                    return Err(storage_errors::storage_full(
+                        "commit",
+                        block_index,
+                        self.quota_block_count().unwrap_or_default(),
+                    ));
+                }
+            }
+        }
+
+        // - page tables swap atomically on checkpoint
+        if self.shadow.is_some() {
+            self.apply_journal_records(&transaction.records)?;
+            return self.checkpoint();
+        }
+
+        // - transaction is committed once journal is synced
+        self.journal.as_mut().unwrap().write(&transaction.records)?;
+
+        // - apply & sync, before journal is cleared
+        self.apply_journal_records(&transaction.records)?;
+        self.sync_writes()?;
+        self.journal.as_mut().unwrap().clear()
+    }
+
+    /// Redo transaction committed to journal, left by a crash during `Storage::commit`
+    /// - a torn journal is a transaction never committed, and is ignored
+    /// - quota is not checked, it bounds new allocations, not a transaction already committed
+    fn recover_journal(&mut self) -> Result<(), Error> {
+        let records = match &self.journal {
+            Some(journal) => journal.read()?,
+            None => return Ok(()),
+        };
+        if let Some(records) = records {
+            let quota = self.quota.take();
+            let apply_result = self.apply_journal_records(&records);
+            self.quota = quota;
+            apply_result?;
+            self.sync_writes()?;
+            self.journal.as_mut().unwrap().clear()?;
+        }
+        Ok(())
+    }
+
+    /// Write & delete blocks as recorded in journal
+    /// - redoing records already applied leaves blocks the same
+    fn apply_journal_records(&mut self, records: &[JournalRecord]) -> Result<(), Error> {
+        for record in records {
+            match record {
+                JournalRecord::Write { block_index, data } => {
+                    self.write_block(*block_index, data)?;
+                }
+                JournalRecord::Delete {
+                    block_index,
+                    hard_delete,
+                } => {
+                    self.delete_block(*block_index, *hard_delete)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Count a write, and sync if due as per sync policy
+    fn sync_after_write(&mut self) -> Result<(), Error> {
+        self.unsynced_write_count += 1;
+        let sync_due = match self.sync_policy {
+            SyncPolicy::Never => false,
+            SyncPolicy::Always => true,
+            SyncPolicy::EveryWrites(write_count) => self.unsynced_write_count >= write_count,
+            SyncPolicy::EveryMillis(millis) => {
+                self.last_sync_instant.elapsed() >= Duration::from_millis(millis)
+            }
+        };
+        if sync_due {
+            return self.sync_writes();
+        }
+        Ok(())
+    }
+
     // ... ... ... ... ... ... ... ... ... ... ... ... ... ... ... ... ...
 
     // ... ... ... ... ... ... Abstract Functions ... ... ... ... ... ... .
 
     /// Return blocks in storage to write data to, in assending order of index
-    /// - collect free blocks
+    /// - picked by block allocator of storage, lowest free blocks first by default
     /// - if free blocks not enough, extend storage
-    pub fn search_block_allocation_indexes(&self, count: BlockIndex) -> Vec<BlockIndex> {
-        let mut available_free_blocks = self
+    /// - returns `storage_full` if storage would grow past its quota
+    /// - block indexes of application of a shadow paged storage, bound by quota on writes only
+    pub fn search_block_allocation_indexes(
+        &self,
+        count: BlockIndex,
+    ) -> Result<Vec<BlockIndex>, Error> {
+        if let Some(shadow) = &self.shadow {
+            return self.block_allocator.allocate(
+                shadow.free_blocks(),
+                shadow.end_block_count(),
+                count,
+            );
+        }
+        self.search_physical_block_allocation_indexes(count)
+    }
+
+    /// Blocks of storage file to write data to, bypassing page tables of a shadow paged storage
+    fn search_physical_block_allocation_indexes(
+        &self,
+        count: BlockIndex,
+    ) -> Result<Vec<BlockIndex>, Error> {
+        let block_indexes =
+            self.block_allocator
+                .allocate(&self.free_blocks, self.end_block_count, count)?;
+        // - indexes are ascending, so last one is the farthest past end of storage
+        if let Some(last_block_index) = block_indexes.last() {
+            if !self.within_quota(*last_block_index) {
+                return Err(storage_errors::storage_full(
+                    "search_block_allocation_indexes",
+                    *last_block_index,
+                    self.quota_block_count().unwrap_or_default(),
+                ));
+            }
+        }
+        Ok(block_indexes)
+    }
+
+    /// Return a run of contiguous free blocks to write data to, regardless of block allocator
+    /// - smallest run holding all blocks, a run at end of file extends beyond it
+    /// - None if no run of free blocks fits, or run would grow storage past its quota,
+    ///   or block indexes would overflow
+    pub fn search_contiguous_block_allocation_indexes(
+        &self,
+        count: BlockIndex,
+    ) -> Option<Vec<BlockIndex>> {
+        if let Some(shadow) = &self.shadow {
+            let first_block_index = block_allocator::contiguous_free_run(
+                shadow.free_blocks(),
+                shadow.end_block_count(),
+                count,
+            )?;
+            let end_block_index = first_block_index.checked_add(count)?;
+            return Some((first_block_index..end_block_index).collect());
+        }
+        let first_block_index =
+            block_allocator::contiguous_free_run(&self.free_blocks, self.end_block_count, count)?;
+        let end_block_index = first_block_index.checked_add(count)?;
+        if !self.within_quota(end_block_index - 1) {
+            return None;
+        }
+        Some((first_block_index..end_block_index).collect())
+    }
+
+    // ... ... ... ... ... ... ... ... . Roots ... ... ... ... ... ... ... ... .
+
+    /// Slot of root table with newest generation, None if storage has no roots
+    fn root_slot(&self) -> Option<usize> {
+        match (&self.root_tables[0], &self.root_tables[1]) {
+            (Some(first), Some(second)) if second.generation > first.generation => Some(1),
+            (Some(_), _) => Some(0),
+            (None, Some(_)) => Some(1),
+            (None, None) => None,
+        }
+    }
+
+    /// Block index of chain head stored under given name
+    pub fn get_root(&self, name: &str) -> Option<BlockIndex> {
+        let root_slot = self.root_slot()?;
+        self.root_tables[root_slot]
+            .as_ref()?
+            .roots
+            .get(name)
+            .cloned()
+    }
+
+    /// All roots as (name, block index), sorted by name
+    pub fn list_roots(&self) -> Vec<(String, BlockIndex)> {
+        match self.root_slot() {
+            Some(root_slot) => self.root_tables[root_slot]
+                .as_ref()
+                .unwrap()
+                .roots
+                .iter()
+                .map(|(name, block_index)| (name.clone(), *block_index))
+                .collect(),
+            None => vec![],
+        }
+    }
+
+    /// Store given block index under given name, replacing previous block index of the name
+    /// - Crash safe, a crash leaves either old or new roots, and syncs regardless of sync policy
+    /// - Roots are stored in blocks of the storage, so a chain head must be a block index the application keeps
+    pub fn set_root(&mut self, name: &str, block_index: BlockIndex) -> Result<(), Error> {
+        if self.read_only {
+            return Err(storage_errors::storage_read_only("set_root"));
+        }
+        // - block index must be storable in root table
+        if block_index >= self.header.block_index_width().max_block_count() {
+            return Err(storage_errors::set_root_block_index_out_of_range(
+                block_index,
+                self.header.block_index_width().max_block_count(),
+            ));
+        }
+        let mut roots = match self.root_slot() {
+            Some(root_slot) => self.root_tables[root_slot].as_ref().unwrap().roots.clone(),
+            None => Default::default(),
+        };
+        roots.insert(name.to_string(), block_index);
+        self.write_root_table(roots)
+    }
+
+    /// Remove root of given name
+    /// - returns: removed block index, None if there was no root of the name
+    pub fn remove_root(&mut self, name: &str) -> Result<Option<BlockIndex>, Error> {
+        if self.read_only {
+            return Err(storage_errors::storage_read_only("remove_root"));
+        }
+        let mut roots = match self.root_slot() {
+            Some(root_slot) => self.root_tables[root_slot].as_ref().unwrap().roots.clone(),
+            None => return Ok(None),
+        };
+        let removed_block_index = roots.remove(name);
+        if removed_block_index.is_some() {
+            self.write_root_table(roots)?;
+        }
+        Ok(removed_block_index)
+    }
+
+    /// Write given roots as a new root table, in slot of older table
+    /// - older table is freed first, newer table stays intact till slot points to new table
+    /// - new table is synced before slot points to it, slot is synced before returning
+    fn write_root_table(&mut self, roots: BTreeMap<String, BlockIndex>) -> Result<(), Error> {
+        if self.header.version == 0 {
+            return Err(storage_errors::set_root_unsupported_format_version(
+                self.header.version,
+            ));
+        }
+        let block_len = self.header.block_len as usize;
+        let block_index_width = self.header.block_index_width();
+        if block_len <= block_index_width.size() {
+            return Err(storage_errors::set_root_block_len_too_small(
+                self.header.block_len,
+            ));
+        }
+        let root_slot = self.root_slot();
+        let target_slot = match root_slot {
+            Some(0) => 1,
+            _ => 0,
+        };
+        let generation = match root_slot {
+            Some(root_slot) => self.root_tables[root_slot].as_ref().unwrap().generation + 1,
+            None => 1,
+        };
+
+        // - free blocks of older table
+        if let Some(older_table) = self.root_tables[target_slot].take() {
+            for block_index in older_table.blocks {
+                self.delete_physical_block(block_index, false)?;
+            }
+        }
+
+        // - write new table to free blocks, and sync
+        // -- page tables of a shadow paged storage swap along with roots
+        let mut root_table = RootTable {
+            generation,
+            roots,
+            blocks: vec![],
+            shadow: self.shadow.as_ref().map(|shadow| shadow.table()),
+        };
+        let bytes = root_table.to_bytes(block_index_width);
+        let block_indexes = self.search_physical_block_allocation_indexes(
+            RootTable::block_count(bytes.len(), block_len, block_index_width) as BlockIndex,
+        )?;
+        let blocks_data =
+            RootTable::split_into_blocks(&bytes, &block_indexes, block_len, block_index_width);
+        let blocks = block_indexes
+            .iter()
+            .cloned()
+            .zip(blocks_data.iter().map(|block_data| block_data.as_slice()))
+            .collect::<Vec<(BlockIndex, &[u8])>>();
+        self.write_physical_blocks(&blocks)?;
+        self.sync_writes()?;
+
+        // - point slot to new table, and sync
+        let previous_slot_value = self.header.root_slots[target_slot];
+        self.header.root_slots[target_slot] = block_indexes[0] + 1;
+        if let Err(error) = self.set_storage_header() {
+            self.header.root_slots[target_slot] = previous_slot_value;
+            return Err(error);
+        }
+        self.sync_writes()?;
+
+        root_table.blocks = block_indexes;
+        self.root_tables[target_slot] = Some(root_table);
+
+        // - release blocks of previous checkpoint, no longer referenced by new page tables
+        let released_blocks = match &mut self.shadow {
+            Some(shadow) => shadow.checkpoint(),
+            None => vec![],
+        };
+        for block_index in released_blocks {
+            self.delete_physical_block(block_index, false)?;
+        }
+        Ok(())
+    }
+
+    /// Load root tables from slots of storage header
+    /// - a slot with torn or corrupt table is treated as empty
+    /// - returns error if both slots are set and neither holds an intact table,
+    ///   as a crash leaves at least one of them intact
+    fn load_root_tables(&mut self) -> Result<(), Error> {
+        let block_len = self.header.block_len as usize;
+        let block_index_width = self.header.block_index_width();
+        for slot in 0..2 {
+            let slot_value = self.header.root_slots[slot];
+            self.root_tables[slot] = None;
+            if slot_value == 0
+                || !self.block_exists(slot_value - 1)
+                || block_len <= block_index_width.size()
+            {
+                continue;
+            }
+            self.root_tables[slot] = RootTable::load(
+                slot_value - 1,
+                block_len,
+                block_index_width,
+                |block_index| {
+                    if !self.block_exists(block_index) {
+                        return None;
+                    }
+                    self.read_physical_block(block_index)
+                        .ok()
+                        .map(|(_, block_data)| block_data)
+                },
+            );
+        }
+        if self
+            .header
+            .root_slots
+            .iter()
+            .all(|slot_value| *slot_value != 0)
+            && self.root_slot().is_none()
+        {
+            return Err(storage_errors::load_root_tables_corrupt(
+                self.header.root_slots,
+            ));
+        }
+        Ok(())
+    }
+
+    // ... ... ... ... ... ... ... . Shadow Paging ... ... ... ... ... ... ... .
+
+    /// Roots of newest root table
+    fn roots(&self) -> BTreeMap<String, BlockIndex> {
+        match self.root_slot() {
+            Some(root_slot) => self.root_tables[root_slot].as_ref().unwrap().roots.clone(),
+            None => Default::default(),
+        }
+    }
+
+    /// Persist changes since last checkpoint of a shadow paged storage
+    /// - page tables are written with roots to a new root table, then a single write of storage header
+    ///   swaps root slot to it. A crash leaves storage as of either checkpoint, writes after the last are lost.
+    /// - blocks of previous checkpoint are released once no longer referenced, unless held by a snapshot
+    /// - `set_root`, `remove_root` & `commit` checkpoint too
+    /// - syncs storage that is not shadow paged
+    pub fn checkpoint(&mut self) -> Result<(), Error> {
+        if self.read_only {
+            return Err(storage_errors::storage_read_only("checkpoint"));
+        }
+        if self.shadow.is_none() {
+            return self.sync();
+        }
+        self.write_root_table(self.roots())
+    }
+
+    /// Retain current version of a shadow paged storage under given name, and checkpoint
+    /// - blocks & roots of the version stay readable with `Storage::open_snapshot` till snapshot is removed
+    /// - replaces snapshot of the name
+    pub fn create_snapshot(&mut self, name: &str) -> Result<(), Error> {
+        if self.read_only {
+            return Err(storage_errors::storage_read_only("create_snapshot"));
+        }
+        let roots = self.roots();
+        match &mut self.shadow {
+            Some(shadow) => shadow.set_snapshot(name, roots.clone()),
+            None => return Err(storage_errors::snapshot_no_shadow_paging("create_snapshot")),
+        }
+        self.write_root_table(roots)
+    }
+
+    /// Remove snapshot of given name, and checkpoint so its blocks are released
+    /// - returns: false if there was no snapshot of the name
+    pub fn remove_snapshot(&mut self, name: &str) -> Result<bool, Error> {
+        if self.read_only {
+            return Err(storage_errors::storage_read_only("remove_snapshot"));
+        }
+        let removed = match &mut self.shadow {
+            Some(shadow) => shadow.remove_snapshot(name).is_some(),
+            None => return Err(storage_errors::snapshot_no_shadow_paging("remove_snapshot")),
+        };
+        if removed {
+            self.write_root_table(self.roots())?;
+        }
+        Ok(removed)
+    }
+
+    /// Names of snapshots, sorted
+    pub fn list_snapshots(&self) -> Vec<String> {
+        match &self.shadow {
+            Some(shadow) => shadow.snapshots().keys().cloned().collect(),
+            None => vec![],
+        }
+    }
+
+    /// Open snapshot of given name of storage file in given path, for reading only
+    /// - blocks & roots read as they were when snapshot was created
+    pub fn open_snapshot(file_path: String, name: &str) -> Result<Storage, Error> {
+        let mut storage = Storage::open_read_only(file_path)?;
+        let snapshot = storage
+            .shadow
+            .as_ref()
+            .and_then(|shadow| shadow.snapshots().get(name).cloned());
+        let snapshot = match snapshot {
+            Some(snapshot) => snapshot,
+            None => return Err(storage_errors::open_snapshot_not_found(name)),
+        };
+        storage.shadow = Some(ShadowPages::new(ShadowTable {
+            pages: snapshot.pages,
+            snapshots: BTreeMap::new(),
+        }));
+        if let Some(root_slot) = storage.root_slot() {
+            storage.root_tables[root_slot].as_mut().unwrap().roots = snapshot.roots;
+        }
+        Ok(storage)
+    }
+
+    /// Load page tables of a shadow paged storage from newest root table
+    /// - blocks written since last checkpoint are released, as if a crash happened before them
+    fn load_shadow_pages(&mut self) -> Result<(), Error> {
+        if !self.header.shadow_paging() {
+            return Ok(());
+        }
+        let table = match self.root_slot() {
+            Some(root_slot) => self.root_tables[root_slot]
+                .as_ref()
+                .unwrap()
+                .shadow
+                .clone()
+                .unwrap_or_default(),
+            None => ShadowTable::default(),
+        };
+        let shadow = ShadowPages::new(table);
+        if self.read_only {
+            self.shadow = Some(shadow);
+            return Ok(());
+        }
+
+        // - blocks of empty data count as free from block headers, and must not be reused while referenced
+        for block_index in shadow.durable_blocks().iter() {
+            if self.free_blocks.remove(block_index) {
+                self.mark_dirty()?;
+                if let Some(free_block_map) = &mut self.free_block_map {
+                    free_block_map.mark_used(*block_index)?;
+                }
+            }
+        }
+
+        // - release used blocks not referenced by page tables or root tables
+        let root_table_blocks = self.root_table_blocks();
+        let unreferenced_blocks = (0..self.end_block_count)
+            .filter(|block_index| {
+                !self.free_blocks.contains(block_index)
+                    && !shadow.durable(*block_index)
+                    && !root_table_blocks.contains(block_index)
+            })
+            .collect::<Vec<BlockIndex>>();
+        self.shadow = Some(shadow);
+        for block_index in unreferenced_blocks {
+            self.delete_physical_block(block_index, false)?;
+        }
+        Ok(())
+    }
+
+    /// Write blocks of a shadow paged storage to fresh blocks of storage file, as a group if more than one
+    /// - blocks replaced are released, unless held by last checkpoint or a snapshot
+    fn write_shadow_blocks(
+        &mut self,
+        operation: &str,
+        blocks: &[(BlockIndex, &[u8])],
+    ) -> Result<usize, Error> {
+        if self.read_only {
+            return Err(storage_errors::storage_read_only(operation));
+        }
+        if blocks.is_empty() {
+            return Ok(self.write_pointer);
+        }
+        // - block indexes must be storable in page tables
+        let max_block_count = self.header.block_index_width().max_block_count();
+        let mut block_indexes = BTreeSet::new();
+        for (block_index, _) in blocks.iter() {
+            if *block_index >= max_block_count {
+                return Err(storage_errors::write_block_index_out_of_range(
+                    *block_index,
+                    max_block_count,
+                ));
+            }
+            if !block_indexes.insert(*block_index) {
+                return Err(storage_errors::write_blocks_repeated_block_index(
+                    *block_index,
+                ));
+            }
+        }
+
+        // - write to fresh blocks, never holding a block of last checkpoint or a snapshot
+        let physical_block_indexes =
+            self.search_physical_block_allocation_indexes(blocks.len() as BlockIndex)?;
+        let physical_blocks = physical_block_indexes
+            .iter()
+            .zip(blocks.iter())
+            .map(|(physical_block_index, (_, data))| (*physical_block_index, *data))
+            .collect::<Vec<(BlockIndex, &[u8])>>();
+        let write_pointer = if physical_blocks.len() == 1 {
+            self.write_physical_block(physical_blocks[0].0, physical_blocks[0].1)?
+        } else {
+            self.write_physical_blocks(&physical_blocks)?
+        };
+
+        // - map blocks to written blocks, release blocks written since last checkpoint they replace
+        for ((block_index, _), physical_block_index) in blocks.iter().zip(physical_block_indexes) {
+            let shadow = self.shadow.as_mut().unwrap();
+            if let Some(replaced_block_index) = shadow.map(*block_index, physical_block_index) {
+                if !shadow.durable(replaced_block_index) {
+                    self.delete_physical_block(replaced_block_index, false)?;
+                }
+            }
+        }
+        Ok(write_pointer)
+    }
+
+    /// Unmap block of a shadow paged storage, releasing its block of storage file unless held
+    /// by last checkpoint or a snapshot
+    fn delete_shadow_block(
+        &mut self,
+        block_index: BlockIndex,
+        hard_delete: bool,
+    ) -> Result<usize, Error> {
+        if self.read_only {
+            return Err(storage_errors::storage_read_only("delete_block"));
+        }
+        let shadow = self.shadow.as_mut().unwrap();
+        match shadow.unmap(block_index) {
+            Some(physical_block_index) if !shadow.durable(physical_block_index) => {
+                self.delete_physical_block(physical_block_index, hard_delete)
+            }
+            _ => Ok(self.write_pointer),
+        }
+    }
+
+    // ... ... ... ... ... ... ... ... . Check ... ... ... ... ... ... ... ... .
+
+    /// Check storage file in given path for problems, and repair those safe to repair if asked
+    /// - walks every block header, verifying data size, length of last block, and checksum if enabled
+    /// - compares free block map with block headers, and loads root tables
+    /// - locks storage file, exclusively to repair, shared otherwise
+    /// - returns error if storage header can not be read, nothing else can be checked then
+    pub fn check_file(
+        file_path: String,
+        repair: bool,
+    ) -> Result<CheckReport<StorageProblem>, Error> {
+        // - exclusive lock creates a missing file, which is not a storage to check
+        if !std::path::Path::new(&file_path).exists() {
+            return Err(storage_errors::check_file_read_file(std::io::Error::from(
+                std::io::ErrorKind::NotFound,
+            )));
+        }
+        let (lock, device) = if repair {
+            let lock = StorageLock::acquire(&file_path, LockMode::Exclusive, None)?;
+            let device = Storage::open_file_device(&file_path, false, StorageBackend::File)?;
+            (lock, device)
+        } else {
+            let lock = StorageLock::acquire(&file_path, LockMode::Shared, None)?;
+            let device = Storage::open_read_only_file_device(&file_path, StorageBackend::File)?;
+            (lock, device)
+        };
+        let options = StorageOptions::default();
+        let mut storage = Storage::init(
+            device,
+            StorageHeader::new(0, false, BlockIndexWidth::U32),
+            &options,
+        );
+        storage.lock = Some(lock);
+        storage.read_only = !repair;
+        storage.get_storage_header()?;
+        storage.check_segment_dir(&file_path)?;
+        let mut report = CheckReport::default();
+
+        // - walk block headers till end of file
+        let file_len = match storage.device.size() {
+            Ok(file_len) => file_len,
+            Err(result_error) => return Err(storage_errors::check_file_read_file(result_error)),
+        };
+        let block_header_size = storage.header.block_header_size() as u64;
+        let block_data_size_len = storage.header.block_data_size_len();
+        let mut free_blocks = BTreeSet::new();
+        let mut block_index = 0;
+        loop {
+            let block_offset = storage.block_offset(block_index) as u64;
+            if block_offset >= file_len {
+                break;
+            }
+            // -- block header torn at end of file, truncated by repair
+            if file_len - block_offset < block_data_size_len as u64 {
+                if repair {
+                    storage.check_file_truncate(block_offset)?;
+                }
+                let size = (file_len - block_offset) as usize;
+                report.push(
+                    StorageProblem::TornBlockHeader { block_index, size },
+                    repair,
+                );
+                break;
+            }
+            let mut block_header_bytes = [0u8; WIDE_BLOCK_HEADER_SIZE];
+            if let Err(result_error) = storage
+                .device
+                .read_at(&mut block_header_bytes[..block_data_size_len], block_offset)
+            {
+                return Err(storage_errors::check_file_read_file(result_error));
+            }
+            let block_header = storage
+                .header
+                .parse_block_header(&block_header_bytes[..block_data_size_len]);
+            let block_data_size = block_header.block_data_size;
+            // -- block data size beyond block capacity, block is freed by repair
+            if block_data_size > storage.header.block_len as BlockDataSize {
+                if repair {
+                    storage.mark_dirty()?;
+                    let write_result = storage.device.write_at(
+                        &BlockHeader::new(0).to_bytes(storage.header.block_index_width()),
+                        block_offset,
+                    );
+                    if let Err(result_error) = write_result {
+                        return Err(storage_errors::check_file_repair(result_error));
+                    }
+                    free_blocks.insert(block_index);
+                }
+                report.push(
+                    StorageProblem::InvalidBlockDataSize {
+                        block_index,
+                        block_data_size,
+                    },
+                    repair,
+                );
+                block_index += 1;
+                continue;
+            }
+            if block_data_size == 0 {
+                free_blocks.insert(block_index);
+                block_index += 1;
+                continue;
+            }
+            // -- last block ends before its data, truncated by repair
+            let block_end = block_offset + block_header_size + block_data_size;
+            if block_end > file_len {
+                if repair {
+                    storage.check_file_truncate(block_offset)?;
+                }
+                let missing_bytes = (block_end - file_len) as usize;
+                report.push(
+                    StorageProblem::TruncatedBlock {
+                        block_index,
+                        missing_bytes,
+                    },
+                    repair,
+                );
+                break;
+            }
+            // -- block data must match its checksum, and compressed block data must decompress
+            if storage.header.block_checksum() || block_header.compressed {
+                if let Err(error) = storage.read_block_from_device(block_index) {
+                    let problem = match error.code() {
+                        "read_block_checksum_mismatch" => {
+                            StorageProblem::ChecksumMismatch { block_index }
+                        }
+                        "read_block_failed_to_decompress" => {
+                            StorageProblem::CorruptCompressedData { block_index }
+                        }
+                        _ => return Err(error),
+                    };
+                    report.push(problem, false);
+                }
+            }
+            block_index += 1;
+        }
+        storage.end_block_count = block_index;
+        storage.free_blocks = free_blocks;
+
+        // - free block map must match block headers, removed by repair to be rebuilt on open
+        let free_block_map_path = FreeBlockMap::file_path(&file_path);
+        if std::path::Path::new(&free_block_map_path).exists()
+            && FreeBlockMap::read(
+                &file_path,
+                storage.end_block_count,
+                storage.block_index_width(),
+            )
+            .map(|(free_blocks, _)| free_blocks)
+            .as_ref()
+                != Some(&storage.free_blocks)
+        {
+            if repair {
+                if let Err(result_error) = std::fs::remove_file(&free_block_map_path) {
+                    return Err(storage_errors::check_file_repair(result_error));
+                }
+            }
+            report.push(StorageProblem::StaleFreeBlockMap, repair);
+        }
+
+        // - at least one root table must be intact
+        if storage.load_root_tables().is_err() {
+            report.push(StorageProblem::CorruptRootTables, false);
+        }
+
+        if repair {
+            storage.sync()?;
+        }
+        Ok(report)
+    }
+
+    /// Truncate storage file at given offset, to repair its last block
+    fn check_file_truncate(&mut self, file_len: u64) -> Result<(), Error> {
+        self.mark_dirty()?;
+        if let Err(result_error) = self.device.set_size(file_len) {
+            return Err(storage_errors::check_file_repair(result_error));
+        }
+        Ok(())
+    }
+
+    // ... ... ... ... ... ... ... ... Compaction ... ... ... ... ... ... ... .
+
+    /// Blocks holding root tables of both slots
+    fn root_table_blocks(&self) -> BTreeSet<BlockIndex> {
+        self.root_tables
+            .iter()
+            .flatten()
+            .flat_map(|root_table| root_table.blocks.iter().cloned())
+            .collect()
+    }
+
+    /// Used blocks, except blocks of root tables, in ascending order
+    /// - used block indexes of application of a shadow paged storage
+    pub fn used_application_blocks(&self) -> Vec<BlockIndex> {
+        if let Some(shadow) = &self.shadow {
+            return shadow.used_blocks();
+        }
+        let root_table_blocks = self.root_table_blocks();
+        (0..self.end_block_count)
+            .filter(|block_index| {
+                !self.free_blocks.contains(block_index) && !root_table_blocks.contains(block_index)
+            })
+            .collect()
+    }
+
+    /// Compact storage in place, moving used blocks from end of file into free blocks, then truncate the file
+    /// - rewrite: given data of a used block & remap, returns new data of the block if it links to a moved block.
+    ///   Called for every used block, so links are rewritten, e.g. next block index of a logchain segment.
+    /// - Roots pointing to moved blocks are updated. Root tables are rewritten, not moved, and follow into freed blocks.
+    /// - Moved blocks are written & synced before any link is rewritten, and released after links & roots are synced.
+    ///   A crash leaves every used block reachable, except a link torn while rewritten in place, like any in-place write.
+    /// - returns: remap of moved blocks, for callers holding block indexes outside the storage
+    pub fn compact(
+        &mut self,
+        mut rewrite: impl FnMut(&[u8], &BlockRemap) -> Option<Vec<u8>>,
+    ) -> Result<BlockRemap, Error> {
+        if self.read_only {
+            return Err(storage_errors::storage_read_only("compact"));
+        }
+        // - blocks of a shadow paged storage move on every write, checkpoints & snapshots would point to moved blocks
+        if self.shadow.is_some() {
+            return Err(storage_errors::compact_shadow_paging());
+        }
+
+        // - plan, last used blocks move into first free blocks below them
+        let used_blocks = self.used_application_blocks();
+        let free_blocks = self
             .free_blocks
             .iter()
             .cloned()
             .collect::<Vec<BlockIndex>>();
-        available_free_blocks.truncate(count as usize);
-        available_free_blocks.sort_unstable();
-        // push indexes beyond end_block_count if required
-        for i in (self.end_block_count as usize)
-            ..(self.end_block_count as usize + count as usize - available_free_blocks.len())
+        let mut remap = BlockRemap::new();
+        for (free_block_index, used_block_index) in free_blocks.iter().zip(used_blocks.iter().rev())
+        {
+            if free_block_index > used_block_index {
+                break;
+            }
+            remap.insert(*used_block_index, *free_block_index);
+        }
+
+        // - write moved blocks to their new block indexes, with rewritten links, and sync
+        let mut moved_blocks = vec![];
+        for (old_block_index, new_block_index) in remap.iter() {
+            let (_, block_data) = self.read_block(*old_block_index)?;
+            let block_data = rewrite(&block_data, &remap).unwrap_or(block_data);
+            moved_blocks.push((*new_block_index, block_data));
+        }
+        let blocks = moved_blocks
+            .iter()
+            .map(|(block_index, block_data)| (*block_index, &block_data[..]))
+            .collect::<Vec<(BlockIndex, &[u8])>>();
+        self.write_blocks(&blocks)?;
+        self.sync_writes()?;
+
+        // - rewrite links of blocks staying in place, and sync
+        for block_index in used_blocks.iter() {
+            if remap.contains_key(block_index) {
+                continue;
+            }
+            let (_, block_data) = self.read_block(*block_index)?;
+            if let Some(block_data) = rewrite(&block_data, &remap) {
+                self.write_block(*block_index, &block_data)?;
+            }
+        }
+        self.sync_writes()?;
+
+        // - point roots to moved blocks, in both root table slots
+        let roots = self.list_roots();
+        if roots
+            .iter()
+            .any(|(_, block_index)| remap.contains_key(block_index))
         {
-            available_free_blocks.push(i as BlockIndex);
+            let roots = roots
+                .into_iter()
+                .map(|(name, block_index)| {
+                    (
+                        name,
+                        remap.get(&block_index).cloned().unwrap_or(block_index),
+                    )
+                })
+                .collect::<BTreeMap<String, BlockIndex>>();
+            self.write_root_table(roots.clone())?;
+            self.write_root_table(roots)?;
         }
-        available_free_blocks
+
+        // - release moved blocks, and truncate free blocks at end of file
+        for old_block_index in remap.keys() {
+            self.delete_block(*old_block_index, false)?;
+        }
+        self.truncate_free_blocks()?;
+
+        // - rewrite root tables left beyond a free block, into first free blocks
+        let lowest_free_block_index = self.free_blocks.first().cloned();
+        let highest_root_table_block_index = self.root_table_blocks().last().cloned();
+        if let (Some(lowest_free_block_index), Some(highest_root_table_block_index)) =
+            (lowest_free_block_index, highest_root_table_block_index)
+        {
+            if highest_root_table_block_index > lowest_free_block_index {
+                let roots = self.list_roots().into_iter().collect::<BTreeMap<_, _>>();
+                self.write_root_table(roots.clone())?;
+                self.write_root_table(roots)?;
+                self.truncate_free_blocks()?;
+            }
+        }
+
+        Ok(remap)
+    }
+
+    /// Truncate free blocks at end of storage file
+    /// - file is truncated & synced before free block map shrinks, a stale map is rebuilt on open
+    fn truncate_free_blocks(&mut self) -> Result<(), Error> {
+        let mut end_block_count = self.end_block_count;
+        while end_block_count > 0 && self.free_blocks.contains(&(end_block_count - 1)) {
+            end_block_count -= 1;
+        }
+        if end_block_count == self.end_block_count {
+            return Ok(());
+        }
+        let file_len = self.block_offset(end_block_count) as u64;
+        self.mark_dirty()?;
+        if let Err(result_error) = self.device.set_size(file_len) {
+            return Err(storage_errors::truncate_free_blocks_truncate_file(
+                result_error,
+            ));
+        }
+        if let Err(result_error) = self.device.sync() {
+            return Err(storage_errors::sync_sync_device(result_error));
+        }
+        for block_index in end_block_count..self.end_block_count {
+            self.free_blocks.remove(&block_index);
+            self.block_cache().remove(block_index);
+        }
+        self.end_block_count = end_block_count;
+        if let Some(free_block_map) = &mut self.free_block_map {
+            free_block_map.truncate(end_block_count)?;
+        }
+        Ok(())
+    }
+
+    /// Compact storage file in given path, into a new file renamed over it
+    /// - Used blocks are renumbered in order from 0, so the new file has no free blocks
+    /// - rewrite: given data of a used block & remap, returns new data of the block if it links to a moved block
+    /// - Roots are carried over, pointing to moved blocks
+    /// - Storage file stays locked till it is replaced, compacted file is locked before it is renamed over it
+    /// - A crash leaves the original file untouched, except its free block map may be removed,
+    ///   which is rebuilt on open
+    /// - Directory is synced after the renames, so the returned remap matches the file after a crash
+    /// - Compacted files are removed if writing or renaming them fails
+    /// - returns: remap of moved blocks
+    pub fn compact_file(
+        file_path: String,
+        options: StorageOptions,
+        mut rewrite: impl FnMut(&[u8], &BlockRemap) -> Option<Vec<u8>>,
+    ) -> Result<BlockRemap, Error> {
+        let mut storage = Storage::open_with_options(file_path.clone(), options.clone())?;
+        // - segments can not be replaced along with storage file, `compact` works in place
+        if storage.header.segmented() {
+            return Err(storage_errors::compact_file_segmented(&file_path));
+        }
+        if storage.shadow.is_some() {
+            return Err(storage_errors::compact_shadow_paging());
+        }
+
+        // - plan, used blocks renumbered in order
+        let used_blocks = storage.used_application_blocks();
+        let remap = used_blocks
+            .iter()
+            .enumerate()
+            .filter(|(new_block_index, old_block_index)| {
+                *new_block_index as BlockIndex != **old_block_index
+            })
+            .map(|(new_block_index, old_block_index)| {
+                (*old_block_index, new_block_index as BlockIndex)
+            })
+            .collect::<BlockRemap>();
+
+        // - write used blocks & roots to new file next to original file, removed if that fails
+        let compact_file_path = format!("{}.compact", file_path);
+        let write_result = Storage::write_compact_file(
+            &storage,
+            &compact_file_path,
+            options,
+            &used_blocks,
+            &remap,
+            &mut rewrite,
+        );
+        let _compact_lock = match write_result {
+            Ok(compact_lock) => compact_lock,
+            Err(error) => {
+                Storage::remove_compact_files(&compact_file_path);
+                return Err(error);
+            }
+        };
+
+        // - close original storage before it is replaced, keeping it locked,
+        //   so nothing is written to the replaced file on drop
+        let _lock = storage.lock.take();
+        drop(storage);
+
+        // - replace original file, stale free block map is removed first
+        let replace_result = Storage::replace_with_compact_file(&file_path, &compact_file_path);
+        if let Err(result_error) = replace_result {
+            Storage::remove_compact_files(&compact_file_path);
+            return Err(storage_errors::compact_file_replace_file(result_error));
+        }
+        // -- sync directory, else renames may be lost on a crash while callers apply the remap
+        if let Err(result_error) = Storage::sync_parent_dir(&file_path) {
+            return Err(storage_errors::compact_file_sync_dir(result_error));
+        }
+        Ok(remap)
+    }
+
+    /// Write used blocks & roots of storage to compacted file in given path, renumbered as per remap
+    /// - returns: lock of compacted file, held till it is renamed over storage file
+    fn write_compact_file(
+        storage: &Storage,
+        compact_file_path: &str,
+        options: StorageOptions,
+        used_blocks: &[BlockIndex],
+        remap: &BlockRemap,
+        rewrite: &mut impl FnMut(&[u8], &BlockRemap) -> Option<Vec<u8>>,
+    ) -> Result<StorageLock, Error> {
+        let compact_options = StorageOptions {
+            block_checksum: storage.block_checksum(),
+            block_index_width: storage.block_index_width(),
+            compression: storage.compression(),
+            sync_policy: SyncPolicy::Never,
+            ..options
+        };
+        let mut compacted_storage = Storage::new_with_options(
+            compact_file_path.to_string(),
+            storage.block_len(),
+            compact_options,
+        )?;
+        for (new_block_index, old_block_index) in used_blocks.iter().enumerate() {
+            let (_, block_data) = storage.read_block(*old_block_index)?;
+            let block_data = rewrite(&block_data, remap).unwrap_or(block_data);
+            compacted_storage.write_block(new_block_index as BlockIndex, &block_data)?;
+        }
+        let roots = storage
+            .list_roots()
+            .into_iter()
+            .map(|(name, block_index)| {
+                (
+                    name,
+                    remap.get(&block_index).cloned().unwrap_or(block_index),
+                )
+            })
+            .collect::<BTreeMap<String, BlockIndex>>();
+        if !roots.is_empty() {
+            compacted_storage.write_root_table(roots)?;
+        }
+        compacted_storage.sync()?;
+        Ok(compacted_storage.lock.take().unwrap())
+    }
+
+    /// Rename compacted file, its free block map & journal over those of storage file in given path
+    /// - stale free block map is removed first, so a crash never pairs it with compacted file
+    /// - journal of original file is empty, as it is recovered on open
+    fn replace_with_compact_file(file_path: &str, compact_file_path: &str) -> std::io::Result<()> {
+        let remove_result = std::fs::remove_file(FreeBlockMap::file_path(file_path));
+        if let Err(result_error) = remove_result {
+            if result_error.kind() != std::io::ErrorKind::NotFound {
+                return Err(result_error);
+            }
+        }
+        std::fs::rename(compact_file_path, file_path)?;
+        std::fs::rename(
+            FreeBlockMap::file_path(compact_file_path),
+            FreeBlockMap::file_path(file_path),
+        )?;
+        std::fs::rename(
+            Journal::file_path(compact_file_path),
+            Journal::file_path(file_path),
+        )
+    }
+
+    /// Remove compacted file, its free block map & journal left by a failed compaction
+    fn remove_compact_files(compact_file_path: &str) {
+        let _ = std::fs::remove_file(compact_file_path);
+        let _ = std::fs::remove_file(FreeBlockMap::file_path(compact_file_path));
+        let _ = std::fs::remove_file(Journal::file_path(compact_file_path));
     }
 
     // ... ... ... ... ... ... ... ... ... ... ... ... ... ... ... ... ...
 }
 
+impl Drop for Storage {
+    /// Sync & mark storage clean on close, so free block map is trusted on next open
+    /// - errors are ignored, storage is left dirty and counted from block headers on next open
+    fn drop(&mut self) {
+        if self.can_mark_clean() {
+            let _ = self.sync();
+        }
+    }
+}
+
 // ... ... ... ... ... ... ... ... ... ... ... ... ... ... ... ... ... ... ... ... ... ..
@@ -0,0 +1,308 @@
+use crate::shadow_pages::ShadowTable;
+use crate::{BlockIndex, BlockIndexWidth};
+use std::collections::BTreeMap;
+use std::convert::TryInto;
+use util::checksum::crc32c;
+
+/// Magic bytes at the start of every root table
+const ROOT_TABLE_MAGIC: [u8; 4] = *b"XDBR";
+
+/// 4 bytes magic, 8 bytes generation, 4 bytes table length, 4 bytes table checksum
+const ROOT_TABLE_HEADER_SIZE: usize = 20;
+
+/// Next block index of last block of a root table
+const ROOT_TABLE_END: BlockIndex = BlockIndex::MAX;
+
+/// Names of chain heads, persisted in a chain of blocks
+/// - Storage header holds 2 slots, each pointing to first block of a root table
+/// - A new table is written to new blocks, then slot of older table points to it.
+///   So the newer table survives a crash at any point, and is loaded on open.
+/// - Table is `[magic, generation, table length, table checksum, table]`,
+///   split over blocks, each starting with next block index
+/// - table: count of roots, then per root `[name length, name, block index]`, 4 bytes integers as little endian
+/// - block indexes are as wide as block indexes of storage file, 4 or 8 bytes
+/// - table of a shadow paged storage continues with its page tables, see `ShadowTable`
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RootTable {
+    /// Incremented on every update, newer table wins on open
+    pub generation: u64,
+    /// name -> block index
+    pub roots: BTreeMap<String, BlockIndex>,
+    /// Blocks holding the table, in chain order
+    pub blocks: Vec<BlockIndex>,
+    /// Page tables of a shadow paged storage, None for other storages
+    pub shadow: Option<ShadowTable>,
+}
+
+impl RootTable {
+    /// Serialize table with its header
+    pub fn to_bytes(&self, block_index_width: BlockIndexWidth) -> Vec<u8> {
+        let mut table = vec![];
+        table.extend_from_slice(&(self.roots.len() as u32).to_le_bytes());
+        for (name, block_index) in self.roots.iter() {
+            table.extend_from_slice(&(name.len() as u32).to_le_bytes());
+            table.extend_from_slice(name.as_bytes());
+            table.extend_from_slice(&block_index_width.to_bytes(*block_index));
+        }
+        if let Some(shadow) = &self.shadow {
+            table.extend_from_slice(&shadow.to_bytes(block_index_width));
+        }
+        let mut bytes = Vec::with_capacity(ROOT_TABLE_HEADER_SIZE + table.len());
+        bytes.extend_from_slice(&ROOT_TABLE_MAGIC);
+        bytes.extend_from_slice(&self.generation.to_le_bytes());
+        bytes.extend_from_slice(&(table.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&crc32c(&table).to_le_bytes());
+        bytes.extend_from_slice(&table);
+        bytes
+    }
+
+    /// Number of blocks needed to hold serialized table of given length
+    /// - block_len must be larger than next block index
+    pub fn block_count(
+        bytes_len: usize,
+        block_len: usize,
+        block_index_width: BlockIndexWidth,
+    ) -> usize {
+        bytes_len.div_ceil(block_len - block_index_width.size())
+    }
+
+    /// Split serialized table into data of given blocks, each starting with next block index
+    pub fn split_into_blocks(
+        bytes: &[u8],
+        block_indexes: &[BlockIndex],
+        block_len: usize,
+        block_index_width: BlockIndexWidth,
+    ) -> Vec<Vec<u8>> {
+        bytes
+            .chunks(block_len - block_index_width.size())
+            .enumerate()
+            .map(|(position, chunk)| {
+                let next_block_index = block_indexes
+                    .get(position + 1)
+                    .cloned()
+                    .unwrap_or(ROOT_TABLE_END);
+                [&block_index_width.to_bytes(next_block_index)[..], chunk].concat()
+            })
+            .collect()
+    }
+
+    /// Read table starting at given block, following next block indexes
+    /// - read_block: returns data of a block, None if block is missing or unreadable
+    /// - returns None if table is torn, corrupt or not a root table
+    pub fn load(
+        first_block_index: BlockIndex,
+        block_len: usize,
+        block_index_width: BlockIndexWidth,
+        read_block: impl Fn(BlockIndex) -> Option<Vec<u8>>,
+    ) -> Option<RootTable> {
+        let next_size = block_index_width.size();
+        let mut bytes = vec![];
+        let mut blocks = vec![];
+        let mut block_index = first_block_index;
+        // - table length is known once header is read
+        let mut table_len: Option<usize> = None;
+        while table_len.is_none_or(|table_len| bytes.len() < ROOT_TABLE_HEADER_SIZE + table_len) {
+            // - a chain longer than table needs, or a cycle, is corrupt
+            let max_block_count = RootTable::block_count(
+                ROOT_TABLE_HEADER_SIZE + table_len.unwrap_or(0),
+                block_len,
+                block_index_width,
+            );
+            if block_index == ROOT_TABLE_END || blocks.len() >= max_block_count {
+                return None;
+            }
+            let block_data = read_block(block_index)?;
+            if block_data.len() <= next_size {
+                return None;
+            }
+            blocks.push(block_index);
+            bytes.extend_from_slice(&block_data[next_size..]);
+            block_index = block_index_width.from_bytes(&block_data)?;
+            if table_len.is_none() && bytes.len() >= ROOT_TABLE_HEADER_SIZE {
+                if bytes[0..4] != ROOT_TABLE_MAGIC {
+                    return None;
+                }
+                table_len =
+                    Some(u32::from_le_bytes([bytes[12], bytes[13], bytes[14], bytes[15]]) as usize);
+            }
+        }
+        if bytes.len() != ROOT_TABLE_HEADER_SIZE + table_len.unwrap()
+            || block_index != ROOT_TABLE_END
+        {
+            return None;
+        }
+        let generation = u64::from_le_bytes(bytes[4..12].try_into().unwrap());
+        let checksum = u32::from_le_bytes([bytes[16], bytes[17], bytes[18], bytes[19]]);
+        let table = &bytes[ROOT_TABLE_HEADER_SIZE..];
+        if crc32c(table) != checksum {
+            return None;
+        }
+        let (roots, roots_len) = parse_roots(table, block_index_width)?;
+        // - bytes after roots are page tables of a shadow paged storage
+        let shadow = if roots_len == table.len() {
+            None
+        } else {
+            Some(ShadowTable::from_bytes(
+                &table[roots_len..],
+                block_index_width,
+            )?)
+        };
+        Some(RootTable {
+            generation,
+            roots,
+            blocks,
+            shadow,
+        })
+    }
+}
+
+/// Parse roots from start of table bytes, None if table is malformed
+/// - returns: (roots, length of roots in bytes)
+fn parse_roots(
+    table: &[u8],
+    block_index_width: BlockIndexWidth,
+) -> Option<(BTreeMap<String, BlockIndex>, usize)> {
+    fn read_u32(table: &[u8], offset: &mut usize) -> Option<u32> {
+        let bytes = table.get(*offset..*offset + 4)?;
+        *offset += 4;
+        Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+    let mut offset = 0;
+    let count = read_u32(table, &mut offset)?;
+    let mut roots = BTreeMap::new();
+    for _ in 0..count {
+        let name_len = read_u32(table, &mut offset)? as usize;
+        let name_bytes = table.get(offset..offset + name_len)?;
+        offset += name_len;
+        let name = String::from_utf8(name_bytes.to_vec()).ok()?;
+        let block_index = block_index_width.from_bytes(table.get(offset..)?)?;
+        offset += block_index_width.size();
+        roots.insert(name, block_index);
+    }
+    Some((roots, offset))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn root_table(generation: u64, roots: &[(&str, BlockIndex)]) -> RootTable {
+        RootTable {
+            generation,
+            roots: roots
+                .iter()
+                .map(|(name, block_index)| (name.to_string(), *block_index))
+                .collect(),
+            blocks: vec![],
+            shadow: None,
+        }
+    }
+
+    fn write_table(
+        table: &RootTable,
+        block_indexes: &[BlockIndex],
+        block_len: usize,
+        block_index_width: BlockIndexWidth,
+    ) -> HashMap<BlockIndex, Vec<u8>> {
+        let bytes = table.to_bytes(block_index_width);
+        assert_eq!(
+            RootTable::block_count(bytes.len(), block_len, block_index_width),
+            block_indexes.len()
+        );
+        let blocks =
+            RootTable::split_into_blocks(&bytes, block_indexes, block_len, block_index_width);
+        block_indexes.iter().cloned().zip(blocks).collect()
+    }
+
+    #[test]
+    fn test_root_table_full_flow() {
+        let table = root_table(7, &[("logs", 3), ("index", 12), ("", 0)]);
+        // 20 bytes header, 4 bytes count, (4 + 4 + 4) + (4 + 5 + 4) + (4 + 0 + 4)
+        assert_eq!(table.to_bytes(BlockIndexWidth::U32).len(), 57);
+        let blocks = write_table(&table, &[9, 2, 5, 4, 1], 16, BlockIndexWidth::U32);
+        let loaded_table = RootTable::load(9, 16, BlockIndexWidth::U32, |block_index| {
+            blocks.get(&block_index).cloned()
+        });
+        assert_eq!(
+            loaded_table,
+            Some(RootTable {
+                blocks: vec![9, 2, 5, 4, 1],
+                ..table
+            })
+        );
+        // empty table fits a block
+        let table = root_table(1, &[]);
+        let blocks = write_table(&table, &[0], 32, BlockIndexWidth::U32);
+        let loaded_table = RootTable::load(0, 32, BlockIndexWidth::U32, |block_index| {
+            blocks.get(&block_index).cloned()
+        });
+        assert_eq!(loaded_table.unwrap().roots, BTreeMap::new());
+    }
+
+    #[test]
+    fn test_root_table_shadow() {
+        let mut table = root_table(4, &[("logs", 3)]);
+        let mut shadow = ShadowTable::default();
+        shadow.pages.insert(3, 7);
+        table.shadow = Some(shadow);
+        // 20 bytes header, 4 bytes count, (4 + 4 + 4), 8 bytes page count, (4 + 4), 4 bytes snapshot count
+        assert_eq!(table.to_bytes(BlockIndexWidth::U32).len(), 56);
+        let blocks = write_table(&table, &[0, 1, 2, 3, 4], 16, BlockIndexWidth::U32);
+        let loaded_table = RootTable::load(0, 16, BlockIndexWidth::U32, |block_index| {
+            blocks.get(&block_index).cloned()
+        });
+        assert_eq!(loaded_table.unwrap().shadow, table.shadow);
+    }
+
+    #[test]
+    fn test_root_table_wide_block_index() {
+        let table = root_table(3, &[("logs", 0x1_0000_0003), ("index", 12)]);
+        // 20 bytes header, 4 bytes count, (4 + 4 + 8) + (4 + 5 + 8)
+        assert_eq!(table.to_bytes(BlockIndexWidth::U64).len(), 57);
+        let blocks = write_table(
+            &table,
+            &[0x1_0000_0000, 2, 5, 4, 1],
+            20,
+            BlockIndexWidth::U64,
+        );
+        assert_eq!(blocks[&0x1_0000_0000][0..8], [2, 0, 0, 0, 0, 0, 0, 0]);
+        let loaded_table =
+            RootTable::load(0x1_0000_0000, 20, BlockIndexWidth::U64, |block_index| {
+                blocks.get(&block_index).cloned()
+            });
+        assert_eq!(loaded_table.unwrap().roots, table.roots);
+    }
+
+    #[test]
+    fn test_root_table_torn_or_corrupt() {
+        let table = root_table(2, &[("logs", 3), ("index", 12)]);
+        let blocks = write_table(&table, &[0, 1, 2, 3, 4], 16, BlockIndexWidth::U32);
+        let load = |blocks: &HashMap<BlockIndex, Vec<u8>>| {
+            RootTable::load(0, 16, BlockIndexWidth::U32, |block_index| {
+                blocks.get(&block_index).cloned()
+            })
+        };
+        assert!(load(&blocks).is_some());
+        // - missing block
+        let mut torn_blocks = blocks.clone();
+        torn_blocks.remove(&2);
+        assert!(load(&torn_blocks).is_none());
+        // - torn block data
+        let mut torn_blocks = blocks.clone();
+        torn_blocks.get_mut(&3).unwrap().truncate(6);
+        assert!(load(&torn_blocks).is_none());
+        // - flipped bit in table
+        let mut corrupt_blocks = blocks.clone();
+        corrupt_blocks.get_mut(&2).unwrap()[6] ^= 1;
+        assert!(load(&corrupt_blocks).is_none());
+        // - not a root table
+        let mut corrupt_blocks = blocks.clone();
+        corrupt_blocks.get_mut(&0).unwrap()[4] = b'Y';
+        assert!(load(&corrupt_blocks).is_none());
+        // - cycle
+        let mut corrupt_blocks = blocks.clone();
+        corrupt_blocks.get_mut(&4).unwrap()[0..4].copy_from_slice(&0u32.to_le_bytes());
+        assert!(load(&corrupt_blocks).is_none());
+    }
+}
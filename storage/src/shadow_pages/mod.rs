@@ -0,0 +1,364 @@
+use crate::{BlockIndex, BlockIndexWidth};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Version of a shadow paged storage retained by `Storage::create_snapshot`
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Snapshot {
+    /// Roots at time of snapshot
+    pub roots: BTreeMap<String, BlockIndex>,
+    /// Logical -> physical block index at time of snapshot
+    pub pages: BTreeMap<BlockIndex, BlockIndex>,
+}
+
+/// Page tables of a shadow paged storage, persisted in root table after its roots
+/// - `[page count, pages, snapshot count, snapshots]`, a page is `[logical block index, physical block index]`,
+///   a snapshot is `[name length, name, root count, roots, page count, pages]`
+/// - counts & name lengths are 4 bytes little endian, page counts 8 bytes,
+///   block indexes are as wide as block indexes of storage file
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ShadowTable {
+    /// Logical -> physical block index of checkpointed version
+    pub pages: BTreeMap<BlockIndex, BlockIndex>,
+    /// Retained versions by name
+    pub snapshots: BTreeMap<String, Snapshot>,
+}
+
+impl ShadowTable {
+    /// Serialize page tables
+    pub fn to_bytes(&self, block_index_width: BlockIndexWidth) -> Vec<u8> {
+        let mut bytes = vec![];
+        write_pages(&mut bytes, &self.pages, block_index_width);
+        bytes.extend_from_slice(&(self.snapshots.len() as u32).to_le_bytes());
+        for (name, snapshot) in self.snapshots.iter() {
+            bytes.extend_from_slice(&(name.len() as u32).to_le_bytes());
+            bytes.extend_from_slice(name.as_bytes());
+            bytes.extend_from_slice(&(snapshot.roots.len() as u32).to_le_bytes());
+            for (root_name, block_index) in snapshot.roots.iter() {
+                bytes.extend_from_slice(&(root_name.len() as u32).to_le_bytes());
+                bytes.extend_from_slice(root_name.as_bytes());
+                bytes.extend_from_slice(&block_index_width.to_bytes(*block_index));
+            }
+            write_pages(&mut bytes, &snapshot.pages, block_index_width);
+        }
+        bytes
+    }
+
+    /// Parse page tables, None if bytes are malformed or not fully consumed
+    pub fn from_bytes(bytes: &[u8], block_index_width: BlockIndexWidth) -> Option<ShadowTable> {
+        let mut offset = 0;
+        let pages = read_pages(bytes, &mut offset, block_index_width)?;
+        let snapshot_count = read_u32(bytes, &mut offset)?;
+        let mut snapshots = BTreeMap::new();
+        for _ in 0..snapshot_count {
+            let name = read_name(bytes, &mut offset)?;
+            let root_count = read_u32(bytes, &mut offset)?;
+            let mut roots = BTreeMap::new();
+            for _ in 0..root_count {
+                let root_name = read_name(bytes, &mut offset)?;
+                let block_index = read_block_index(bytes, &mut offset, block_index_width)?;
+                roots.insert(root_name, block_index);
+            }
+            let pages = read_pages(bytes, &mut offset, block_index_width)?;
+            snapshots.insert(name, Snapshot { roots, pages });
+        }
+        if offset != bytes.len() {
+            return None;
+        }
+        Some(ShadowTable { pages, snapshots })
+    }
+}
+
+fn write_pages(
+    bytes: &mut Vec<u8>,
+    pages: &BTreeMap<BlockIndex, BlockIndex>,
+    block_index_width: BlockIndexWidth,
+) {
+    bytes.extend_from_slice(&(pages.len() as u64).to_le_bytes());
+    for (logical_block_index, physical_block_index) in pages.iter() {
+        bytes.extend_from_slice(&block_index_width.to_bytes(*logical_block_index));
+        bytes.extend_from_slice(&block_index_width.to_bytes(*physical_block_index));
+    }
+}
+
+fn read_u32(bytes: &[u8], offset: &mut usize) -> Option<u32> {
+    let value_bytes = bytes.get(*offset..*offset + 4)?;
+    *offset += 4;
+    Some(u32::from_le_bytes([
+        value_bytes[0],
+        value_bytes[1],
+        value_bytes[2],
+        value_bytes[3],
+    ]))
+}
+
+fn read_name(bytes: &[u8], offset: &mut usize) -> Option<String> {
+    let name_len = read_u32(bytes, offset)? as usize;
+    let name_bytes = bytes.get(*offset..*offset + name_len)?;
+    *offset += name_len;
+    String::from_utf8(name_bytes.to_vec()).ok()
+}
+
+fn read_block_index(
+    bytes: &[u8],
+    offset: &mut usize,
+    block_index_width: BlockIndexWidth,
+) -> Option<BlockIndex> {
+    let block_index = block_index_width.from_bytes(bytes.get(*offset..)?)?;
+    *offset += block_index_width.size();
+    Some(block_index)
+}
+
+fn read_pages(
+    bytes: &[u8],
+    offset: &mut usize,
+    block_index_width: BlockIndexWidth,
+) -> Option<BTreeMap<BlockIndex, BlockIndex>> {
+    let mut count_bytes = [0u8; 8];
+    count_bytes.copy_from_slice(bytes.get(*offset..*offset + 8)?);
+    *offset += 8;
+    let page_count = u64::from_le_bytes(count_bytes);
+    // - a count larger than bytes left is corrupt, checked before looping over it
+    if page_count > (bytes.len() - *offset) as u64 / (2 * block_index_width.size()) as u64 {
+        return None;
+    }
+    let mut pages = BTreeMap::new();
+    for _ in 0..page_count {
+        let logical_block_index = read_block_index(bytes, offset, block_index_width)?;
+        let physical_block_index = read_block_index(bytes, offset, block_index_width)?;
+        pages.insert(logical_block_index, physical_block_index);
+    }
+    Some(pages)
+}
+
+/// In memory state of a shadow paged storage
+/// - block indexes seen by application are logical, each mapped to a physical block of storage file
+/// - a write goes to a fresh physical block, so blocks of last checkpoint & snapshots are never overwritten
+/// - free logical blocks are tracked like free blocks of storage file, for block allocators
+#[derive(Clone, Debug, Default)]
+pub(crate) struct ShadowPages {
+    /// Logical -> physical block index of working version, with writes since last checkpoint
+    pages: BTreeMap<BlockIndex, BlockIndex>,
+    /// Free logical blocks, below end_block_count
+    free_blocks: BTreeSet<BlockIndex>,
+    /// Number of logical blocks, used or free
+    end_block_count: BlockIndex,
+    /// Retained versions by name, persisted on next checkpoint
+    snapshots: BTreeMap<String, Snapshot>,
+    /// Physical blocks referenced by persisted page tables, of last checkpoint & its snapshots
+    /// - released only once a checkpoint no longer references them
+    durable_blocks: BTreeSet<BlockIndex>,
+}
+
+impl ShadowPages {
+    /// State of a storage, as of given persisted page tables
+    pub fn new(table: ShadowTable) -> ShadowPages {
+        let mut shadow_pages = ShadowPages {
+            snapshots: table.snapshots,
+            ..Default::default()
+        };
+        for (logical_block_index, physical_block_index) in table.pages {
+            shadow_pages.map(logical_block_index, physical_block_index);
+        }
+        shadow_pages.durable_blocks = shadow_pages.referenced_blocks();
+        shadow_pages
+    }
+
+    /// Physical block of given logical block, None if logical block is free
+    pub fn physical(&self, logical_block_index: BlockIndex) -> Option<BlockIndex> {
+        self.pages.get(&logical_block_index).cloned()
+    }
+
+    /// Map logical block to physical block
+    /// - returns: physical block previously mapped, if any
+    pub fn map(
+        &mut self,
+        logical_block_index: BlockIndex,
+        physical_block_index: BlockIndex,
+    ) -> Option<BlockIndex> {
+        if logical_block_index >= self.end_block_count {
+            self.free_blocks
+                .extend(self.end_block_count..logical_block_index);
+            self.end_block_count = logical_block_index + 1;
+        }
+        self.free_blocks.remove(&logical_block_index);
+        self.pages.insert(logical_block_index, physical_block_index)
+    }
+
+    /// Free logical block
+    /// - returns: physical block previously mapped, if any
+    pub fn unmap(&mut self, logical_block_index: BlockIndex) -> Option<BlockIndex> {
+        let physical_block_index = self.pages.remove(&logical_block_index)?;
+        self.free_blocks.insert(logical_block_index);
+        Some(physical_block_index)
+    }
+
+    /// Check if physical block is referenced by persisted page tables, so it must not be released
+    pub fn durable(&self, physical_block_index: BlockIndex) -> bool {
+        self.durable_blocks.contains(&physical_block_index)
+    }
+
+    pub fn durable_blocks(&self) -> &BTreeSet<BlockIndex> {
+        &self.durable_blocks
+    }
+
+    pub fn free_blocks(&self) -> &BTreeSet<BlockIndex> {
+        &self.free_blocks
+    }
+
+    pub fn end_block_count(&self) -> BlockIndex {
+        self.end_block_count
+    }
+
+    /// Used logical blocks, in ascending order
+    pub fn used_blocks(&self) -> Vec<BlockIndex> {
+        self.pages.keys().cloned().collect()
+    }
+
+    pub fn snapshots(&self) -> &BTreeMap<String, Snapshot> {
+        &self.snapshots
+    }
+
+    /// Retain working version & given roots under given name, replacing a snapshot of the name
+    pub fn set_snapshot(&mut self, name: &str, roots: BTreeMap<String, BlockIndex>) {
+        let snapshot = Snapshot {
+            roots,
+            pages: self.pages.clone(),
+        };
+        self.snapshots.insert(name.to_string(), snapshot);
+    }
+
+    /// Drop snapshot of given name, its blocks are released on next checkpoint
+    pub fn remove_snapshot(&mut self, name: &str) -> Option<Snapshot> {
+        self.snapshots.remove(name)
+    }
+
+    /// Page tables to persist on checkpoint
+    pub fn table(&self) -> ShadowTable {
+        ShadowTable {
+            pages: self.pages.clone(),
+            snapshots: self.snapshots.clone(),
+        }
+    }
+
+    /// Mark page tables persisted, once checkpoint is synced
+    /// - returns: physical blocks of previous checkpoint no longer referenced, to release
+    pub fn checkpoint(&mut self) -> Vec<BlockIndex> {
+        let referenced_blocks = self.referenced_blocks();
+        let released_blocks = self
+            .durable_blocks
+            .difference(&referenced_blocks)
+            .cloned()
+            .collect();
+        self.durable_blocks = referenced_blocks;
+        released_blocks
+    }
+
+    /// Physical blocks of working version & snapshots
+    fn referenced_blocks(&self) -> BTreeSet<BlockIndex> {
+        self.pages
+            .values()
+            .chain(
+                self.snapshots
+                    .values()
+                    .flat_map(|snapshot| snapshot.pages.values()),
+            )
+            .cloned()
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shadow_table_to_bytes_and_back() {
+        let mut table = ShadowTable::default();
+        for block_index_width in [BlockIndexWidth::U32, BlockIndexWidth::U64] {
+            let bytes = table.to_bytes(block_index_width);
+            assert_eq!(
+                ShadowTable::from_bytes(&bytes, block_index_width),
+                Some(table.clone())
+            );
+        }
+        table.pages.insert(0, 3);
+        table.pages.insert(5, 1);
+        table.snapshots.insert(
+            String::from("daily"),
+            Snapshot {
+                roots: vec![(String::from("logs"), 5)].into_iter().collect(),
+                pages: vec![(5, 2)].into_iter().collect(),
+            },
+        );
+        let bytes = table.to_bytes(BlockIndexWidth::U32);
+        assert_eq!(
+            bytes.len(),
+            8 + 2 * 8 + 4 + (4 + 5) + 4 + (4 + 4 + 4) + 8 + 8
+        );
+        assert_eq!(
+            ShadowTable::from_bytes(&bytes, BlockIndexWidth::U32),
+            Some(table.clone())
+        );
+        // - truncated or padded tables are malformed
+        for len in 0..bytes.len() {
+            assert_eq!(
+                ShadowTable::from_bytes(&bytes[..len], BlockIndexWidth::U32),
+                None
+            );
+        }
+        let padded_bytes = [&bytes[..], &[0]].concat();
+        assert_eq!(
+            ShadowTable::from_bytes(&padded_bytes, BlockIndexWidth::U32),
+            None
+        );
+    }
+
+    #[test]
+    fn test_shadow_pages_checkpoint() {
+        let table = ShadowTable {
+            pages: vec![(0, 0), (2, 1)].into_iter().collect(),
+            snapshots: BTreeMap::new(),
+        };
+        let mut shadow_pages = ShadowPages::new(table.clone());
+        assert_eq!(shadow_pages.end_block_count(), 3);
+        assert_eq!(
+            shadow_pages
+                .free_blocks()
+                .iter()
+                .cloned()
+                .collect::<Vec<_>>(),
+            vec![1]
+        );
+        assert!(shadow_pages.durable(0) && shadow_pages.durable(1));
+        // - rewritten & freed blocks stay durable till checkpoint
+        assert_eq!(shadow_pages.map(0, 2), Some(0));
+        assert_eq!(shadow_pages.unmap(2), Some(1));
+        assert_eq!(shadow_pages.unmap(2), None);
+        assert_eq!(shadow_pages.map(4, 3), None);
+        assert_eq!(shadow_pages.used_blocks(), vec![0, 4]);
+        assert_eq!(
+            shadow_pages
+                .free_blocks()
+                .iter()
+                .cloned()
+                .collect::<Vec<_>>(),
+            vec![1, 2, 3]
+        );
+        assert!(shadow_pages.durable(0) && !shadow_pages.durable(2));
+        // - snapshot retains blocks of its version past checkpoint
+        shadow_pages.set_snapshot("before", BTreeMap::new());
+        assert_eq!(shadow_pages.checkpoint(), vec![0, 1]);
+        assert!(shadow_pages.durable(2) && shadow_pages.durable(3));
+        shadow_pages.map(0, 4);
+        assert_eq!(shadow_pages.checkpoint(), vec![]);
+        assert_eq!(
+            shadow_pages.remove_snapshot("before").unwrap().pages.len(),
+            2
+        );
+        assert_eq!(shadow_pages.checkpoint(), vec![2]);
+        assert_eq!(
+            shadow_pages.table().pages,
+            vec![(0, 4), (4, 3)].into_iter().collect()
+        );
+    }
+}
@@ -1,24 +1,25 @@
+use crate::{BlockCompression, BlockIndex};
 use util::error::{Error, ErrorType};
 
-// .... .... Storage::open_file_writer .... ....
+// .... .... Storage::open_file_device .... ....
 
-pub fn open_file_writer_open_file(io_error: std::io::Error) -> Error {
+pub fn open_file_device_open_file(io_error: std::io::Error) -> Error {
     Error::new(
         ErrorType::Happens,
-        "open_file_writer_failed_to_open_file",
+        "open_file_device_failed_to_open_file",
         Some(format!(
-            "Failed to open file for writing, check permissions and path.\n {}",
+            "Failed to open file for reading & writing, check permissions and path.\n {}",
             io_error
         )),
     )
 }
 
-// .... .... Storage::open_file_reader .... ....
+// .... .... Storage::open_read_only_file_device .... ....
 
-pub fn open_file_reader_open_file(io_error: std::io::Error) -> Error {
+pub fn open_read_only_file_device_open_file(io_error: std::io::Error) -> Error {
     Error::new(
         ErrorType::Happens,
-        "open_file_reader_failed_to_open_file",
+        "open_read_only_file_device_failed_to_open_file",
         Some(format!(
             "Failed to open file for reading, check permissions and path.\n {}",
             io_error
@@ -26,19 +27,189 @@ pub fn open_file_reader_open_file(io_error: std::io::Error) -> Error {
     )
 }
 
-// .... .... Storage::set_storage_header .... ....
+// .... .... Storage::open_read_only_with_options .... ....
 
-pub fn set_storage_header_seek_start(io_error: std::io::Error) -> Error {
+pub fn open_read_only_pending_journal(file_path: &str) -> Error {
     Error::new(
-        ErrorType::Unexpected,
-        "set_storage_header_failed_to_seek_write_ptr_to_start",
+        ErrorType::Happens,
+        "storage_pending_journal",
+        Some(format!(
+            "Journal holds a transaction not yet applied to storage, open it with Storage::open to redo it.\n\tFile Path: {}",
+            file_path
+        )),
+    )
+}
+
+// .... .... Storage::space .... ....
+
+pub fn space_read_size(io_error: std::io::Error) -> Error {
+    Error::new(
+        ErrorType::Happens,
+        "space_failed_to_read_size",
+        Some(format!(
+            "Failed to read size of storage file, check permissions and disk state.\n {}",
+            io_error
+        )),
+    )
+}
+
+// .... .... StorageHeader::from_bytes .... ....
+
+pub fn storage_header_invalid_magic(magic_bytes: Vec<u8>) -> Error {
+    Error::new(
+        ErrorType::Happens,
+        "storage_header_invalid_magic",
+        Some(format!(
+            "File is not a storage file, magic bytes do not match.\n\tMagic Bytes: {:?}",
+            magic_bytes
+        )),
+    )
+}
+
+pub fn storage_header_unsupported_version(version: u32) -> Error {
+    Error::new(
+        ErrorType::Happens,
+        "storage_header_unsupported_version",
+        Some(format!(
+            "Storage file format version is newer than supported, upgrade storage engine.\n\tFormat Version: {}",
+            version
+        )),
+    )
+}
+
+pub fn storage_header_unsupported_features(features: u32) -> Error {
+    Error::new(
+        ErrorType::Happens,
+        "storage_header_unsupported_features",
+        Some(format!(
+            "Storage file uses features unknown to this storage engine, upgrade storage engine.\n\tFeature Flags: {:#034b}",
+            features
+        )),
+    )
+}
+
+// .... .... Storage::new_with_options .... ....
+
+pub fn new_invalid_block_len(block_len: u32) -> Error {
+    Error::new(
+        ErrorType::Happens,
+        "new_invalid_block_len",
+        Some(format!(
+            "Block length must be greater than 0.\n\tBlock Length: {} bytes",
+            block_len
+        )),
+    )
+}
+
+pub fn new_block_len_too_large_to_compress(block_len: u32) -> Error {
+    Error::new(
+        ErrorType::Happens,
+        "new_block_len_too_large_to_compress",
+        Some(format!(
+            "Block length of a compressed storage must be below 2^31 bytes, highest bit of block data size marks a compressed block.\n\tBlock Length: {} bytes",
+            block_len
+        )),
+    )
+}
+
+pub fn new_invalid_segment_block_count(segment_block_count: BlockIndex) -> Error {
+    Error::new(
+        ErrorType::Happens,
+        "new_invalid_segment_block_count",
+        Some(format!(
+            "Segment must hold at least 1 block, and fit in 64 bits.\n\tSegment Block Count: {}",
+            segment_block_count
+        )),
+    )
+}
+
+pub fn new_remove_segments(io_error: std::io::Error) -> Error {
+    Error::new(
+        ErrorType::Happens,
+        "new_failed_to_remove_segments",
         Some(format!(
-            "Failed to seek write pointer to start of file.\n {}",
+            "Failed to remove segments of overwritten storage, check permissions and path.\n {}",
             io_error
         )),
     )
 }
 
+// .... .... Storage::create_segmented_device .... ....
+
+pub fn create_segmented_device_create_segments(io_error: std::io::Error) -> Error {
+    Error::new(
+        ErrorType::Happens,
+        "create_segmented_device_failed_to_create_segments",
+        Some(format!(
+            "Failed to create segment directory, check permissions and path.\n {}",
+            io_error
+        )),
+    )
+}
+
+// .... .... Storage::check_segment_dir .... ....
+
+pub fn check_segment_dir_mismatch(file_path: &str, segmented: bool) -> Error {
+    let problem = if segmented {
+        "Storage file is segmented, but its segment directory is missing"
+    } else {
+        "Storage file is not segmented, but a segment directory is next to it"
+    };
+    Error::new(
+        ErrorType::Critical,
+        "storage_segments_mismatch",
+        Some(format!("{}.\n\tFile Path: {}", problem, file_path)),
+    )
+}
+
+// .... .... Storage::migrate .... ....
+
+pub fn migrate_write_header(io_error: std::io::Error) -> Error {
+    Error::new(
+        ErrorType::Happens,
+        "migrate_failed_to_write_header",
+        Some(format!(
+            "Failed to write storage header to migrated file, check permissions and disk state.\n {}",
+            io_error
+        )),
+    )
+}
+
+pub fn migrate_copy_blocks(io_error: std::io::Error) -> Error {
+    Error::new(
+        ErrorType::Happens,
+        "migrate_failed_to_copy_blocks",
+        Some(format!(
+            "Failed to copy blocks to migrated file, check permissions and disk state.\n {}",
+            io_error
+        )),
+    )
+}
+
+pub fn migrate_replace_file(io_error: std::io::Error) -> Error {
+    Error::new(
+        ErrorType::Happens,
+        "migrate_failed_to_replace_file",
+        Some(format!(
+            "Failed to replace storage file with migrated file, check permissions and path.\n {}",
+            io_error
+        )),
+    )
+}
+
+pub fn migrate_sync_dir(io_error: std::io::Error) -> Error {
+    Error::new(
+        ErrorType::Happens,
+        "migrate_failed_to_sync_dir",
+        Some(format!(
+            "Failed to sync directory of migrated storage file, migration may be lost on a crash, check disk state.\n {}",
+            io_error
+        )),
+    )
+}
+
+// .... .... Storage::set_storage_header .... ....
+
 pub fn set_storage_header_write_header(io_error: std::io::Error) -> Error {
     Error::new(
         ErrorType::Happens,
@@ -63,17 +234,6 @@ pub fn set_storage_header_write_header_success(bytes_written: usize) -> Error {
 
 // .... .... Storage::get_storage_header .... ....
 
-pub fn get_storage_header_seek_start(io_error: std::io::Error) -> Error {
-    Error::new(
-        ErrorType::Unexpected,
-        "get_storage_header_failed_to_seek_read_ptr_to_start",
-        Some(format!(
-            "Failed to seek read pointer to start of file.\n {}",
-            io_error
-        )),
-    )
-}
-
 pub fn get_storage_header_read_header(io_error: std::io::Error) -> Error {
     Error::new(
         ErrorType::Happens,
@@ -96,19 +256,45 @@ pub fn get_storage_header_read_header_success(bytes_read: usize) -> Error {
     )
 }
 
-// .... .... Storage::read_storage_block_headers .... ....
+// .... .... Storage::verify_v0_storage_file .... ....
 
-pub fn read_storage_block_headers_seek_1st_block_offset(io_error: std::io::Error) -> Error {
+pub fn verify_v0_storage_file_read_file(io_error: std::io::Error) -> Error {
     Error::new(
-        ErrorType::Unexpected,
-        "read_storage_block_headers_failed_to_seek_read_ptr_to_start",
+        ErrorType::Happens,
+        "verify_v0_storage_file_failed_to_read_file",
         Some(format!(
-            "Failed to seek read pointer to start of file.\n {}",
+            "Failed to read headerless storage file, check permissions and disk state.\n {}",
             io_error
         )),
     )
 }
 
+pub fn not_storage_file(reason: String) -> Error {
+    Error::new(
+        ErrorType::Happens,
+        "not_storage_file",
+        Some(format!(
+            "File is not an XDB storage file, it has no magic bytes and does not parse as headerless format version 0.\n\tReason: {}",
+            reason
+        )),
+    )
+}
+
+// .... .... Storage::end_block_count_from_file_len .... ....
+
+pub fn end_block_count_from_file_len_read_metadata(io_error: std::io::Error) -> Error {
+    Error::new(
+        ErrorType::Happens,
+        "end_block_count_from_file_len_failed_to_read_metadata",
+        Some(format!(
+            "Failed to read length of storage file, check permissions and disk state.\n {}",
+            io_error
+        )),
+    )
+}
+
+// .... .... Storage::read_storage_block_headers .... ....
+
 pub fn read_storage_block_headers_read_block_header(io_error: std::io::Error) -> Error {
     Error::new(
         ErrorType::Happens,
@@ -131,58 +317,72 @@ pub fn read_storage_block_headers_read_block_header_success(bytes_read: usize) -
     )
 }
 
-pub fn read_storage_block_headers_seek_next_block(io_error: std::io::Error) -> Error {
+pub fn read_storage_block_headers_truncate_torn_block(io_error: std::io::Error) -> Error {
     Error::new(
-        ErrorType::Unexpected,
-        "read_storage_block_headers_failed_to_seek_read_ptr_to_next_block",
+        ErrorType::Happens,
+        "read_storage_block_headers_failed_to_truncate_torn_block",
         Some(format!(
-            "Failed to seek read pointer to next block.\n {}",
+            "Failed to truncate torn block header at end of file.\n {}",
             io_error
         )),
     )
 }
 
+pub fn read_storage_block_headers_invalid_block_data_size(
+    block_index: BlockIndex,
+    block_data_size: u64,
+) -> Error {
+    Error::new(
+        ErrorType::Critical,
+        "read_storage_block_headers_invalid_block_data_size",
+        Some(format!(
+            "Storage file is corrupt or not a storage file: Block data size exceeds block length.\n\tBlock Index: {}\n\tBlock Data Size: {} bytes",
+            block_index, block_data_size
+        )),
+    )
+}
+
 // .... .... Storage::read_block .... ....
 
-pub fn read_block_seek_block_offset(io_error: std::io::Error) -> Error {
+pub fn read_block_read_block_header(io_error: std::io::Error) -> Error {
     Error::new(
-        ErrorType::Critical,
-        "read_block_failed_to_seek_read_ptr_to_block_offset",
+        ErrorType::Happens,
+        "read_block_failed_to_read_block_header",
         Some(format!(
-            "Could be logical issue: Failed to seek read pointer to block offset.\n {}",
+            "Failed to read block header from file, check permissions and disk state.\n {}",
             io_error
         )),
     )
 }
 
-pub fn read_block_seek_block_offset_success(bytes_read: usize) -> Error {
+pub fn read_block_read_block_header_success(bytes_read: usize) -> Error {
     Error::new(
         ErrorType::Unexpected,
-        "read_block_failed_to_seek_read_ptr_to_block_offset",
+        "read_block_failed_to_read_block_header",
         Some(format!(
-            "Failed to seek read pointer to block offset.\n\tBytes Read: {} bytes",
+            "Failed to read block header from file, check permissions and path.\n\tBytes Read: {} bytes",
             bytes_read
         )),
     )
 }
 
-pub fn read_block_read_block_header(io_error: std::io::Error) -> Error {
+pub fn read_block_read_block_checksum(io_error: std::io::Error) -> Error {
     Error::new(
         ErrorType::Happens,
-        "read_block_failed_to_read_block_header",
+        "read_block_failed_to_read_block_checksum",
         Some(format!(
-            "Failed to read block header from file, check permissions and disk state.\n {}",
+            "Failed to read block checksum from file, check permissions and disk state.\n {}",
             io_error
         )),
     )
 }
 
-pub fn read_block_read_block_header_success(bytes_read: usize) -> Error {
+pub fn read_block_read_block_checksum_success(bytes_read: usize) -> Error {
     Error::new(
         ErrorType::Unexpected,
-        "read_block_failed_to_read_block_header",
+        "read_block_failed_to_read_block_checksum",
         Some(format!(
-            "Failed to read block header from file, check permissions and path.\n\tBytes Read: {} bytes",
+            "Failed to read block checksum from file, check permissions and path.\n\tBytes Read: {} bytes",
             bytes_read
         )),
     )
@@ -210,30 +410,152 @@ pub fn read_block_read_block_data_success(bytes_read: usize) -> Error {
     )
 }
 
-// .... .... Storage::write_block .... ....
+pub fn read_block_invalid_block_data_size(block_index: BlockIndex, block_data_size: u64) -> Error {
+    Error::new(
+        ErrorType::Critical,
+        "read_block_invalid_block_data_size",
+        Some(format!(
+            "Storage file corrupt: Block data size exceeds block length.\n\tBlock Index: {}\n\tBlock Data Size: {}",
+            block_index, block_data_size
+        )),
+    )
+}
 
-pub fn write_block_seek_block_offset(io_error: std::io::Error) -> Error {
+pub fn read_block_checksum_mismatch(
+    block_index: BlockIndex,
+    expected_checksum: u32,
+    actual_checksum: u32,
+) -> Error {
     Error::new(
         ErrorType::Critical,
-        "write_block_failed_to_seek_write_ptr_to_block_offset",
+        "read_block_checksum_mismatch",
+        Some(format!(
+            "Storage file is corrupt: Checksum of block data does not match checksum in block header.\n\tBlock Index: {}\n\tExpected Checksum: {:#010x}\n\tActual Checksum: {:#010x}",
+            block_index, expected_checksum, actual_checksum
+        )),
+    )
+}
+
+pub fn read_block_decompress(block_index: BlockIndex, compression: BlockCompression) -> Error {
+    Error::new(
+        ErrorType::Critical,
+        "read_block_failed_to_decompress",
+        Some(format!(
+            "Storage file is corrupt: Compressed block data can not be decompressed.\n\tBlock Index: {}\n\tCompression: {:?}",
+            block_index, compression
+        )),
+    )
+}
+
+// .... .... Storage::read_block_data_size .... ....
+
+pub fn read_block_data_size_read_block_header(io_error: std::io::Error) -> Error {
+    Error::new(
+        ErrorType::Happens,
+        "read_block_data_size_failed_to_read_block_header",
+        Some(format!(
+            "Failed to read block header from file, check permissions and disk state.\n {}",
+            io_error
+        )),
+    )
+}
+
+pub fn read_block_data_size_read_block_header_success(bytes_read: usize) -> Error {
+    Error::new(
+        ErrorType::Unexpected,
+        "read_block_data_size_failed_to_read_block_header",
+        Some(format!(
+            "Failed to read block header from file, check permissions and path.\n\tBytes Read: {} bytes",
+            bytes_read
+        )),
+    )
+}
+
+// .... .... Storage::read_compressed_data_size .... ....
+
+pub fn read_compressed_data_size_read_data_size(io_error: std::io::Error) -> Error {
+    Error::new(
+        ErrorType::Happens,
+        "read_compressed_data_size_failed_to_read_data_size",
         Some(format!(
-            "Could be logical issue: Failed to seek write pointer to block offset.\n {}",
+            "Failed to read data size of compressed block, check permissions and disk state.\n {}",
             io_error
         )),
     )
 }
 
-pub fn write_block_seek_block_offset_success(bytes_read: usize) -> Error {
+pub fn read_compressed_data_size_read_data_size_success(bytes_read: usize) -> Error {
     Error::new(
         ErrorType::Unexpected,
-        "write_block_failed_to_seek_write_ptr_to_block_offset",
+        "read_compressed_data_size_failed_to_read_data_size",
         Some(format!(
-            "Failed to seek write pointer to block offset.\n\tBytes Read: {} bytes",
+            "Failed to read data size of compressed block from file, check permissions and path.\n\tBytes Read: {} bytes",
             bytes_read
         )),
     )
 }
 
+// .... .... Storage::write_block, Storage::write_blocks, Storage::delete_block .... ....
+
+pub fn storage_read_only(operation: &str) -> Error {
+    Error::new(
+        ErrorType::Happens,
+        "storage_read_only",
+        Some(format!(
+            "Storage is opened read-only, open it with Storage::open to modify it.\n\tOperation: {}",
+            operation
+        )),
+    )
+}
+
+// .... .... Storage::search_block_allocation_indexes, Storage::write_block, Storage::write_blocks .... ....
+
+pub fn storage_full(
+    operation: &str,
+    block_index: BlockIndex,
+    quota_block_count: BlockIndex,
+) -> Error {
+    Error::new(
+        ErrorType::Happens,
+        "storage_full",
+        Some(format!(
+            "Storage would grow past its quota, delete or compact blocks, or open it with a larger quota.\n\tOperation: {}\n\tBlock Index: {}\n\tQuota: {} blocks",
+            operation, block_index, quota_block_count
+        )),
+    )
+}
+
+// .... .... Storage::write_block .... ....
+
+pub fn write_block_data_too_large(
+    block_index: BlockIndex,
+    data_len: usize,
+    block_len: u32,
+) -> Error {
+    Error::new(
+        ErrorType::Unexpected,
+        "write_block_data_too_large",
+        Some(format!(
+            "Possible logical error: Data does not fit in block.\n\tBlock Index: {}\n\tData Length: {} bytes\n\tBlock Length: {} bytes",
+            block_index, data_len, block_len
+        )),
+    )
+}
+
+pub fn write_block_index_out_of_range(
+    block_index: BlockIndex,
+    max_block_count: BlockIndex,
+) -> Error {
+    Error::new(
+        ErrorType::Unexpected,
+        "write_block_index_out_of_range",
+        Some(format!(
+            "Possible logical error: Block index does not fit in storage file, create it with 64-bit block indexes to hold more blocks.\n\tBlock Index: {}\n\tMax Block Count: {}",
+            block_index, max_block_count
+        )),
+    )
+}
+
 pub fn write_block_write_block_header(io_error: std::io::Error) -> Error {
     Error::new(
         ErrorType::Happens,
@@ -256,6 +578,28 @@ pub fn write_block_write_block_header_success(bytes_read: usize) -> Error {
     )
 }
 
+pub fn write_block_write_block_checksum(io_error: std::io::Error) -> Error {
+    Error::new(
+        ErrorType::Happens,
+        "write_block_failed_to_write_block_checksum",
+        Some(format!(
+            "Failed to write block checksum to file, check permissions and disk state.\n {}",
+            io_error
+        )),
+    )
+}
+
+pub fn write_block_write_block_checksum_success(bytes_written: usize) -> Error {
+    Error::new(
+        ErrorType::Unexpected,
+        "write_block_failed_to_write_block_checksum",
+        Some(format!(
+            "Failed to write block checksum to file, check permissions and path.\n\tBytes Written: {} bytes",
+            bytes_written
+        )),
+    )
+}
+
 pub fn write_block_write_block_data(io_error: std::io::Error) -> Error {
     Error::new(
         ErrorType::Critical,
@@ -278,30 +622,85 @@ pub fn write_block_write_block_data_success(bytes_read: usize) -> Error {
     )
 }
 
-// .... .... Storage::delete_block .... ....
+// .... .... Storage::write_blocks .... ....
 
-pub fn delete_block_seek_block_offset(io_error: std::io::Error) -> Error {
+pub fn write_blocks_data_too_large(
+    block_index: BlockIndex,
+    data_len: usize,
+    block_len: u32,
+) -> Error {
     Error::new(
-        ErrorType::Critical,
-        "delete_block_failed_to_seek_write_ptr_to_block_offset",
+        ErrorType::Unexpected,
+        "write_blocks_data_too_large",
+        Some(format!(
+            "Possible logical error: Data does not fit in block.\n\tBlock Index: {}\n\tData Length: {} bytes\n\tBlock Length: {} bytes",
+            block_index, data_len, block_len
+        )),
+    )
+}
+
+pub fn write_blocks_index_out_of_range(
+    block_index: BlockIndex,
+    max_block_count: BlockIndex,
+) -> Error {
+    Error::new(
+        ErrorType::Unexpected,
+        "write_blocks_index_out_of_range",
         Some(format!(
-            "Could be logical issue: Failed to seek write pointer to block offset.\n {}",
+            "Possible logical error: Block index does not fit in storage file, create it with 64-bit block indexes to hold more blocks.\n\tBlock Index: {}\n\tMax Block Count: {}",
+            block_index, max_block_count
+        )),
+    )
+}
+
+pub fn write_blocks_repeated_block_index(block_index: BlockIndex) -> Error {
+    Error::new(
+        ErrorType::Unexpected,
+        "write_blocks_repeated_block_index",
+        Some(format!(
+            "Possible logical error: Block index repeats in a group write.\n\tBlock Index: {}",
+            block_index
+        )),
+    )
+}
+
+pub fn write_blocks_write_blocks(io_error: std::io::Error) -> Error {
+    Error::new(
+        ErrorType::Happens,
+        "write_blocks_failed_to_write_blocks",
+        Some(format!(
+            "Failed to write blocks to file, check permissions and disk state.\n {}",
             io_error
         )),
     )
 }
 
-pub fn delete_block_seek_block_offset_success(bytes_read: usize) -> Error {
+pub fn write_blocks_write_blocks_success(bytes_written: usize) -> Error {
     Error::new(
         ErrorType::Unexpected,
-        "delete_block_failed_to_seek_write_ptr_to_block_offset",
+        "write_blocks_failed_to_write_blocks",
         Some(format!(
-            "Failed to seek write pointer to block offset.\n\tBytes Read: {} bytes",
-            bytes_read
+            "Failed to write blocks to file, check disk state.\n\tBytes Written: {} bytes",
+            bytes_written
         )),
     )
 }
 
+// .... .... Storage::punch_compressed_tail .... ....
+
+pub fn punch_compressed_tail_punch_hole(io_error: std::io::Error, offset: u64, len: u64) -> Error {
+    Error::new(
+        ErrorType::Happens,
+        "punch_compressed_tail_failed_to_punch_hole",
+        Some(format!(
+            "Failed to punch hole into unused tail of compressed block, check permissions and disk state.\n\tOffset: {}\n\tLength: {} bytes\n {}",
+            offset, len, io_error
+        )),
+    )
+}
+
+// .... .... Storage::delete_block .... ....
+
 pub fn delete_block_write_block_header(io_error: std::io::Error) -> Error {
     Error::new(
         ErrorType::Happens,
@@ -345,3 +744,213 @@ pub fn delete_block_write_block_data_success(bytes_read: usize) -> Error {
         )),
     )
 }
+
+pub fn delete_block_truncate(io_error: std::io::Error) -> Error {
+    Error::new(
+        ErrorType::Happens,
+        "delete_block_failed_to_truncate",
+        Some(format!(
+            "Failed to set size of storage file to full length of deleted block, check permissions and disk state.\n {}",
+            io_error
+        )),
+    )
+}
+
+pub fn delete_block_punch_hole(io_error: std::io::Error, offset: u64, len: u64) -> Error {
+    Error::new(
+        ErrorType::Happens,
+        "delete_block_failed_to_punch_hole",
+        Some(format!(
+            "Failed to punch hole into deleted block, check permissions and disk state.\n\tOffset: {}\n\tLength: {} bytes\n {}",
+            offset, len, io_error
+        )),
+    )
+}
+
+// .... .... Storage::sync .... ....
+
+pub fn sync_sync_device(io_error: std::io::Error) -> Error {
+    Error::new(
+        ErrorType::Happens,
+        "sync_failed_to_sync_device",
+        Some(format!(
+            "Failed to sync storage file to durable storage, recent writes may be lost.\n {}",
+            io_error
+        )),
+    )
+}
+
+// .... .... Storage::create_snapshot, Storage::remove_snapshot .... ....
+
+pub fn snapshot_no_shadow_paging(operation: &str) -> Error {
+    Error::new(
+        ErrorType::Happens,
+        "snapshot_no_shadow_paging",
+        Some(format!(
+            "Snapshots are kept by shadow paged storages only, create storage with StorageOptions {{ shadow_paging: true, .. }}.\n\tOperation: {}",
+            operation
+        )),
+    )
+}
+
+// .... .... Storage::open_snapshot .... ....
+
+pub fn open_snapshot_not_found(name: &str) -> Error {
+    Error::new(
+        ErrorType::Happens,
+        "open_snapshot_not_found",
+        Some(format!(
+            "Storage has no snapshot of given name, list them with Storage::list_snapshots.\n\tName: {}",
+            name
+        )),
+    )
+}
+
+// .... .... Storage::compact, Storage::compact_file .... ....
+
+pub fn compact_shadow_paging() -> Error {
+    Error::new(
+        ErrorType::Happens,
+        "compact_shadow_paging",
+        Some(
+            "Shadow paged storage can not be compacted, its checkpoint & snapshots point to blocks of storage file."
+                .to_string(),
+        ),
+    )
+}
+
+// .... .... Storage::commit .... ....
+
+pub fn commit_no_journal() -> Error {
+    Error::new(
+        ErrorType::Happens,
+        "commit_no_journal",
+        Some(
+            "Storage has no journal to commit transactions through, create or open it with a journal device."
+                .to_string(),
+        ),
+    )
+}
+
+// .... .... Storage::set_root, Storage::remove_root .... ....
+
+pub fn set_root_unsupported_format_version(version: u32) -> Error {
+    Error::new(
+        ErrorType::Happens,
+        "set_root_unsupported_format_version",
+        Some(format!(
+            "Roots are stored in storage header of format version 1 or later, upgrade file with Storage::migrate.\n\tFormat Version: {}",
+            version
+        )),
+    )
+}
+
+pub fn set_root_block_len_too_small(block_len: u32) -> Error {
+    Error::new(
+        ErrorType::Happens,
+        "set_root_block_len_too_small",
+        Some(format!(
+            "Block length must be larger than a block index to store roots, each block of root table starts with next block index.\n\tBlock Length: {} bytes",
+            block_len
+        )),
+    )
+}
+
+pub fn set_root_block_index_out_of_range(
+    block_index: BlockIndex,
+    max_block_count: BlockIndex,
+) -> Error {
+    Error::new(
+        ErrorType::Unexpected,
+        "set_root_block_index_out_of_range",
+        Some(format!(
+            "Possible logical error: Block index does not fit in block index width of storage file.\n\tBlock Index: {}\n\tMax Block Count: {}",
+            block_index, max_block_count
+        )),
+    )
+}
+
+// .... .... Storage::load_root_tables .... ....
+
+pub fn load_root_tables_corrupt(root_slots: [BlockIndex; 2]) -> Error {
+    Error::new(
+        ErrorType::Critical,
+        "load_root_tables_corrupt",
+        Some(format!(
+            "Neither root table slot of storage header holds an intact root table, storage file is corrupt.\n\tRoot Slots: {:?}",
+            root_slots
+        )),
+    )
+}
+
+// .... .... Storage::check_file .... ....
+
+pub fn check_file_read_file(io_error: std::io::Error) -> Error {
+    Error::new(
+        ErrorType::Happens,
+        "check_file_failed_to_read_file",
+        Some(format!(
+            "Failed to read storage file, check permissions and disk state.\n {}",
+            io_error
+        )),
+    )
+}
+
+pub fn check_file_repair(io_error: std::io::Error) -> Error {
+    Error::new(
+        ErrorType::Happens,
+        "check_file_failed_to_repair",
+        Some(format!(
+            "Failed to repair storage file, check permissions and disk state.\n {}",
+            io_error
+        )),
+    )
+}
+
+// .... .... Storage::truncate_free_blocks .... ....
+
+pub fn truncate_free_blocks_truncate_file(io_error: std::io::Error) -> Error {
+    Error::new(
+        ErrorType::Happens,
+        "truncate_free_blocks_failed_to_truncate_file",
+        Some(format!(
+            "Failed to truncate free blocks at end of storage file, check disk state.\n {}",
+            io_error
+        )),
+    )
+}
+
+// .... .... Storage::compact_file .... ....
+
+pub fn compact_file_segmented(file_path: &str) -> Error {
+    Error::new(
+        ErrorType::Happens,
+        "compact_file_segmented",
+        Some(format!(
+            "Segmented storage can not be compacted into a new file, compact it in place.\n\tFile Path: {}",
+            file_path
+        )),
+    )
+}
+
+pub fn compact_file_replace_file(io_error: std::io::Error) -> Error {
+    Error::new(
+        ErrorType::Happens,
+        "compact_file_failed_to_replace_file",
+        Some(format!(
+            "Failed to replace storage file with compacted file, check permissions and path.\n {}",
+            io_error
+        )),
+    )
+}
+
+pub fn compact_file_sync_dir(io_error: std::io::Error) -> Error {
+    Error::new(
+        ErrorType::Happens,
+        "compact_file_failed_to_sync_dir",
+        Some(format!(
+            "Failed to sync directory of compacted storage file, compaction may be lost on a crash, check disk state.\n {}",
+            io_error
+        )),
+    )
+}
@@ -0,0 +1,145 @@
+use std::fs::{File, OpenOptions, TryLockError};
+use std::time::{Duration, Instant};
+use util::error::Error;
+
+mod storage_lock_errors;
+
+/// Interval between attempts to lock a storage file held by another storage
+const STORAGE_LOCK_RETRY_INTERVAL: Duration = Duration::from_millis(10);
+
+/// How a storage file is locked
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LockMode {
+    /// Held by a storage that writes, excludes every other lock
+    Exclusive,
+    /// Held by storages that only read, excludes an exclusive lock only
+    Shared,
+}
+
+/// Advisory lock on a storage file, released on drop
+/// - `flock` on unix, `LockFileEx` on windows, so it binds every process using this lock
+/// - Lock is held through a handle of its own, so two storages in one process exclude each other too
+/// - Storage file is locked, not a lock file next to it, so a crash leaves no stale lock behind
+pub struct StorageLock {
+    // lock is released when file handle is closed
+    _file: File,
+    mode: LockMode,
+}
+
+impl StorageLock {
+    /// Lock storage file in given path
+    /// - creates an empty file if it does not exist, without truncating an existing one
+    /// - timeout: None fails at once with `storage_locked`, if another storage holds a conflicting lock
+    /// - timeout: Some retries until given time passed, then fails with `storage_locked`
+    pub fn acquire(
+        file_path: &str,
+        mode: LockMode,
+        timeout: Option<Duration>,
+    ) -> Result<StorageLock, Error> {
+        let file_result = OpenOptions::new()
+            .read(true)
+            .write(mode == LockMode::Exclusive)
+            .create(mode == LockMode::Exclusive)
+            .truncate(false)
+            .open(file_path);
+        if let Err(result_error) = file_result {
+            return Err(storage_lock_errors::acquire_open_file(result_error));
+        }
+        let file = file_result.unwrap();
+
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        loop {
+            let lock_result = match mode {
+                LockMode::Exclusive => file.try_lock(),
+                LockMode::Shared => file.try_lock_shared(),
+            };
+            match lock_result {
+                Ok(()) => return Ok(StorageLock { _file: file, mode }),
+                Err(TryLockError::Error(result_error)) => {
+                    return Err(storage_lock_errors::acquire_lock_file(result_error));
+                }
+                Err(TryLockError::WouldBlock) => {}
+            }
+            // - held by another storage, retry until deadline
+            match deadline {
+                Some(deadline) if Instant::now() < deadline => {
+                    std::thread::sleep(STORAGE_LOCK_RETRY_INTERVAL);
+                }
+                _ => {
+                    return Err(storage_lock_errors::acquire_storage_locked(
+                        file_path,
+                        &format!("{:?}", mode),
+                    ));
+                }
+            }
+        }
+    }
+
+    pub fn mode(&self) -> LockMode {
+        self.mode
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tmp_storage_file_path(tmp_dir: &tempfile::TempDir) -> String {
+        tmp_dir
+            .path()
+            .join("storage.hex")
+            .to_str()
+            .unwrap()
+            .to_string()
+    }
+
+    #[test]
+    fn test_storage_lock_modes() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let storage_file_path = tmp_storage_file_path(&tmp_dir);
+        // - exclusive excludes every other lock
+        let lock = StorageLock::acquire(&storage_file_path, LockMode::Exclusive, None).unwrap();
+        assert_eq!(lock.mode(), LockMode::Exclusive);
+        for mode in [LockMode::Exclusive, LockMode::Shared] {
+            let result = StorageLock::acquire(&storage_file_path, mode, None);
+            assert_eq!(result.err().unwrap().code(), "storage_locked");
+        }
+        drop(lock);
+        // - shared excludes exclusive only
+        let lock = StorageLock::acquire(&storage_file_path, LockMode::Shared, None).unwrap();
+        let other_lock = StorageLock::acquire(&storage_file_path, LockMode::Shared, None).unwrap();
+        let result = StorageLock::acquire(&storage_file_path, LockMode::Exclusive, None);
+        assert_eq!(result.err().unwrap().code(), "storage_locked");
+        drop(lock);
+        drop(other_lock);
+        StorageLock::acquire(&storage_file_path, LockMode::Exclusive, None).unwrap();
+    }
+
+    #[test]
+    fn test_storage_lock_timeout() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let storage_file_path = tmp_storage_file_path(&tmp_dir);
+        let lock = StorageLock::acquire(&storage_file_path, LockMode::Exclusive, None).unwrap();
+        // - gives up after timeout
+        let start = Instant::now();
+        let result = StorageLock::acquire(
+            &storage_file_path,
+            LockMode::Exclusive,
+            Some(Duration::from_millis(50)),
+        );
+        assert_eq!(result.err().unwrap().code(), "storage_locked");
+        assert!(start.elapsed() >= Duration::from_millis(50));
+        // - acquires once released within timeout
+        let release = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(50));
+            drop(lock);
+        });
+        StorageLock::acquire(
+            &storage_file_path,
+            LockMode::Exclusive,
+            Some(Duration::from_secs(10)),
+        )
+        .unwrap();
+        release.join().unwrap();
+    }
+}
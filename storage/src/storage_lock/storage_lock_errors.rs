@@ -0,0 +1,36 @@
+use util::error::{Error, ErrorType};
+
+// .... .... StorageLock::acquire .... ....
+
+pub fn acquire_open_file(io_error: std::io::Error) -> Error {
+    Error::new(
+        ErrorType::Happens,
+        "storage_lock_acquire_failed_to_open_file",
+        Some(format!(
+            "Failed to open storage file to lock it, check permissions and path.\n {}",
+            io_error
+        )),
+    )
+}
+
+pub fn acquire_lock_file(io_error: std::io::Error) -> Error {
+    Error::new(
+        ErrorType::Happens,
+        "storage_lock_acquire_failed_to_lock_file",
+        Some(format!(
+            "Failed to lock storage file, check file system supports locks.\n {}",
+            io_error
+        )),
+    )
+}
+
+pub fn acquire_storage_locked(file_path: &str, mode: &str) -> Error {
+    Error::new(
+        ErrorType::Happens,
+        "storage_locked",
+        Some(format!(
+            "Storage file is locked by another storage, close it or wait for it.\n\tFile Path: {}\n\tRequested Lock: {}",
+            file_path, mode
+        )),
+    )
+}
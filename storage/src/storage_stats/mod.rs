@@ -0,0 +1,260 @@
+use crate::{BlockCacheStats, BlockIndex};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Space usage & I/O counters of a storage, returned by `Storage::stats`
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct StorageStats {
+    /// Capacity of a block in bytes
+    pub block_len: u32,
+    /// Number of blocks in storage file, used or free
+    pub total_blocks: BlockIndex,
+    /// Number of blocks holding data, root tables included
+    pub used_blocks: BlockIndex,
+    /// Number of free blocks in storage file, reused before the file grows
+    pub free_blocks: BlockIndex,
+    /// Number of runs of adjacent free blocks, higher is more fragmented
+    pub free_runs: BlockIndex,
+    /// Number of blocks in longest run of adjacent free blocks
+    pub largest_free_run: BlockIndex,
+    /// Bytes of data stored in used blocks, headers & unused tail of blocks excluded
+    /// - size before compression for compressed blocks
+    pub data_bytes: u64,
+    /// Bytes of data as written to used blocks, compressed blocks at their compressed size
+    /// - equals data bytes unless storage is compressed
+    pub stored_data_bytes: u64,
+    /// Number of used blocks stored compressed
+    pub compressed_blocks: BlockIndex,
+    /// Length of storage file in bytes
+    pub logical_size: u64,
+    /// Bytes of disk allocated to storage file, less than logical size if it has holes
+    pub physical_size: u64,
+    /// Bytes read from storage file by block reads, reads served by block cache excluded
+    pub bytes_read: u64,
+    /// Bytes written to storage file by block writes & deletes
+    pub bytes_written: u64,
+    /// Number of blocks read, reads served by block cache included
+    pub reads: u64,
+    /// Number of blocks written
+    pub writes: u64,
+    /// Number of blocks deleted
+    pub deletes: u64,
+    /// Number of syncs of storage file to durable storage
+    pub syncs: u64,
+    /// Hit & miss counters of block cache
+    pub block_cache: BlockCacheStats,
+}
+
+impl StorageStats {
+    /// Average fraction of capacity of used blocks holding data, from 0 to 1
+    /// - 0 if storage has no used blocks
+    pub fn average_fill(&self) -> f64 {
+        if self.used_blocks == 0 || self.block_len == 0 {
+            return 0.0;
+        }
+        self.data_bytes as f64 / (self.used_blocks as f64 * self.block_len as f64)
+    }
+
+    /// Bytes of storage file not allocated on disk, logical size less physical size
+    /// - holes of deleted blocks & of unused tails of compressed blocks, as reported by `Storage::space`
+    pub fn unallocated_bytes(&self) -> u64 {
+        self.logical_size.saturating_sub(self.physical_size)
+    }
+}
+
+/// Running totals of data in used blocks, kept as blocks are written & deleted
+/// - persisted in free block map, so `Storage::stats` does not read every block header
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DataTotals {
+    /// Bytes of data, size before compression for compressed blocks
+    pub data_bytes: u64,
+    /// Bytes of data as stored, compressed blocks at their compressed size
+    pub stored_data_bytes: u64,
+    /// Number of blocks stored compressed
+    pub compressed_blocks: BlockIndex,
+}
+
+/// Size of data totals as stored, 3 unsigned integers of 8 bytes as little endian
+pub const DATA_TOTALS_SIZE: usize = 24;
+
+impl DataTotals {
+    /// Totals of a single used block
+    pub fn of_block(data_size: u64, stored_data_size: u64, compressed: bool) -> Self {
+        DataTotals {
+            data_bytes: data_size,
+            stored_data_bytes: stored_data_size,
+            compressed_blocks: compressed as BlockIndex,
+        }
+    }
+
+    /// Add totals of other blocks
+    pub fn add(&mut self, other: &DataTotals) {
+        self.data_bytes += other.data_bytes;
+        self.stored_data_bytes += other.stored_data_bytes;
+        self.compressed_blocks += other.compressed_blocks;
+    }
+
+    /// Take totals of other blocks off, a block deleted or overwritten
+    pub fn subtract(&mut self, other: &DataTotals) {
+        self.data_bytes = self.data_bytes.saturating_sub(other.data_bytes);
+        self.stored_data_bytes = self
+            .stored_data_bytes
+            .saturating_sub(other.stored_data_bytes);
+        self.compressed_blocks = self
+            .compressed_blocks
+            .saturating_sub(other.compressed_blocks);
+    }
+
+    pub fn to_bytes(self) -> [u8; DATA_TOTALS_SIZE] {
+        let mut bytes = [0u8; DATA_TOTALS_SIZE];
+        bytes[0..8].copy_from_slice(&self.data_bytes.to_le_bytes());
+        bytes[8..16].copy_from_slice(&self.stored_data_bytes.to_le_bytes());
+        bytes[16..24].copy_from_slice(&self.compressed_blocks.to_le_bytes());
+        bytes
+    }
+
+    /// Parse data totals from given bytes, at least `DATA_TOTALS_SIZE` long
+    pub fn from_bytes(bytes: &[u8]) -> Self {
+        let value_at = |offset: usize| {
+            let mut value_bytes = [0u8; 8];
+            value_bytes.copy_from_slice(&bytes[offset..offset + 8]);
+            u64::from_le_bytes(value_bytes)
+        };
+        DataTotals {
+            data_bytes: value_at(0),
+            stored_data_bytes: value_at(8),
+            compressed_blocks: value_at(16),
+        }
+    }
+
+    /// Copy totals into given stats
+    pub fn fill(&self, stats: &mut StorageStats) {
+        stats.data_bytes = self.data_bytes;
+        stats.stored_data_bytes = self.stored_data_bytes;
+        stats.compressed_blocks = self.compressed_blocks;
+    }
+}
+
+/// Cumulative I/O counters of a storage, since it was opened
+/// - atomic, as reads take shared reference to storage
+#[derive(Default)]
+pub struct IoCounters {
+    bytes_read: AtomicU64,
+    bytes_written: AtomicU64,
+    reads: AtomicU64,
+    writes: AtomicU64,
+    deletes: AtomicU64,
+    syncs: AtomicU64,
+}
+
+impl IoCounters {
+    pub fn count_read(&self) {
+        self.reads.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn count_bytes_read(&self, bytes: usize) {
+        self.bytes_read.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub fn count_writes(&self, blocks: usize, bytes: usize) {
+        self.writes.fetch_add(blocks as u64, Ordering::Relaxed);
+        self.bytes_written
+            .fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub fn count_delete(&self, bytes: usize) {
+        self.deletes.fetch_add(1, Ordering::Relaxed);
+        self.bytes_written
+            .fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub fn count_sync(&self) {
+        self.syncs.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Copy counters into given stats
+    pub fn fill(&self, stats: &mut StorageStats) {
+        stats.bytes_read = self.bytes_read.load(Ordering::Relaxed);
+        stats.bytes_written = self.bytes_written.load(Ordering::Relaxed);
+        stats.reads = self.reads.load(Ordering::Relaxed);
+        stats.writes = self.writes.load(Ordering::Relaxed);
+        stats.deletes = self.deletes.load(Ordering::Relaxed);
+        stats.syncs = self.syncs.load(Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_io_counters() {
+        let io_counters = IoCounters::default();
+        io_counters.count_read();
+        io_counters.count_bytes_read(12);
+        io_counters.count_writes(2, 24);
+        io_counters.count_delete(4);
+        io_counters.count_sync();
+        let mut stats = StorageStats::default();
+        io_counters.fill(&mut stats);
+        assert_eq!(
+            (stats.reads, stats.bytes_read, stats.writes, stats.deletes),
+            (1, 12, 2, 1)
+        );
+        assert_eq!((stats.bytes_written, stats.syncs), (28, 1));
+    }
+
+    #[test]
+    fn test_data_totals() {
+        let mut data_totals = DataTotals::default();
+        data_totals.add(&DataTotals::of_block(8, 8, false));
+        data_totals.add(&DataTotals::of_block(256, 40, true));
+        assert_eq!(
+            data_totals,
+            DataTotals {
+                data_bytes: 264,
+                stored_data_bytes: 48,
+                compressed_blocks: 1,
+            }
+        );
+        assert_eq!(DataTotals::from_bytes(&data_totals.to_bytes()), data_totals);
+        data_totals.subtract(&DataTotals::of_block(256, 40, true));
+        assert_eq!(data_totals, DataTotals::of_block(8, 8, false));
+        let mut stats = StorageStats::default();
+        data_totals.fill(&mut stats);
+        assert_eq!(
+            (
+                stats.data_bytes,
+                stats.stored_data_bytes,
+                stats.compressed_blocks
+            ),
+            (8, 8, 0)
+        );
+    }
+
+    #[test]
+    fn test_storage_stats_average_fill() {
+        let mut stats = StorageStats {
+            block_len: 8,
+            ..Default::default()
+        };
+        assert_eq!(stats.average_fill(), 0.0);
+        stats.used_blocks = 4;
+        stats.data_bytes = 16;
+        assert_eq!(stats.average_fill(), 0.5);
+    }
+
+    #[test]
+    fn test_storage_stats_unallocated_bytes() {
+        let mut stats = StorageStats {
+            logical_size: 16384,
+            physical_size: 16384,
+            ..Default::default()
+        };
+        assert_eq!(stats.unallocated_bytes(), 0);
+        stats.physical_size = 4096;
+        assert_eq!(stats.unallocated_bytes(), 12288);
+        // - file system may allocate past end of file
+        stats.physical_size = 20480;
+        assert_eq!(stats.unallocated_bytes(), 0);
+    }
+}
@@ -0,0 +1,586 @@
+use std::collections::BTreeMap;
+use storage::block_device::{Fault, FaultyBlockDevice, MemoryBlockDevice};
+use storage::{BlockIndex, Storage, StorageOptions, SyncPolicy, Transaction};
+
+/// Storage on a memory device wrapped in a fault injecting device
+/// - returns (storage, device) - device is a clone, to script faults with
+fn new_faulty_storage(block_len: u32, block_checksum: bool) -> (Storage, FaultyBlockDevice) {
+    let device = FaultyBlockDevice::new(Box::new(MemoryBlockDevice::new()));
+    let options = StorageOptions {
+        block_checksum,
+        ..Default::default()
+    };
+    let storage = Storage::new_with_device(Box::new(device.clone()), block_len, options).unwrap();
+    (storage, device)
+}
+
+/// Reopen storage on same bytes, like after a restart
+fn reopen(device: &FaultyBlockDevice) -> Result<Storage, util::error::Error> {
+    device.revive();
+    Storage::open_device(Box::new(device.clone()), StorageOptions::default())
+}
+
+#[test]
+fn storage_faults_new_and_open() {
+    // - storage header write fails
+    for fault in [Fault::Short(10), Fault::NoSpace, Fault::Io] {
+        let device = FaultyBlockDevice::new(Box::new(MemoryBlockDevice::new()));
+        device.fail_write(0, fault);
+        let result = Storage::new_with_device(Box::new(device.clone()), 8, Default::default());
+        assert_eq!(
+            result.err().unwrap().code(),
+            "set_storage_header_failed_to_write_header"
+        );
+    }
+    let (mut storage, device) = new_faulty_storage(8, false);
+    storage.write_block(0, &[1; 8]).unwrap();
+    storage.write_block(1, &[2; 3]).unwrap();
+    drop(storage);
+    // - storage header read fails, 1st read is magic bytes, 2nd read is rest of header
+    for (nth, fault) in [(0, Fault::Io), (0, Fault::Short(2)), (1, Fault::Short(20))] {
+        device.fail_read(nth, fault);
+        let result = Storage::open_device(Box::new(device.clone()), Default::default());
+        assert_eq!(
+            result.err().unwrap().code(),
+            "get_storage_header_failed_to_read_header"
+        );
+        device.revive();
+    }
+    // - block header read fails while counting free blocks
+    for fault in [Fault::Io, Fault::Short(2)] {
+        device.fail_read(3, fault);
+        let result = Storage::open_device(Box::new(device.clone()), Default::default());
+        assert_eq!(
+            result.err().unwrap().code(),
+            "read_storage_block_headers_failed_to_read_headers"
+        );
+        device.revive();
+    }
+    // - storage opens once faults are gone
+    let storage = reopen(&device).unwrap();
+    let (_, block_data) = storage.read_block(1).unwrap();
+    assert_eq!(block_data, vec![2; 3]);
+}
+
+#[test]
+fn storage_faults_write_block() {
+    let (mut storage, device) = new_faulty_storage(8, true);
+    storage.write_block(0, &[1; 8]).unwrap();
+    // - write of new block fails at header, checksum or data
+    let expected_codes = [
+        "write_block_failed_to_write_block_header",
+        "write_block_failed_to_write_block_checksum",
+        "write_block_failed_to_write_block_data",
+    ];
+    for (nth, expected_code) in expected_codes.iter().enumerate() {
+        for fault in [Fault::Short(1), Fault::NoSpace, Fault::Io] {
+            device.fail_write(nth, fault);
+            let result = storage.write_block(1, &[2; 8]);
+            assert_eq!(result.err().unwrap().code(), *expected_code);
+            // -- block is still free, other blocks are intact
+            assert_eq!(storage.search_block_allocation_indexes(1).unwrap(), vec![1]);
+            let (_, block_data) = storage.read_block(1).unwrap();
+            assert_eq!(block_data.len(), 0);
+            let (_, block_data) = storage.read_block(0).unwrap();
+            assert_eq!(block_data, vec![1; 8]);
+        }
+    }
+    // - retry succeeds
+    storage.write_block(1, &[2; 8]).unwrap();
+    assert_eq!(storage.search_block_allocation_indexes(1).unwrap(), vec![2]);
+    // - overwrite torn in block data is caught by checksum, retry repairs the block
+    device.fail_write(2, Fault::Short(3));
+    let result = storage.write_block(0, &[3; 8]);
+    assert_eq!(
+        result.err().unwrap().code(),
+        "write_block_failed_to_write_block_data"
+    );
+    let result = storage.read_block(0);
+    assert_eq!(result.err().unwrap().code(), "read_block_checksum_mismatch");
+    storage.write_block(0, &[3; 8]).unwrap();
+    let (_, block_data) = storage.read_block(0).unwrap();
+    assert_eq!(block_data, vec![3; 8]);
+}
+
+#[test]
+fn storage_faults_read_block() {
+    let (mut storage, device) = new_faulty_storage(8, true);
+    storage.write_block(0, &[1; 8]).unwrap();
+    let faults = [
+        (0, Fault::Io, "read_block_failed_to_read_block_header"),
+        (0, Fault::Short(3), "read_block_failed_to_read_block_header"),
+        (1, Fault::Io, "read_block_failed_to_read_block_checksum"),
+        (
+            1,
+            Fault::Short(1),
+            "read_block_failed_to_read_block_checksum",
+        ),
+        (2, Fault::Io, "read_block_failed_to_read_block_data"),
+        (2, Fault::Short(7), "read_block_failed_to_read_block_data"),
+    ];
+    for (nth, fault, expected_code) in faults {
+        device.fail_read(nth, fault);
+        let result = storage.read_block(0);
+        assert_eq!(result.err().unwrap().code(), expected_code);
+    }
+    let (_, block_data) = storage.read_block(0).unwrap();
+    assert_eq!(block_data, vec![1; 8]);
+}
+
+#[test]
+fn storage_faults_delete_block() {
+    let (mut storage, device) = new_faulty_storage(8, false);
+    storage.write_block(0, &[1; 8]).unwrap();
+    // - block header write fails, block is still used
+    device.fail_write(0, Fault::Io);
+    let result = storage.delete_block(0, false);
+    assert_eq!(
+        result.err().unwrap().code(),
+        "delete_block_failed_to_write_block_header"
+    );
+    assert_eq!(storage.search_block_allocation_indexes(1).unwrap(), vec![1]);
+    let (_, block_data) = storage.read_block(0).unwrap();
+    assert_eq!(block_data, vec![1; 8]);
+    // - zero fill of hard delete fails, retry succeeds
+    device.fail_write(1, Fault::NoSpace);
+    let result = storage.delete_block(0, true);
+    assert_eq!(
+        result.err().unwrap().code(),
+        "delete_block_failed_to_write_block_data"
+    );
+    assert_eq!(storage.search_block_allocation_indexes(1).unwrap(), vec![1]);
+    storage.delete_block(0, true).unwrap();
+    assert_eq!(storage.search_block_allocation_indexes(1).unwrap(), vec![0]);
+}
+
+#[test]
+fn storage_faults_delete_block_punch_hole() {
+    let device = FaultyBlockDevice::new(Box::new(MemoryBlockDevice::new()));
+    let options = StorageOptions {
+        punch_holes: true,
+        ..Default::default()
+    };
+    let mut storage = Storage::new_with_device(Box::new(device.clone()), 8, options).unwrap();
+    storage.write_blocks(&[(0, &[1; 8]), (1, &[2; 8])]).unwrap();
+    // - memory device punches holes with zeros, its write fails, block is still used
+    device.fail_write(1, Fault::Io);
+    let result = storage.delete_block(0, false);
+    assert_eq!(
+        result.err().unwrap().code(),
+        "delete_block_failed_to_punch_hole"
+    );
+    assert_eq!(storage.search_block_allocation_indexes(1).unwrap(), vec![2]);
+    storage.delete_block(0, false).unwrap();
+    assert_eq!(storage.search_block_allocation_indexes(1).unwrap(), vec![0]);
+}
+
+#[test]
+fn storage_faults_crash_recovery() {
+    type Op = (BlockIndex, Option<Vec<u8>>);
+    let ops: Vec<Op> = vec![
+        (0, Some(vec![1; 8])),
+        (1, Some(vec![2; 5])),
+        (3, Some(vec![3; 8])),
+        (1, Some(vec![4; 8])),
+        (0, None),
+        (4, Some(vec![5; 2])),
+    ];
+    // crash at every byte written by ops, till ops complete without crash
+    let mut crash_after_bytes = 0;
+    loop {
+        let (mut storage, device) = new_faulty_storage(8, true);
+        device.crash_after_bytes(crash_after_bytes);
+        // - apply ops till crash, track last committed data of each block
+        let mut committed: BTreeMap<BlockIndex, Vec<u8>> = BTreeMap::new();
+        let mut torn_op = None;
+        for (block_index, data) in ops.iter() {
+            let result = match data {
+                Some(data) => storage.write_block(*block_index, data).map(|_| ()),
+                None => storage.delete_block(*block_index, true).map(|_| ()),
+            };
+            if result.is_err() {
+                assert!(device.crashed());
+                torn_op = Some((*block_index, data.clone()));
+                break;
+            }
+            match data {
+                Some(data) => committed.insert(*block_index, data.clone()),
+                None => committed.remove(block_index),
+            };
+        }
+        drop(storage);
+        // - reopen, torn block header at end of file is truncated
+        let mut storage = reopen(&device).unwrap();
+        for block_index in 0..6 {
+            let old_data = committed.get(&block_index).cloned().unwrap_or_default();
+            let result = storage.read_block(block_index);
+            match &torn_op {
+                Some((torn_block_index, new_data)) if *torn_block_index == block_index => {
+                    // -- torn block reads old or new data, or fails cleanly
+                    match result {
+                        Ok((_, block_data)) => assert!(
+                            block_data == old_data
+                                || block_data == new_data.clone().unwrap_or_default(),
+                            "crash after {} bytes, block {}: {:?}",
+                            crash_after_bytes,
+                            block_index,
+                            block_data
+                        ),
+                        Err(error) => assert!(
+                            [
+                                "read_block_checksum_mismatch",
+                                "read_block_failed_to_read_block_data",
+                                "read_block_failed_to_read_block_checksum",
+                            ]
+                            .contains(&error.code()),
+                            "crash after {} bytes, block {}: {}",
+                            crash_after_bytes,
+                            block_index,
+                            error.code()
+                        ),
+                    }
+                }
+                // -- every other block reads committed data
+                _ => assert_eq!(result.unwrap().1, old_data),
+            }
+        }
+        // - torn block can be written again
+        if let Some((torn_block_index, _)) = torn_op {
+            storage.write_block(torn_block_index, &[9; 8]).unwrap();
+            let (_, block_data) = storage.read_block(torn_block_index).unwrap();
+            assert_eq!(block_data, vec![9; 8]);
+        } else {
+            break;
+        }
+        crash_after_bytes += 1;
+    }
+    assert!(crash_after_bytes > 0);
+}
+
+#[test]
+fn storage_faults_set_root_crash() {
+    type Op = (&'static str, Option<BlockIndex>);
+    let ops: Vec<Op> = vec![
+        ("logs", Some(1)),
+        ("index", Some(2)),
+        ("logs", Some(3)),
+        ("index", None),
+        ("a root with a name longer than a block", Some(4)),
+    ];
+    let apply = |roots: &mut BTreeMap<String, BlockIndex>, (name, block_index): &Op| {
+        match block_index {
+            Some(block_index) => roots.insert(name.to_string(), *block_index),
+            None => roots.remove(*name),
+        };
+    };
+    // crash at every byte written by ops, with & without losing unsynced writes
+    for lose_unsynced_writes in [false, true] {
+        let mut crash_after_bytes = 0;
+        loop {
+            let (mut storage, device) = new_faulty_storage(16, true);
+            storage.sync().unwrap();
+            device.crash_after_bytes(crash_after_bytes);
+            // - apply ops till crash, track last committed roots
+            let mut committed: BTreeMap<String, BlockIndex> = BTreeMap::new();
+            let mut torn_op = None;
+            for op in ops.iter() {
+                let result = match op {
+                    (name, Some(block_index)) => storage.set_root(name, *block_index),
+                    (name, None) => storage.remove_root(name).map(|_| ()),
+                };
+                if result.is_err() {
+                    assert!(device.crashed());
+                    torn_op = Some(op);
+                    break;
+                }
+                apply(&mut committed, op);
+            }
+            drop(storage);
+            if lose_unsynced_writes {
+                device.revive();
+                device.lose_unsynced_writes(|_| false).unwrap();
+            }
+            // - reopen, roots are committed roots, or torn op applied to them
+            let mut storage = reopen(&device).unwrap();
+            let roots = storage.list_roots().into_iter().collect::<BTreeMap<_, _>>();
+            let mut new_roots = committed.clone();
+            if let Some(op) = torn_op {
+                apply(&mut new_roots, op);
+            }
+            assert!(
+                roots == committed || roots == new_roots,
+                "crash after {} bytes: {:?}",
+                crash_after_bytes,
+                roots
+            );
+            // - roots can be set again
+            storage.set_root("after", 9).unwrap();
+            let storage = reopen(&device).unwrap();
+            assert_eq!(storage.get_root("after"), Some(9));
+            if torn_op.is_none() {
+                break;
+            }
+            crash_after_bytes += 1;
+        }
+        assert!(crash_after_bytes > 0);
+    }
+}
+
+#[test]
+fn storage_faults_set_root_power_failure() {
+    let old_roots = vec![(String::from("logs"), 1)];
+    let new_roots = vec![(String::from("index"), 2), (String::from("logs"), 1)];
+    // storage with synced roots, whose update fails at given sync, leaving its writes unsynced
+    let new_storage = |failed_sync: usize| {
+        let (mut storage, device) = new_faulty_storage(16, true);
+        storage.set_root("logs", 1).unwrap();
+        storage.set_root("logs", 1).unwrap();
+        device.fail_sync(failed_sync, Fault::Io);
+        let result = storage.set_root("index", 2);
+        (device, result.is_ok())
+    };
+    let mut failed_sync = 0;
+    loop {
+        let (device, updated) = new_storage(failed_sync);
+        if updated {
+            break;
+        }
+        let unsynced_write_count = device.unsynced_write_count();
+        // - power failure loses any single unsynced write, all of them, or none
+        let mut keep_patterns: Vec<Box<dyn Fn(usize) -> bool>> =
+            vec![Box::new(|_| false), Box::new(|_| true)];
+        for lost_position in 0..unsynced_write_count {
+            keep_patterns.push(Box::new(move |position| position != lost_position));
+            keep_patterns.push(Box::new(move |position| position == lost_position));
+        }
+        for keep in keep_patterns {
+            let (device, _) = new_storage(failed_sync);
+            device.lose_unsynced_writes(keep).unwrap();
+            // -- slot never points to a lost table, roots are old or new
+            let storage = reopen(&device).unwrap();
+            let roots = storage.list_roots();
+            assert!(
+                roots == old_roots || roots == new_roots,
+                "sync {} failed: {:?}",
+                failed_sync,
+                roots
+            );
+        }
+        failed_sync += 1;
+    }
+    assert_eq!(failed_sync, 2);
+}
+
+/// Journaled storage on a memory device, with journal on a sibling memory device
+/// - returns (storage, device, journal device) - devices are clones, to script faults with
+fn new_faulty_journaled_storage(
+    block_len: u32,
+    options: StorageOptions,
+) -> (Storage, FaultyBlockDevice, FaultyBlockDevice) {
+    let device = FaultyBlockDevice::new(Box::new(MemoryBlockDevice::new()));
+    let journal_device = device.new_sibling(Box::new(MemoryBlockDevice::new()));
+    let storage = Storage::new_with_devices(
+        Box::new(device.clone()),
+        Box::new(journal_device.clone()),
+        block_len,
+        options,
+    )
+    .unwrap();
+    (storage, device, journal_device)
+}
+
+/// Data of given blocks, empty for free blocks & blocks beyond end of storage
+fn read_blocks(storage: &Storage, block_indexes: &[BlockIndex]) -> Vec<Vec<u8>> {
+    block_indexes
+        .iter()
+        .map(|block_index| storage.read_block(*block_index).unwrap().1)
+        .collect()
+}
+
+#[test]
+fn storage_faults_commit_crash() {
+    let block_indexes = [0, 1, 2, 3, 4];
+    let old_blocks = vec![vec![1; 8], vec![2; 8], vec![3; 8], vec![], vec![]];
+    let new_blocks = vec![vec![6; 3], vec![], vec![7; 8], vec![], vec![8; 8]];
+    let mut transaction = Transaction::new();
+    transaction.write_block(4, &[8; 8]);
+    transaction.write_block(0, &[6; 8]);
+    transaction.delete_block(1, true);
+    transaction.write_block(2, &[7; 8]);
+    transaction.write_block(0, &[6; 3]);
+    // crash at every byte written to storage or journal by commit, till commit completes without crash
+    let mut crash_after_bytes = 0;
+    loop {
+        let options = StorageOptions {
+            block_checksum: true,
+            ..Default::default()
+        };
+        let (mut storage, device, journal_device) = new_faulty_journaled_storage(8, options);
+        for (block_index, data) in old_blocks.iter().enumerate().take(3) {
+            storage
+                .write_block(block_index as BlockIndex, data)
+                .unwrap();
+        }
+        storage.sync().unwrap();
+        device.crash_after_bytes(crash_after_bytes);
+        let result = storage.commit(&transaction);
+        let crashed = result.is_err();
+        assert_eq!(crashed, journal_device.crashed());
+        drop(storage);
+        // - reopen, journal is redone if it is intact, so blocks are all old or all new
+        // -- strict open verifies every block passes its checksum, torn writes are redone
+        device.revive();
+        let options = StorageOptions {
+            strict: true,
+            ..Default::default()
+        };
+        let mut storage = Storage::open_devices(
+            Box::new(device.clone()),
+            Box::new(journal_device.clone()),
+            options,
+        )
+        .unwrap();
+        let blocks = read_blocks(&storage, &block_indexes);
+        assert!(
+            blocks == old_blocks || blocks == new_blocks,
+            "crash after {} bytes: {:?}",
+            crash_after_bytes,
+            blocks
+        );
+        // - storage is usable after recovery
+        storage.commit(&transaction).unwrap();
+        assert_eq!(read_blocks(&storage, &block_indexes), new_blocks);
+        if !crashed {
+            break;
+        }
+        crash_after_bytes += 1;
+    }
+    assert!(crash_after_bytes > 0);
+}
+
+#[test]
+fn storage_faults_commit_power_failure() {
+    let block_indexes = [0, 1, 2];
+    let old_blocks = vec![vec![1; 8], vec![2; 8], vec![]];
+    let new_blocks = vec![vec![], vec![4; 8], vec![5; 8]];
+    // storage syncing only on commit, whose n-th sync of storage or journal fails, leaving writes unsynced
+    let new_storage = |failed_sync: usize, fail_journal: bool| {
+        let options = StorageOptions {
+            sync_policy: SyncPolicy::Never,
+            ..Default::default()
+        };
+        let (mut storage, device, journal_device) = new_faulty_journaled_storage(8, options);
+        storage.write_block(0, &[1; 8]).unwrap();
+        storage.write_block(1, &[2; 8]).unwrap();
+        storage.sync().unwrap();
+        let mut transaction = Transaction::new();
+        transaction.delete_block(0, false);
+        transaction.write_block(1, &[4; 8]);
+        transaction.write_block(2, &[5; 8]);
+        if fail_journal {
+            journal_device.fail_sync(failed_sync, Fault::Io);
+        } else {
+            device.fail_sync(failed_sync, Fault::Io);
+        }
+        let result = storage.commit(&transaction);
+        (device, journal_device, result.is_ok())
+    };
+    for fail_journal in [false, true] {
+        let mut failed_sync = 0;
+        loop {
+            let (device, journal_device, committed) = new_storage(failed_sync, fail_journal);
+            if committed {
+                break;
+            }
+            let unsynced_write_count =
+                device.unsynced_write_count() + journal_device.unsynced_write_count();
+            // - power failure loses any single unsynced write of either device, all of them, or none
+            let mut keep_patterns: Vec<Box<dyn Fn(usize) -> bool>> =
+                vec![Box::new(|_| false), Box::new(|_| true)];
+            for lost_position in 0..unsynced_write_count {
+                keep_patterns.push(Box::new(move |position| position != lost_position));
+                keep_patterns.push(Box::new(move |position| position == lost_position));
+            }
+            for keep in keep_patterns {
+                let (device, journal_device, _) = new_storage(failed_sync, fail_journal);
+                let device_unsynced_write_count = device.unsynced_write_count();
+                device.lose_unsynced_writes(&keep).unwrap();
+                journal_device
+                    .lose_unsynced_writes(|position| keep(device_unsynced_write_count + position))
+                    .unwrap();
+                // -- blocks are all old or all new
+                device.revive();
+                let storage = Storage::open_devices(
+                    Box::new(device.clone()),
+                    Box::new(journal_device.clone()),
+                    StorageOptions::default(),
+                )
+                .unwrap();
+                let blocks = read_blocks(&storage, &block_indexes);
+                assert!(
+                    blocks == old_blocks || blocks == new_blocks,
+                    "sync {} of {} failed: {:?}",
+                    failed_sync,
+                    if fail_journal { "journal" } else { "storage" },
+                    blocks
+                );
+            }
+            failed_sync += 1;
+        }
+        assert!(failed_sync > 0);
+    }
+}
+
+#[test]
+fn storage_faults_shadow_paging_crash() {
+    let block_indexes = [0, 1, 2, 3];
+    let old_blocks = vec![vec![1; 8], vec![2; 8], vec![3; 8], vec![]];
+    let new_blocks = vec![vec![6; 3], vec![], vec![7; 8], vec![8; 8]];
+    // crash at every byte written by checkpoint, till checkpoint completes without crash
+    let mut crash_after_bytes = 0;
+    loop {
+        let device = FaultyBlockDevice::new(Box::new(MemoryBlockDevice::new()));
+        let options = StorageOptions {
+            shadow_paging: true,
+            ..Default::default()
+        };
+        let mut storage = Storage::new_with_device(Box::new(device.clone()), 8, options).unwrap();
+        for (block_index, data) in old_blocks.iter().enumerate().take(3) {
+            storage
+                .write_block(block_index as BlockIndex, data)
+                .unwrap();
+        }
+        storage.set_root("logs", 0).unwrap();
+        storage.write_block(0, &[6; 3]).unwrap();
+        storage.delete_block(1, true).unwrap();
+        storage.write_block(2, &[7; 8]).unwrap();
+        storage.write_block(3, &[8; 8]).unwrap();
+        device.crash_after_bytes(crash_after_bytes);
+        let result = storage.set_root("logs", 3);
+        let crashed = result.is_err();
+        assert_eq!(crashed, device.crashed());
+        drop(storage);
+        // - reopen, blocks of last checkpoint are never overwritten, so blocks are all old or all new
+        let mut storage = reopen(&device).unwrap();
+        let blocks = read_blocks(&storage, &block_indexes);
+        let root = storage.get_root("logs");
+        assert!(
+            (blocks == old_blocks && root == Some(0)) || (blocks == new_blocks && root == Some(3)),
+            "crash after {} bytes: {:?} {:?}",
+            crash_after_bytes,
+            blocks,
+            root
+        );
+        // - storage is usable after recovery
+        storage.write_block(1, &[9; 8]).unwrap();
+        storage.checkpoint().unwrap();
+        drop(storage);
+        let storage = reopen(&device).unwrap();
+        assert_eq!(storage.read_block(1).unwrap().1, vec![9; 8]);
+        if !crashed {
+            break;
+        }
+        crash_after_bytes += 1;
+    }
+    assert!(crash_after_bytes > 0);
+}
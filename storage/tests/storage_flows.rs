@@ -1,4 +1,10 @@
-use storage::Storage;
+use storage::block_allocator::{self, BlockAllocator};
+use storage::block_device::{BlockDevice, Fault, FaultyBlockDevice, MemoryBlockDevice};
+use storage::fsck::StorageProblem;
+use storage::{
+    AllocationPolicy, BlockCompression, BlockIndex, BlockIndexWidth, QuotaWarning, Storage,
+    StorageBackend, StorageOptions, StorageQuota, SyncPolicy, Transaction, STORAGE_FORMAT_VERSION,
+};
 
 fn read_full_file(file_name: &str) -> Vec<u8> {
     use std::fs::read;
@@ -38,7 +44,7 @@ fn storage_open_new_file() {
         read_full_file(path.to_str().unwrap())
     }
     // let tmp_file_path = "./tmp/storage_open_new_file.hex";
-    let tmp_dir_path = tempfile::tempdir().unwrap().into_path();
+    let tmp_dir_path = tempfile::tempdir().unwrap().keep();
     let tmp_file_path: std::path::PathBuf = [
         tmp_dir_path.to_str().unwrap().to_string(),
         String::from("storage_open_new_file.hex"),
@@ -48,7 +54,7 @@ fn storage_open_new_file() {
     let tmp_file_path = tmp_file_path.to_str().unwrap();
     // create new storage
     let storage_result = Storage::new(String::from(tmp_file_path), 8);
-    assert_eq!(storage_result.is_ok(), true);
+    assert!(storage_result.is_ok());
     let mut storage = storage_result.unwrap();
     let expected = fetch_state("on_create.hex");
     let actual = read_full_file(tmp_file_path);
@@ -58,7 +64,7 @@ fn storage_open_new_file() {
     let result = storage.write_block(0, &block_0_data);
     assert!(result.is_ok());
     let write_ptr = result.unwrap();
-    assert_eq!(write_ptr, 16); // 4 + (4 + 8) * 0 + 4 + 8
+    assert_eq!(write_ptr, 44); // 32 + (4 + 8) * 0 + 4 + 8
     let expected = fetch_state("on_write_block_0.hex");
     let actual = read_full_file(tmp_file_path);
     assert_eq!(actual, expected);
@@ -67,7 +73,7 @@ fn storage_open_new_file() {
     let result = storage.write_block(1, &block_1_data);
     assert!(result.is_ok());
     let write_ptr = result.unwrap();
-    assert_eq!(write_ptr, 28); // 4 + (4 + 8) * 1 + 4 + 8
+    assert_eq!(write_ptr, 56); // 32 + (4 + 8) * 1 + 4 + 8
     let expected = fetch_state("on_write_block_1.hex");
     let actual = read_full_file(tmp_file_path);
     assert_eq!(actual, expected);
@@ -76,7 +82,7 @@ fn storage_open_new_file() {
     let result = storage.write_block(2, &block_2_data);
     assert!(result.is_ok());
     let write_ptr = result.unwrap();
-    assert_eq!(write_ptr, 36); // 4 + (4 + 8) * 2 + 4 + 4
+    assert_eq!(write_ptr, 64); // 32 + (4 + 8) * 2 + 4 + 4
     let expected = fetch_state("on_write_block_2.hex");
     let actual = read_full_file(tmp_file_path);
     assert_eq!(actual, expected);
@@ -84,31 +90,31 @@ fn storage_open_new_file() {
     let result = storage.read_block(2);
     assert!(result.is_ok());
     let (read_ptr, actual_data) = result.unwrap();
-    assert_eq!(read_ptr, 36); // 4 + (4 + 8) * 2 + 4 + 4
+    assert_eq!(read_ptr, 64); // 32 + (4 + 8) * 2 + 4 + 4
     assert_eq!(actual_data, block_2_data);
     // read from block 1
     let result = storage.read_block(1);
     assert!(result.is_ok());
     let (read_ptr, actual_data) = result.unwrap();
-    assert_eq!(read_ptr, 28); // 4 + (4 + 8) * 1 + 4 + 8
+    assert_eq!(read_ptr, 56); // 32 + (4 + 8) * 1 + 4 + 8
     assert_eq!(actual_data, block_1_data);
     // read from block 0
     let result = storage.read_block(0);
     assert!(result.is_ok());
     let (read_ptr, actual_data) = result.unwrap();
-    assert_eq!(read_ptr, 16); // 4 + (4 + 8) * 0 + 4 + 8
+    assert_eq!(read_ptr, 44); // 32 + (4 + 8) * 0 + 4 + 8
     assert_eq!(actual_data, block_0_data);
     // read from block 3
     let result = storage.read_block(3);
     assert!(result.is_ok());
     let (read_ptr, actual_data) = result.unwrap();
-    assert_eq!(read_ptr, 16); // no change
+    assert_eq!(read_ptr, 44); // no change
     assert_eq!(actual_data.len(), 0); // no data
                                       // soft delete_block 0
     let result = storage.delete_block(0, false);
     assert!(result.is_ok());
     let write_ptr = result.unwrap();
-    assert_eq!(write_ptr, 8); // 4 + (4 + 8) * 0 + 4 + 0
+    assert_eq!(write_ptr, 36); // 32 + (4 + 8) * 0 + 4 + 0
     let expected = fetch_state("on_soft_delete_block_0.hex");
     let actual = read_full_file(tmp_file_path);
     assert_eq!(actual, expected);
@@ -116,7 +122,7 @@ fn storage_open_new_file() {
     let result = storage.delete_block(0, true);
     assert!(result.is_ok());
     let write_ptr = result.unwrap();
-    assert_eq!(write_ptr, 16); // 4 + (4 + 8) * 0 + 4 + 8
+    assert_eq!(write_ptr, 44); // 32 + (4 + 8) * 0 + 4 + 8
     let expected = fetch_state("on_hard_delete_block_0.hex");
     let actual = read_full_file(tmp_file_path);
     assert_eq!(actual, expected);
@@ -124,7 +130,7 @@ fn storage_open_new_file() {
     let result = storage.delete_block(1, false);
     assert!(result.is_ok());
     let write_ptr = result.unwrap();
-    assert_eq!(write_ptr, 20); // 4 + (4 + 8) * 1 + 4 + 0
+    assert_eq!(write_ptr, 48); // 32 + (4 + 8) * 1 + 4 + 0
     let expected = fetch_state("on_soft_delete_block_1.hex");
     let actual = read_full_file(tmp_file_path);
     assert_eq!(actual, expected);
@@ -132,7 +138,7 @@ fn storage_open_new_file() {
     let result = storage.delete_block(2, true);
     assert!(result.is_ok());
     let write_ptr = result.unwrap();
-    assert_eq!(write_ptr, 40); // 4 + (4 + 8) * 2 + 4 + 8
+    assert_eq!(write_ptr, 68); // 32 + (4 + 8) * 2 + 4 + 8
     let expected = fetch_state("on_hard_delete_block_2.hex");
     let actual = read_full_file(tmp_file_path);
     assert_eq!(actual, expected);
@@ -151,7 +157,7 @@ fn storage_open_existing_file1() {
         read_full_file(path.to_str().unwrap())
     }
     // let tmp_file_path = "./tmp/storage_open_existing_file1.hex";
-    let tmp_dir_path = tempfile::tempdir().unwrap().into_path();
+    let tmp_dir_path = tempfile::tempdir().unwrap().keep();
     let tmp_file_path: std::path::PathBuf = [
         tmp_dir_path.to_str().unwrap().to_string(),
         String::from("storage_open_existing_file1.hex"),
@@ -242,7 +248,7 @@ fn storage_open_existing_file2() {}
 #[test]
 fn storage_open_existing_file1_test_abstract_fn() {
     // let tmp_file_path = "./tmp/storage_open_existing_file1.hex";
-    let tmp_dir_path = tempfile::tempdir().unwrap().into_path();
+    let tmp_dir_path = tempfile::tempdir().unwrap().keep();
     let tmp_file_path: std::path::PathBuf = [
         tmp_dir_path.to_str().unwrap().to_string(),
         String::from("storage_open_existing_file1.hex"),
@@ -258,23 +264,2278 @@ fn storage_open_existing_file1_test_abstract_fn() {
     let storage = Storage::open(String::from(tmp_file_path)).unwrap();
     // available free blocks: {0, 1}, endblock: 2
     // - search for 1 block
-    let expected = vec![0_u32];
-    let actual = storage.search_block_allocation_indexes(1);
+    let expected = vec![0 as BlockIndex];
+    let actual = storage.search_block_allocation_indexes(1).unwrap();
     assert_eq!(actual, expected);
     // - search for 2 blocks
     let expected = vec![0, 1];
-    let actual = storage.search_block_allocation_indexes(2);
+    let actual = storage.search_block_allocation_indexes(2).unwrap();
     assert_eq!(actual, expected);
     // - search for 3 blocks
     let expected = vec![0, 1, 3];
-    let actual = storage.search_block_allocation_indexes(3);
+    let actual = storage.search_block_allocation_indexes(3).unwrap();
     assert_eq!(actual, expected);
     // - search for 4 blocks
     let expected = vec![0, 1, 3, 4];
-    let actual = storage.search_block_allocation_indexes(4);
+    let actual = storage.search_block_allocation_indexes(4).unwrap();
     assert_eq!(actual, expected);
     // - search for 5 blocks
     let expected = vec![0, 1, 3, 4, 5];
-    let actual = storage.search_block_allocation_indexes(5);
+    let actual = storage.search_block_allocation_indexes(5).unwrap();
     assert_eq!(actual, expected);
 }
+
+#[test]
+fn storage_block_checksum() {
+    let tmp_dir_path = tempfile::tempdir().unwrap().keep();
+    let tmp_file_path: std::path::PathBuf = [
+        tmp_dir_path.to_str().unwrap().to_string(),
+        String::from("storage_block_checksum.hex"),
+    ]
+    .iter()
+    .collect();
+    let tmp_file_path = tmp_file_path.to_str().unwrap();
+    // create new storage with block checksum
+    let options = StorageOptions {
+        block_checksum: true,
+        ..Default::default()
+    };
+    let mut storage = Storage::new_with_options(String::from(tmp_file_path), 8, options).unwrap();
+    assert!(storage.block_checksum());
+    assert_eq!(storage.block_len(), 8);
+    // write to block 0 and 1
+    let block_0_data = vec![1_u8, 2_u8, 3_u8, 4_u8, 5_u8, 6_u8, 7_u8, 8_u8];
+    let write_ptr = storage.write_block(0, &block_0_data).unwrap();
+    assert_eq!(write_ptr, 48); // 32 + (4 + 4 + 8) * 0 + 4 + 4 + 8
+    let block_1_data = vec![9_u8, 10_u8, 11_u8];
+    let write_ptr = storage.write_block(1, &block_1_data).unwrap();
+    assert_eq!(write_ptr, 59); // 32 + (4 + 4 + 8) * 1 + 4 + 4 + 3
+    let actual = read_full_file(tmp_file_path);
+    assert_eq!(actual[8..12], [1, 0, 0, 0]); // feature flags, with block checksum
+    assert_eq!(actual[12..16], [8, 0, 0, 0]); // block_len
+    assert_eq!(
+        actual[36..40],
+        util::checksum::crc32c(&block_0_data).to_le_bytes()
+    );
+    // read blocks
+    let (read_ptr, actual_data) = storage.read_block(1).unwrap();
+    assert_eq!(read_ptr, 59);
+    assert_eq!(actual_data, block_1_data);
+    // reopen storage, checksum flag is loaded from storage header
+    drop(storage);
+    let mut storage = Storage::open(String::from(tmp_file_path)).unwrap();
+    assert!(storage.block_checksum());
+    assert_eq!(storage.block_len(), 8);
+    assert_eq!(storage.search_block_allocation_indexes(1).unwrap(), vec![2]);
+    let (_, actual_data) = storage.read_block(0).unwrap();
+    assert_eq!(actual_data, block_0_data);
+    // hard delete block 0
+    let write_ptr = storage.delete_block(0, true).unwrap();
+    assert_eq!(write_ptr, 48);
+    assert_eq!(storage.search_block_allocation_indexes(1).unwrap(), vec![0]);
+    // corrupt a byte of block 1 data
+    drop(storage);
+    let mut bytes = read_full_file(tmp_file_path);
+    bytes[32 + 16 + 4 + 4] ^= 0xFF;
+    std::fs::write(tmp_file_path, &bytes).unwrap();
+    let mut storage = Storage::open(String::from(tmp_file_path)).unwrap();
+    let result = storage.read_block(1);
+    assert!(result.is_err());
+    assert_eq!(result.err().unwrap().code(), "read_block_checksum_mismatch");
+    // rewrite block 1 restores checksum
+    storage.write_block(1, &block_1_data).unwrap();
+    let (_, actual_data) = storage.read_block(1).unwrap();
+    assert_eq!(actual_data, block_1_data);
+    // clear clutter
+    remove_dir_contents(tmp_dir_path);
+}
+
+#[test]
+fn storage_format_version_and_migrate() {
+    let tmp_dir_path = tempfile::tempdir().unwrap().keep();
+    let tmp_file_path: std::path::PathBuf = [
+        tmp_dir_path.to_str().unwrap().to_string(),
+        String::from("storage_format_version_and_migrate.hex"),
+    ]
+    .iter()
+    .collect();
+    let tmp_file_path = tmp_file_path.to_str().unwrap();
+    // new storage is created in current format version
+    let storage = Storage::new(String::from(tmp_file_path), 8).unwrap();
+    assert_eq!(storage.format_version(), STORAGE_FORMAT_VERSION);
+    drop(storage);
+    let storage = Storage::open(String::from(tmp_file_path)).unwrap();
+    assert_eq!(storage.format_version(), STORAGE_FORMAT_VERSION);
+    drop(storage);
+    // migrate is a no-op for current format version
+    let old_version = Storage::migrate(String::from(tmp_file_path)).unwrap();
+    assert_eq!(old_version, STORAGE_FORMAT_VERSION);
+
+    // foreign file is rejected, before a journal is created next to it
+    std::fs::remove_file(format!("{}.journal", tmp_file_path)).unwrap();
+    std::fs::write(tmp_file_path, b"hello world, not a storage file").unwrap();
+    let result = Storage::open(String::from(tmp_file_path));
+    assert_eq!(result.err().unwrap().code(), "not_storage_file");
+    assert!(!std::path::Path::new(&format!("{}.journal", tmp_file_path)).exists());
+    // - block headers fit, but file ends in the middle of a block
+    let mut bytes = vec![8, 0, 0, 0, 2, 0, 0, 0, 1, 2, 0, 0, 0, 0, 0, 0];
+    bytes.extend_from_slice(&[3, 0, 0, 0, 1, 2]);
+    std::fs::write(tmp_file_path, &bytes).unwrap();
+    let result = Storage::open(String::from(tmp_file_path));
+    assert_eq!(result.err().unwrap().code(), "not_storage_file");
+    std::fs::write(tmp_file_path, [0u8; 4]).unwrap();
+    let result = Storage::open(String::from(tmp_file_path));
+    assert_eq!(result.err().unwrap().code(), "storage_header_invalid_magic");
+    // too new format version is rejected
+    let mut bytes = vec![b'X', b'D', b'B', b'S', 2, 0, 0, 0, 0, 0, 0, 0, 8, 0, 0, 0];
+    bytes.extend_from_slice(&[0u8; 16]);
+    std::fs::write(tmp_file_path, &bytes).unwrap();
+    let result = Storage::open(String::from(tmp_file_path));
+    assert_eq!(
+        result.err().unwrap().code(),
+        "storage_header_unsupported_version"
+    );
+
+    // headerless file of format version 0 opens as is
+    let src_path: std::path::PathBuf = [
+        "tests/samples/storage_open_existing_file1",
+        "w-0_w-1_w-2_sd-0_hd-0_sd-1_hd-2_w-3_w-4_w-5_sd-3.hex",
+    ]
+    .iter()
+    .collect();
+    std::fs::copy(&src_path, tmp_file_path).unwrap();
+    let storage = Storage::open(String::from(tmp_file_path)).unwrap();
+    assert_eq!(storage.format_version(), 0);
+    let (_, block_5_data) = storage.read_block(5).unwrap();
+    assert_eq!(block_5_data, vec![5_u8, 10_u8, 20_u8, 40_u8, 80_u8]);
+    let allocation_indexes = storage.search_block_allocation_indexes(3).unwrap();
+    drop(storage);
+    // migrate to current format version
+    let old_version = Storage::migrate(String::from(tmp_file_path)).unwrap();
+    assert_eq!(old_version, 0);
+    let v0_bytes = read_full_file(src_path.to_str().unwrap());
+    let actual = read_full_file(tmp_file_path);
+    assert_eq!(actual.len(), v0_bytes.len() + 28);
+    assert_eq!(actual[0..4], *b"XDBS");
+    assert_eq!(actual[12..16], v0_bytes[0..4]); // block_len
+    assert_eq!(actual[32..], v0_bytes[4..]); // blocks are copied as is
+    assert!(!std::path::Path::new(&format!("{}.migrate", tmp_file_path)).exists());
+    let storage = Storage::open(String::from(tmp_file_path)).unwrap();
+    assert_eq!(storage.format_version(), STORAGE_FORMAT_VERSION);
+    assert_eq!(storage.block_len(), 8);
+    let (_, actual_data) = storage.read_block(5).unwrap();
+    assert_eq!(actual_data, block_5_data);
+    let (_, actual_data) = storage.read_block(3).unwrap();
+    assert_eq!(actual_data.len(), 0); // soft deleted
+    assert_eq!(
+        storage.search_block_allocation_indexes(3).unwrap(),
+        allocation_indexes
+    );
+    // clear clutter
+    remove_dir_contents(tmp_dir_path);
+}
+
+#[test]
+fn storage_free_block_map() {
+    let tmp_dir_path = tempfile::tempdir().unwrap().keep();
+    let tmp_file_path: std::path::PathBuf = [
+        tmp_dir_path.to_str().unwrap().to_string(),
+        String::from("storage_free_block_map.hex"),
+    ]
+    .iter()
+    .collect();
+    let tmp_file_path = tmp_file_path.to_str().unwrap();
+    let free_block_map_path = format!("{}.free", tmp_file_path);
+    // create storage with blocks 0..5, delete block 1 & 3
+    let mut storage = Storage::new(String::from(tmp_file_path), 8).unwrap();
+    for block_index in 0..5 {
+        storage
+            .write_block(block_index, &[block_index as u8; 8])
+            .unwrap();
+    }
+    storage.delete_block(1, false).unwrap();
+    storage.delete_block(3, true).unwrap();
+    drop(storage);
+    let free_block_map = read_full_file(&free_block_map_path);
+    assert_eq!(free_block_map[..8], [b'X', b'D', b'B', b'F', 5, 0, 0, 0]);
+    // - data bytes, stored data bytes & compressed blocks of used blocks 0, 2 & 4
+    assert_eq!(free_block_map[8..16], 24u64.to_le_bytes());
+    assert_eq!(free_block_map[16..24], 24u64.to_le_bytes());
+    assert_eq!(free_block_map[24..32], 0u64.to_le_bytes());
+    assert_eq!(free_block_map[32..], [0b0000_1010]);
+    // reopen loads free blocks from free block map
+    let storage = Storage::open(String::from(tmp_file_path)).unwrap();
+    assert_eq!(
+        storage.search_block_allocation_indexes(3).unwrap(),
+        vec![1, 3, 5]
+    );
+    drop(storage);
+    // free block map is trusted over block headers, while it matches storage file length
+    let mut bytes = read_full_file(tmp_file_path);
+    bytes[32 + 12 * 2..32 + 12 * 2 + 4].copy_from_slice(&[0, 0, 0, 0]);
+    std::fs::write(tmp_file_path, &bytes).unwrap();
+    let storage = Storage::open(String::from(tmp_file_path)).unwrap();
+    assert_eq!(
+        storage.search_block_allocation_indexes(3).unwrap(),
+        vec![1, 3, 5]
+    );
+    // - stats take data totals from it too
+    assert_eq!(storage.stats().unwrap().data_bytes, 24);
+    drop(storage);
+    // storage not marked clean, as left by a crash, falls back to scan of block headers
+    let mut bytes = read_full_file(tmp_file_path);
+    assert_eq!(bytes[11], 0x80);
+    bytes[11] = 0;
+    std::fs::write(tmp_file_path, &bytes).unwrap();
+    let mut storage = Storage::open(String::from(tmp_file_path)).unwrap();
+    assert_eq!(
+        storage.search_block_allocation_indexes(4).unwrap(),
+        vec![1, 2, 3, 5]
+    );
+    // - sync marks storage clean, a change marks it dirty before it is made
+    storage.sync().unwrap();
+    assert_eq!(read_full_file(tmp_file_path)[11], 0x80);
+    storage.write_block(2, &[2; 8]).unwrap();
+    storage.delete_block(2, false).unwrap();
+    assert_eq!(read_full_file(tmp_file_path)[11], 0);
+    drop(storage);
+    assert_eq!(read_full_file(tmp_file_path)[11], 0x80);
+    // missing free block map falls back to scan of block headers, and is rebuilt
+    std::fs::remove_file(&free_block_map_path).unwrap();
+    let storage = Storage::open(String::from(tmp_file_path)).unwrap();
+    assert_eq!(
+        storage.search_block_allocation_indexes(4).unwrap(),
+        vec![1, 2, 3, 5]
+    );
+    drop(storage);
+    let free_block_map = read_full_file(&free_block_map_path);
+    assert_eq!(free_block_map[..8], [b'X', b'D', b'B', b'F', 5, 0, 0, 0]);
+    assert_eq!(free_block_map[8..16], 16u64.to_le_bytes());
+    assert_eq!(free_block_map[32..], [0b0000_1110]);
+    // stale free block map, storage file written without it, falls back to scan
+    let mut bytes = read_full_file(tmp_file_path);
+    bytes.extend_from_slice(&[0; 12]); // block 5, free
+    bytes.extend_from_slice(&[1, 0, 0, 0, 6]); // block 6, used
+    std::fs::write(tmp_file_path, &bytes).unwrap();
+    let mut storage = Storage::open(String::from(tmp_file_path)).unwrap();
+    assert_eq!(
+        storage.search_block_allocation_indexes(5).unwrap(),
+        vec![1, 2, 3, 5, 7]
+    );
+    // blocks skipped by a write beyond end are free
+    storage.write_block(10, &[10]).unwrap();
+    assert_eq!(
+        storage.search_block_allocation_indexes(6).unwrap(),
+        vec![1, 2, 3, 5, 7, 8]
+    );
+    drop(storage);
+    let storage = Storage::open(String::from(tmp_file_path)).unwrap();
+    assert_eq!(
+        storage.search_block_allocation_indexes(8).unwrap(),
+        vec![1, 2, 3, 5, 7, 8, 9, 11]
+    );
+    // clear clutter
+    remove_dir_contents(tmp_dir_path);
+}
+
+#[test]
+fn storage_block_cache() {
+    let tmp_dir_path = tempfile::tempdir().unwrap().keep();
+    let tmp_file_path: std::path::PathBuf = [
+        tmp_dir_path.to_str().unwrap().to_string(),
+        String::from("storage_block_cache.hex"),
+    ]
+    .iter()
+    .collect();
+    let tmp_file_path = tmp_file_path.to_str().unwrap();
+    let options = StorageOptions {
+        block_cache_capacity: 2,
+        ..Default::default()
+    };
+    // writes go through to file and update the cache
+    let mut storage =
+        Storage::new_with_options(String::from(tmp_file_path), 8, options.clone()).unwrap();
+    for block_index in 0..3 {
+        storage
+            .write_block(block_index, &[block_index as u8; 8])
+            .unwrap();
+    }
+    let stats = storage.block_cache_stats();
+    assert_eq!(stats.capacity, 2);
+    assert_eq!(stats.cached_blocks, 2);
+    // block 1 & 2 are cached, block 0 is evicted
+    let (_, block_data) = storage.read_block(2).unwrap();
+    assert_eq!(block_data, vec![2; 8]);
+    let (_, block_data) = storage.read_block(1).unwrap();
+    assert_eq!(block_data, vec![1; 8]);
+    let (read_ptr, block_data) = storage.read_block(0).unwrap();
+    assert_eq!(read_ptr, 44); // 32 + (4 + 8) * 0 + 4 + 8
+    assert_eq!(block_data, vec![0; 8]);
+    let stats = storage.block_cache_stats();
+    assert_eq!((stats.hits, stats.misses), (2, 1));
+    // block 0 is cached on read, served from cache without moving read pointer
+    let (read_ptr, block_data) = storage.read_block(0).unwrap();
+    assert_eq!(read_ptr, 44);
+    assert_eq!(block_data, vec![0; 8]);
+    // overwrite is visible through the cache
+    storage.write_block(0, &[9, 9]).unwrap();
+    let (_, block_data) = storage.read_block(0).unwrap();
+    assert_eq!(block_data, vec![9, 9]);
+    // deleted block is dropped from cache
+    storage.delete_block(0, false).unwrap();
+    let (_, block_data) = storage.read_block(0).unwrap();
+    assert_eq!(block_data.len(), 0);
+    let stats = storage.block_cache_stats();
+    assert_eq!((stats.hits, stats.misses), (4, 1));
+    assert_eq!(stats.cached_blocks, 1);
+    drop(storage);
+    // cache is configured on open
+    let storage = Storage::open_with_options(String::from(tmp_file_path), options).unwrap();
+    storage.read_block(1).unwrap();
+    storage.read_block(1).unwrap();
+    let stats = storage.block_cache_stats();
+    assert_eq!((stats.hits, stats.misses), (1, 1));
+    drop(storage);
+    // cache is disabled by default
+    let storage = Storage::open(String::from(tmp_file_path)).unwrap();
+    storage.read_block(1).unwrap();
+    storage.read_block(1).unwrap();
+    assert_eq!(storage.block_cache_stats(), Default::default());
+    // clear clutter
+    remove_dir_contents(tmp_dir_path);
+}
+
+#[test]
+fn storage_block_devices() {
+    fn run_flow(storage: &mut Storage) {
+        storage.write_block(0, &[1, 2, 3, 4]).unwrap();
+        storage.write_block(3, &[5; 8]).unwrap();
+        storage.write_block(1, &[6, 7]).unwrap();
+        storage.delete_block(1, true).unwrap();
+        storage.delete_block(0, false).unwrap();
+        assert_eq!(
+            storage.search_block_allocation_indexes(4).unwrap(),
+            vec![0, 1, 2, 4]
+        );
+        let (_, block_data) = storage.read_block(3).unwrap();
+        assert_eq!(block_data, vec![5; 8]);
+    }
+    fn device_bytes(device: &dyn BlockDevice) -> Vec<u8> {
+        let mut bytes = vec![0u8; device.size().unwrap() as usize];
+        device.read_at(&mut bytes, 0).unwrap();
+        bytes
+    }
+    let tmp_dir_path = tempfile::tempdir().unwrap().keep();
+    let tmp_file_path = |file_name: &str| -> String {
+        let tmp_file_path: std::path::PathBuf = [
+            tmp_dir_path.to_str().unwrap().to_string(),
+            String::from(file_name),
+        ]
+        .iter()
+        .collect();
+        tmp_file_path.to_str().unwrap().to_string()
+    };
+    // file backend
+    let file_path = tmp_file_path("storage_block_devices_file.hex");
+    let mut storage = Storage::new(file_path.clone(), 8).unwrap();
+    run_flow(&mut storage);
+    drop(storage);
+    let file_bytes = read_full_file(&file_path);
+    // mmap backend writes the same bytes
+    let mmap_file_path = tmp_file_path("storage_block_devices_mmap.hex");
+    let mmap_options = StorageOptions {
+        backend: StorageBackend::Mmap,
+        ..Default::default()
+    };
+    let mut storage =
+        Storage::new_with_options(mmap_file_path.clone(), 8, mmap_options.clone()).unwrap();
+    run_flow(&mut storage);
+    drop(storage);
+    assert_eq!(read_full_file(&mmap_file_path), file_bytes);
+    let storage = Storage::open_with_options(mmap_file_path, mmap_options).unwrap();
+    assert_eq!(
+        storage.search_block_allocation_indexes(4).unwrap(),
+        vec![0, 1, 2, 4]
+    );
+    let (_, block_data) = storage.read_block(3).unwrap();
+    assert_eq!(block_data, vec![5; 8]);
+    // in memory storage writes the same bytes, but is never marked clean without a free block map
+    let mut storage = Storage::new_in_memory(8, StorageOptions::default()).unwrap();
+    run_flow(&mut storage);
+    let mut file_bytes = file_bytes;
+    assert_eq!(file_bytes[11], 0x80);
+    file_bytes[11] = 0;
+    assert_eq!(device_bytes(storage.device()), file_bytes);
+    // storage file opened on a memory device
+    let device = MemoryBlockDevice::from_bytes(file_bytes);
+    let storage = Storage::open_device(Box::new(device), StorageOptions::default()).unwrap();
+    assert_eq!(storage.block_len(), 8);
+    assert_eq!(
+        storage.search_block_allocation_indexes(4).unwrap(),
+        vec![0, 1, 2, 4]
+    );
+    let (_, block_data) = storage.read_block(3).unwrap();
+    assert_eq!(block_data, vec![5; 8]);
+    // clear clutter
+    remove_dir_contents(tmp_dir_path);
+}
+
+#[test]
+fn storage_sync_policy() {
+    /// Storage with given sync policy, on a device counting syncs
+    fn new_storage(sync_policy: SyncPolicy) -> (Storage, FaultyBlockDevice) {
+        let device = FaultyBlockDevice::new(Box::new(MemoryBlockDevice::new()));
+        let options = StorageOptions {
+            sync_policy,
+            ..Default::default()
+        };
+        let storage = Storage::new_with_device(Box::new(device.clone()), 8, options).unwrap();
+        (storage, device)
+    }
+    fn sync_count(device: &FaultyBlockDevice) -> usize {
+        device.call_counts().2
+    }
+    // - never: only explicit syncs
+    let (mut storage, device) = new_storage(SyncPolicy::Never);
+    storage.write_block(0, &[1; 8]).unwrap();
+    storage.delete_block(0, false).unwrap();
+    storage.sync_barrier().unwrap();
+    assert_eq!(sync_count(&device), 0);
+    storage.sync().unwrap();
+    assert_eq!(sync_count(&device), 1);
+    assert_eq!(device.unsynced_write_count(), 0);
+    // - always: storage header, every write & delete
+    let (mut storage, device) = new_storage(SyncPolicy::Always);
+    assert_eq!(sync_count(&device), 1);
+    storage.write_block(0, &[1; 8]).unwrap();
+    storage.write_block(1, &[2; 8]).unwrap();
+    storage.delete_block(0, true).unwrap();
+    assert_eq!(sync_count(&device), 4);
+    // -- no-op delete is not a write
+    storage.delete_block(0, false).unwrap();
+    // -- barrier has nothing to sync
+    storage.sync_barrier().unwrap();
+    assert_eq!(sync_count(&device), 4);
+    // - every 3 writes, storage header included, barrier syncs pending writes
+    let (mut storage, device) = new_storage(SyncPolicy::EveryWrites(3));
+    for block_index in 0..5 {
+        storage.write_block(block_index, &[1; 8]).unwrap();
+    }
+    assert_eq!(sync_count(&device), 2);
+    storage.write_block(5, &[1; 8]).unwrap();
+    // -- block 5 is pending, its block header & data
+    assert_eq!(device.unsynced_write_count(), 2);
+    storage.sync_barrier().unwrap();
+    assert_eq!(sync_count(&device), 3);
+    assert_eq!(device.unsynced_write_count(), 0);
+    // - every N milliseconds
+    let (mut storage, device) = new_storage(SyncPolicy::EveryMillis(60_000));
+    storage.write_block(0, &[1; 8]).unwrap();
+    assert_eq!(sync_count(&device), 0);
+    let (mut storage, device) = new_storage(SyncPolicy::EveryMillis(10));
+    std::thread::sleep(std::time::Duration::from_millis(20));
+    storage.write_block(0, &[1; 8]).unwrap();
+    assert_eq!(sync_count(&device), 1);
+    // - failed sync fails the write
+    let (mut storage, device) = new_storage(SyncPolicy::Always);
+    device.fail_sync(0, Fault::Io);
+    let result = storage.write_block(0, &[1; 8]);
+    assert_eq!(result.err().unwrap().code(), "sync_failed_to_sync_device");
+    // - storage file & free block map sync
+    let tmp_dir_path = tempfile::tempdir().unwrap().keep();
+    let tmp_file_path: std::path::PathBuf = [
+        tmp_dir_path.to_str().unwrap().to_string(),
+        String::from("storage_sync_policy.hex"),
+    ]
+    .iter()
+    .collect();
+    let options = StorageOptions {
+        sync_policy: SyncPolicy::Always,
+        ..Default::default()
+    };
+    let mut storage =
+        Storage::new_with_options(tmp_file_path.to_str().unwrap().to_string(), 8, options).unwrap();
+    storage.write_block(0, &[1; 8]).unwrap();
+    storage.sync().unwrap();
+    // clear clutter
+    remove_dir_contents(tmp_dir_path);
+}
+
+#[test]
+fn storage_write_blocks() {
+    let blocks: Vec<(BlockIndex, &[u8])> = vec![
+        (5, &[5; 8]),
+        (0, &[1, 2, 3]),
+        (2, &[6; 4]),
+        (1, &[4; 8]),
+        (7, &[7, 7]),
+    ];
+    // - same bytes as writing block by block
+    let storage_options = StorageOptions {
+        block_checksum: true,
+        sync_policy: SyncPolicy::Always,
+        ..Default::default()
+    };
+    let mut storage = Storage::new_in_memory(8, storage_options.clone()).unwrap();
+    for (block_index, data) in blocks.iter() {
+        storage.write_block(*block_index, data).unwrap();
+    }
+    let expected_bytes = {
+        let device = storage.device();
+        let mut bytes = vec![0u8; device.size().unwrap() as usize];
+        device.read_at(&mut bytes, 0).unwrap();
+        bytes
+    };
+    let device = FaultyBlockDevice::new(Box::new(MemoryBlockDevice::new()));
+    let mut storage =
+        Storage::new_with_device(Box::new(device.clone()), 8, storage_options).unwrap();
+    let (_, writes_before, syncs_before) = device.call_counts();
+    let write_ptr = storage.write_blocks(&blocks).unwrap();
+    assert_eq!(write_ptr, 32 + (8 + 8) * 7 + 8 + 2);
+    // -- runs [0, 1, 2], [5], [7] are a write each, group is synced once
+    let (_, writes_after, syncs_after) = device.call_counts();
+    assert_eq!(writes_after - writes_before, 3);
+    assert_eq!(syncs_after - syncs_before, 1);
+    let mut bytes = vec![0u8; expected_bytes.len()];
+    device.read_at(&mut bytes, 0).unwrap();
+    assert_eq!(bytes, expected_bytes);
+    assert_eq!(
+        storage.search_block_allocation_indexes(3).unwrap(),
+        vec![3, 4, 6]
+    );
+    for (block_index, data) in blocks.iter() {
+        let (_, block_data) = storage.read_block(*block_index).unwrap();
+        assert_eq!(block_data, data.to_vec());
+    }
+    // - unused tail of a block followed by another block is zero filled
+    storage.delete_block(0, false).unwrap();
+    storage.write_blocks(&[(0, &[9]), (1, &[8; 8])]).unwrap();
+    let mut tail = [0u8; 7];
+    device.read_at(&mut tail, 32 + 8 + 1).unwrap();
+    assert_eq!(tail, [0; 7]);
+    let (_, block_data) = storage.read_block(0).unwrap();
+    assert_eq!(block_data, vec![9]);
+    // - repeated block index is rejected, nothing is written
+    let (_, writes_before, _) = device.call_counts();
+    let result = storage.write_blocks(&[(3, &[1]), (4, &[2]), (3, &[3])]);
+    assert_eq!(
+        result.err().unwrap().code(),
+        "write_blocks_repeated_block_index"
+    );
+    assert_eq!(device.call_counts().1, writes_before);
+    assert_eq!(storage.search_block_allocation_indexes(1).unwrap(), vec![3]);
+    // - file backend writes with pwritev, and blocks are found on open
+    let tmp_dir_path = tempfile::tempdir().unwrap().keep();
+    let tmp_file_path: std::path::PathBuf = [
+        tmp_dir_path.to_str().unwrap().to_string(),
+        String::from("storage_write_blocks.hex"),
+    ]
+    .iter()
+    .collect();
+    let tmp_file_path = tmp_file_path.to_str().unwrap();
+    let mut storage = Storage::new(String::from(tmp_file_path), 8).unwrap();
+    storage.write_blocks(&blocks).unwrap();
+    drop(storage);
+    let storage = Storage::open(String::from(tmp_file_path)).unwrap();
+    assert_eq!(
+        storage.search_block_allocation_indexes(3).unwrap(),
+        vec![3, 4, 6]
+    );
+    for (block_index, data) in blocks.iter() {
+        let (_, block_data) = storage.read_block(*block_index).unwrap();
+        assert_eq!(block_data, data.to_vec());
+    }
+    // clear clutter
+    remove_dir_contents(tmp_dir_path);
+}
+
+#[test]
+fn storage_oversize_data() {
+    let mut storage = Storage::new_in_memory(8, Default::default()).unwrap();
+    storage.write_block(0, &[1; 8]).unwrap();
+    storage.write_block(1, &[2; 8]).unwrap();
+    // - data longer than block length is rejected, next block is intact
+    let result = storage.write_block(0, &[3; 9]);
+    assert_eq!(result.err().unwrap().code(), "write_block_data_too_large");
+    let (_, block_data) = storage.read_block(0).unwrap();
+    assert_eq!(block_data, vec![1; 8]);
+    let (_, block_data) = storage.read_block(1).unwrap();
+    assert_eq!(block_data, vec![2; 8]);
+    // - group write with an oversize block writes nothing
+    let result = storage.write_blocks(&[(0, &[4; 8]), (2, &[5; 12])]);
+    assert_eq!(result.err().unwrap().code(), "write_blocks_data_too_large");
+    let (_, block_data) = storage.read_block(0).unwrap();
+    assert_eq!(block_data, vec![1; 8]);
+    assert_eq!(storage.search_block_allocation_indexes(1).unwrap(), vec![2]);
+}
+
+#[test]
+fn storage_strict_open() {
+    let tmp_dir_path = tempfile::tempdir().unwrap().keep();
+    let tmp_file_path: std::path::PathBuf = [
+        tmp_dir_path.to_str().unwrap().to_string(),
+        String::from("storage_strict_open.hex"),
+    ]
+    .iter()
+    .collect();
+    let tmp_file_path = tmp_file_path.to_str().unwrap();
+    let strict_options = StorageOptions {
+        strict: true,
+        ..Default::default()
+    };
+    let options = StorageOptions {
+        block_checksum: true,
+        ..Default::default()
+    };
+    let mut storage = Storage::new_with_options(String::from(tmp_file_path), 8, options).unwrap();
+    storage.write_block(0, &[1; 8]).unwrap();
+    storage.write_block(1, &[2; 8]).unwrap();
+    drop(storage);
+    Storage::open_with_options(String::from(tmp_file_path), strict_options.clone()).unwrap();
+    let file_bytes = read_full_file(tmp_file_path);
+    // - block data corrupt, caught by checksum on open only in strict mode
+    let mut corrupt_bytes = file_bytes.clone();
+    corrupt_bytes[32 + 16 + 8 + 3] = 9;
+    std::fs::write(tmp_file_path, &corrupt_bytes).unwrap();
+    let storage = Storage::open(String::from(tmp_file_path)).unwrap();
+    let result = storage.read_block(1);
+    assert_eq!(result.err().unwrap().code(), "read_block_checksum_mismatch");
+    drop(storage);
+    let result = Storage::open_with_options(String::from(tmp_file_path), strict_options.clone());
+    assert_eq!(result.err().unwrap().code(), "read_block_checksum_mismatch");
+    // - block data size exceeds block length, free block map hides it unless strict
+    let mut corrupt_bytes = file_bytes.clone();
+    corrupt_bytes[32 + 16] = 200;
+    std::fs::write(tmp_file_path, &corrupt_bytes).unwrap();
+    let storage = Storage::open(String::from(tmp_file_path)).unwrap();
+    let result = storage.read_block(1);
+    assert_eq!(
+        result.err().unwrap().code(),
+        "read_block_invalid_block_data_size"
+    );
+    drop(storage);
+    let result = Storage::open_with_options(String::from(tmp_file_path), strict_options.clone());
+    assert_eq!(
+        result.err().unwrap().code(),
+        "read_storage_block_headers_invalid_block_data_size"
+    );
+    // - strict mode counts free blocks from block headers, not from free block map
+    let mut corrupt_bytes = file_bytes.clone();
+    corrupt_bytes[32 + 16..32 + 16 + 4].copy_from_slice(&[0; 4]);
+    std::fs::write(tmp_file_path, &corrupt_bytes).unwrap();
+    let storage = Storage::open(String::from(tmp_file_path)).unwrap();
+    assert_eq!(storage.search_block_allocation_indexes(1).unwrap(), vec![2]);
+    drop(storage);
+    let storage = Storage::open_with_options(String::from(tmp_file_path), strict_options).unwrap();
+    assert_eq!(storage.search_block_allocation_indexes(1).unwrap(), vec![1]);
+    // clear clutter
+    remove_dir_contents(tmp_dir_path);
+}
+
+#[test]
+fn storage_concurrent_reads() {
+    use std::sync::{Arc, RwLock};
+    use std::thread;
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<Storage>();
+    let tmp_dir_path = tempfile::tempdir().unwrap().keep();
+    for (name, backend) in [
+        ("file", StorageBackend::File),
+        ("mmap", StorageBackend::Mmap),
+    ] {
+        let tmp_file_path: std::path::PathBuf = [
+            tmp_dir_path.to_str().unwrap().to_string(),
+            format!("storage_concurrent_reads_{}.hex", name),
+        ]
+        .iter()
+        .collect();
+        let options = StorageOptions {
+            block_checksum: true,
+            block_cache_capacity: 4,
+            backend,
+            ..Default::default()
+        };
+        let mut storage =
+            Storage::new_with_options(tmp_file_path.to_str().unwrap().to_string(), 8, options)
+                .unwrap();
+        for idx in 0..16 {
+            storage.write_block(idx, &[idx as u8; 8]).unwrap();
+        }
+        // - readers share one storage
+        let storage = Arc::new(storage);
+        let readers: Vec<_> = (0..4)
+            .map(|reader| {
+                let storage = Arc::clone(&storage);
+                thread::spawn(move || {
+                    for round in 0..64 {
+                        let idx = (reader * 5 + round * 3) % 16;
+                        let (_, block_data) = storage.read_block(idx).unwrap();
+                        assert_eq!(block_data, vec![idx as u8; 8]);
+                    }
+                })
+            })
+            .collect();
+        for reader in readers {
+            reader.join().unwrap();
+        }
+        // - writer serialized with readers by a lock
+        let storage = Arc::new(RwLock::new(Arc::into_inner(storage).unwrap()));
+        let writer = {
+            let storage = Arc::clone(&storage);
+            thread::spawn(move || {
+                for idx in 16..32 {
+                    storage
+                        .write()
+                        .unwrap()
+                        .write_block(idx, &[idx as u8; 8])
+                        .unwrap();
+                }
+            })
+        };
+        let readers: Vec<_> = (0..4)
+            .map(|_| {
+                let storage = Arc::clone(&storage);
+                thread::spawn(move || {
+                    for idx in 0..32 {
+                        let storage = storage.read().unwrap();
+                        if let Ok((_, block_data)) = storage.read_block(idx) {
+                            if !block_data.is_empty() {
+                                assert_eq!(block_data, vec![idx as u8; 8]);
+                            }
+                        }
+                    }
+                })
+            })
+            .collect();
+        writer.join().unwrap();
+        for reader in readers {
+            reader.join().unwrap();
+        }
+        let storage = storage.read().unwrap();
+        for idx in 0..32 {
+            let (_, block_data) = storage.read_block(idx).unwrap();
+            assert_eq!(block_data, vec![idx as u8; 8]);
+        }
+    }
+    // clear clutter
+    remove_dir_contents(tmp_dir_path);
+}
+
+#[test]
+fn storage_file_lock() {
+    use std::time::Duration;
+    use storage::LockMode;
+    let tmp_dir_path = tempfile::tempdir().unwrap().keep();
+    let tmp_file_path: std::path::PathBuf = [
+        tmp_dir_path.to_str().unwrap().to_string(),
+        String::from("storage_file_lock.hex"),
+    ]
+    .iter()
+    .collect();
+    let tmp_file_path = tmp_file_path.to_str().unwrap();
+    let mut storage = Storage::new(String::from(tmp_file_path), 8).unwrap();
+    assert_eq!(storage.lock_mode(), Some(LockMode::Exclusive));
+    storage.write_block(0, &[1; 8]).unwrap();
+    let file_bytes = read_full_file(tmp_file_path);
+    // - second writer is refused, and a locked file is never truncated
+    let result = Storage::open(String::from(tmp_file_path));
+    assert_eq!(result.err().unwrap().code(), "storage_locked");
+    let result = Storage::new(String::from(tmp_file_path), 8);
+    assert_eq!(result.err().unwrap().code(), "storage_locked");
+    assert_eq!(read_full_file(tmp_file_path), file_bytes);
+    // - waiting writer gives up after timeout
+    let wait_options = StorageOptions {
+        lock_timeout: Some(Duration::from_millis(30)),
+        ..Default::default()
+    };
+    let result = Storage::open_with_options(String::from(tmp_file_path), wait_options);
+    assert_eq!(result.err().unwrap().code(), "storage_locked");
+    // - waiting writer opens once lock is released
+    let wait_options = StorageOptions {
+        lock_timeout: Some(Duration::from_secs(10)),
+        ..Default::default()
+    };
+    let release = std::thread::spawn(move || {
+        std::thread::sleep(Duration::from_millis(30));
+        drop(storage);
+    });
+    let storage = Storage::open_with_options(String::from(tmp_file_path), wait_options).unwrap();
+    release.join().unwrap();
+    let (_, block_data) = storage.read_block(0).unwrap();
+    assert_eq!(block_data, vec![1; 8]);
+    drop(storage);
+    // - storages on a given device are not locked
+    let storage = Storage::new_in_memory(8, StorageOptions::default()).unwrap();
+    assert_eq!(storage.lock_mode(), None);
+    // clear clutter
+    remove_dir_contents(tmp_dir_path);
+}
+
+#[test]
+fn storage_read_only() {
+    use storage::LockMode;
+    let tmp_dir_path = tempfile::tempdir().unwrap().keep();
+    let tmp_file_path: std::path::PathBuf = [
+        tmp_dir_path.to_str().unwrap().to_string(),
+        String::from("storage_read_only.hex"),
+    ]
+    .iter()
+    .collect();
+    let tmp_file_path = tmp_file_path.to_str().unwrap();
+    let free_block_map_path = format!("{}.free", tmp_file_path);
+    // - missing file is not created
+    let result = Storage::open_read_only(String::from(tmp_file_path));
+    assert!(result.is_err());
+    assert!(!std::path::Path::new(tmp_file_path).exists());
+    let mut storage = Storage::new(String::from(tmp_file_path), 8).unwrap();
+    storage.write_block(0, &[1; 8]).unwrap();
+    storage.write_block(2, &[3; 4]).unwrap();
+    // - writer excludes readers
+    let result = Storage::open_read_only(String::from(tmp_file_path));
+    assert_eq!(result.err().unwrap().code(), "storage_locked");
+    drop(storage);
+    let file_bytes = read_full_file(tmp_file_path);
+    let free_block_map_bytes = read_full_file(&free_block_map_path);
+    for backend in [StorageBackend::File, StorageBackend::Mmap] {
+        let options = StorageOptions {
+            backend,
+            ..Default::default()
+        };
+        let mut storage =
+            Storage::open_read_only_with_options(String::from(tmp_file_path), options).unwrap();
+        assert!(storage.read_only());
+        assert_eq!(storage.lock_mode(), Some(LockMode::Shared));
+        // - readers share the file, and exclude writers
+        let other_storage = Storage::open_read_only(String::from(tmp_file_path)).unwrap();
+        let result = Storage::open(String::from(tmp_file_path));
+        assert_eq!(result.err().unwrap().code(), "storage_locked");
+        drop(other_storage);
+        // - reads work, free blocks loaded from free block map
+        assert_eq!(storage.read_block(0).unwrap().1, vec![1; 8]);
+        assert_eq!(storage.read_block(1).unwrap().1, vec![]);
+        assert_eq!(storage.read_block(2).unwrap().1, vec![3; 4]);
+        assert_eq!(
+            storage.search_block_allocation_indexes(2).unwrap(),
+            vec![1, 3]
+        );
+        // - mutating calls are refused
+        let result = storage.write_block(1, &[2; 8]);
+        assert_eq!(result.err().unwrap().code(), "storage_read_only");
+        let result = storage.write_blocks(&[(1, &[2; 8])]);
+        assert_eq!(result.err().unwrap().code(), "storage_read_only");
+        let result = storage.delete_block(0, true);
+        assert_eq!(result.err().unwrap().code(), "storage_read_only");
+    }
+    assert_eq!(read_full_file(tmp_file_path), file_bytes);
+    assert_eq!(read_full_file(&free_block_map_path), free_block_map_bytes);
+    // - missing free block map is counted from block headers, not rebuilt
+    std::fs::remove_file(&free_block_map_path).unwrap();
+    let storage = Storage::open_read_only(String::from(tmp_file_path)).unwrap();
+    assert_eq!(
+        storage.search_block_allocation_indexes(2).unwrap(),
+        vec![1, 3]
+    );
+    drop(storage);
+    assert!(!std::path::Path::new(&free_block_map_path).exists());
+    // - torn block at end of file is skipped, not truncated
+    let mut torn_bytes = file_bytes.clone();
+    torn_bytes.resize(32 + 3 * (4 + 8), 0);
+    torn_bytes.extend_from_slice(&[8, 0]);
+    std::fs::write(tmp_file_path, &torn_bytes).unwrap();
+    let storage = Storage::open_read_only(String::from(tmp_file_path)).unwrap();
+    assert_eq!(storage.read_block(2).unwrap().1, vec![3; 4]);
+    assert_eq!(
+        storage.search_block_allocation_indexes(2).unwrap(),
+        vec![1, 3]
+    );
+    drop(storage);
+    assert_eq!(read_full_file(tmp_file_path), torn_bytes);
+    // clear clutter
+    remove_dir_contents(tmp_dir_path);
+}
+
+#[test]
+fn storage_roots() {
+    let tmp_dir_path = tempfile::tempdir().unwrap().keep();
+    let tmp_file_path: std::path::PathBuf = [
+        tmp_dir_path.to_str().unwrap().to_string(),
+        String::from("storage_roots.hex"),
+    ]
+    .iter()
+    .collect();
+    let tmp_file_path = tmp_file_path.to_str().unwrap();
+    let mut storage = Storage::new(String::from(tmp_file_path), 16).unwrap();
+    assert_eq!(storage.list_roots(), vec![]);
+    assert_eq!(storage.get_root("logs"), None);
+    storage.write_block(0, &[1; 16]).unwrap();
+    // - set, replace & remove roots
+    storage.set_root("logs", 0).unwrap();
+    storage.set_root("index", 7).unwrap();
+    storage.set_root("logs", 5).unwrap();
+    assert_eq!(storage.get_root("logs"), Some(5));
+    assert_eq!(storage.remove_root("index").unwrap(), Some(7));
+    assert_eq!(storage.remove_root("index").unwrap(), None);
+    // - a table spanning many blocks
+    let names = (0..20).map(|i| format!("chain {}", i)).collect::<Vec<_>>();
+    for (i, name) in names.iter().enumerate() {
+        storage.set_root(name, i as BlockIndex).unwrap();
+    }
+    let mut expected_roots = names
+        .iter()
+        .enumerate()
+        .map(|(i, name)| (name.clone(), i as BlockIndex))
+        .collect::<Vec<_>>();
+    expected_roots.push((String::from("logs"), 5));
+    expected_roots.sort();
+    assert_eq!(storage.list_roots(), expected_roots);
+    // - blocks of replaced tables are reused, only 2 tables are kept
+    // -- last of 64 allocation indexes is number of used blocks + 63
+    let used_block_count =
+        |storage: &Storage| storage.search_block_allocation_indexes(64).unwrap()[63] - 63;
+    storage.set_root("logs", 6).unwrap();
+    let table_used_block_count = used_block_count(&storage);
+    storage.set_root("logs", 5).unwrap();
+    storage.set_root("logs", 6).unwrap();
+    storage.set_root("logs", 5).unwrap();
+    assert_eq!(used_block_count(&storage), table_used_block_count);
+    assert_eq!(storage.read_block(0).unwrap().1, vec![1; 16]);
+    drop(storage);
+    // - roots survive reopen, also read-only & strict
+    let storage = Storage::open(String::from(tmp_file_path)).unwrap();
+    assert_eq!(storage.list_roots(), expected_roots);
+    drop(storage);
+    std::fs::remove_file(format!("{}.free", tmp_file_path)).unwrap();
+    let strict_options = StorageOptions {
+        strict: true,
+        ..Default::default()
+    };
+    let storage =
+        Storage::open_read_only_with_options(String::from(tmp_file_path), strict_options).unwrap();
+    assert_eq!(storage.list_roots(), expected_roots);
+    assert_eq!(storage.get_root("chain 3"), Some(3));
+    let mut storage = storage;
+    let result = storage.set_root("logs", 1);
+    assert_eq!(result.err().unwrap().code(), "storage_read_only");
+    drop(storage);
+    // - block too small to hold next block index
+    let mut storage = Storage::new_in_memory(4, StorageOptions::default()).unwrap();
+    let result = storage.set_root("logs", 1);
+    assert_eq!(result.err().unwrap().code(), "set_root_block_len_too_small");
+    // - headerless file of format version 0 has no room for roots
+    let src_path: std::path::PathBuf = [
+        "tests/samples/storage_open_existing_file1",
+        "w-0_w-1_w-2_sd-0_hd-0_sd-1_hd-2_w-3_w-4_w-5_sd-3.hex",
+    ]
+    .iter()
+    .collect();
+    std::fs::copy(&src_path, tmp_file_path).unwrap();
+    let mut storage = Storage::open(String::from(tmp_file_path)).unwrap();
+    assert_eq!(storage.list_roots(), vec![]);
+    let result = storage.set_root("logs", 1);
+    assert_eq!(
+        result.err().unwrap().code(),
+        "set_root_unsupported_format_version"
+    );
+    assert_eq!(
+        read_full_file(tmp_file_path),
+        read_full_file(src_path.to_str().unwrap())
+    );
+    drop(storage);
+    // clear clutter
+    remove_dir_contents(tmp_dir_path);
+}
+
+#[test]
+fn storage_compact() {
+    let tmp_dir_path = tempfile::tempdir().unwrap().keep();
+    let tmp_file_path: std::path::PathBuf = [
+        tmp_dir_path.to_str().unwrap().to_string(),
+        String::from("storage_compact.hex"),
+    ]
+    .iter()
+    .collect();
+    let tmp_file_path = tmp_file_path.to_str().unwrap();
+    let block_stride = 4 + 8;
+    let new_storage = || {
+        let mut storage = Storage::new(String::from(tmp_file_path), 8).unwrap();
+        for block_index in 0..8 {
+            storage
+                .write_block(block_index, &[block_index as u8; 8])
+                .unwrap();
+        }
+        for block_index in [1, 3, 4] {
+            storage.delete_block(block_index, false).unwrap();
+        }
+        storage
+    };
+    // - last blocks move into first free blocks, free blocks at end are truncated
+    let mut storage = new_storage();
+    let remap = storage.compact(|_, _| None).unwrap();
+    assert_eq!(
+        remap.into_iter().collect::<Vec<_>>(),
+        vec![(5, 4), (6, 3), (7, 1)]
+    );
+    assert_eq!(read_full_file(tmp_file_path).len(), 32 + 5 * block_stride);
+    assert_eq!(
+        storage.search_block_allocation_indexes(2).unwrap(),
+        vec![5, 6]
+    );
+    for (block_index, data) in [(0, 0), (1, 7), (2, 2), (3, 6), (4, 5)] {
+        assert_eq!(storage.read_block(block_index).unwrap().1, vec![data; 8]);
+    }
+    // - nothing to move on compacted storage
+    assert_eq!(storage.compact(|_, _| None).unwrap(), Default::default());
+    drop(storage);
+    // - free block map matches file after truncation
+    let storage = Storage::open(String::from(tmp_file_path)).unwrap();
+    assert_eq!(
+        storage.search_block_allocation_indexes(2).unwrap(),
+        vec![5, 6]
+    );
+    drop(storage);
+    // - rewrite sees remap of every moved block, for each used block
+    let mut storage = new_storage();
+    let mut rewritten_blocks = vec![];
+    storage
+        .compact(|block_data, remap| {
+            assert_eq!(remap.len(), 3);
+            rewritten_blocks.push(block_data[0]);
+            // link of block 2 to block 7 follows it
+            if block_data[0] == 2 {
+                return Some(vec![2, remap[&7] as u8]);
+            }
+            None
+        })
+        .unwrap();
+    rewritten_blocks.sort_unstable();
+    assert_eq!(rewritten_blocks, vec![0, 2, 5, 6, 7]);
+    assert_eq!(storage.read_block(2).unwrap().1, vec![2, 1]);
+    // - roots follow moved blocks, root tables move into freed blocks
+    storage.set_root("tail", 4).unwrap();
+    storage.delete_block(0, false).unwrap();
+    let remap = storage.compact(|_, _| None).unwrap();
+    let moved_block_index = remap[&4];
+    assert_eq!(storage.get_root("tail"), Some(moved_block_index));
+    assert_eq!(storage.read_block(moved_block_index).unwrap().1, vec![5; 8]);
+    let end_block_count = storage.search_block_allocation_indexes(1).unwrap()[0];
+    drop(storage);
+    let storage = Storage::open(String::from(tmp_file_path)).unwrap();
+    assert_eq!(storage.get_root("tail"), Some(moved_block_index));
+    assert_eq!(
+        storage.search_block_allocation_indexes(1).unwrap(),
+        vec![end_block_count]
+    );
+    assert_eq!(
+        read_full_file(tmp_file_path).len(),
+        32 + end_block_count as usize * block_stride
+    );
+    drop(storage);
+    // - read-only storage is not compacted
+    let mut storage = Storage::open_read_only(String::from(tmp_file_path)).unwrap();
+    let result = storage.compact(|_, _| None);
+    assert_eq!(result.err().unwrap().code(), "storage_read_only");
+    drop(storage);
+    // clear clutter
+    remove_dir_contents(tmp_dir_path);
+}
+
+#[test]
+fn storage_compact_file() {
+    let tmp_dir_path = tempfile::tempdir().unwrap().keep();
+    let tmp_file_path: std::path::PathBuf = [
+        tmp_dir_path.to_str().unwrap().to_string(),
+        String::from("storage_compact_file.hex"),
+    ]
+    .iter()
+    .collect();
+    let tmp_file_path = tmp_file_path.to_str().unwrap();
+    let options = StorageOptions {
+        block_checksum: true,
+        ..Default::default()
+    };
+    let mut storage =
+        Storage::new_with_options(String::from(tmp_file_path), 8, options.clone()).unwrap();
+    for block_index in 0..8 {
+        storage
+            .write_block(block_index, &[block_index as u8; 8])
+            .unwrap();
+    }
+    for block_index in [0, 3, 4] {
+        storage.delete_block(block_index, false).unwrap();
+    }
+    storage.set_root("tail", 7).unwrap();
+    storage.set_root("tail", 7).unwrap();
+    // - storage in use is not compacted
+    let result =
+        Storage::compact_file(String::from(tmp_file_path), Default::default(), |_, _| None);
+    assert_eq!(result.err().unwrap().code(), "storage_locked");
+    drop(storage);
+    // - used blocks are renumbered in order, root tables are rewritten after them
+    let remap = Storage::compact_file(String::from(tmp_file_path), Default::default(), |_, _| None)
+        .unwrap();
+    assert_eq!(
+        remap.into_iter().collect::<Vec<_>>(),
+        vec![(1, 0), (2, 1), (5, 2), (6, 3), (7, 4)]
+    );
+    let storage = Storage::open(String::from(tmp_file_path)).unwrap();
+    assert!(storage.block_checksum());
+    assert_eq!(storage.get_root("tail"), Some(4));
+    for (block_index, data) in [(0, 1), (1, 2), (2, 5), (3, 6), (4, 7)] {
+        assert_eq!(storage.read_block(block_index).unwrap().1, vec![data; 8]);
+    }
+    let allocation_indexes = storage.search_block_allocation_indexes(2).unwrap();
+    drop(storage);
+    // - free block map matches compacted file
+    let strict_options = StorageOptions {
+        strict: true,
+        ..Default::default()
+    };
+    let storage = Storage::open_with_options(String::from(tmp_file_path), strict_options).unwrap();
+    assert_eq!(
+        storage.search_block_allocation_indexes(2).unwrap(),
+        allocation_indexes
+    );
+    drop(storage);
+    assert!(!std::path::Path::new(&format!("{}.compact", tmp_file_path)).exists());
+    // - failed compaction removes compacted files, original file is untouched
+    let file = read_full_file(tmp_file_path);
+    let result = Storage::compact_file(String::from(tmp_file_path), Default::default(), |_, _| {
+        Some(vec![0; 9])
+    });
+    assert_eq!(result.err().unwrap().code(), "write_block_data_too_large");
+    for suffix in [".compact", ".compact.free", ".compact.journal"] {
+        assert!(!std::path::Path::new(&format!("{}{}", tmp_file_path, suffix)).exists());
+    }
+    assert_eq!(read_full_file(tmp_file_path), file);
+    // clear clutter
+    remove_dir_contents(tmp_dir_path);
+}
+
+#[test]
+fn storage_punch_holes() {
+    let tmp_dir_path = tempfile::tempdir().unwrap().keep();
+    // blocks of 2 pages, not aligned to pages, as file starts with storage header
+    let block_len = 8192 - 4;
+    let block_stride = 8192;
+    for (name, backend) in [
+        ("file", StorageBackend::File),
+        ("mmap", StorageBackend::Mmap),
+    ] {
+        let tmp_file_path: std::path::PathBuf = [
+            tmp_dir_path.to_str().unwrap().to_string(),
+            format!("storage_punch_holes_{}.hex", name),
+        ]
+        .iter()
+        .collect();
+        let tmp_file_path = tmp_file_path.to_str().unwrap();
+        let options = StorageOptions {
+            backend,
+            punch_holes: true,
+            ..Default::default()
+        };
+        let mut storage =
+            Storage::new_with_options(String::from(tmp_file_path), block_len, options.clone())
+                .unwrap();
+        for block_index in 0..8 {
+            storage
+                .write_block(
+                    block_index,
+                    &vec![block_index as u8 + 1; block_len as usize],
+                )
+                .unwrap();
+        }
+        storage.sync().unwrap();
+        let space = storage.space().unwrap();
+        assert_eq!(space.logical_size, 32 + 8 * block_stride);
+        // - deleted blocks are released, pages shared with used blocks are kept
+        for block_index in [3, 2, 4] {
+            storage.delete_block(block_index, false).unwrap();
+        }
+        let punched_space = storage.space().unwrap();
+        assert_eq!(punched_space.logical_size, space.logical_size);
+        assert!(
+            punched_space.physical_size <= space.physical_size - 5 * 4096,
+            "{}: {:?} -> {:?}",
+            name,
+            space,
+            punched_space
+        );
+        for block_index in [0, 1, 5, 6, 7] {
+            let (_, block_data) = storage.read_block(block_index).unwrap();
+            assert_eq!(block_data, vec![block_index as u8 + 1; block_len as usize]);
+        }
+        let file_data = read_full_file(tmp_file_path);
+        let holes = &file_data[32 + 2 * block_stride as usize..32 + 5 * block_stride as usize];
+        assert!(holes.iter().all(|byte| *byte == 0));
+        // - free blocks at end of file are truncated, down to last used block
+        storage.delete_block(7, false).unwrap();
+        assert_eq!(storage.space().unwrap().logical_size, 32 + 7 * block_stride);
+        storage.delete_block(5, false).unwrap();
+        storage.delete_block(6, false).unwrap();
+        assert_eq!(storage.space().unwrap().logical_size, 32 + 2 * block_stride);
+        assert_eq!(
+            storage.search_block_allocation_indexes(2).unwrap(),
+            vec![2, 3]
+        );
+        drop(storage);
+        // - free block map matches truncated file
+        let strict_options = StorageOptions {
+            strict: true,
+            punch_holes: false,
+            ..options
+        };
+        let mut storage =
+            Storage::open_with_options(String::from(tmp_file_path), strict_options).unwrap();
+        assert_eq!(
+            storage.search_block_allocation_indexes(2).unwrap(),
+            vec![2, 3]
+        );
+        // - hard delete without punch holes option, still zero fills a block
+        storage.delete_block(0, true).unwrap();
+        drop(storage);
+        let file_data = read_full_file(tmp_file_path);
+        assert_eq!(file_data.len(), 32 + 2 * block_stride as usize);
+        assert!(file_data[32..32 + block_stride as usize]
+            .iter()
+            .all(|byte| *byte == 0));
+    }
+    // clear clutter
+    remove_dir_contents(tmp_dir_path);
+}
+
+#[test]
+fn storage_allocation_policy() {
+    let new_storage = |allocation_policy| {
+        let options = StorageOptions {
+            allocation_policy,
+            ..Default::default()
+        };
+        let mut storage = Storage::new_in_memory(8, options).unwrap();
+        for block_index in 0..8 {
+            storage.write_block(block_index, &[1; 8]).unwrap();
+        }
+        // - free runs [1], [3, 4, 5]
+        for block_index in [1, 3, 4, 5] {
+            storage.delete_block(block_index, false).unwrap();
+        }
+        storage
+    };
+    for (allocation_policy, expected_block_indexes) in [
+        (AllocationPolicy::FirstFitLowest, vec![vec![1], vec![1, 3]]),
+        (
+            AllocationPolicy::BestFitContiguous,
+            vec![vec![1], vec![3, 4]],
+        ),
+        (AllocationPolicy::AppendOnly, vec![vec![8], vec![8, 9]]),
+    ] {
+        let storage = new_storage(allocation_policy);
+        for (count, expected_block_indexes) in (1..).zip(expected_block_indexes) {
+            assert_eq!(
+                storage.search_block_allocation_indexes(count).unwrap(),
+                expected_block_indexes,
+                "{:?}",
+                allocation_policy
+            );
+        }
+        // - contiguous run is found regardless of policy
+        assert_eq!(
+            storage.search_contiguous_block_allocation_indexes(3),
+            Some(vec![3, 4, 5])
+        );
+        assert_eq!(storage.search_contiguous_block_allocation_indexes(4), None);
+    }
+    // - written blocks are allocated contiguous, file grows when no run fits
+    let mut storage = new_storage(AllocationPolicy::BestFitContiguous);
+    let block_indexes = storage.search_block_allocation_indexes(4).unwrap();
+    assert_eq!(block_indexes, vec![8, 9, 10, 11]);
+    let blocks = block_indexes
+        .iter()
+        .map(|block_index| (*block_index, &[2u8; 8][..]))
+        .collect::<Vec<(BlockIndex, &[u8])>>();
+    storage.write_blocks(&blocks).unwrap();
+    assert_eq!(
+        storage.search_block_allocation_indexes(3).unwrap(),
+        vec![3, 4, 5]
+    );
+    // - custom allocator, highest free blocks first
+    struct HighestFirst;
+    impl BlockAllocator for HighestFirst {
+        fn allocate(
+            &self,
+            free_blocks: &std::collections::BTreeSet<BlockIndex>,
+            end_block_count: BlockIndex,
+            count: BlockIndex,
+        ) -> Result<Vec<BlockIndex>, util::error::Error> {
+            let mut block_indexes = free_blocks
+                .iter()
+                .rev()
+                .take(count as usize)
+                .cloned()
+                .collect::<Vec<BlockIndex>>();
+            let extend_count = count - block_indexes.len() as BlockIndex;
+            block_indexes.extend(
+                end_block_count..block_allocator::extend_end(end_block_count, extend_count)?,
+            );
+            block_indexes.sort_unstable();
+            Ok(block_indexes)
+        }
+    }
+    storage.set_block_allocator(Box::new(HighestFirst));
+    assert_eq!(
+        storage.search_block_allocation_indexes(2).unwrap(),
+        vec![4, 5]
+    );
+}
+
+#[test]
+fn storage_stats() {
+    let mut storage = Storage::new_in_memory(8, StorageOptions::default()).unwrap();
+    let stats = storage.stats().unwrap();
+    assert_eq!(
+        (stats.total_blocks, stats.used_blocks, stats.free_blocks),
+        (0, 0, 0)
+    );
+    assert_eq!(stats.logical_size, 32);
+    assert_eq!(stats.average_fill(), 0.0);
+    // - blocks 0..6 holding 1..=6 bytes, blocks 1, 3 & 4 deleted
+    for block_index in 0..6 {
+        let data = vec![1; block_index as usize + 1];
+        storage.write_block(block_index, &data).unwrap();
+    }
+    for block_index in [1, 3, 4] {
+        storage.delete_block(block_index, false).unwrap();
+    }
+    storage.read_block(0).unwrap();
+    storage.read_block(5).unwrap();
+    storage.sync().unwrap();
+    let stats = storage.stats().unwrap();
+    assert_eq!(stats.block_len, 8);
+    assert_eq!(
+        (stats.total_blocks, stats.used_blocks, stats.free_blocks),
+        (6, 3, 3)
+    );
+    assert_eq!((stats.free_runs, stats.largest_free_run), (2, 2));
+    assert_eq!(stats.data_bytes, 1 + 3 + 6);
+    assert_eq!(stats.average_fill(), 10.0 / 24.0);
+    // last block is only as long as its data
+    assert_eq!(stats.logical_size, 32 + 5 * 12 + 4 + 6);
+    assert_eq!(stats.physical_size, stats.logical_size);
+    // - headers & data of writes, headers of deletes, headers & data of reads
+    assert_eq!(
+        (stats.writes, stats.deletes, stats.reads, stats.syncs),
+        (6, 3, 2, 1)
+    );
+    assert_eq!(stats.bytes_written, 6 * 4 + 21 + 3 * 4);
+    assert_eq!(stats.bytes_read, 4 + 1 + 4 + 6);
+    // - group write counts every block, reads served by cache read nothing
+    let mut storage = Storage::new_in_memory(
+        8,
+        StorageOptions {
+            block_cache_capacity: 2,
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    storage
+        .write_blocks(&[(0, &[1; 8]), (1, &[2; 4]), (3, &[3; 2])])
+        .unwrap();
+    storage.read_block(3).unwrap();
+    let stats = storage.stats().unwrap();
+    assert_eq!(
+        (stats.writes, stats.bytes_written),
+        (3, (4 + 8 + 4 + 4) + (4 + 2))
+    );
+    assert_eq!((stats.reads, stats.bytes_read), (1, 0));
+    assert_eq!(stats.block_cache.hits, 1);
+    assert_eq!((stats.free_runs, stats.largest_free_run), (1, 1));
+    assert_eq!(stats.data_bytes, 8 + 4 + 2);
+    // - data of overwritten & deleted blocks is taken off running totals
+    storage.write_block(0, &[4; 2]).unwrap();
+    storage.write_blocks(&[(1, &[5; 1])]).unwrap();
+    storage.delete_block(3, false).unwrap();
+    assert_eq!(storage.stats().unwrap().data_bytes, 2 + 1);
+}
+
+#[test]
+fn storage_check_file() {
+    let tmp_dir_path = tempfile::tempdir().unwrap().keep();
+    let tmp_file_path: std::path::PathBuf = [
+        tmp_dir_path.to_str().unwrap().to_string(),
+        String::from("storage_check_file.hex"),
+    ]
+    .iter()
+    .collect();
+    let tmp_file_path = tmp_file_path.to_str().unwrap();
+    let patch_file = |offset: u64, bytes: &[u8]| {
+        use std::os::unix::fs::FileExt;
+        let file = std::fs::OpenOptions::new()
+            .write(true)
+            .open(tmp_file_path)
+            .unwrap();
+        file.write_all_at(bytes, offset).unwrap();
+    };
+    // header, checksum & data of 8 bytes
+    let block_offset = |block_index: u64| 32 + block_index * 16;
+    let options = StorageOptions {
+        block_checksum: true,
+        ..Default::default()
+    };
+    let mut storage =
+        Storage::new_with_options(String::from(tmp_file_path), 8, options.clone()).unwrap();
+    for block_index in 0..4 {
+        storage
+            .write_block(block_index, &[block_index as u8; 8])
+            .unwrap();
+    }
+    storage.set_root("first", 0).unwrap();
+    let end_block_count = storage.end_block_count();
+    // - storage in use is not repaired
+    let result = Storage::check_file(String::from(tmp_file_path), true);
+    assert_eq!(result.err().unwrap().code(), "storage_locked");
+    drop(storage);
+    // - intact storage has no problem
+    let report = Storage::check_file(String::from(tmp_file_path), false).unwrap();
+    assert!(report.is_clean(), "{:?}", report);
+    // - corrupt data of block 1, data size of block 2, and tear a block header at end of file
+    patch_file(block_offset(1) + 8, &[9]);
+    patch_file(block_offset(2), &100u32.to_le_bytes());
+    // last block ends after its data, pad it to its full length first
+    let file_len = block_offset(end_block_count as u64);
+    patch_file(file_len, &[1, 0]);
+    let expected_problems = vec![
+        StorageProblem::ChecksumMismatch { block_index: 1 },
+        StorageProblem::InvalidBlockDataSize {
+            block_index: 2,
+            block_data_size: 100,
+        },
+        StorageProblem::TornBlockHeader {
+            block_index: end_block_count,
+            size: 2,
+        },
+    ];
+    // - check reports problems, and changes nothing
+    let file_data = read_full_file(tmp_file_path);
+    let report = Storage::check_file(String::from(tmp_file_path), false).unwrap();
+    let problems = report
+        .findings
+        .iter()
+        .map(|finding| finding.problem.clone())
+        .collect::<Vec<_>>();
+    assert_eq!(problems, expected_problems);
+    assert!(report.findings.iter().all(|finding| !finding.repaired));
+    assert_eq!(read_full_file(tmp_file_path), file_data);
+    // - repair frees block 2, truncates torn header, and drops free block map
+    let report = Storage::check_file(String::from(tmp_file_path), true).unwrap();
+    let findings = report
+        .findings
+        .iter()
+        .map(|finding| (finding.problem.clone(), finding.repaired))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        findings,
+        vec![
+            (expected_problems[0].clone(), false),
+            (expected_problems[1].clone(), true),
+            (expected_problems[2].clone(), true),
+            (StorageProblem::StaleFreeBlockMap, true),
+        ]
+    );
+    assert_eq!(read_full_file(tmp_file_path).len() as u64, file_len);
+    // -- only checksum mismatch is left
+    let report = Storage::check_file(String::from(tmp_file_path), false).unwrap();
+    assert_eq!(
+        report.unrepaired().cloned().collect::<Vec<_>>(),
+        vec![StorageProblem::ChecksumMismatch { block_index: 1 }]
+    );
+    let storage = Storage::open(String::from(tmp_file_path)).unwrap();
+    assert_eq!(storage.get_root("first"), Some(0));
+    assert!(storage.is_free_block(2));
+    assert_eq!(
+        storage.read_block(1).err().unwrap().code(),
+        "read_block_checksum_mismatch"
+    );
+    drop(storage);
+    // - last block ends before its data
+    let mut storage = Storage::open(String::from(tmp_file_path)).unwrap();
+    let block_index = storage.end_block_count();
+    storage.write_block(block_index, &[7; 8]).unwrap();
+    drop(storage);
+    let file_len = read_full_file(tmp_file_path).len() as u64;
+    std::fs::OpenOptions::new()
+        .write(true)
+        .open(tmp_file_path)
+        .unwrap()
+        .set_len(file_len - 3)
+        .unwrap();
+    let report = Storage::check_file(String::from(tmp_file_path), true).unwrap();
+    assert_eq!(
+        report.findings[1].problem,
+        StorageProblem::TruncatedBlock {
+            block_index,
+            missing_bytes: 3,
+        }
+    );
+    assert!(report.findings[1].repaired);
+    assert_eq!(
+        read_full_file(tmp_file_path).len() as u64,
+        block_offset(block_index as u64)
+    );
+    // clear clutter
+    remove_dir_contents(tmp_dir_path);
+}
+
+#[test]
+fn storage_iter_blocks() {
+    let tmp_dir_path = tempfile::tempdir().unwrap().keep();
+    let tmp_file_path: std::path::PathBuf = [
+        tmp_dir_path.to_str().unwrap().to_string(),
+        String::from("storage_iter_blocks.hex"),
+    ]
+    .iter()
+    .collect();
+    let tmp_file_path = tmp_file_path.to_str().unwrap();
+    let options = StorageOptions {
+        block_checksum: true,
+        block_cache_capacity: 4,
+        ..Default::default()
+    };
+    let mut storage =
+        Storage::new_with_options(String::from(tmp_file_path), 8, options.clone()).unwrap();
+    assert_eq!(storage.iter_blocks().count(), 0);
+    // - blocks 0..5 holding 1..=5 bytes, blocks 1 & 3 deleted
+    for block_index in 0..5 {
+        let data = vec![block_index as u8; block_index as usize + 1];
+        storage.write_block(block_index, &data).unwrap();
+    }
+    storage.delete_block(1, false).unwrap();
+    storage.delete_block(3, false).unwrap();
+    let used_blocks = storage
+        .iter_used_blocks()
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    assert_eq!(
+        used_blocks,
+        vec![(0, vec![0]), (2, vec![2; 3]), (4, vec![4; 5])]
+    );
+    // -- free blocks yield empty data
+    let blocks = storage
+        .iter_blocks()
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    assert_eq!(
+        blocks
+            .iter()
+            .map(|(_, data)| data.len())
+            .collect::<Vec<_>>(),
+        vec![1, 0, 3, 0, 5]
+    );
+    // -- headers only
+    let headers = storage
+        .iter_block_headers()
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    assert_eq!(headers, vec![(0, 1), (1, 0), (2, 3), (3, 0), (4, 5)]);
+    // -- reads bypass block cache
+    let block_cache_stats = storage.block_cache_stats();
+    storage.iter_used_blocks().for_each(drop);
+    assert_eq!(storage.block_cache_stats(), block_cache_stats);
+    // -- blocks of root tables are skipped, as in used_application_blocks
+    storage.set_root("head", 4).unwrap();
+    let end_block_count = storage.end_block_count();
+    assert!(!storage.is_free_block(1) && !storage.is_free_block(3));
+    storage.write_block(end_block_count, &[5; 6]).unwrap();
+    let used_block_indexes = storage
+        .iter_used_blocks()
+        .map(|result| result.unwrap().0)
+        .collect::<Vec<_>>();
+    assert_eq!(used_block_indexes, storage.used_application_blocks());
+    assert_eq!(used_block_indexes, vec![0, 2, 4, end_block_count]);
+    let headers = storage
+        .iter_block_headers()
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    assert_eq!(headers, vec![(0, 1), (2, 3), (4, 5), (end_block_count, 6)]);
+    drop(storage);
+    // - block with corrupt data yields error, iteration goes on
+    {
+        use std::os::unix::fs::FileExt;
+        let file = std::fs::OpenOptions::new()
+            .write(true)
+            .open(tmp_file_path)
+            .unwrap();
+        // header, checksum & data of 8 bytes
+        file.write_all_at(&[9], 32 + 2 * 16 + 8).unwrap();
+    }
+    let storage = Storage::open_with_options(String::from(tmp_file_path), options).unwrap();
+    let results = storage.iter_used_blocks().collect::<Vec<_>>();
+    assert_eq!(results.len(), 4);
+    assert_eq!(
+        results[1].as_ref().err().unwrap().code(),
+        "read_block_checksum_mismatch"
+    );
+    assert_eq!(results[2].as_ref().unwrap().0, 4);
+    drop(storage);
+    // clear clutter
+    remove_dir_contents(tmp_dir_path);
+}
+
+#[test]
+fn storage_wide_block_index() {
+    let tmp_dir_path = tempfile::tempdir().unwrap().keep();
+    let tmp_file_path: std::path::PathBuf = [
+        tmp_dir_path.to_str().unwrap().to_string(),
+        String::from("storage_wide_block_index.hex"),
+    ]
+    .iter()
+    .collect();
+    let tmp_file_path = tmp_file_path.to_str().unwrap();
+    let options = StorageOptions {
+        block_index_width: BlockIndexWidth::U64,
+        block_checksum: true,
+        ..Default::default()
+    };
+    let mut storage =
+        Storage::new_with_options(String::from(tmp_file_path), 32, options.clone()).unwrap();
+    assert_eq!(storage.block_index_width(), BlockIndexWidth::U64);
+    storage.write_block(0, &[1; 8]).unwrap();
+    storage.write_blocks(&[(1, &[2; 3]), (2, &[3; 8])]).unwrap();
+    storage.set_root("first", 2).unwrap();
+    storage.delete_block(1, false).unwrap();
+    drop(storage);
+    // - feature flag in storage header, 8 bytes block data size, 4 bytes checksum & 32 bytes data
+    let file_data = read_full_file(tmp_file_path);
+    assert_eq!(file_data[8..12], [3, 0, 0, 0x80]);
+    assert_eq!(file_data[32..40], [8, 0, 0, 0, 0, 0, 0, 0]);
+    assert_eq!(file_data[32 + 44..32 + 52], [0; 8]);
+    assert_eq!(file_data[32 + 88..32 + 96], [8, 0, 0, 0, 0, 0, 0, 0]);
+    // -- root table in blocks 3 & 4, root slot & next block index are 8 bytes
+    assert_eq!(file_data[16..24], [4, 0, 0, 0, 0, 0, 0, 0]);
+    let root_table_offset = 32 + 44 * 3 + 12;
+    assert_eq!(
+        file_data[root_table_offset..root_table_offset + 8],
+        [4, 0, 0, 0, 0, 0, 0, 0]
+    );
+    // - width is loaded from storage header, given options are ignored
+    for strict in [false, true] {
+        let storage = Storage::open_with_options(
+            String::from(tmp_file_path),
+            StorageOptions {
+                strict,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(storage.block_index_width(), BlockIndexWidth::U64);
+        assert_eq!(storage.read_block(0).unwrap().1, vec![1; 8]);
+        assert!(storage.is_free_block(1));
+        assert_eq!(storage.read_block(2).unwrap().1, vec![3; 8]);
+        assert_eq!(storage.get_root("first"), Some(2));
+        assert_eq!(
+            storage
+                .iter_block_headers()
+                .take(3)
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap(),
+            vec![(0, 8), (1, 0), (2, 8)]
+        );
+    }
+    let report = Storage::check_file(String::from(tmp_file_path), false).unwrap();
+    assert!(report.is_clean(), "{:?}", report);
+    // - block index must fit in file, largest value of 32-bit width is reserved
+    let mut storage = Storage::new_in_memory(8, StorageOptions::default()).unwrap();
+    assert_eq!(storage.block_index_width(), BlockIndexWidth::U32);
+    assert_eq!(storage.max_block_count(), u32::MAX as BlockIndex);
+    let result = storage.write_block(u32::MAX as BlockIndex, &[1]);
+    assert_eq!(
+        result.err().unwrap().code(),
+        "write_block_index_out_of_range"
+    );
+    let result = storage.write_blocks(&[(0, &[1]), (u32::MAX as BlockIndex, &[1])]);
+    assert_eq!(
+        result.err().unwrap().code(),
+        "write_blocks_index_out_of_range"
+    );
+    let result = storage.set_root("first", 1 << 32);
+    assert_eq!(
+        result.err().unwrap().code(),
+        "set_root_block_index_out_of_range"
+    );
+    assert_eq!(storage.end_block_count(), 0);
+    // -- 64-bit block indexes are bound by largest file offset
+    let mut storage = Storage::new_in_memory(8, options).unwrap();
+    assert!(storage.max_block_count() > u32::MAX as BlockIndex);
+    assert!(storage.max_block_count() < BlockIndex::MAX);
+    let result = storage.write_block(storage.max_block_count(), &[1]);
+    assert_eq!(
+        result.err().unwrap().code(),
+        "write_block_index_out_of_range"
+    );
+    // clear clutter
+    remove_dir_contents(tmp_dir_path);
+}
+
+#[test]
+fn storage_segmented() {
+    use storage::block_device::SegmentedBlockDevice;
+    let tmp_dir_path = tempfile::tempdir().unwrap().keep();
+    let tmp_file_path: std::path::PathBuf = [
+        tmp_dir_path.to_str().unwrap().to_string(),
+        String::from("storage_segmented.hex"),
+    ]
+    .iter()
+    .collect();
+    let tmp_file_path = tmp_file_path.to_str().unwrap();
+    let segment_dir_path = Storage::segment_dir_path(tmp_file_path);
+    let segment_file_path =
+        |segment_index| SegmentedBlockDevice::segment_file_path(&segment_dir_path, segment_index);
+    let options = StorageOptions {
+        backend: StorageBackend::Segmented {
+            segment_block_count: 2,
+        },
+        ..Default::default()
+    };
+    let mut storage =
+        Storage::new_with_options(String::from(tmp_file_path), 8, options.clone()).unwrap();
+    // - segments are created on demand, storage file holds storage header only
+    assert!(!std::path::Path::new(&segment_file_path(0)).exists());
+    for block_index in 0..5 {
+        let block_data = [block_index as u8 + 1; 8];
+        storage.write_block(block_index, &block_data).unwrap();
+    }
+    storage.delete_block(1, false).unwrap();
+    drop(storage);
+    let file_data = read_full_file(tmp_file_path);
+    assert_eq!(file_data.len(), 32);
+    assert_eq!(file_data[8..12], [4, 0, 0, 0x80]);
+    // -- 2 blocks of 4 bytes block header & 8 bytes data in each segment
+    let segment_data = read_full_file(&segment_file_path(0));
+    assert_eq!(segment_data.len(), 24);
+    assert_eq!(segment_data[..12], [8, 0, 0, 0, 1, 1, 1, 1, 1, 1, 1, 1]);
+    assert_eq!(segment_data[12..16], [0, 0, 0, 0]);
+    assert_eq!(
+        read_full_file(&segment_file_path(2))[..12],
+        [8, 0, 0, 0, 5, 5, 5, 5, 5, 5, 5, 5]
+    );
+    // -- last segment holds a single block
+    assert_eq!(read_full_file(&segment_file_path(2)).len(), 12);
+    assert!(!std::path::Path::new(&segment_file_path(3)).exists());
+
+    // - segmented storage is opened as such, whichever backend is given
+    let storage = Storage::open(String::from(tmp_file_path)).unwrap();
+    assert_eq!(storage.end_block_count(), 5);
+    assert!(storage.is_free_block(1));
+    assert_eq!(storage.read_block(4).unwrap().1, vec![5; 8]);
+    drop(storage);
+    let storage = Storage::open_read_only(String::from(tmp_file_path)).unwrap();
+    assert_eq!(storage.read_block(0).unwrap().1, vec![1; 8]);
+    drop(storage);
+    let report = Storage::check_file(String::from(tmp_file_path), false).unwrap();
+    assert!(report.is_clean(), "{:?}", report);
+
+    // - archived segment can not be read, other segments still can
+    let archived_file_path = format!("{}.archived", segment_file_path(0));
+    std::fs::rename(segment_file_path(0), &archived_file_path).unwrap();
+    let storage = Storage::open(String::from(tmp_file_path)).unwrap();
+    assert!(storage.read_block(0).is_err());
+    assert_eq!(storage.read_block(3).unwrap().1, vec![4; 8]);
+    drop(storage);
+    std::fs::rename(&archived_file_path, segment_file_path(0)).unwrap();
+
+    // - compaction truncates segments of free blocks at end
+    let mut storage = Storage::open(String::from(tmp_file_path)).unwrap();
+    storage.delete_block(4, false).unwrap();
+    storage.delete_block(3, false).unwrap();
+    let remap = storage.compact(|_, _| None).unwrap();
+    assert_eq!(remap.get(&2), Some(&1));
+    assert_eq!(storage.read_block(1).unwrap().1, vec![3; 8]);
+    assert!(std::path::Path::new(&segment_file_path(0)).exists());
+    assert!(!std::path::Path::new(&segment_file_path(1)).exists());
+    drop(storage);
+    let result = Storage::compact_file(String::from(tmp_file_path), options.clone(), |_, _| None);
+    assert_eq!(result.err().unwrap().code(), "compact_file_segmented");
+
+    // - storage file is not opened without its segments
+    let moved_dir_path = format!("{}.moved", segment_dir_path);
+    std::fs::rename(&segment_dir_path, &moved_dir_path).unwrap();
+    let result = Storage::open(String::from(tmp_file_path));
+    assert_eq!(result.err().unwrap().code(), "storage_segments_mismatch");
+    std::fs::rename(&moved_dir_path, &segment_dir_path).unwrap();
+
+    // - segment must hold a block, overwriting storage removes its segments
+    let result = Storage::new_with_options(
+        String::from(tmp_file_path),
+        8,
+        StorageOptions {
+            backend: StorageBackend::Segmented {
+                segment_block_count: 0,
+            },
+            ..Default::default()
+        },
+    );
+    assert_eq!(
+        result.err().unwrap().code(),
+        "new_invalid_segment_block_count"
+    );
+    let storage = Storage::new(String::from(tmp_file_path), 8).unwrap();
+    assert!(!std::path::Path::new(&segment_dir_path).exists());
+    drop(storage);
+    // clear clutter
+    remove_dir_contents(tmp_dir_path);
+}
+
+#[test]
+fn storage_quota() {
+    use std::sync::{Arc, Mutex};
+    let options = StorageOptions {
+        quota: Some(StorageQuota::Blocks(4)),
+        soft_quota: Some(StorageQuota::Blocks(2)),
+        ..Default::default()
+    };
+    let mut storage = Storage::new_in_memory(8, options).unwrap();
+    let warnings = Arc::new(Mutex::new(vec![]));
+    let hook_warnings = warnings.clone();
+    storage.set_quota_warning_hook(Box::new(move |warning: &QuotaWarning| {
+        hook_warnings.lock().unwrap().push(*warning);
+    }));
+    assert_eq!(storage.quota_block_count(), Some(4));
+    assert_eq!(storage.soft_quota_block_count(), Some(2));
+    // - allocation past quota fails
+    assert_eq!(
+        storage.search_block_allocation_indexes(4).unwrap(),
+        vec![0, 1, 2, 3]
+    );
+    let result = storage.search_block_allocation_indexes(5);
+    assert_eq!(result.err().unwrap().code(), "storage_full");
+    // - hook is called once, when a write grows storage past soft quota
+    storage.write_blocks(&[(0, &[1; 8]), (1, &[2; 8])]).unwrap();
+    assert!(warnings.lock().unwrap().is_empty());
+    storage.write_block(2, &[3; 8]).unwrap();
+    storage.write_block(3, &[4; 8]).unwrap();
+    assert_eq!(
+        *warnings.lock().unwrap(),
+        vec![QuotaWarning {
+            end_block_count: 3,
+            soft_quota_block_count: 2,
+            quota_block_count: Some(4),
+        }]
+    );
+    // - writes past quota fail, nothing is written
+    let result = storage.write_block(4, &[5; 8]);
+    assert_eq!(result.err().unwrap().code(), "storage_full");
+    let result = storage.write_blocks(&[(0, &[5; 8]), (6, &[5; 8])]);
+    assert_eq!(result.err().unwrap().code(), "storage_full");
+    assert_eq!(storage.end_block_count(), 4);
+    assert_eq!(storage.read_block(0).unwrap().1, vec![1; 8]);
+    // - blocks within storage are rewritten, free blocks reused
+    storage.write_block(3, &[6; 8]).unwrap();
+    storage.delete_block(1, false).unwrap();
+    assert_eq!(storage.search_block_allocation_indexes(1).unwrap(), vec![1]);
+    assert_eq!(
+        storage.search_contiguous_block_allocation_indexes(1),
+        Some(vec![1])
+    );
+    assert_eq!(storage.search_contiguous_block_allocation_indexes(2), None);
+    storage.write_block(1, &[7; 8]).unwrap();
+    assert_eq!(warnings.lock().unwrap().len(), 1);
+    // - quota in bytes is rounded down to whole blocks of 4 bytes header & 8 bytes data
+    let options = StorageOptions {
+        quota: Some(StorageQuota::Bytes(32 + 12 * 2 + 11)),
+        ..Default::default()
+    };
+    let mut storage = Storage::new_in_memory(8, options).unwrap();
+    assert_eq!(storage.quota_block_count(), Some(2));
+    assert_eq!(storage.soft_quota_block_count(), None);
+    storage.write_block(1, &[1; 8]).unwrap();
+    let result = storage.write_block(2, &[2; 8]);
+    assert_eq!(result.err().unwrap().code(), "storage_full");
+    // - no quota, storage grows to max block count
+    let storage = Storage::new_in_memory(8, StorageOptions::default()).unwrap();
+    assert_eq!(storage.quota_block_count(), None);
+}
+
+#[test]
+fn storage_journal() {
+    use storage::block_device::FileBlockDevice;
+    let tmp_dir_path = tempfile::tempdir().unwrap().keep();
+    let mut tmp_file_path_buf = tmp_dir_path.clone();
+    tmp_file_path_buf.push("storage_journal.bin");
+    let tmp_file_path = tmp_file_path_buf.to_str().unwrap().to_string();
+    let journal_file_path = format!("{}.journal", tmp_file_path);
+    // - file storage commits through journal next to it, empty once commit is done
+    let mut storage = Storage::new(tmp_file_path.clone(), 8).unwrap();
+    assert!(storage.journaled());
+    storage.write_block(0, &[1; 8]).unwrap();
+    let mut transaction = Transaction::new();
+    transaction.write_block(1, &[2; 8]);
+    transaction.write_block(2, &[3; 4]);
+    transaction.delete_block(0, false);
+    storage.commit(&transaction).unwrap();
+    assert!(read_full_file(&journal_file_path).is_empty());
+    assert!(storage.is_free_block(0));
+    assert_eq!(storage.read_block(2).unwrap().1, vec![3; 4]);
+    // - failing check of any write leaves storage untouched
+    let mut transaction = Transaction::new();
+    transaction.write_block(3, &[4; 8]);
+    transaction.write_block(1, &[5; 9]);
+    let result = storage.commit(&transaction);
+    assert_eq!(result.err().unwrap().code(), "write_block_data_too_large");
+    assert_eq!(storage.end_block_count(), 3);
+    assert_eq!(storage.read_block(1).unwrap().1, vec![2; 8]);
+    drop(storage);
+
+    // - commit cut short after journal is synced, storage sync fails
+    let device = FaultyBlockDevice::new(Box::new(
+        FileBlockDevice::open(&tmp_file_path, false).unwrap(),
+    ));
+    let journal_device = device.new_sibling(Box::new(
+        FileBlockDevice::open(&journal_file_path, false).unwrap(),
+    ));
+    let mut storage = Storage::open_devices(
+        Box::new(device.clone()),
+        Box::new(journal_device.clone()),
+        StorageOptions::default(),
+    )
+    .unwrap();
+    let mut transaction = Transaction::new();
+    transaction.write_block(1, &[6; 8]);
+    transaction.write_block(3, &[7; 8]);
+    device.fail_sync(0, Fault::Io);
+    let result = storage.commit(&transaction);
+    assert_eq!(result.err().unwrap().code(), "sync_failed_to_sync_device");
+    drop(storage);
+    assert!(!read_full_file(&journal_file_path).is_empty());
+    // - read-only open can not redo the pending transaction
+    let result = Storage::open_read_only(tmp_file_path.clone());
+    assert_eq!(result.err().unwrap().code(), "storage_pending_journal");
+    // - open redoes it, and clears journal
+    let storage = Storage::open(tmp_file_path.clone()).unwrap();
+    assert!(read_full_file(&journal_file_path).is_empty());
+    assert_eq!(storage.read_block(1).unwrap().1, vec![6; 8]);
+    assert_eq!(storage.read_block(3).unwrap().1, vec![7; 8]);
+    drop(storage);
+    let mut storage = Storage::open_read_only(tmp_file_path.clone()).unwrap();
+    let result = storage.commit(&Transaction::new());
+    assert_eq!(result.err().unwrap().code(), "storage_read_only");
+    drop(storage);
+    // - commit cut short before any block is written, growing storage to 6 blocks
+    let device = FaultyBlockDevice::new(Box::new(
+        FileBlockDevice::open(&tmp_file_path, false).unwrap(),
+    ));
+    let journal_device = device.new_sibling(Box::new(
+        FileBlockDevice::open(&journal_file_path, false).unwrap(),
+    ));
+    let mut storage = Storage::open_devices(
+        Box::new(device.clone()),
+        Box::new(journal_device.clone()),
+        StorageOptions::default(),
+    )
+    .unwrap();
+    let mut transaction = Transaction::new();
+    transaction.write_block(5, &[8; 8]);
+    device.fail_write(0, Fault::Io);
+    assert!(storage.commit(&transaction).is_err());
+    drop(storage);
+    // - open redoes it past a smaller quota, quota bounds new allocations only
+    let options = StorageOptions {
+        quota: Some(StorageQuota::Blocks(4)),
+        ..Default::default()
+    };
+    let mut storage = Storage::open_with_options(tmp_file_path.clone(), options).unwrap();
+    assert!(read_full_file(&journal_file_path).is_empty());
+    assert_eq!(storage.end_block_count(), 6);
+    assert_eq!(storage.read_block(5).unwrap().1, vec![8; 8]);
+    let result = storage.write_block(6, &[9; 8]);
+    assert_eq!(result.err().unwrap().code(), "storage_full");
+    drop(storage);
+    // - storage on a given device alone has no journal
+    let mut storage = Storage::new_with_device(
+        Box::new(MemoryBlockDevice::new()),
+        8,
+        StorageOptions::default(),
+    )
+    .unwrap();
+    assert!(!storage.journaled());
+    let result = storage.commit(&transaction);
+    assert_eq!(result.err().unwrap().code(), "commit_no_journal");
+    // clear clutter
+    remove_dir_contents(tmp_dir_path);
+}
+
+#[test]
+fn storage_shadow_paging() {
+    let tmp_dir_path = tempfile::tempdir().unwrap().keep();
+    let mut tmp_file_path_buf = tmp_dir_path.clone();
+    tmp_file_path_buf.push("storage_shadow_paging.bin");
+    let tmp_file_path = tmp_file_path_buf.to_str().unwrap().to_string();
+    let options = StorageOptions {
+        shadow_paging: true,
+        ..Default::default()
+    };
+    let mut storage = Storage::new_with_options(tmp_file_path.clone(), 16, options).unwrap();
+    assert!(storage.shadow_paging());
+    // - blocks are written to fresh blocks of storage file, rewrites before checkpoint reuse them
+    storage.write_block(0, &[1; 16]).unwrap();
+    storage.write_block(0, &[2; 16]).unwrap();
+    storage
+        .write_blocks(&[(1, &[3; 16]), (3, &[4; 16])])
+        .unwrap();
+    assert_eq!(storage.end_block_count(), 4);
+    assert!(storage.is_free_block(2));
+    assert_eq!(
+        storage.search_block_allocation_indexes(2).unwrap(),
+        vec![2, 4]
+    );
+    storage.set_root("logs", 3).unwrap();
+    // - live blocks of checkpoint are never overwritten, rewrite goes to a fresh block
+    let checkpoint_file = read_full_file(&tmp_file_path);
+    storage.write_block(3, &[5; 16]).unwrap();
+    storage.delete_block(1, false).unwrap();
+    let file = read_full_file(&tmp_file_path);
+    assert_eq!(file[..checkpoint_file.len()], checkpoint_file[..]);
+    assert_eq!(storage.read_block(3).unwrap().1, vec![5; 16]);
+    assert_eq!(storage.read_block(1).unwrap().1, Vec::<u8>::new());
+    assert_eq!(storage.used_application_blocks(), vec![0, 3]);
+    // -- iterators yield blocks of application, neither page tables nor stale versions
+    let blocks = storage
+        .iter_used_blocks()
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    assert_eq!(blocks, vec![(0, vec![2; 16]), (3, vec![5; 16])]);
+    let headers = storage
+        .iter_block_headers()
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    assert_eq!(headers, vec![(0, 16), (1, 0), (2, 0), (3, 16)]);
+    // - writes since last checkpoint are lost on reopen
+    drop(storage);
+    let mut storage = Storage::open(tmp_file_path.clone()).unwrap();
+    assert_eq!(storage.read_block(1).unwrap().1, vec![3; 16]);
+    assert_eq!(storage.read_block(3).unwrap().1, vec![4; 16]);
+    assert_eq!(storage.get_root("logs"), Some(3));
+    // - snapshot retains its version, checkpoint releases blocks nothing holds
+    storage.create_snapshot("before").unwrap();
+    storage.write_block(3, &[5; 16]).unwrap();
+    storage.delete_block(1, false).unwrap();
+    storage.set_root("index", 0).unwrap();
+    let used_blocks = storage.stats().unwrap().used_blocks;
+    assert_eq!(storage.list_snapshots(), vec![String::from("before")]);
+    drop(storage);
+    let snapshot = Storage::open_snapshot(tmp_file_path.clone(), "before").unwrap();
+    assert_eq!(snapshot.read_block(1).unwrap().1, vec![3; 16]);
+    assert_eq!(snapshot.read_block(3).unwrap().1, vec![4; 16]);
+    assert_eq!(snapshot.list_roots(), vec![(String::from("logs"), 3)]);
+    drop(snapshot);
+    let result = Storage::open_snapshot(tmp_file_path.clone(), "after");
+    assert_eq!(result.err().unwrap().code(), "open_snapshot_not_found");
+    let mut storage = Storage::open(tmp_file_path.clone()).unwrap();
+    assert_eq!(storage.read_block(3).unwrap().1, vec![5; 16]);
+    assert_eq!(storage.get_root("index"), Some(0));
+    assert!(storage.remove_snapshot("before").unwrap());
+    assert!(!storage.remove_snapshot("before").unwrap());
+    assert!(storage.stats().unwrap().used_blocks < used_blocks);
+    // - commit checkpoints, without journal
+    let mut transaction = Transaction::new();
+    transaction.write_block(1, &[6; 16]);
+    transaction.delete_block(0, false);
+    storage.commit(&transaction).unwrap();
+    assert!(read_full_file(&format!("{}.journal", tmp_file_path)).is_empty());
+    drop(storage);
+    let mut storage = Storage::open(tmp_file_path.clone()).unwrap();
+    assert_eq!(storage.used_application_blocks(), vec![1, 3]);
+    assert_eq!(storage.read_block(1).unwrap().1, vec![6; 16]);
+    // - blocks move on every write, so shadow paged storage is not compacted
+    let result = storage.compact(|_, _| None);
+    assert_eq!(result.err().unwrap().code(), "compact_shadow_paging");
+    drop(storage);
+    // - storage that is not shadow paged keeps no snapshots, checkpoint syncs
+    let mut storage = Storage::new_in_memory(16, StorageOptions::default()).unwrap();
+    assert!(!storage.shadow_paging());
+    storage.checkpoint().unwrap();
+    let result = storage.create_snapshot("before");
+    assert_eq!(result.err().unwrap().code(), "snapshot_no_shadow_paging");
+    // clear clutter
+    remove_dir_contents(tmp_dir_path);
+}
+
+#[test]
+fn storage_compression() {
+    let tmp_dir_path = tempfile::tempdir().unwrap().keep();
+    let mut tmp_file_path_buf = tmp_dir_path.clone();
+    tmp_file_path_buf.push("storage_compression.bin");
+    let tmp_file_path = tmp_file_path_buf.to_str().unwrap().to_string();
+    let text = b"2024-01-01 INFO index synced ".repeat(9)[..256].to_vec();
+    let random = (0..256u32)
+        .map(|value| (value.wrapping_mul(2654435761) >> 24) as u8)
+        .collect::<Vec<u8>>();
+    for compression in [BlockCompression::Lz4, BlockCompression::Zstd] {
+        let options = StorageOptions {
+            compression,
+            block_checksum: true,
+            ..Default::default()
+        };
+        let mut storage = Storage::new_with_options(tmp_file_path.clone(), 256, options).unwrap();
+        assert_eq!(storage.compression(), compression);
+        // - compressible data is stored compressed, a full block of random data is stored raw
+        storage.write_block(0, &text).unwrap();
+        storage.write_block(1, &random).unwrap();
+        storage
+            .write_blocks(&[(2, &text[..100]), (3, &[7; 3]), (4, &text)])
+            .unwrap();
+        storage.delete_block(4, false).unwrap();
+        // -- overwritten block is counted as last written
+        storage.write_block(0, &random).unwrap();
+        storage.write_block(0, &text).unwrap();
+        assert_eq!(storage.read_block(0).unwrap().1, text);
+        let file = read_full_file(&tmp_file_path);
+        assert!(!file.windows(64).any(|window| window == &text[..64]));
+        // - stats report data before compression, and bytes actually stored
+        let stats = storage.stats().unwrap();
+        assert_eq!(stats.data_bytes, 256 + 256 + 100 + 3);
+        assert_eq!(stats.compressed_blocks, 2);
+        assert!(stats.stored_data_bytes < 256 + 3 + 2 * 64, "{:?}", stats);
+        drop(storage);
+        // - codec is recorded in storage header, strict open decompresses every block
+        let options = StorageOptions {
+            strict: true,
+            ..Default::default()
+        };
+        let storage = Storage::open_with_options(tmp_file_path.clone(), options).unwrap();
+        assert_eq!(storage.compression(), compression);
+        // -- data totals kept as blocks are written & deleted match those counted from block headers
+        let counted_stats = storage.stats().unwrap();
+        assert_eq!(
+            (
+                counted_stats.data_bytes,
+                counted_stats.stored_data_bytes,
+                counted_stats.compressed_blocks
+            ),
+            (
+                stats.data_bytes,
+                stats.stored_data_bytes,
+                stats.compressed_blocks
+            )
+        );
+        let blocks = storage
+            .iter_used_blocks()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(
+            blocks,
+            vec![
+                (0, text.clone()),
+                (1, random.clone()),
+                (2, text[..100].to_vec()),
+                (3, vec![7; 3])
+            ]
+        );
+        // -- block headers yield data size before compression
+        let headers = storage
+            .iter_block_headers()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(
+            headers,
+            vec![
+                (0, text.len() as u64),
+                (1, random.len() as u64),
+                (2, 100),
+                (3, 3),
+                (4, 0)
+            ]
+        );
+        drop(storage);
+        let report = Storage::check_file(tmp_file_path.clone(), false).unwrap();
+        assert!(report.is_clean(), "{:?}", report);
+        // - compacted file stays compressed
+        Storage::compact_file(tmp_file_path.clone(), Default::default(), |_, _| None).unwrap();
+        let storage = Storage::open(tmp_file_path.clone()).unwrap();
+        assert_eq!(storage.compression(), compression);
+        assert_eq!(storage.read_block(2).unwrap().1, text[..100].to_vec());
+        assert_eq!(storage.stats().unwrap().compressed_blocks, 2);
+        drop(storage);
+    }
+    // - pages in unused tails of compressed blocks are released, so compression saves disk space
+    let log_data = b"2024-01-01 INFO index synced ".repeat(600)[..16384].to_vec();
+    let mut physical_sizes = Vec::new();
+    for compression in [BlockCompression::None, BlockCompression::Zstd] {
+        let options = StorageOptions {
+            compression,
+            ..Default::default()
+        };
+        let mut storage =
+            Storage::new_with_options(tmp_file_path.clone(), 16384, options.clone()).unwrap();
+        let blocks = (0..8)
+            .map(|block_index| (block_index, &log_data[..]))
+            .collect::<Vec<(BlockIndex, &[u8])>>();
+        storage.write_blocks(&blocks[..4]).unwrap();
+        for (block_index, data) in blocks[4..].iter() {
+            storage.write_block(*block_index, data).unwrap();
+        }
+        storage.sync().unwrap();
+        let stats = storage.stats().unwrap();
+        assert_eq!(
+            stats.unallocated_bytes() > 0,
+            compression != BlockCompression::None
+        );
+        physical_sizes.push(stats.physical_size);
+        drop(storage);
+        let storage = Storage::open_with_options(
+            tmp_file_path.clone(),
+            StorageOptions {
+                strict: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        for block_index in 0..8 {
+            assert_eq!(storage.read_block(block_index).unwrap().1, log_data);
+        }
+        drop(storage);
+    }
+    assert!(
+        physical_sizes[1] < physical_sizes[0] / 2,
+        "{:?}",
+        physical_sizes
+    );
+    // - compressed data that does not decompress is corrupt
+    let options = StorageOptions {
+        compression: BlockCompression::Lz4,
+        ..Default::default()
+    };
+    let mut storage = Storage::new_in_memory(256, options).unwrap();
+    storage.write_block(0, &text).unwrap();
+    let mut corrupt_device = MemoryBlockDevice::new();
+    let mut bytes = vec![0u8; storage.device().size().unwrap() as usize];
+    storage.device().read_at(&mut bytes, 0).unwrap();
+    // -- data size ahead of compressed data, after 32 bytes storage header & 4 bytes block header,
+    //    larger than the block
+    bytes[36..40].copy_from_slice(&[0, 1, 0, 1]);
+    corrupt_device.write_at(&bytes, 0).unwrap();
+    let storage =
+        Storage::open_device(Box::new(corrupt_device), StorageOptions::default()).unwrap();
+    let result = storage.read_block(0);
+    assert_eq!(
+        result.err().unwrap().code(),
+        "read_block_failed_to_decompress"
+    );
+    // -- check reports it, without block checksum
+    std::fs::write(&tmp_file_path, &bytes).unwrap();
+    std::fs::remove_file(format!("{}.free", tmp_file_path)).unwrap();
+    let report = Storage::check_file(tmp_file_path.clone(), false).unwrap();
+    assert_eq!(
+        report.unrepaired().cloned().collect::<Vec<_>>(),
+        vec![StorageProblem::CorruptCompressedData { block_index: 0 }]
+    );
+    // - highest bit of block data size marks a compressed block
+    let options = StorageOptions {
+        compression: BlockCompression::Zstd,
+        ..Default::default()
+    };
+    let result = Storage::new_in_memory(1 << 31, options);
+    assert_eq!(
+        result.err().unwrap().code(),
+        "new_block_len_too_large_to_compress"
+    );
+    // clear clutter
+    remove_dir_contents(tmp_dir_path);
+}
@@ -0,0 +1,131 @@
+use proptest::prelude::*;
+use std::collections::{BTreeMap, BTreeSet};
+use storage::{BlockIndex, Storage, StorageOptions};
+
+const BLOCK_LEN: u32 = 8;
+const MAX_BLOCK_INDEX: BlockIndex = 12;
+
+#[derive(Clone, Debug)]
+enum Op {
+    Write(BlockIndex, Vec<u8>),
+    WriteGroup(Vec<(BlockIndex, Vec<u8>)>),
+    Delete(BlockIndex, bool),
+}
+
+/// Data up to a few bytes longer than a block, oversize data must be rejected
+fn data_strategy() -> impl Strategy<Value = Vec<u8>> {
+    prop::collection::vec(any::<u8>(), 0..=(BLOCK_LEN as usize + 4))
+}
+
+fn op_strategy() -> impl Strategy<Value = Op> {
+    prop_oneof![
+        4 => (0..MAX_BLOCK_INDEX, data_strategy()).prop_map(|(i, data)| Op::Write(i, data)),
+        2 => prop::collection::btree_map(0..MAX_BLOCK_INDEX, data_strategy(), 1..5)
+            .prop_map(|blocks| Op::WriteGroup(blocks.into_iter().collect())),
+        2 => (0..MAX_BLOCK_INDEX, any::<bool>()).prop_map(|(i, hard)| Op::Delete(i, hard)),
+    ]
+}
+
+/// Apply op to storage & to a model of block contents
+/// - empty data is kept as a used block in storage, but reads back empty like a free block
+fn apply_op(storage: &mut Storage, model: &mut BTreeMap<BlockIndex, Vec<u8>>, op: &Op) {
+    match op {
+        Op::Write(block_index, data) => {
+            let result = storage.write_block(*block_index, data);
+            if data.len() > BLOCK_LEN as usize {
+                assert_eq!(result.err().unwrap().code(), "write_block_data_too_large");
+            } else {
+                result.unwrap();
+                model.insert(*block_index, data.clone());
+            }
+        }
+        Op::WriteGroup(blocks) => {
+            let blocks = blocks
+                .iter()
+                .map(|(block_index, data)| (*block_index, &data[..]))
+                .collect::<Vec<(BlockIndex, &[u8])>>();
+            let result = storage.write_blocks(&blocks);
+            if blocks
+                .iter()
+                .any(|(_, data)| data.len() > BLOCK_LEN as usize)
+            {
+                assert_eq!(result.err().unwrap().code(), "write_blocks_data_too_large");
+            } else {
+                result.unwrap();
+                for (block_index, data) in blocks {
+                    model.insert(block_index, data.to_vec());
+                }
+            }
+        }
+        Op::Delete(block_index, hard_delete) => {
+            storage.delete_block(*block_index, *hard_delete).unwrap();
+            model.remove(block_index);
+        }
+    }
+}
+
+/// Every block reads back its own data, so no write spilled into another block
+fn assert_blocks(storage: &mut Storage, model: &BTreeMap<BlockIndex, Vec<u8>>) {
+    for block_index in 0..MAX_BLOCK_INDEX {
+        let (_, block_data) = storage.read_block(block_index).unwrap();
+        let expected_data = model.get(&block_index).cloned().unwrap_or_default();
+        assert_eq!(block_data, expected_data, "block {}", block_index);
+    }
+}
+
+fn storage_options(block_checksum: bool) -> StorageOptions {
+    StorageOptions {
+        block_checksum,
+        ..Default::default()
+    }
+}
+
+proptest! {
+    #[test]
+    fn storage_blocks_never_overlap(
+        ops in prop::collection::vec(op_strategy(), 1..40),
+        block_checksum in any::<bool>(),
+    ) {
+        let mut storage = Storage::new_in_memory(BLOCK_LEN, storage_options(block_checksum)).unwrap();
+        let mut model = BTreeMap::new();
+        for op in ops.iter() {
+            apply_op(&mut storage, &mut model, op);
+            assert_blocks(&mut storage, &model);
+        }
+    }
+
+    #[test]
+    fn storage_blocks_survive_strict_reopen(
+        ops in prop::collection::vec(op_strategy(), 1..40),
+        block_checksum in any::<bool>(),
+    ) {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let tmp_file_path = tmp_dir.path().join("storage_properties.hex");
+        let tmp_file_path = tmp_file_path.to_str().unwrap().to_string();
+        let mut storage =
+            Storage::new_with_options(tmp_file_path.clone(), BLOCK_LEN, storage_options(block_checksum))
+                .unwrap();
+        let mut model = BTreeMap::new();
+        for op in ops.iter() {
+            apply_op(&mut storage, &mut model, op);
+        }
+        drop(storage);
+        // - strict open verifies every block header & checksum
+        let strict_options = StorageOptions { strict: true, ..Default::default() };
+        let mut storage = Storage::open_with_options(tmp_file_path.clone(), strict_options).unwrap();
+        assert_blocks(&mut storage, &model);
+        // - free blocks on open are blocks without data, allocation never hands out a used block
+        let used_blocks = model
+            .iter()
+            .filter(|(_, data)| !data.is_empty())
+            .map(|(block_index, _)| *block_index)
+            .collect::<BTreeSet<BlockIndex>>();
+        let allocated_blocks = storage.search_block_allocation_indexes(MAX_BLOCK_INDEX).unwrap();
+        prop_assert!(allocated_blocks.iter().all(|block_index| !used_blocks.contains(block_index)));
+        drop(storage);
+        // - non strict open with free block map agrees
+        let mut storage = Storage::open(tmp_file_path).unwrap();
+        assert_blocks(&mut storage, &model);
+        prop_assert_eq!(storage.search_block_allocation_indexes(MAX_BLOCK_INDEX).unwrap(), allocated_blocks);
+    }
+}
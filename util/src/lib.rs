@@ -1,15 +1,16 @@
 pub mod byte_cursor;
+pub mod checksum;
 pub mod error;
 
 /// convert 4 bytes unsinged integer little endian bytes array
 /// @deprecated - use u32::to_le_bytes()
-pub fn u32_to_bytes(n: u32) -> ([u8; 4]) {
+pub fn u32_to_bytes(n: u32) -> [u8; 4] {
     // block_size is in bytes as little endian
     let mut bytes = [0u8; 4];
     bytes[3] = (n >> 24) as u8;
     bytes[2] = (n >> 16) as u8;
     bytes[1] = (n >> 8) as u8;
-    bytes[0] = (n >> 0) as u8;
+    bytes[0] = n as u8;
     bytes
 }
 
@@ -17,17 +18,17 @@ pub fn u32_to_bytes(n: u32) -> ([u8; 4]) {
 /// @deprecated - use u32::from_le_bytes()
 pub fn bytes_to_u32(bytes: &[u8]) -> u32 {
     let mut n: u32 = 0;
-    n |= (bytes[0] as u32) << 0;
+    n |= bytes[0] as u32;
     n |= (bytes[1] as u32) << 8;
     n |= (bytes[2] as u32) << 16;
     n |= (bytes[3] as u32) << 24;
     n
 }
 
-pub fn make_chunks(data: &[u8], chunk_len: usize) -> (usize, std::slice::Chunks<u8>) {
+pub fn make_chunks(data: &[u8], chunk_len: usize) -> (usize, std::slice::Chunks<'_, u8>) {
     let chunks = data.chunks(chunk_len);
-    let blocks_required = data.len() / chunk_len as usize
-        + if (data.len() % chunk_len as usize) > 0 {
+    let blocks_required = data.len() / chunk_len
+        + if !data.len().is_multiple_of(chunk_len) {
             1
         } else {
             0